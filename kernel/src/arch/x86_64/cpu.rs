@@ -1,3 +1,5 @@
+use core::arch::asm;
+
 use x86::cpuid::{cpuid, CpuIdResult};
 
 /// @brief 获取当前cpu的apic id
@@ -8,6 +10,21 @@ pub fn current_cpu_id() -> u32 {
     return cpu_id;
 }
 
+/// 开中断，并让cpu停机，直到下一个中断到来
+///
+/// `sti`会推迟到下一条指令执行完之后才响应中断，因此在`sti`与`hlt`之间不会丢失中断
+#[inline]
+pub fn cpu_idle() {
+    unsafe { asm!("sti", "hlt") };
+}
+
+/// 关中断，并让当前cpu永久停机
+pub fn cpu_halt() -> ! {
+    loop {
+        unsafe { asm!("cli", "hlt") };
+    }
+}
+
 /// 重置cpu
 pub fn cpu_reset() -> ! {
     // 重启计算机
@@ -1,10 +1,23 @@
 use super::interrupt::TrapFrame;
 
-use crate::{arch::CurrentIrqArch, exception::InterruptArch};
+use crate::{
+    arch::CurrentIrqArch,
+    exception::InterruptArch,
+    ipc::signal::Signal,
+    process::{ProcessFlags, ProcessManager},
+};
 
 #[no_mangle]
-pub unsafe extern "C" fn do_signal(_frame: &mut TrapFrame) {
+pub unsafe extern "C" fn do_signal(frame: &mut TrapFrame) {
     CurrentIrqArch::interrupt_enable();
+    // 收到SIGKILL（包括被OOM杀死）的进程在返回用户态之前退出
+    if frame.from_user()
+        && ProcessManager::current_pcb()
+            .flags()
+            .contains(ProcessFlags::WAKEKILL)
+    {
+        ProcessManager::exit(Signal::SIGKILL.termination_status());
+    }
     // todo: 处理信号
     return;
 }
@@ -1,11 +1,16 @@
 use core::{
     arch::asm,
+    ffi::{c_uint, c_void},
     intrinsics::unlikely,
     mem::ManuallyDrop,
     sync::atomic::{compiler_fence, Ordering},
 };
 
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::{
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
 
 use memoffset::offset_of;
 use x86::{controlregs::Cr4, segmentation::SegmentSelector};
@@ -13,15 +18,10 @@ use x86::{controlregs::Cr4, segmentation::SegmentSelector};
 use crate::{
     arch::process::table::TSSManager,
     exception::InterruptArch,
+    include::bindings::bindings::{multiboot2_get_cmdline, multiboot2_iter},
     libs::spinlock::SpinLockGuard,
-    mm::{
-        percpu::{PerCpu, PerCpuVar},
-        VirtAddr,
-    },
-    process::{
-        fork::CloneFlags, KernelStack, ProcessControlBlock, ProcessFlags, ProcessManager,
-        SwitchResult, SWITCH_RESULT,
-    },
+    mm::VirtAddr,
+    process::{fork::CloneFlags, KernelStack, ProcessControlBlock, ProcessFlags, ProcessManager},
     syscall::{Syscall, SystemError},
 };
 
@@ -42,6 +42,22 @@ extern "C" {
     fn ret_from_intr();
 }
 
+/// 从multiboot2中读取内核启动参数
+fn boot_cmdline() -> String {
+    let mut buf = [0u8; 256];
+    let mut len = buf.len() as c_uint;
+    unsafe {
+        multiboot2_iter(
+            Some(multiboot2_get_cmdline),
+            buf.as_mut_ptr() as *mut c_void,
+            &mut len,
+        )
+    };
+    // 没有启动参数时，len不会被修改，此时buf全为0
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    return String::from_utf8_lossy(&buf[..len]).into_owned();
+}
+
 #[allow(dead_code)]
 #[repr(align(32768))]
 union InitProcUnion {
@@ -191,6 +207,11 @@ impl ArchPCBInfo {
     pub fn gsbase(&self) -> usize {
         self.gsbase
     }
+
+    /// 设置线程局部存储的地址，x86_64使用fsbase作为TLS寄存器，在进程切换到该线程时生效
+    pub fn set_tls(&mut self, addr: VirtAddr) {
+        self.fsbase = addr.data();
+    }
 }
 
 impl ProcessControlBlock {
@@ -205,37 +226,34 @@ impl ProcessControlBlock {
             panic!("current_pcb is null");
         }
         unsafe {
-            // 为了防止内核栈的pcb指针被释放，这里需要将其包装一下，使得Arc的drop不会被调用
-            let arc_wrapper: ManuallyDrop<Arc<ProcessControlBlock>> =
-                ManuallyDrop::new(Arc::from_raw(*p));
-
-            let new_arc: Arc<ProcessControlBlock> = Arc::clone(&arc_wrapper);
-            return new_arc;
+            // 内核栈只持有pcb的弱引用，这里需要将其包装一下，使得Weak的drop不会被调用。
+            // 正在运行的进程总是被当前cpu的pcb缓存持有（见`ProcessManager::current_pcb`），
+            // 因此一定能够升级
+            let weak_wrapper: ManuallyDrop<Weak<ProcessControlBlock>> =
+                ManuallyDrop::new(Weak::from_raw(*p));
+
+            return weak_wrapper
+                .upgrade()
+                .expect("current_pcb has already been dropped");
         }
     }
 }
 
 impl ProcessManager {
     pub fn arch_init() {
-        {
-            // 初始化进程切换结果 per cpu变量
-            let mut switch_res_vec: Vec<SwitchResult> = Vec::new();
-            for _ in 0..PerCpu::MAX_CPU_NUM {
-                switch_res_vec.push(SwitchResult::new());
-            }
-            unsafe {
-                SWITCH_RESULT = Some(PerCpuVar::new(switch_res_vec).unwrap());
-            }
-        }
+        Self::parse_boot_params(&boot_cmdline());
     }
     /// fork的过程中复制线程
     ///
     /// 由于这个过程与具体的架构相关，所以放在这里
+    ///
+    /// 指定了`CLONE_SETTLS`时，新线程的TLS寄存器被设置为`tls`，否则继承当前线程的TLS
     pub fn copy_thread(
-        _clone_flags: &CloneFlags,
+        clone_flags: &CloneFlags,
         current_pcb: &Arc<ProcessControlBlock>,
         new_pcb: &Arc<ProcessControlBlock>,
         current_trapframe: &TrapFrame,
+        tls: VirtAddr,
     ) -> Result<(), SystemError> {
         let mut child_trapframe = current_trapframe.clone();
 
@@ -272,6 +290,10 @@ impl ProcessManager {
         }
         drop(current_arch_guard);
 
+        if clone_flags.contains(CloneFlags::CLONE_SETTLS) {
+            new_arch_guard.set_tls(tls);
+        }
+
         // 设置返回地址（子进程开始执行的指令地址）
 
         if new_pcb.flags().contains(ProcessFlags::KTHREAD) {
@@ -324,8 +346,7 @@ impl ProcessManager {
         // 恢复当前的 preempt count*2
         ProcessManager::current_pcb().preempt_enable();
         ProcessManager::current_pcb().preempt_enable();
-        SWITCH_RESULT.as_mut().unwrap().get_mut().prev_pcb = Some(prev.clone());
-        SWITCH_RESULT.as_mut().unwrap().get_mut().next_pcb = Some(next.clone());
+        ProcessManager::prepare_switch(&prev, &next);
 
         // 切换tss
         TSSManager::current_tss().set_rsp(
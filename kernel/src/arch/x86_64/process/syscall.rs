@@ -7,9 +7,11 @@ use crate::{
         CurrentIrqArch,
     },
     exception::InterruptArch,
-    mm::ucontext::AddressSpace,
+    mm::ucontext::{AddressSpace, UserStack},
     process::{
         exec::{load_binary_file, ExecParam, ExecParamFlags},
+        fork::CloneFlags,
+        resource::RLimitID,
         ProcessManager,
     },
     syscall::{Syscall, SystemError},
@@ -41,13 +43,24 @@ impl Syscall {
             basic_info.set_user_vm(None);
         }
         // 创建新的地址空间并设置为当前地址空间
-        let address_space = AddressSpace::new(true).expect("Failed to create new address space");
+        // 初始的用户栈不超过RLIMIT_STACK的软限制，此后由缺页处理在限制以内扩展
+        let address_space = AddressSpace::new(false).expect("Failed to create new address space");
+        let stack_limit = usize::try_from(pcb.rlimit(RLimitID::Stack).cur).unwrap_or(usize::MAX);
+        address_space
+            .write()
+            .new_user_stack(core::cmp::min(
+                stack_limit,
+                UserStack::DEFAULT_USER_STACK_SIZE,
+            ))
+            .expect("Failed to create user stack");
         unsafe {
             basic_info.set_user_vm(Some(address_space.clone()));
         }
 
         // to avoid deadlock
         drop(basic_info);
+        // 新的地址空间只属于当前进程
+        pcb.clear_shared(CloneFlags::CLONE_VM);
 
         assert!(
             AddressSpace::is_current(&address_space),
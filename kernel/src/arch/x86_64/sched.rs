@@ -1,9 +1,13 @@
-use crate::{include::bindings::bindings::enter_syscall_int, syscall::SYS_SCHED};
+use crate::{
+    include::bindings::bindings::enter_syscall_int, process::ProcessManager, syscall::SYS_SCHED,
+};
 
 /// @brief 若内核代码不处在中断上下文中，那么将可以使用本函数，发起一个sys_sched系统调用，然后运行调度器。
 /// 由于只能在中断上下文中进行进程切换，因此需要发起一个系统调用SYS_SCHED。
 #[no_mangle]
 pub extern "C" fn sched() {
+    // 所有的阻塞原语都通过本函数发起调度，在这里统一检查是否持有自旋锁
+    ProcessManager::might_sleep();
     unsafe {
         enter_syscall_int(SYS_SCHED as u64, 0, 0, 0, 0, 0, 0, 0, 0);
     }
@@ -15,9 +15,6 @@
 #pragma GCC optimize("O0")
 // 导出定义在irq.c中的中段门表
 extern void (*interrupt_table[25])(void);
-extern uint32_t rs_current_pcb_preempt_count();
-extern uint32_t rs_current_pcb_pid();
-extern uint32_t rs_current_pcb_flags();
 
 static bool flag_support_apic = false;
 static bool flag_support_x2apic = false;
@@ -467,20 +464,9 @@ void do_IRQ(struct pt_regs *rsp, ul number)
     rs_do_softirq();
 
     // kdebug("after softirq");
-    // 检测当前进程是否持有自旋锁，若持有自旋锁，则不进行抢占式的进程调度
-    if (rs_current_pcb_preempt_count() > 0)
-    {
-        return;
-    }
-    else if (rs_current_pcb_preempt_count() < 0)
-        kBUG("current_pcb->preempt_count<0! pid=%d", rs_current_pcb_pid()); // should not be here
-
-    // 检测当前进程是否可被调度
-    if ((rs_current_pcb_flags() & PF_NEED_SCHED) && number == APIC_TIMER_IRQ_NUM)
-    {
-        io_mfence();
-        sched();
-    }
+    // 从中断返回前，检测当前进程是否需要被抢占（持有自旋锁的进程不会被抢占）
+    io_mfence();
+    sched_irq_return();
 }
 
 /**
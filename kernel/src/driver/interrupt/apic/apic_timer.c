@@ -90,7 +90,7 @@ hardware_intr_controller apic_timer_intr_controller = {
 void apic_timer_handler(uint64_t number, uint64_t param, struct pt_regs *regs)
 {
     io_mfence();
-    sched_update_jiffies();
+    sched_update_jiffies(user_mode(regs));
     io_mfence();
 }
 
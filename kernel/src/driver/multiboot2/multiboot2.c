@@ -133,4 +133,27 @@ bool multiboot2_get_acpi_new_RSDP(const struct iter_data_t *_iter_data, void *da
         return false;
     *(struct multiboot_tag_new_acpi_t *)data = *(struct multiboot_tag_new_acpi_t *)_iter_data;
     return true;
-}
\ No newline at end of file
+}
+
+/**
+ * @brief 获取内核启动参数
+ *
+ * @param _iter_data 要被迭代的信息的结构体
+ * @param data 存放启动参数的缓冲区
+ * @param count 传入缓冲区的大小，返回启动参数的长度（不包括结尾的'\0'）
+ */
+bool multiboot2_get_cmdline(const struct iter_data_t *_iter_data, void *data, unsigned int *count)
+{
+    if (_iter_data->type != MULTIBOOT_TAG_TYPE_CMDLINE)
+        return false;
+    const char *cmdline = ((struct multiboot_tag_string_t *)_iter_data)->string;
+    unsigned int len = 0;
+    while (len + 1 < *count && cmdline[len] != '\0')
+    {
+        ((char *)data)[len] = cmdline[len];
+        ++len;
+    }
+    ((char *)data)[len] = '\0';
+    *count = len;
+    return true;
+}
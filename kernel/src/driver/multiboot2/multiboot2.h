@@ -471,4 +471,13 @@ bool multiboot2_get_acpi_old_RSDP(const struct iter_data_t *_iter_data, void *da
  * @param reserved
  * @return uint8_t*  struct multiboot_tag_old_acpi_t
  */
-bool multiboot2_get_acpi_new_RSDP(const struct iter_data_t *_iter_data, void *data, unsigned int *reserved);
\ No newline at end of file
+bool multiboot2_get_acpi_new_RSDP(const struct iter_data_t *_iter_data, void *data, unsigned int *reserved);
+
+/**
+ * @brief 获取内核启动参数
+ *
+ * @param _iter_data 要被迭代的信息的结构体
+ * @param data 存放启动参数的缓冲区
+ * @param count 传入缓冲区的大小，返回启动参数的长度（不包括结尾的'\0'）
+ */
+bool multiboot2_get_cmdline(const struct iter_data_t *_iter_data, void *data, unsigned int *count);
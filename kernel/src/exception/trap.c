@@ -207,6 +207,10 @@ void do_page_fault(struct pt_regs *regs, unsigned long error_code)
 
     __asm__ __volatile__("movq	%%cr2,	%0" : "=r"(cr2)::"memory");
 
+    // 用户态访问不存在的页时，先尝试扩展用户栈（受RLIMIT_STACK限制）
+    if ((error_code & 0x04) && !(error_code & 0x01) && rs_user_stack_fault(cr2) == 0)
+        return;
+
     kerror("do_page_fault(14),Error code :%#018lx,RSP:%#018lx, RBP=%#018lx, RIP:%#018lx CPU:%d, pid=%d\n", error_code,
            regs->rsp, regs->rbp, regs->rip, rs_current_pcb_cpuid(), rs_current_pcb_pid());
     kerror("regs->rax = %#018lx\n", regs->rax);
@@ -4,22 +4,29 @@ use alloc::{
     borrow::ToOwned,
     collections::BTreeMap,
     format,
-    string::String,
+    string::{String, ToString},
     sync::{Arc, Weak},
     vec::Vec,
 };
 
 use crate::{
+    arch::MMArch,
     filesystem::vfs::{
         core::{generate_inode_id, ROOT_INODE},
-        FileType,
+        FileType, MAX_PATHLEN,
     },
     kerror, kinfo,
     libs::{
         once::Once,
         spinlock::{SpinLock, SpinLockGuard},
     },
-    process::{Pid, ProcessManager},
+    mm::MemoryManagementArch,
+    process::{cred::CAPFlags, Pid, ProcessManager},
+    sched::{
+        cfs::__get_cfs_scheduler,
+        core::{total_idle_time_us, SCHED_TICK_INTERVAL_US},
+        loadavg::{load_frac, load_int, loadavg},
+    },
     syscall::SystemError,
     time::TimeSpec,
 };
@@ -37,6 +44,22 @@ use super::vfs::{
 pub enum ProcFileType {
     ///展示进程状态信息
     ProcStatus = 0,
+    ///进程打开的文件描述符所在的文件夹
+    ProcFdDir = 1,
+    ///指向文件描述符所对应的文件的链接
+    ProcFdLink = 2,
+    ///展示系统的平均负载
+    ProcLoadavg = 3,
+    ///展示系统的运行时间与空闲时间
+    ProcUptime = 4,
+    ///进程的oom_score_adj，可读写
+    ProcOomScoreAdj = 5,
+    ///展示每个cpu的调度统计信息
+    ProcSchedstat = 6,
+    ///展示进程所在进程组的cpu带宽控制统计信息
+    ProcCpuStat = 7,
+    ///以缩进的形式展示进程树
+    ProcPstree = 8,
     //todo: 其他文件类型
     ///默认文件类型
     Default,
@@ -46,6 +69,14 @@ impl From<u8> for ProcFileType {
     fn from(value: u8) -> Self {
         match value {
             0 => ProcFileType::ProcStatus,
+            1 => ProcFileType::ProcFdDir,
+            2 => ProcFileType::ProcFdLink,
+            3 => ProcFileType::ProcLoadavg,
+            4 => ProcFileType::ProcUptime,
+            5 => ProcFileType::ProcOomScoreAdj,
+            6 => ProcFileType::ProcSchedstat,
+            7 => ProcFileType::ProcCpuStat,
+            8 => ProcFileType::ProcPstree,
             _ => ProcFileType::Default,
         }
     }
@@ -58,6 +89,8 @@ pub struct InodeInfo {
     pid: Pid,
     ///文件类型
     ftype: ProcFileType,
+    ///文件描述符序号（仅对fd链接有效）
+    fd: i32,
     //其他需要传入的信息在此定义
 }
 
@@ -183,6 +216,10 @@ impl ProcFSInode {
         let text = (address_space_guard.end_code - address_space_guard.start_code) / 1024;
         // 进程数据段的大小
         let data = (address_space_guard.end_data - address_space_guard.start_data) / 1024;
+        // 进程的常驻内存
+        let rss = address_space_guard.rss() * MMArch::PAGE_SIZE / 1024;
+        // 进程的虚拟内存大小
+        let vm_size = address_space_guard.total_vm() * MMArch::PAGE_SIZE / 1024;
         drop(address_space_guard);
 
         pdata.append(
@@ -190,6 +227,8 @@ impl ProcFSInode {
                 .as_bytes()
                 .to_owned(),
         );
+        pdata.append(&mut format!("\nVmSize:\t{} kB", vm_size).as_bytes().to_owned());
+        pdata.append(&mut format!("\nVmRSS:\t{} kB", rss).as_bytes().to_owned());
         pdata.append(&mut format!("\nVmData:\t{} kB", data).as_bytes().to_owned());
         pdata.append(&mut format!("\nVmExe:\t{} kB", text).as_bytes().to_owned());
         pdata.append(
@@ -204,6 +243,261 @@ impl ProcFSInode {
         return Ok((pdata.len() * size_of::<u8>()) as i64);
     }
 
+    /// @brief 打开fd链接文件，获取文件描述符所指向的目标
+    ///
+    /// 如果文件描述符对应的是socket，那么目标的格式为`socket:[inode号]`，否则为文件的绝对路径。
+    /// 解析路径时可能访问procfs中的任意inode（包括这个fd链接自身），因此调用者不能持有任何procfs inode的锁
+    fn open_fd_link(
+        pid: Pid,
+        fd: i32,
+        pdata: &mut ProcfsFilePrivateData,
+    ) -> Result<i64, SystemError> {
+        let pcb = ProcessManager::find(pid).ok_or(SystemError::ESRCH)?;
+        let fd_table = pcb.fd_table();
+        drop(pcb);
+        let file = fd_table
+            .read()
+            .get_file_by_fd(fd)
+            .ok_or(SystemError::ENOENT)?;
+        drop(fd_table);
+
+        // 在释放文件的锁之后再解析路径，文件可能就是被打开的这个fd链接
+        let (inode, file_type) = {
+            let guard = file.lock();
+            (guard.inode(), guard.file_type())
+        };
+        let target = if file_type == FileType::Socket {
+            format!("socket:[{}]", inode.metadata()?.inode_id.into())
+        } else {
+            inode_absolute_path(&inode)?
+        };
+
+        let pdata: &mut Vec<u8> = &mut pdata.data;
+        pdata.append(&mut target.as_bytes().to_owned());
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// @brief 读取fd链接文件的内容
+    ///
+    /// 由于路径解析时会在不打开文件的情况下读取符号链接，因此这里每次都重新生成链接的目标。
+    /// 与`open_fd_link`相同，调用者不能持有任何procfs inode的锁
+    fn read_fd_link(
+        pid: Pid,
+        fd: i32,
+        offset: usize,
+        len: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, SystemError> {
+        let mut private_data = ProcfsFilePrivateData::new();
+        Self::open_fd_link(pid, fd, &mut private_data)?;
+        let start = private_data.data.len().min(offset);
+        let end = private_data.data.len().min(offset + len);
+        // buffer空间不足
+        if buf.len() < (end - start) {
+            return Err(SystemError::ENOBUFS);
+        }
+        buf[0..end - start].copy_from_slice(&private_data.data[start..end]);
+        return Ok(end - start);
+    }
+
+    /// @brief 使fd文件夹下的目录项与进程当前打开的文件描述符保持一致
+    fn sync_fd_dir(&mut self) -> Result<(), SystemError> {
+        let pcb = ProcessManager::find(self.fdata.pid).ok_or(SystemError::ESRCH)?;
+        let fds: Vec<i32> = pcb.fd_table().read().opened_fds();
+        drop(pcb);
+
+        // 删除已经被关闭的文件描述符所对应的目录项
+        self.children
+            .retain(|name, _| name.parse::<i32>().map_or(false, |fd| fds.contains(&fd)));
+
+        // 为新打开的文件描述符创建目录项
+        for fd in fds {
+            let name = fd.to_string();
+            if self.children.contains_key(&name) {
+                continue;
+            }
+            let link: Arc<LockedProcFSInode> =
+                Arc::new(LockedProcFSInode(SpinLock::new(ProcFSInode {
+                    parent: self.self_ref.clone(),
+                    self_ref: Weak::default(),
+                    children: BTreeMap::new(),
+                    data: Vec::new(),
+                    metadata: Metadata {
+                        dev_id: 0,
+                        inode_id: generate_inode_id(),
+                        size: 0,
+                        blk_size: 0,
+                        blocks: 0,
+                        atime: TimeSpec::default(),
+                        mtime: TimeSpec::default(),
+                        ctime: TimeSpec::default(),
+                        file_type: FileType::SymLink,
+                        mode: ModeType::from_bits_truncate(0o700),
+                        nlinks: 1,
+                        uid: 0,
+                        gid: 0,
+                        raw_dev: 0,
+                    },
+                    fs: self.fs.clone(),
+                    fdata: InodeInfo {
+                        pid: self.fdata.pid,
+                        ftype: ProcFileType::ProcFdLink,
+                        fd,
+                    },
+                })));
+            link.0.lock().self_ref = Arc::downgrade(&link);
+            self.children.insert(name, link);
+        }
+
+        return Ok(());
+    }
+
+    /// @brief 打开loadavg文件
+    ///
+    /// 格式与Linux一致：`1分钟 5分钟 15分钟 可运行进程数/进程总数 最近分配的pid`
+    fn open_loadavg(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let (avg1, avg5, avg15) = loadavg();
+        let pdata: &mut Vec<u8> = &mut pdata.data;
+
+        pdata.append(
+            &mut format!(
+                "{}.{:02} {}.{:02} {}.{:02} {}/{} {}\n",
+                load_int(avg1),
+                load_frac(avg1),
+                load_int(avg5),
+                load_frac(avg5),
+                load_int(avg15),
+                load_frac(avg15),
+                ProcessManager::runnable_count(),
+                ProcessManager::process_count(),
+                ProcessManager::last_pid().into()
+            )
+            .as_bytes()
+            .to_owned(),
+        );
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// @brief 打开uptime文件
+    ///
+    /// 格式与Linux一致：`系统运行时间 所有cpu的空闲时间之和`，单位为秒，保留两位小数
+    fn open_uptime(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        // 以10ms为单位
+        let uptime = ProcessManager::uptime_us() / 10000;
+        let idle = total_idle_time_us() / 10000;
+        let pdata: &mut Vec<u8> = &mut pdata.data;
+
+        pdata.append(
+            &mut format!(
+                "{}.{:02} {}.{:02}\n",
+                uptime / 100,
+                uptime % 100,
+                idle / 100,
+                idle % 100
+            )
+            .as_bytes()
+            .to_owned(),
+        );
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// @brief 打开schedstat文件
+    ///
+    /// 格式参考Linux的schedstat第15版，每个cpu一行：
+    /// `cpu<N> yld_count 0 sched_count sched_goidle ttwu_count ttwu_local rq_cpu_time run_delay pcount`，
+    /// 未统计的字段为0，rq_cpu_time的单位为纳秒。在这些字段之后，追加该cpu迁入、迁出的进程数
+    fn open_schedstat(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let cfs = __get_cfs_scheduler();
+        let pdata: &mut Vec<u8> = &mut pdata.data;
+
+        let mut text = format!(
+            "version 15\ntimestamp {}\n",
+            ProcessManager::uptime_us() / SCHED_TICK_INTERVAL_US
+        );
+        for cpu in 0..cfs.cpu_num() {
+            let stat = cfs.cpu_stat(cpu);
+            text.push_str(&format!(
+                "cpu{} 0 0 {} {} 0 0 {} 0 0 {} {}\n",
+                cpu,
+                stat.sched_count(),
+                stat.sched_goidle(),
+                stat.run_ticks() * SCHED_TICK_INTERVAL_US * 1000,
+                stat.migrations_in(),
+                stat.migrations_out()
+            ));
+        }
+        pdata.append(&mut text.as_bytes().to_owned());
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// @brief 打开pstree文件
+    ///
+    /// 每个进程一行：`pid 名字`，子进程相对父进程缩进两个空格
+    fn open_pstree(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let pdata: &mut Vec<u8> = &mut pdata.data;
+
+        let mut depths: BTreeMap<Pid, usize> = BTreeMap::new();
+        let mut text = String::new();
+        for (pid, ppid, name) in ProcessManager::process_tree() {
+            // 进程树按照深度优先的顺序排列，父进程总是先于子进程出现
+            let depth = depths.get(&ppid).map_or(0, |depth| depth + 1);
+            depths.insert(pid, depth);
+            text.push_str(&format!(
+                "{:indent$}{} {}\n",
+                "",
+                pid.into(),
+                name,
+                indent = depth * 2
+            ));
+        }
+        pdata.append(&mut text.as_bytes().to_owned());
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// @brief 打开oom_score_adj文件
+    fn open_oom_score_adj(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let pcb = ProcessManager::find(self.fdata.pid).ok_or(SystemError::ESRCH)?;
+        let pdata: &mut Vec<u8> = &mut pdata.data;
+        pdata.append(&mut format!("{}\n", pcb.oom_score_adj()).as_bytes().to_owned());
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// @brief 打开cpu.stat文件
+    ///
+    /// 展示进程所在进程组的带宽控制统计信息，进程不属于任何进程组时，所有计数均为0
+    fn open_cpu_stat(&self, pdata: &mut ProcfsFilePrivateData) -> Result<i64, SystemError> {
+        let pcb = ProcessManager::find(self.fdata.pid).ok_or(SystemError::ESRCH)?;
+        let stat = pcb.basic().tg().map(|tg| tg.cpu_stat()).unwrap_or_default();
+        let pdata: &mut Vec<u8> = &mut pdata.data;
+        pdata.append(&mut stat.render().as_bytes().to_owned());
+
+        return Ok((pdata.len() * size_of::<u8>()) as i64);
+    }
+
+    /// @brief 写入oom_score_adj文件
+    ///
+    /// 降低oom_score_adj需要写入者拥有`CAP_SYS_RESOURCE`能力
+    fn write_oom_score_adj(&self, buf: &[u8]) -> Result<usize, SystemError> {
+        let adj: i32 = core::str::from_utf8(buf)
+            .map_err(|_| SystemError::EINVAL)?
+            .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+            .parse()
+            .map_err(|_| SystemError::EINVAL)?;
+
+        let pcb = ProcessManager::find(self.fdata.pid).ok_or(SystemError::ESRCH)?;
+        let privileged = ProcessManager::current_pcb()
+            .cred()
+            .has_capability(CAPFlags::CAP_SYS_RESOURCE);
+        pcb.set_oom_score_adj(adj, privileged)?;
+        return Ok(buf.len());
+    }
+
     /// status文件读取函数
     fn read_status(
         &self,
@@ -273,6 +567,7 @@ impl ProcFS {
                 fdata: InodeInfo {
                     pid: Pid::new(0),
                     ftype: ProcFileType::Default,
+                    fd: -1,
                 },
             })));
 
@@ -286,6 +581,67 @@ impl ProcFS {
         // 释放锁
         drop(root_guard);
 
+        // 创建与进程无关的系统信息文件
+        // loadavg文件
+        let binding: Arc<dyn IndexNode> = result
+            .root_inode
+            .create(
+                "loadavg",
+                FileType::File,
+                ModeType::from_bits_truncate(0o444),
+            )
+            .expect("Failed to create /proc/loadavg");
+        let loadavg_file: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        loadavg_file.0.lock().fdata.ftype = ProcFileType::ProcLoadavg;
+
+        // uptime文件
+        let binding: Arc<dyn IndexNode> = result
+            .root_inode
+            .create(
+                "uptime",
+                FileType::File,
+                ModeType::from_bits_truncate(0o444),
+            )
+            .expect("Failed to create /proc/uptime");
+        let uptime_file: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        uptime_file.0.lock().fdata.ftype = ProcFileType::ProcUptime;
+
+        // schedstat文件
+        let binding: Arc<dyn IndexNode> = result
+            .root_inode
+            .create(
+                "schedstat",
+                FileType::File,
+                ModeType::from_bits_truncate(0o444),
+            )
+            .expect("Failed to create /proc/schedstat");
+        let schedstat_file: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        schedstat_file.0.lock().fdata.ftype = ProcFileType::ProcSchedstat;
+
+        // pstree文件
+        let binding: Arc<dyn IndexNode> = result
+            .root_inode
+            .create(
+                "pstree",
+                FileType::File,
+                ModeType::from_bits_truncate(0o444),
+            )
+            .expect("Failed to create /proc/pstree");
+        let pstree_file: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        pstree_file.0.lock().fdata.ftype = ProcFileType::ProcPstree;
+
         return result;
     }
 
@@ -314,6 +670,42 @@ impl ProcFS {
         _sf.0.lock().fdata.pid = pid;
         _sf.0.lock().fdata.ftype = ProcFileType::ProcStatus;
 
+        // fd文件夹
+        let binding: Arc<dyn IndexNode> =
+            _pf.create("fd", FileType::Dir, ModeType::from_bits_truncate(0o500))?;
+        let _fdf: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        _fdf.0.lock().fdata.pid = pid;
+        _fdf.0.lock().fdata.ftype = ProcFileType::ProcFdDir;
+
+        // oom_score_adj文件
+        let binding: Arc<dyn IndexNode> = _pf.create(
+            "oom_score_adj",
+            FileType::File,
+            ModeType::from_bits_truncate(0o644),
+        )?;
+        let _of: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        _of.0.lock().fdata.pid = pid;
+        _of.0.lock().fdata.ftype = ProcFileType::ProcOomScoreAdj;
+
+        // cpu.stat文件
+        let binding: Arc<dyn IndexNode> = _pf.create(
+            "cpu.stat",
+            FileType::File,
+            ModeType::from_bits_truncate(0o444),
+        )?;
+        let _cf: &LockedProcFSInode = binding
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        _cf.0.lock().fdata.pid = pid;
+        _cf.0.lock().fdata.ftype = ProcFileType::ProcCpuStat;
+
         //todo: 创建其他文件
 
         return Ok(());
@@ -328,6 +720,19 @@ impl ProcFS {
         let pid_dir: Arc<dyn IndexNode> = proc.find(&pid.to_string())?;
         // 删除进程文件夹下文件
         pid_dir.unlink("status")?;
+        // fd文件夹下的链接是随着进程打开文件动态生成的，先删除它们，再删除fd文件夹
+        let fd_dir: Arc<dyn IndexNode> = pid_dir.find("fd")?;
+        let fd_dir: &LockedProcFSInode = fd_dir
+            .as_any_ref()
+            .downcast_ref::<LockedProcFSInode>()
+            .unwrap();
+        let links = core::mem::take(&mut fd_dir.0.lock().children);
+        for link in links.values() {
+            link.0.lock().metadata.nlinks -= 1;
+        }
+        pid_dir.unlink("fd")?;
+        pid_dir.unlink("oom_score_adj")?;
+        pid_dir.unlink("cpu.stat")?;
 
         // 查看进程文件是否还存在
         // let pf= pid_dir.find("status").expect("Cannot find status");
@@ -349,9 +754,24 @@ impl IndexNode for LockedProcFSInode {
             return Ok(());
         }
         let mut private_data = ProcfsFilePrivateData::new();
+        // fd链接的目标可能就是它自身，因此在释放锁之后再解析
+        if let ProcFileType::ProcFdLink = inode.fdata.ftype {
+            let (pid, fd) = (inode.fdata.pid, inode.fdata.fd);
+            drop(inode);
+            let file_size = ProcFSInode::open_fd_link(pid, fd, &mut private_data)?;
+            *data = FilePrivateData::Procfs(private_data);
+            self.0.lock().metadata.size = file_size;
+            return Ok(());
+        }
         // 根据文件类型获取相应数据
         let file_size = match inode.fdata.ftype {
             ProcFileType::ProcStatus => inode.open_status(&mut private_data)?,
+            ProcFileType::ProcLoadavg => inode.open_loadavg(&mut private_data)?,
+            ProcFileType::ProcUptime => inode.open_uptime(&mut private_data)?,
+            ProcFileType::ProcOomScoreAdj => inode.open_oom_score_adj(&mut private_data)?,
+            ProcFileType::ProcSchedstat => inode.open_schedstat(&mut private_data)?,
+            ProcFileType::ProcCpuStat => inode.open_cpu_stat(&mut private_data)?,
+            ProcFileType::ProcPstree => inode.open_pstree(&mut private_data)?,
             _ => {
                 todo!()
             }
@@ -399,6 +819,14 @@ impl IndexNode for LockedProcFSInode {
             return Err(SystemError::EISDIR);
         }
 
+        // fd链接可能在未被打开的情况下读取，因此不依赖文件私有信息。
+        // 链接的目标可能就是它自身，因此在释放锁之后再解析
+        if let ProcFileType::ProcFdLink = inode.fdata.ftype {
+            let (pid, fd) = (inode.fdata.pid, inode.fdata.fd);
+            drop(inode);
+            return ProcFSInode::read_fd_link(pid, fd, offset, len, buf);
+        }
+
         // 获取数据信息
         let private_data = match data {
             FilePrivateData::Procfs(p) => p,
@@ -409,8 +837,14 @@ impl IndexNode for LockedProcFSInode {
 
         // 根据文件类型读取相应数据
         match inode.fdata.ftype {
-            ProcFileType::ProcStatus => return inode.read_status(offset, len, buf, private_data),
-            ProcFileType::Default => (),
+            ProcFileType::ProcStatus
+            | ProcFileType::ProcLoadavg
+            | ProcFileType::ProcUptime
+            | ProcFileType::ProcOomScoreAdj
+            | ProcFileType::ProcSchedstat
+            | ProcFileType::ProcCpuStat
+            | ProcFileType::ProcPstree => return inode.read_status(offset, len, buf, private_data),
+            ProcFileType::ProcFdDir | ProcFileType::ProcFdLink | ProcFileType::Default => (),
         };
 
         // 默认读取
@@ -431,11 +865,18 @@ impl IndexNode for LockedProcFSInode {
     fn write_at(
         &self,
         _offset: usize,
-        _len: usize,
-        _buf: &[u8],
+        len: usize,
+        buf: &[u8],
         _data: &mut FilePrivateData,
     ) -> Result<usize, SystemError> {
-        return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP);
+        if buf.len() < len {
+            return Err(SystemError::EINVAL);
+        }
+        let inode: SpinLockGuard<ProcFSInode> = self.0.lock();
+        match inode.fdata.ftype {
+            ProcFileType::ProcOomScoreAdj => return inode.write_oom_score_adj(&buf[..len]),
+            _ => return Err(SystemError::EOPNOTSUPP_OR_ENOTSUP),
+        }
     }
 
     fn poll(&self) -> Result<PollStatus, SystemError> {
@@ -532,6 +973,7 @@ impl IndexNode for LockedProcFSInode {
                 fdata: InodeInfo {
                     pid: Pid::new(0),
                     ftype: ProcFileType::Default,
+                    fd: -1,
                 },
             })));
 
@@ -605,12 +1047,16 @@ impl IndexNode for LockedProcFSInode {
     }
 
     fn find(&self, name: &str) -> Result<Arc<dyn IndexNode>, SystemError> {
-        let inode = self.0.lock();
+        let mut inode = self.0.lock();
 
         if inode.metadata.file_type != FileType::Dir {
             return Err(SystemError::ENOTDIR);
         }
 
+        if let ProcFileType::ProcFdDir = inode.fdata.ftype {
+            inode.sync_fd_dir()?;
+        }
+
         match name {
             "" | "." => {
                 return Ok(inode.self_ref.upgrade().ok_or(SystemError::ENOENT)?);
@@ -675,15 +1121,50 @@ impl IndexNode for LockedProcFSInode {
             return Err(SystemError::ENOTDIR);
         }
 
+        let mut inode = self.0.lock();
+        if let ProcFileType::ProcFdDir = inode.fdata.ftype {
+            inode.sync_fd_dir()?;
+        }
+
         let mut keys: Vec<String> = Vec::new();
         keys.push(String::from("."));
         keys.push(String::from(".."));
-        keys.append(&mut self.0.lock().children.keys().cloned().collect());
+        keys.append(&mut inode.children.keys().cloned().collect());
 
         return Ok(keys);
     }
 }
 
+/// @brief 通过逐级向上查找父目录，获取inode的绝对路径
+fn inode_absolute_path(inode: &Arc<dyn IndexNode>) -> Result<String, SystemError> {
+    let mut names: Vec<String> = Vec::new();
+    let mut current: Arc<dyn IndexNode> = inode.clone();
+    loop {
+        let current_id = current.metadata()?.inode_id;
+        let parent: Arc<dyn IndexNode> = current.find("..")?;
+        // 到达根目录
+        if parent.metadata()?.inode_id == current_id {
+            break;
+        }
+        names.push(parent.get_entry_name(current_id)?);
+        if names.len() > MAX_PATHLEN {
+            return Err(SystemError::ENAMETOOLONG);
+        }
+        current = parent;
+    }
+
+    if names.is_empty() {
+        return Ok(String::from("/"));
+    }
+
+    let mut path = String::new();
+    for name in names.iter().rev() {
+        path.push('/');
+        path.push_str(name);
+    }
+    return Ok(path);
+}
+
 /// @brief 向procfs注册进程
 pub fn procfs_register_pid(pid: Pid) -> Result<(), SystemError> {
     let procfs_inode = ROOT_INODE().find("proc")?;
@@ -492,6 +492,17 @@ impl FileDescriptorVec {
         return self.fds[fd as usize].clone();
     }
 
+    /// 获取所有已被使用的文件描述符序号（升序）
+    pub fn opened_fds(&self) -> Vec<i32> {
+        return self
+            .fds
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.is_some())
+            .map(|(fd, _)| fd as i32)
+            .collect();
+    }
+
     /// 释放文件描述符，同时关闭文件。
     ///
     /// ## 参数
@@ -510,4 +521,11 @@ impl FileDescriptorVec {
         assert!(Arc::strong_count(&file) == 1);
         return Ok(());
     }
+
+    /// 释放所有文件描述符，并返回其中的文件对象
+    ///
+    /// 文件在返回值被drop时关闭，调用者可以在释放文件描述符数组的锁之后再关闭它们
+    pub fn take_all(&mut self) -> Vec<Arc<SpinLock<File>>> {
+        return self.fds.iter_mut().filter_map(|f| f.take()).collect();
+    }
 }
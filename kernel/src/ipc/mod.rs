@@ -1,2 +1,3 @@
 pub mod pipe;
+pub mod signal;
 pub mod syscall;
@@ -0,0 +1,226 @@
+use crate::syscall::SystemError;
+
+/// 信号，编号与Linux x86_64一致
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(usize)]
+pub enum Signal {
+    SIGHUP = 1,
+    SIGINT,
+    SIGQUIT,
+    SIGILL,
+    SIGTRAP,
+    SIGABRT,
+    SIGBUS,
+    SIGFPE,
+    SIGKILL,
+    SIGUSR1,
+    SIGSEGV,
+    SIGUSR2,
+    SIGPIPE,
+    SIGALRM,
+    SIGTERM,
+    SIGSTKFLT,
+    SIGCHLD,
+    SIGCONT,
+    SIGSTOP,
+    SIGTSTP,
+    SIGTTIN,
+    SIGTTOU,
+    SIGURG,
+    SIGXCPU,
+    SIGXFSZ,
+    SIGVTALRM,
+    SIGPROF,
+    SIGWINCH,
+    SIGIO,
+    SIGPWR,
+    SIGSYS,
+}
+
+impl Signal {
+    /// 信号在信号集中对应的位
+    pub const fn mask(&self) -> SigSet {
+        return SigSet::from_bits_truncate(1 << (*self as usize - 1));
+    }
+
+    /// 信号是否不能被阻塞（SIGKILL与SIGSTOP）
+    pub fn is_unblockable(&self) -> bool {
+        return matches!(self, Signal::SIGKILL | Signal::SIGSTOP);
+    }
+
+    /// 信号是否会无条件地终止进程（SIGKILL），接收者会被标记为`WAKEKILL`
+    pub fn is_fatal(&self) -> bool {
+        return *self == Signal::SIGKILL;
+    }
+
+    /// 进程被该信号终止时，父进程通过wait得到的状态
+    ///
+    /// 与Linux一致，低7位为信号的编号，退出码所在的第8~15位为0，
+    /// 因此不会与正常退出的状态混淆
+    pub fn termination_status(&self) -> usize {
+        return *self as usize & 0x7f;
+    }
+
+    /// 信号是否为停止信号（默认行为是停止进程）
+    pub fn is_stop(&self) -> bool {
+        return matches!(
+            self,
+            Signal::SIGSTOP | Signal::SIGTSTP | Signal::SIGTTIN | Signal::SIGTTOU
+        );
+    }
+}
+
+impl TryFrom<usize> for Signal {
+    type Error = SystemError;
+
+    /// 将信号编号转换为信号，不支持的编号返回`EINVAL`
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        if value < Signal::SIGHUP as usize || value > Signal::SIGSYS as usize {
+            return Err(SystemError::EINVAL);
+        }
+        // 信号的编号是连续的
+        return Ok(unsafe { core::mem::transmute::<usize, Signal>(value) });
+    }
+}
+
+bitflags! {
+    /// 信号集，第i位表示编号为i+1的信号
+    #[derive(Default)]
+    pub struct SigSet: u64 {
+        const UNBLOCKABLE = (1 << (Signal::SIGKILL as u64 - 1)) | (1 << (Signal::SIGSTOP as u64 - 1));
+        const STOP = (1 << (Signal::SIGSTOP as u64 - 1))
+            | (1 << (Signal::SIGTSTP as u64 - 1))
+            | (1 << (Signal::SIGTTIN as u64 - 1))
+            | (1 << (Signal::SIGTTOU as u64 - 1));
+    }
+}
+
+impl SigSet {
+    /// 按照编号从小到大的顺序，获取信号集中的第一个信号
+    pub fn first(&self) -> Option<Signal> {
+        if self.is_empty() {
+            return None;
+        }
+        return Signal::try_from(self.bits().trailing_zeros() as usize + 1).ok();
+    }
+}
+
+/// 线程的信号状态
+///
+/// 发送给某个线程的信号记录在该线程的待处理信号集中，不会影响同一线程组的其他线程
+#[derive(Debug, Default)]
+pub struct SignalState {
+    /// 待处理的信号
+    pending: SigSet,
+    /// 被阻塞的信号
+    blocked: SigSet,
+}
+
+impl SignalState {
+    pub fn pending(&self) -> SigSet {
+        return self.pending;
+    }
+
+    pub fn blocked(&self) -> SigSet {
+        return self.blocked;
+    }
+
+    /// 设置被阻塞的信号，SIGKILL与SIGSTOP不能被阻塞
+    pub fn set_blocked(&mut self, blocked: SigSet) {
+        self.blocked = blocked - SigSet::UNBLOCKABLE;
+    }
+
+    /// 将信号加入待处理信号集
+    ///
+    /// ## 返回值
+    ///
+    /// 信号是否能够立即被处理（没有被阻塞）
+    pub fn add_pending(&mut self, sig: Signal) -> bool {
+        self.pending.insert(sig.mask());
+        return !self.blocked.contains(sig.mask());
+    }
+
+    /// 将信号集中的信号从待处理信号集中移除
+    pub fn remove_pending(&mut self, set: SigSet) {
+        self.pending.remove(set);
+    }
+
+    /// 是否有没有被阻塞的待处理信号
+    pub fn has_deliverable(&self) -> bool {
+        return !(self.pending - self.blocked).is_empty();
+    }
+
+    /// 取出一个没有被阻塞的待处理信号，编号小的信号优先
+    pub fn dequeue(&mut self) -> Option<Signal> {
+        let sig = (self.pending - self.blocked).first()?;
+        self.pending.remove(sig.mask());
+        return Some(sig);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{process::cred::Credentials, syscall::SystemError};
+
+    use super::{SigSet, Signal, SignalState};
+
+    /// 被阻塞的信号保持待处理，但不能被取出；SIGKILL与SIGSTOP不能被阻塞
+    #[test]
+    fn test_blocked_pending() {
+        let mut state = SignalState::default();
+        state.set_blocked(Signal::SIGUSR2.mask() | SigSet::UNBLOCKABLE);
+        assert_eq!(state.blocked(), Signal::SIGUSR2.mask());
+
+        assert!(!state.add_pending(Signal::SIGUSR2));
+        assert!(state.pending().contains(Signal::SIGUSR2.mask()));
+        assert!(!state.has_deliverable());
+        assert_eq!(state.dequeue(), None);
+
+        assert!(state.add_pending(Signal::SIGUSR1));
+        assert!(state.has_deliverable());
+        assert_eq!(state.dequeue(), Some(Signal::SIGUSR1));
+        assert_eq!(state.pending(), Signal::SIGUSR2.mask());
+    }
+
+    /// 编号小的信号优先被取出，移除停止信号不会影响其他信号
+    #[test]
+    fn test_dequeue_order() {
+        let mut state = SignalState::default();
+        for sig in [Signal::SIGTERM, Signal::SIGTSTP, Signal::SIGHUP, Signal::SIGSTOP] {
+            state.add_pending(sig);
+        }
+        state.remove_pending(SigSet::STOP);
+        assert_eq!(state.dequeue(), Some(Signal::SIGHUP));
+        assert_eq!(state.dequeue(), Some(Signal::SIGTERM));
+        assert_eq!(state.dequeue(), None);
+        assert_eq!(Signal::try_from(0), Err(SystemError::EINVAL));
+        assert_eq!(Signal::try_from(17), Ok(Signal::SIGCHLD));
+    }
+
+    /// 被允许发送的SIGKILL即使在接收者阻塞了所有信号时也会被投递，接收者随之被标记为需要退出
+    #[test]
+    fn test_permitted_sigkill_is_fatal() {
+        let sender = Credentials::user(1000, 1000);
+        assert!(sender.can_signal(&Credentials::user(1000, 1000)));
+
+        let mut state = SignalState::default();
+        state.set_blocked(SigSet::all());
+        assert!(state.add_pending(Signal::SIGKILL));
+        assert!(state.has_deliverable());
+        assert!(Signal::SIGKILL.is_fatal());
+        assert!(!Signal::SIGTERM.is_fatal());
+        assert!(!Signal::SIGSTOP.is_fatal());
+
+        // 没有权限时信号根本不会被加入待处理信号集
+        assert!(!sender.can_signal(&Credentials::user(2000, 2000)));
+    }
+
+    /// 被信号终止的wait状态只包含信号的编号，与正常退出的状态不同
+    #[test]
+    fn test_termination_status() {
+        assert_eq!(Signal::SIGKILL.termination_status(), 9);
+        assert_ne!(Signal::SIGKILL.termination_status(), (9 & 0xff) << 8);
+        assert_eq!(Signal::SIGKILL.termination_status() & 0xff00, 0);
+    }
+}
@@ -0,0 +1,250 @@
+use alloc::{collections::LinkedList, sync::Arc};
+use hashbrown::HashMap;
+
+use crate::{
+    arch::{sched::sched, CurrentIrqArch},
+    exception::InterruptArch,
+    mm::VirtAddr,
+    process::{ProcessControlBlock, ProcessManager, SleepOutcome},
+    syscall::{user_access::UserBufferReader, Syscall, SystemError},
+};
+
+use super::spinlock::SpinLock;
+
+/// 如果futex的值等于期望值，则睡眠直到被唤醒
+pub const FUTEX_WAIT: u32 = 0;
+/// 唤醒在futex上等待的进程
+pub const FUTEX_WAKE: u32 = 1;
+/// futex只在进程内部使用。futex总是按照地址空间区分，因此该标志不影响行为
+pub const FUTEX_PRIVATE_FLAG: u32 = 128;
+/// 指定超时时间使用CLOCK_REALTIME
+pub const FUTEX_CLOCK_REALTIME: u32 = 256;
+/// 从futex操作中取出命令的掩码
+pub const FUTEX_CMD_MASK: u32 = !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME);
+
+/// futex的键：同一个地址空间中的同一个用户地址对应同一个futex
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FutexKey {
+    /// 地址空间的地址
+    address_space: usize,
+    /// futex的用户地址
+    uaddr: usize,
+}
+
+impl FutexKey {
+    /// 获取当前进程的地址空间中，用户地址对应的键
+    pub fn current(uaddr: VirtAddr) -> Self {
+        let address_space = ProcessManager::current_pcb()
+            .basic()
+            .user_vm()
+            .map_or(0, |vm| Arc::as_ptr(&vm) as usize);
+        return Self {
+            address_space,
+            uaddr: uaddr.data(),
+        };
+    }
+}
+
+lazy_static! {
+    /// 在每个futex上等待的进程，没有进程等待的futex不占用表项
+    static ref FUTEX_WAITERS: SpinLock<HashMap<FutexKey, LinkedList<Arc<ProcessControlBlock>>>> =
+        SpinLock::new(HashMap::new());
+}
+
+#[derive(Debug)]
+pub struct Futex;
+
+impl Futex {
+    /// 如果`uaddr`处的值等于`val`，则睡眠，直到被`wake`唤醒
+    ///
+    /// 读取futex的值与加入等待队列在同一把锁下完成，因此不会错过读取之后、睡眠之前发生的唤醒
+    ///
+    /// ## 参数
+    ///
+    /// - `uaddr` : futex的地址，必须按4字节对齐
+    /// - `val` : 期望的值
+    /// - `from_user` : 地址是否来自用户空间
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：被唤醒后返回Ok(())
+    /// - 失败：地址没有对齐时返回`EINVAL`；地址不合法时返回`EFAULT`；
+    /// futex的值不等于`val`时返回`EAGAIN`；被信号打断时返回`ERESTARTSYS`
+    pub fn wait(uaddr: VirtAddr, val: u32, from_user: bool) -> Result<(), SystemError> {
+        if uaddr.data() % core::mem::size_of::<u32>() != 0 {
+            return Err(SystemError::EINVAL);
+        }
+        let reader = UserBufferReader::new(
+            uaddr.data() as *const u32,
+            core::mem::size_of::<u32>(),
+            from_user,
+        )?;
+        let key = FutexKey::current(uaddr);
+        let current = ProcessManager::current_pcb();
+
+        let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let mut waiters = FUTEX_WAITERS.lock();
+        let mut value = 0;
+        reader.copy_one_from_user(&mut value, 0)?;
+        if value != val {
+            return Err(SystemError::EAGAIN);
+        }
+        if ProcessManager::mark_sleep(true)? == SleepOutcome::Interrupted {
+            return Err(SystemError::ERESTARTSYS);
+        }
+        waiters.entry(key).or_default().push_back(current.clone());
+        drop(waiters);
+        drop(irq_guard);
+        sched();
+
+        let outcome = ProcessManager::sleep_outcome();
+        if outcome == SleepOutcome::Interrupted {
+            // 被信号唤醒的进程仍然在等待队列中
+            Self::remove_waiter(key, &current);
+        }
+        return outcome.into_result();
+    }
+
+    /// 唤醒最多`nr`个在`uaddr`上等待的进程
+    ///
+    /// ## 返回值
+    ///
+    /// 被唤醒的进程的数目
+    pub fn wake(uaddr: VirtAddr, nr: usize) -> usize {
+        return Self::wake_key(FutexKey::current(uaddr), nr);
+    }
+
+    /// 按照等待的先后顺序，唤醒最多`nr`个在futex上等待的进程
+    fn wake_key(key: FutexKey, nr: usize) -> usize {
+        let mut waiters = FUTEX_WAITERS.lock_irqsave();
+        let queue = match waiters.get_mut(&key) {
+            Some(queue) => queue,
+            None => return 0,
+        };
+        let mut woken = 0;
+        while woken < nr {
+            let pcb = match queue.pop_front() {
+                Some(pcb) => pcb,
+                None => break,
+            };
+            if ProcessManager::wakeup(&pcb).is_ok() {
+                woken += 1;
+            }
+        }
+        if queue.is_empty() {
+            waiters.remove(&key);
+        }
+        return woken;
+    }
+
+    /// 将进程从futex的等待队列中移出
+    fn remove_waiter(key: FutexKey, pcb: &Arc<ProcessControlBlock>) {
+        let mut waiters = FUTEX_WAITERS.lock_irqsave();
+        if let Some(queue) = waiters.get_mut(&key) {
+            queue
+                .drain_filter(|waiter| Arc::ptr_eq(waiter, pcb))
+                .for_each(drop);
+            if queue.is_empty() {
+                waiters.remove(&key);
+            }
+        }
+    }
+}
+
+impl Syscall {
+    /// futex系统调用，目前支持`FUTEX_WAIT`与`FUTEX_WAKE`，暂不支持超时
+    ///
+    /// ## 参数
+    ///
+    /// - `uaddr` : futex的地址
+    /// - `op` : futex操作，可以带有`FUTEX_PRIVATE_FLAG`
+    /// - `val` : `FUTEX_WAIT`时为期望的值，`FUTEX_WAKE`时为最多唤醒的进程数
+    /// - `timeout` : `FUTEX_WAIT`的超时时间，必须为空
+    /// - `from_user` : 地址是否来自用户空间
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：`FUTEX_WAIT`返回0，`FUTEX_WAKE`返回被唤醒的进程数
+    /// - 失败：不支持的操作或者指定了超时时间时返回`ENOSYS`，其余见`Futex::wait`
+    pub fn futex(
+        uaddr: *const u32,
+        op: u32,
+        val: u32,
+        timeout: *const u8,
+        from_user: bool,
+    ) -> Result<usize, SystemError> {
+        let uaddr = VirtAddr::new(uaddr as usize);
+        match op & FUTEX_CMD_MASK {
+            FUTEX_WAIT => {
+                if !timeout.is_null() {
+                    return Err(SystemError::ENOSYS);
+                }
+                Futex::wait(uaddr, val, from_user)?;
+                return Ok(0);
+            }
+            FUTEX_WAKE => return Ok(Futex::wake(uaddr, val as usize)),
+            _ => return Err(SystemError::ENOSYS),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, collections::LinkedList, string::ToString, vec::Vec};
+
+    use crate::{
+        mm::VirtAddr,
+        process::{
+            kthread::{KernelThreadClosure, KernelThreadMechanism},
+            ProcessManager, ProcessState,
+        },
+    };
+
+    use super::{Futex, FutexKey, FUTEX_WAITERS};
+
+    /// 线程退出时，tid地址被清零，并且等待在该地址上的线程（pthread_join）被唤醒
+    #[test]
+    fn test_clear_child_tid_wakes_joiner() {
+        let mut tid: i32 = 42;
+        let tid_ptr = &mut tid as *mut i32;
+        let uaddr = VirtAddr::new(tid_ptr as usize);
+        let key = FutexKey::current(uaddr);
+
+        // 新创建的内核线程处于睡眠状态，将它作为等待在tid地址上的线程
+        let joiners: Vec<_> = (0..2)
+            .map(|_| {
+                KernelThreadMechanism::create(
+                    KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                    "futex-joiner".to_string(),
+                )
+                .unwrap()
+            })
+            .collect();
+        FUTEX_WAITERS
+            .lock_irqsave()
+            .insert(key, joiners.iter().cloned().collect::<LinkedList<_>>());
+
+        let exiting = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "futex-exiting".to_string(),
+        )
+        .unwrap();
+        exiting.set_clear_child_tid(uaddr);
+        ProcessManager::clear_child_tid(&exiting, false);
+
+        assert_eq!(unsafe { core::ptr::read_volatile(tid_ptr) }, 0);
+        // 被唤醒的线程可能已经开始运行甚至退出，因此只检查它不再睡眠
+        assert_ne!(
+            joiners[0].sched_info().state(),
+            ProcessState::Blocked(false)
+        );
+        assert_eq!(
+            joiners[1].sched_info().state(),
+            ProcessState::Blocked(false)
+        );
+        // 地址只被清零一次
+        ProcessManager::clear_child_tid(&exiting, false);
+        assert_eq!(Futex::wake(uaddr, usize::MAX), 1);
+        assert!(!FUTEX_WAITERS.lock_irqsave().contains_key(&key));
+    }
+}
@@ -3,6 +3,7 @@ pub mod atomic;
 pub mod casting;
 pub mod elf;
 pub mod ffi_convert;
+pub mod futex;
 #[macro_use]
 pub mod int_like;
 pub mod keyboard_parser;
@@ -11,6 +12,7 @@ pub mod lib_ui;
 pub mod mutex;
 pub mod notifier;
 pub mod once;
+pub mod pi_spinlock;
 pub mod printk;
 pub mod rbtree;
 #[macro_use]
@@ -0,0 +1,173 @@
+use core::{
+    cell::UnsafeCell,
+    hint::spin_loop,
+    ops::{Deref, DerefMut},
+};
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{
+    libs::spinlock::SpinLockGuard,
+    process::{ProcessControlBlock, ProcessManager},
+    sched::core::CPU_EXECUTING,
+    syscall::SystemError,
+};
+
+use super::spinlock::SpinLock;
+
+#[derive(Debug)]
+struct PiSpinLockInner {
+    /// 锁的持有者，为None表示锁是空闲的
+    owner: Option<Arc<ProcessControlBlock>>,
+    /// 正在等待获得这个锁的进程
+    waiters: Vec<Arc<ProcessControlBlock>>,
+}
+
+/// 支持实时优先级继承的自旋锁
+///
+/// 与`SpinLock`不同，持有这个锁时不会关闭抢占，持有者可能被优先级更高的进程抢占。
+/// 实时进程在这个锁上发生竞争时，持有者的优先级会被临时提升到与等待者相同，直到它放锁，
+/// 避免持有者被优先级介于两者之间的进程抢占，导致等待者无限期地等待（优先级反转）。
+///
+/// 持有者正在某个cpu上运行时，等待者自旋；否则等待者让出cpu，使被提升的持有者尽快运行。
+/// 请注意！由于等待者可能让出cpu，不能在中断上下文中使用这个锁
+#[derive(Debug)]
+pub struct PiSpinLock<T> {
+    /// 该锁保护的数据
+    data: UnsafeCell<T>,
+    /// 锁内部的信息
+    inner: SpinLock<PiSpinLockInner>,
+}
+
+/// PiSpinLock的守卫
+#[derive(Debug)]
+pub struct PiSpinLockGuard<'a, T: 'a> {
+    lock: &'a PiSpinLock<T>,
+}
+
+unsafe impl<T> Sync for PiSpinLock<T> where T: Send {}
+
+impl<T> PiSpinLock<T> {
+    #[allow(dead_code)]
+    pub const fn new(value: T) -> Self {
+        return Self {
+            data: UnsafeCell::new(value),
+            inner: SpinLock::new(PiSpinLockInner {
+                owner: None,
+                waiters: Vec::new(),
+            }),
+        };
+    }
+
+    /// 加锁。锁已经被持有时，提升持有者的优先级，并等待它放锁
+    #[allow(dead_code)]
+    pub fn lock(&self) -> PiSpinLockGuard<T> {
+        let current = ProcessManager::current_pcb();
+        let mut queued = false;
+        loop {
+            let mut inner = self.inner.lock_irqsave();
+            let owner = match inner.owner.clone() {
+                Some(owner) => owner,
+                None => {
+                    self.set_owner(inner, &current);
+                    return PiSpinLockGuard { lock: self };
+                }
+            };
+
+            if !queued {
+                inner.waiters.push(current.clone());
+                drop(inner);
+                queued = true;
+                current.set_pi_blocked_on(Some(&owner));
+                ProcessManager::pi_boost(&owner, &current, self.id());
+            } else {
+                drop(inner);
+            }
+
+            if Self::is_running(&owner) {
+                spin_loop();
+            } else {
+                ProcessManager::yield_now();
+            }
+        }
+    }
+
+    /// 尝试加锁。锁已经被持有时，返回`EBUSY`，不会提升持有者的优先级
+    #[allow(dead_code)]
+    pub fn try_lock(&self) -> Result<PiSpinLockGuard<T>, SystemError> {
+        let inner = self.inner.lock_irqsave();
+        if inner.owner.is_some() {
+            return Err(SystemError::EBUSY);
+        }
+        self.set_owner(inner, &ProcessManager::current_pcb());
+        return Ok(PiSpinLockGuard { lock: self });
+    }
+
+    /// 将进程标记为锁的持有者
+    ///
+    /// 仍在等待这个锁的进程转而等待新的持有者，并提升新的持有者的优先级。
+    /// 提升在放开内部的自旋锁之后进行
+    fn set_owner(
+        &self,
+        mut inner: SpinLockGuard<PiSpinLockInner>,
+        pcb: &Arc<ProcessControlBlock>,
+    ) {
+        inner.owner = Some(pcb.clone());
+        inner.waiters.retain(|waiter| !Arc::ptr_eq(waiter, pcb));
+        let waiters = inner.waiters.clone();
+        drop(inner);
+
+        pcb.set_pi_blocked_on(None);
+        for waiter in waiters.iter() {
+            waiter.set_pi_blocked_on(Some(pcb));
+            ProcessManager::pi_boost(pcb, waiter, self.id());
+        }
+    }
+
+    /// 放锁，撤销这个锁的等待者对持有者的提升
+    ///
+    /// 本函数只能被守卫的drop方法调用
+    fn unlock(&self) {
+        let owner = self.inner.lock_irqsave().owner.take();
+        // 当前锁一定是已经加锁的状态
+        let owner = owner.expect("PiSpinLock: unlock a free lock");
+        ProcessManager::pi_release(&owner, self.id());
+    }
+
+    /// 锁的标识，用于在持有者上区分不同的锁的等待者
+    #[inline(always)]
+    fn id(&self) -> usize {
+        return self as *const Self as usize;
+    }
+
+    /// 进程当前是否正在某个cpu上运行
+    fn is_running(pcb: &Arc<ProcessControlBlock>) -> bool {
+        return pcb
+            .sched_info()
+            .on_cpu()
+            .map_or(false, |cpu_id| CPU_EXECUTING.get(cpu_id) == pcb.pid());
+    }
+}
+
+/// 实现Deref trait，支持通过获取PiSpinLockGuard来获取临界区数据的不可变引用
+impl<T> Deref for PiSpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        return unsafe { &*self.lock.data.get() };
+    }
+}
+
+/// 实现DerefMut trait，支持通过获取PiSpinLockGuard来获取临界区数据的可变引用
+impl<T> DerefMut for PiSpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        return unsafe { &mut *self.lock.data.get() };
+    }
+}
+
+/// 为PiSpinLockGuard实现Drop方法，守卫的生命周期结束时自动放锁
+impl<T> Drop for PiSpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.unlock();
+    }
+}
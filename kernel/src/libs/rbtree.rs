@@ -341,6 +341,8 @@ impl<K: Ord + Clone, V: Clone> NodePtr<K, V> {
 pub struct RBTree<K: Ord, V> {
     root: NodePtr<K, V>,
     len: usize,
+    /// 缓存的最左（最小）节点，使得获取/弹出最小元素为O(1)
+    leftmost: NodePtr<K, V>,
 }
 
 // Drop all owned pointers if the tree is dropped
@@ -358,6 +360,9 @@ impl<K: Ord + Clone, V: Clone> Clone for RBTree<K, V> {
             let mut new = RBTree::new();
             new.root = self.root.deep_clone();
             new.len = self.len;
+            if !new.root.is_null() {
+                new.leftmost = new.root.min_node();
+            }
             new
         }
     }
@@ -844,6 +849,7 @@ impl<K: Ord, V> RBTree<K, V> {
         RBTree {
             root: NodePtr::null(),
             len: 0,
+            leftmost: NodePtr::null(),
         }
     }
 
@@ -1023,6 +1029,8 @@ impl<K: Ord, V> RBTree<K, V> {
         let mut node = NodePtr::new(k, v);
         let mut y = NodePtr::null();
         let mut x = self.root;
+        // 新节点只有一路向左下降，才会成为最左节点
+        let mut is_leftmost = true;
 
         while !x.is_null() {
             y = x;
@@ -1032,11 +1040,16 @@ impl<K: Ord, V> RBTree<K, V> {
                 }
                 _ => {
                     x = x.right();
+                    is_leftmost = false;
                 }
             };
         }
         node.set_parent(y);
 
+        if is_leftmost {
+            self.leftmost = node;
+        }
+
         if y.is_null() {
             self.root = node;
         } else {
@@ -1080,15 +1093,7 @@ impl<K: Ord, V> RBTree<K, V> {
 
     #[inline]
     fn first_child(&self) -> NodePtr<K, V> {
-        if self.root.is_null() {
-            NodePtr::null()
-        } else {
-            let mut temp = self.root;
-            while !temp.left().is_null() {
-                temp = temp.left();
-            }
-            return temp;
-        }
+        self.leftmost
     }
 
     #[inline]
@@ -1202,6 +1207,7 @@ impl<K: Ord, V> RBTree<K, V> {
     pub fn clear(&mut self) {
         let root = self.root;
         self.root = NodePtr::null();
+        self.leftmost = NodePtr::null();
         self.clear_recurse(root);
     }
 
@@ -1209,6 +1215,7 @@ impl<K: Ord, V> RBTree<K, V> {
     #[inline]
     fn fast_clear(&mut self) {
         self.root = NodePtr::null();
+        self.leftmost = NodePtr::null();
     }
 
     #[inline]
@@ -1220,6 +1227,34 @@ impl<K: Ord, V> RBTree<K, V> {
         unsafe { Some(self.delete(node).1) }
     }
 
+    /// 删除键为`k`、并且值满足`f`的第一个节点
+    ///
+    /// 树中允许存在相同的键，它们在中序遍历中是相邻的，因此只需要检查这些节点
+    #[inline]
+    pub fn remove_if<F: Fn(&V) -> bool>(&mut self, k: &K, f: F) -> Option<V> {
+        let mut node = self.find_node(k);
+        if node.is_null() {
+            return None;
+        }
+        unsafe {
+            // 回退到相同的键中的第一个节点
+            loop {
+                let prev = node.prev();
+                if prev.is_null() || k.cmp(&(*prev.0).key) != Ordering::Equal {
+                    break;
+                }
+                node = prev;
+            }
+            while !node.is_null() && k.cmp(&(*node.0).key) == Ordering::Equal {
+                if f(&(*node.0).value) {
+                    return Some(self.delete(node).1);
+                }
+                node = node.next();
+            }
+        }
+        return None;
+    }
+
     #[inline]
     unsafe fn delete_fixup(&mut self, mut node: NodePtr<K, V>, mut parent: NodePtr<K, V>) {
         let mut other;
@@ -1299,6 +1334,10 @@ impl<K: Ord, V> RBTree<K, V> {
         let color;
 
         self.len -= 1;
+        // 被删除的是最左节点，则它的后继成为新的最左节点
+        if node == self.leftmost {
+            self.leftmost = node.next();
+        }
         // 被删除节点的"左右孩子都不为空"的情况。
         if !node.left().is_null() && !node.right().is_null() {
             // 被删节点的后继节点。(称为"取代节点")
@@ -1593,6 +1632,56 @@ mod tests {
         assert_eq!(m.get_last(), Some((&2, &4)));
     }
 
+    #[test]
+    fn test_leftmost_cache() {
+        let mut m = RBTree::new();
+        // 简单的线性同余发生器，生成可复现的伪随机序列
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut rand = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as u32
+        };
+
+        // 不依赖红黑树实现的对照组，用于暴力求出最小值
+        let mut shadow: Vec<u32> = Vec::new();
+
+        for _ in 0..2000 {
+            let key = rand() % 256;
+            match rand() % 4 {
+                0 => {
+                    let removed = m.remove(&key).is_some();
+                    let pos = shadow.iter().position(|k| *k == key);
+                    assert_eq!(removed, pos.is_some());
+                    if let Some(pos) = pos {
+                        shadow.swap_remove(pos);
+                    }
+                }
+                1 => {
+                    let min = shadow.iter().copied().min();
+                    assert_eq!(m.pop_first().map(|(k, _)| k), min);
+                    if let Some(pos) = shadow.iter().position(|k| Some(*k) == min) {
+                        shadow.swap_remove(pos);
+                    }
+                }
+                _ => {
+                    m.insert(key, key);
+                    shadow.push(key);
+                }
+            }
+
+            assert_eq!(m.len(), shadow.len());
+            let expected = shadow.iter().copied().min();
+            assert_eq!(m.get_first().map(|(k, _)| *k), expected);
+        }
+
+        m.clear();
+        assert_eq!(m.get_first(), None);
+        m.insert(7, 7);
+        assert_eq!(m.get_first(), Some((&7, &7)));
+    }
+
     #[test]
     fn test_iterate() {
         let mut m = RBTree::new();
@@ -1790,4 +1879,18 @@ mod tests {
         assert_eq!(a[&2], "two");
         assert_eq!(a[&3], "three");
     }
+
+    #[test]
+    fn test_remove_if_duplicate_keys() {
+        let mut m = RBTree::new();
+        for i in 0..8 {
+            m.insert(i % 3, i);
+        }
+        assert_eq!(m.remove_if(&1, |v| *v == 7), Some(7));
+        assert_eq!(m.remove_if(&1, |v| *v == 7), None);
+        assert_eq!(m.remove_if(&5, |_| true), None);
+        assert_eq!(m.len(), 7);
+        let ones: Vec<_> = m.iter().filter(|(k, _)| **k == 1).map(|(_, v)| *v).collect();
+        assert_eq!(ones, vec![1, 4]);
+    }
 }
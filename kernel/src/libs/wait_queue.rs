@@ -20,6 +20,9 @@ struct InnerWaitQueue {
 }
 
 /// 被自旋锁保护的等待队列
+///
+/// 等待队列保证先进先出：进程按照开始等待的先后顺序被加入队列尾部，`wakeup`、`wakeup_n`
+/// 总是从队列头部开始唤醒，因此最早开始等待的进程最先被唤醒，不会被后来的进程反复插队而饿死
 #[derive(Debug)]
 pub struct WaitQueue(SpinLock<InnerWaitQueue>);
 
@@ -79,6 +82,18 @@ impl WaitQueue {
         drop(guard);
     }
 
+    /// @brief 将当前进程加入等待队列，但是不改变进程的状态，也不调用调度函数。
+    ///
+    /// 用于同时在多个等待队列上等待的场景：调用者先通过`ProcessManager::mark_sleep`将当前进程标记为睡眠，
+    /// 再将它加入每一个等待队列，最后手动调用调度函数。
+    ///
+    /// 执行本函数前，需要确保处于【中断禁止】状态。
+    pub unsafe fn add_current_without_sleep(&self) {
+        // 安全检查：确保当前处于中断禁止状态
+        assert!(CurrentIrqArch::is_irq_enabled() == false);
+        self.0.lock().wait_list.push_back(ProcessManager::current_pcb());
+    }
+
     pub unsafe fn sleep_without_schedule_uninterruptible(&self) {
         // 安全检查：确保当前处于中断禁止状态
         assert!(CurrentIrqArch::is_irq_enabled() == false);
@@ -103,23 +118,14 @@ impl WaitQueue {
     }
 
     /// @brief 让当前进程在等待队列上进行等待，并且，允许被信号打断。
-    /// 在当前进程的pcb加入队列后，解锁指定的自旋锁。
-    pub fn sleep_unlock_spinlock<T>(&self, to_unlock: SpinLockGuard<T>) {
-        let mut guard: SpinLockGuard<InnerWaitQueue> = self.0.lock();
-        let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
-        ProcessManager::mark_sleep(true).unwrap_or_else(|e| {
-            panic!("sleep error: {:?}", e);
-        });
-        drop(irq_guard);
-        guard.wait_list.push_back(ProcessManager::current_pcb());
-        drop(to_unlock);
-        drop(guard);
-        sched();
-    }
-
-    /// @brief 让当前进程在等待队列上进行等待，并且，允许被信号打断。
-    /// 在当前进程的pcb加入队列后，解锁指定的Mutex。
-    pub fn sleep_unlock_mutex<T>(&self, to_unlock: MutexGuard<T>) {
+    /// 在当前进程的pcb加入队列后，才解锁指定的锁，最后进行调度。
+    ///
+    /// 用于“在自旋锁内检查条件，条件不满足则释放锁并睡眠”的场景：
+    /// 唤醒者必须持有同一把锁才能修改条件，而当前进程在释放锁之前已经成为等待者，
+    /// 因此在释放锁与睡眠之间发生的唤醒不会丢失
+    ///
+    /// `to_unlock`可以是任意锁的守卫（如`SpinLockGuard`、`MutexGuard`），它在这里被drop从而释放锁
+    pub fn sleep_unlock<G>(&self, to_unlock: G) {
         let mut guard: SpinLockGuard<InnerWaitQueue> = self.0.lock();
         let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
         ProcessManager::mark_sleep(true).unwrap_or_else(|e| {
@@ -172,20 +178,46 @@ impl WaitQueue {
     /// @return true 成功唤醒进程
     /// @return false 没有唤醒进程
     pub fn wakeup(&self, state: Option<ProcessState>) -> bool {
+        return self.wakeup_n(1, state) == 1;
+    }
+
+    /// @brief 按照开始等待的先后顺序，唤醒队列头部的至多n个进程。
+    /// 遇到state不符合条件的进程时停止，不会越过它去唤醒后面的进程。
+    ///
+    /// 被信号打断的睡眠会在队列中留下已经不再睡眠的项，这些项被直接移出队列，不计入唤醒的进程数，
+    /// 从而不会占用唤醒的名额，导致真正在睡眠的进程错过唤醒。
+    ///
+    /// @param n 最多唤醒的进程数
+    /// @param state 用于判断的state，如果队列头部的进程与这个state相同，或者为None(表示不进行这个判断)，则唤醒这个进程。
+    ///
+    /// @return 成功唤醒的进程数
+    pub fn wakeup_n(&self, n: usize, state: Option<ProcessState>) -> usize {
         let mut guard: SpinLockGuard<InnerWaitQueue> = self.0.lock();
-        // 如果队列为空，则返回
-        if guard.wait_list.is_empty() {
-            return false;
-        }
-        // 如果队列头部的pcb的state与给定的state相与，结果不为0，则唤醒
-        if let Some(state) = state {
-            if guard.wait_list.front().unwrap().sched_info().state() != state {
-                return false;
+        let mut woken = 0;
+        while woken < n {
+            // 如果队列为空，则返回
+            let front = match guard.wait_list.front() {
+                Some(front) => front,
+                None => break,
+            };
+            let front_state = front.sched_info().state();
+            if !front_state.is_blocked() {
+                // 已经不再睡眠的进程留下的过时的项
+                guard.wait_list.pop_front();
+                continue;
+            }
+            // 如果队列头部的pcb的state与给定的state相与，结果不为0，则唤醒
+            if let Some(state) = state {
+                if front_state != state {
+                    break;
+                }
+            }
+            let to_wakeup = guard.wait_list.pop_front().unwrap();
+            if let Ok(true) = ProcessManager::try_to_wake_up(&to_wakeup) {
+                woken += 1;
             }
         }
-        let to_wakeup = guard.wait_list.pop_front().unwrap();
-        let res = ProcessManager::wakeup(&to_wakeup).is_ok();
-        return res;
+        return woken;
     }
 
     /// @brief 唤醒在队列中，符合条件的所有进程。
@@ -227,3 +259,90 @@ impl InnerWaitQueue {
         wait_list: LinkedList::new(),
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        hint::spin_loop,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use alloc::{boxed::Box, collections::VecDeque, format, string::ToString, sync::Arc, vec::Vec};
+
+    use crate::{
+        libs::spinlock::SpinLock,
+        process::{
+            kthread::{KernelThreadClosure, KernelThreadMechanism},
+            Pid, ProcessControlBlock,
+        },
+    };
+
+    use super::WaitQueue;
+
+    fn waiting_pids(wq: &WaitQueue) -> Vec<Pid> {
+        return wq.0.lock().wait_list.iter().map(|pcb| pcb.pid()).collect();
+    }
+
+    /// 进程按照开始等待的先后顺序被唤醒
+    #[test]
+    fn test_wakeup_fifo() {
+        let wq = WaitQueue::INIT;
+        let waiters: Vec<Arc<ProcessControlBlock>> = (0..4)
+            .map(|i| {
+                KernelThreadMechanism::create(
+                    KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                    format!("wq-fifo-{}", i),
+                )
+                .unwrap()
+            })
+            .collect();
+        let pids: Vec<Pid> = waiters.iter().map(|pcb| pcb.pid()).collect();
+        for pcb in waiters.iter() {
+            wq.0.lock().wait_list.push_back(pcb.clone());
+        }
+
+        assert!(wq.wakeup(None));
+        assert_eq!(waiting_pids(&wq), pids[1..].to_vec());
+        assert_eq!(wq.wakeup_n(2, None), 2);
+        assert_eq!(waiting_pids(&wq), pids[3..].to_vec());
+        assert_eq!(wq.wakeup_n(2, None), 1);
+        assert!(waiting_pids(&wq).is_empty());
+        assert!(!wq.wakeup(None));
+    }
+
+    /// 消费者在锁内发现缓冲区为空后，原子地释放锁并睡眠，生产者在释放锁之后的唤醒不会丢失。
+    /// 如果释放锁与加入等待队列之间存在窗口，消费者可能错过唤醒而永远睡眠
+    #[test]
+    fn test_sleep_unlock_producer_consumer() {
+        const ITEMS: usize = 64;
+        let buffer: Arc<SpinLock<VecDeque<usize>>> = Arc::new(SpinLock::new(VecDeque::new()));
+        let wq = Arc::new(WaitQueue::INIT);
+        let consumed = Arc::new(AtomicUsize::new(0));
+
+        let (b, w, c) = (buffer.clone(), wq.clone(), consumed.clone());
+        let consumer = move || loop {
+            let mut guard = b.lock();
+            while guard.pop_front().is_some() {
+                c.fetch_add(1, Ordering::SeqCst);
+            }
+            if c.load(Ordering::SeqCst) == ITEMS {
+                return 0;
+            }
+            w.sleep_unlock(guard);
+        };
+        KernelThreadMechanism::create_and_run(
+            KernelThreadClosure::EmptyClosure((Box::new(consumer), ())),
+            "wq-consumer".to_string(),
+        )
+        .unwrap();
+
+        for i in 0..ITEMS {
+            buffer.lock().push_back(i);
+            wq.wakeup(None);
+        }
+        while consumed.load(Ordering::SeqCst) < ITEMS {
+            spin_loop();
+        }
+        assert!(buffer.lock().is_empty());
+    }
+}
@@ -83,6 +83,20 @@ impl<T> PerCpuVar<T> {
         let cpu_id = smp_get_processor_id();
         &mut self.inner[cpu_id as usize]
     }
+
+    /// 获取指定cpu（而不是当前cpu）的变量
+    ///
+    /// 调用者需要自行保证对其他cpu的变量的访问是并发安全的
+    pub fn force_get(&self, cpu_id: usize) -> &T {
+        &self.inner[cpu_id]
+    }
+
+    /// 获取指定cpu（而不是当前cpu）的变量的可变引用
+    ///
+    /// 调用者需要自行保证对其他cpu的变量的访问是并发安全的
+    pub fn force_get_mut(&mut self, cpu_id: usize) -> &mut T {
+        &mut self.inner[cpu_id]
+    }
 }
 
 /// PerCpu变量是线程安全的，因为每个CPU都有自己的变量。
@@ -5,7 +5,7 @@ use core::{
     hash::Hasher,
     intrinsics::unlikely,
     ops::Add,
-    sync::atomic::{compiler_fence, Ordering},
+    sync::atomic::{compiler_fence, AtomicUsize, Ordering},
 };
 
 use alloc::{
@@ -20,10 +20,10 @@ use crate::{
     exception::InterruptArch,
     libs::{
         align::page_align_up,
-        rwlock::{RwLock, RwLockWriteGuard},
+        rwlock::RwLock,
         spinlock::{SpinLock, SpinLockGuard},
     },
-    process::ProcessManager,
+    process::{resource::RLimitID, ProcessManager},
     syscall::SystemError,
 };
 
@@ -118,6 +118,13 @@ pub struct InnerAddressSpace {
     pub end_code: VirtAddr,
     pub start_data: VirtAddr,
     pub end_data: VirtAddr,
+
+    /// 地址空间中已映射的物理页数，即进程的常驻内存（RSS）页数
+    ///
+    /// 与Linux的惯例相同，被多个地址空间共享的页在每个地址空间中都会被计入
+    rss: AtomicUsize,
+    /// 地址空间中所有映射的总页数，受RLIMIT_AS限制
+    total_vm: AtomicUsize,
 }
 
 impl InnerAddressSpace {
@@ -135,6 +142,8 @@ impl InnerAddressSpace {
             end_code: VirtAddr(0),
             start_data: VirtAddr(0),
             end_data: VirtAddr(0),
+            rss: AtomicUsize::new(0),
+            total_vm: AtomicUsize::new(0),
         };
         if create_stack {
             // kdebug!("to create user stack.");
@@ -178,6 +187,14 @@ impl InnerAddressSpace {
                 (),
             )?;
             new_guard.mappings.vmas.insert(new_vma.clone());
+            new_guard.rss.fetch_add(
+                vma_guard.region.size() / MMArch::PAGE_SIZE,
+                Ordering::SeqCst,
+            );
+            new_guard.total_vm.fetch_add(
+                vma_guard.region.size() / MMArch::PAGE_SIZE,
+                Ordering::SeqCst,
+            );
             // kdebug!("new vma: {:x?}", new_vma);
             let mut new_vma_guard = new_vma.lock();
             for page in new_vma_guard.pages().map(|p| p.virt_address()) {
@@ -227,6 +244,37 @@ impl InnerAddressSpace {
         return self.user_mapper.utable.is_current();
     }
 
+    /// 获取地址空间的常驻内存（RSS）页数
+    pub fn rss(&self) -> usize {
+        return self.rss.load(Ordering::SeqCst);
+    }
+
+    /// 获取地址空间中所有映射的总页数
+    pub fn total_vm(&self) -> usize {
+        return self.total_vm.load(Ordering::SeqCst);
+    }
+
+    /// 检查地址空间再映射`page_count`页之后，是否仍然在当前进程的RLIMIT_AS软限制以内
+    ///
+    /// 与Linux相同，即使拥有`CAP_SYS_RESOURCE`能力，也受自己的限制约束，除非先提高限制
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：超出限制时，返回`ENOMEM`
+    fn may_expand_vm(&self, page_count: PageFrameCount) -> Result<(), SystemError> {
+        // 进程管理初始化之前创建的地址空间不受限制
+        if !ProcessManager::initialized() {
+            return Ok(());
+        }
+        let limit = ProcessManager::current_pcb().rlimit(RLimitID::As).cur;
+        let total = (self.total_vm() + page_count.data()) as u64 * MMArch::PAGE_SIZE as u64;
+        if total > limit {
+            return Err(SystemError::ENOMEM);
+        }
+        return Ok(());
+    }
+
     /// 进行匿名页映射
     ///
     /// ## 参数
@@ -319,6 +367,7 @@ impl InnerAddressSpace {
             return Err(SystemError::EINVAL);
         }
         // kdebug!("mmap: addr: {addr:?}, page_count: {page_count:?}, prot_flags: {prot_flags:?}, map_flags: {map_flags:?}");
+        self.may_expand_vm(page_count)?;
 
         // 找到未使用的区域
         let region = match addr {
@@ -354,6 +403,8 @@ impl InnerAddressSpace {
             &mut self.user_mapper.utable,
             flusher,
         )?);
+        self.rss.fetch_add(page_count.data(), Ordering::SeqCst);
+        self.total_vm.fetch_add(page_count.data(), Ordering::SeqCst);
 
         return Ok(page);
     }
@@ -397,7 +448,10 @@ impl InnerAddressSpace {
                 self.mappings.insert_vma(after);
             }
 
+            let pages = r.lock().region().size() / MMArch::PAGE_SIZE;
             r.unmap(&mut self.user_mapper.utable, &mut flusher);
+            self.rss.fetch_sub(pages, Ordering::SeqCst);
+            self.total_vm.fetch_sub(pages, Ordering::SeqCst);
         }
 
         // TODO: 当引入后备页映射后，这里需要增加通知文件的逻辑
@@ -485,12 +539,36 @@ impl InnerAddressSpace {
         return self.user_stack.as_mut();
     }
 
-    /// 取消用户空间内的所有映射
+    /// 用户栈缺页时，向下扩展用户栈，使其覆盖发生缺页的地址
+    ///
+    /// ## 参数
+    ///
+    /// - `addr`：发生缺页的地址
+    /// - `limit`：用户栈大小的上限（RLIMIT_STACK的软限制）
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：没有用户栈，或者地址不在用户栈下方`UserStack::MAX_GROW_GAP`的范围以内时，返回`EFAULT`；
+    /// 扩展后的大小超过上限时，返回`ENOMEM`
+    pub fn grow_user_stack(&mut self, addr: VirtAddr, limit: usize) -> Result<(), SystemError> {
+        let mut stack = self.user_stack.take().ok_or(SystemError::EFAULT)?;
+        let r = stack.grow_to(self, addr, limit);
+        self.user_stack = Some(stack);
+        return r;
+    }
+
+    /// 取消用户空间内的所有映射，并释放它们占用的物理页
+    ///
+    /// 被取消映射的VMA同时从地址空间中移除，因此可以被重复调用
     pub unsafe fn unmap_all(&mut self) {
         let mut flusher: PageFlushAll<MMArch> = PageFlushAll::new();
-        for vma in self.mappings.iter_vmas() {
+        let mappings = core::mem::take(&mut self.mappings);
+        for vma in mappings.iter_vmas() {
             vma.unmap(&mut self.user_mapper.utable, &mut flusher);
         }
+        self.rss.store(0, Ordering::SeqCst);
+        self.total_vm.store(0, Ordering::SeqCst);
     }
 
     /// 设置进程的堆的内存空间
@@ -1182,6 +1260,9 @@ impl UserStack {
     pub const DEFAULT_USER_STACK_SIZE: usize = 8 * 1024 * 1024;
     /// 用户栈的保护页数量
     pub const GUARD_PAGES_NUM: usize = 4;
+    /// 触发用户栈扩展的缺页地址与用户栈最低地址之间的最大距离（与Linux的stack_guard_gap相同，为256页），
+    /// 更远的缺页被认为是非法访问，而不是栈的增长
+    pub const MAX_GROW_GAP: usize = 256 * MMArch::PAGE_SIZE;
 
     /// 创建一个用户栈
     pub fn new(
@@ -1277,10 +1358,9 @@ impl UserStack {
     ///
     /// - **Ok(())** 扩展成功
     /// - **Err(SystemError)** 扩展失败
-    #[allow(dead_code)]
     pub fn extend(
         &mut self,
-        vm: &mut RwLockWriteGuard<InnerAddressSpace>,
+        vm: &mut InnerAddressSpace,
         mut bytes: usize,
     ) -> Result<(), SystemError> {
         let prot_flags = ProtFlags::PROT_READ | ProtFlags::PROT_WRITE | ProtFlags::PROT_EXEC;
@@ -1300,6 +1380,36 @@ impl UserStack {
         return Ok(());
     }
 
+    /// 向下扩展用户栈，使其覆盖地址`addr`
+    ///
+    /// ## 参数
+    ///
+    /// - `vm` 用户地址空间结构体
+    /// - `addr` 要覆盖的地址，必须低于用户栈当前的最低地址，且与它的距离不超过`MAX_GROW_GAP`
+    /// - `limit` 用户栈大小（不包括保护页）的上限
+    ///
+    /// ## 返回值
+    ///
+    /// - **Ok(())** 扩展成功
+    /// - **Err(SystemError::EFAULT)** 地址不在用户栈下方`MAX_GROW_GAP`的范围以内
+    /// - **Err(SystemError::ENOMEM)** 扩展后的大小超过上限
+    pub fn grow_to(
+        &mut self,
+        vm: &mut InnerAddressSpace,
+        addr: VirtAddr,
+        limit: usize,
+    ) -> Result<(), SystemError> {
+        let lowest = self.lowest_addr();
+        if addr >= lowest || lowest - addr > Self::MAX_GROW_GAP {
+            return Err(SystemError::EFAULT);
+        }
+        let bytes = page_align_up(lowest - addr);
+        if self.stack_size().saturating_add(bytes) > limit {
+            return Err(SystemError::ENOMEM);
+        }
+        return self.extend(vm, bytes);
+    }
+
     /// 获取栈顶地址
     ///
     /// 请注意，如果用户栈的栈顶地址发生变化，这个值可能不会实时更新！
@@ -1320,6 +1430,11 @@ impl UserStack {
         };
     }
 
+    /// 获取用户栈已映射区域的最低地址，低于它的访问会触发用户栈的扩展
+    pub fn lowest_addr(&self) -> VirtAddr {
+        return self.stack_bottom - self.mapped_size;
+    }
+
     /// 获取当前用户栈的大小（不包括保护页）
     pub fn stack_size(&self) -> usize {
         return self.mapped_size - Self::GUARD_PAGES_NUM * MMArch::PAGE_SIZE;
@@ -0,0 +1,256 @@
+use alloc::sync::Arc;
+
+use crate::{
+    driver::base::block::SeekFrom,
+    filesystem::vfs::{
+        file::{File, FileMode},
+        FileType, IndexNode, ROOT_INODE,
+    },
+    kwarn,
+    libs::spinlock::SpinLock,
+    syscall::SystemError,
+    time::timer::clock,
+};
+
+use super::{ProcessControlBlock, ProcessManager};
+
+/// 记账记录中进程名的长度（包括结尾的'\0'）
+pub const ACCT_COMM: usize = 16;
+
+/// 开启进程记账时，记录被追加写入的文件
+static ACCT_FILE: SpinLock<Option<Arc<SpinLock<File>>>> = SpinLock::new(None);
+
+/// BSD风格的进程记账记录，每个进程退出时写入一条
+///
+/// 记录的长度固定为`AcctRecord::SIZE`字节，各字段按照声明的顺序以小端序依次存放
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcctRecord {
+    /// 进程名，超出`ACCT_COMM - 1`字节的部分被截断，剩余部分以'\0'填充
+    pub comm: [u8; ACCT_COMM],
+    /// 实际用户id
+    pub uid: u32,
+    /// 实际组id
+    pub gid: u32,
+    /// 进程在用户态运行的时间（单位：微秒）
+    pub utime: u64,
+    /// 进程在内核态运行的时间（单位：微秒）
+    pub stime: u64,
+    /// 进程从创建到退出经过的时间（单位：微秒）
+    pub elapsed: u64,
+    /// 进程的退出码
+    pub exit_code: u32,
+}
+
+impl AcctRecord {
+    /// 一条记录的字节数
+    pub const SIZE: usize = ACCT_COMM + 4 + 4 + 8 + 8 + 8 + 4;
+
+    /// 根据即将退出的进程生成记账记录
+    pub fn from_pcb(pcb: &ProcessControlBlock, exit_code: usize) -> Self {
+        let mut comm = [0u8; ACCT_COMM];
+        {
+            let basic = pcb.basic();
+            let name = basic.name().as_bytes();
+            let len = core::cmp::min(name.len(), ACCT_COMM - 1);
+            comm[..len].copy_from_slice(&name[..len]);
+        }
+
+        let cred = pcb.cred();
+        let sched_info = pcb.sched_info();
+        return Self {
+            comm,
+            uid: cred.uid as u32,
+            gid: cred.gid as u32,
+            utime: sched_info.utime(),
+            stime: sched_info.stime(),
+            elapsed: clock().saturating_sub(pcb.start_time()),
+            exit_code: exit_code as u32,
+        };
+    }
+
+    /// 将记录编码为写入记账文件的字节序列
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0u8; Self::SIZE];
+        let mut offset = 0;
+        let mut put = |bytes: &[u8]| {
+            buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+            offset += bytes.len();
+        };
+        put(&self.comm);
+        put(&self.uid.to_le_bytes());
+        put(&self.gid.to_le_bytes());
+        put(&self.utime.to_le_bytes());
+        put(&self.stime.to_le_bytes());
+        put(&self.elapsed.to_le_bytes());
+        put(&self.exit_code.to_le_bytes());
+        return buf;
+    }
+
+    /// 从记账文件中的字节序列解码出一条记录
+    pub fn from_bytes(buf: &[u8; Self::SIZE]) -> Self {
+        let mut offset = 0;
+        let comm = take_bytes(buf, &mut offset);
+        let uid = u32::from_le_bytes(take_bytes(buf, &mut offset));
+        let gid = u32::from_le_bytes(take_bytes(buf, &mut offset));
+        let utime = u64::from_le_bytes(take_bytes(buf, &mut offset));
+        let stime = u64::from_le_bytes(take_bytes(buf, &mut offset));
+        let elapsed = u64::from_le_bytes(take_bytes(buf, &mut offset));
+        let exit_code = u32::from_le_bytes(take_bytes(buf, &mut offset));
+        return Self {
+            comm,
+            uid,
+            gid,
+            utime,
+            stime,
+            elapsed,
+            exit_code,
+        };
+    }
+}
+
+/// 从`buf`的`offset`处取出`N`个字节，并将`offset`向后移动
+fn take_bytes<const N: usize>(buf: &[u8], offset: &mut usize) -> [u8; N] {
+    let mut bytes = [0u8; N];
+    bytes.copy_from_slice(&buf[*offset..*offset + N]);
+    *offset += N;
+    return bytes;
+}
+
+impl ProcessManager {
+    /// 开启进程记账，此后每个进程退出时都会向`path`指定的文件追加一条记账记录
+    ///
+    /// 如果记账已经开启，那么改为写入新的文件
+    ///
+    /// ## 错误
+    ///
+    /// - `ENOENT` : 文件不存在
+    /// - `EACCES` : 文件不是普通文件
+    pub fn acct_on(path: &str) -> Result<(), SystemError> {
+        let inode = ROOT_INODE().lookup(path)?;
+        return Self::acct_on_inode(inode);
+    }
+
+    fn acct_on_inode(inode: Arc<dyn IndexNode>) -> Result<(), SystemError> {
+        if inode.metadata()?.file_type != FileType::File {
+            return Err(SystemError::EACCES);
+        }
+        let file = File::new(inode, FileMode::O_WRONLY | FileMode::O_APPEND)?;
+        let old = ACCT_FILE.lock().replace(Arc::new(SpinLock::new(file)));
+        // 在释放锁之后再关闭旧的记账文件
+        drop(old);
+        return Ok(());
+    }
+
+    /// 关闭进程记账
+    pub fn acct_off() {
+        let old = ACCT_FILE.lock().take();
+        drop(old);
+    }
+
+    /// 进程记账是否已经开启
+    pub fn acct_enabled() -> bool {
+        return ACCT_FILE.lock().is_some();
+    }
+
+    /// 进程退出时，如果开启了进程记账，那么向记账文件追加一条该进程的记录
+    ///
+    /// 写入失败时只打印警告，不影响进程的退出
+    pub(super) fn acct_process(pcb: &ProcessControlBlock, exit_code: usize) {
+        let file = match ACCT_FILE.lock().clone() {
+            Some(file) => file,
+            None => return,
+        };
+        let record = AcctRecord::from_pcb(pcb, exit_code).to_bytes();
+        let mut guard = file.lock();
+        let r = guard
+            .lseek(SeekFrom::SeekEnd(0))
+            .and_then(|_| guard.write(record.len(), &record));
+        if let Err(e) = r {
+            kwarn!("pid {:?}: failed to write acct record: {:?}", pcb.pid(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, string::ToString};
+
+    use crate::{
+        filesystem::{
+            ramfs::RamFS,
+            vfs::{
+                file::{File, FileMode},
+                syscall::ModeType,
+                FileSystem, FileType,
+            },
+        },
+        process::{
+            cred::Credentials,
+            kthread::{KernelThreadClosure, KernelThreadMechanism},
+            ProcessManager,
+        },
+    };
+
+    use super::{AcctRecord, ACCT_COMM};
+
+    /// 开启记账后，每个退出的进程都向记账文件追加一条记录；关闭记账后不再写入
+    #[test]
+    fn test_acct_records_exit() {
+        let inode = RamFS::new()
+            .root_inode()
+            .create("acct", FileType::File, ModeType::from_bits_truncate(0o644))
+            .unwrap();
+        ProcessManager::acct_on_inode(inode.clone()).unwrap();
+        assert!(ProcessManager::acct_enabled());
+
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "acct-a-very-long-process-name".to_string(),
+        )
+        .unwrap();
+        pcb.set_cred(Credentials::user(1000, 100));
+        ProcessManager::acct_process(&pcb, 3);
+        ProcessManager::acct_process(&pcb, 4);
+        ProcessManager::acct_off();
+        ProcessManager::acct_process(&pcb, 5);
+        assert!(!ProcessManager::acct_enabled());
+
+        let mut file = File::new(inode, FileMode::O_RDONLY).unwrap();
+        let mut buf = [0u8; AcctRecord::SIZE * 3];
+        assert_eq!(
+            file.read(buf.len(), &mut buf).unwrap(),
+            AcctRecord::SIZE * 2
+        );
+        for (i, exit_code) in [3, 4].iter().enumerate() {
+            let bytes = buf[i * AcctRecord::SIZE..(i + 1) * AcctRecord::SIZE]
+                .try_into()
+                .unwrap();
+            let record = AcctRecord::from_bytes(bytes);
+            assert_eq!(&record.comm[..ACCT_COMM - 1], &b"acct-a-very-lon"[..]);
+            assert_eq!(record.comm[ACCT_COMM - 1], 0);
+            assert_eq!(record.uid, 1000);
+            assert_eq!(record.gid, 100);
+            assert_eq!(record.exit_code, *exit_code);
+        }
+    }
+
+    /// 记录编码之后能够被完整地解码，各字段按照声明的顺序以小端序存放
+    #[test]
+    fn test_record_round_trip() {
+        let mut comm = [0u8; ACCT_COMM];
+        comm[..4].copy_from_slice(b"acct");
+        let record = AcctRecord {
+            comm,
+            uid: 1000,
+            gid: 100,
+            utime: 1,
+            stime: 2,
+            elapsed: 3,
+            exit_code: 4,
+        };
+        let bytes = record.to_bytes();
+        assert_eq!(&bytes[ACCT_COMM..ACCT_COMM + 4], &1000u32.to_le_bytes());
+        assert_eq!(&bytes[AcctRecord::SIZE - 4..], &4u32.to_le_bytes());
+        assert_eq!(AcctRecord::from_bytes(&bytes), record);
+    }
+}
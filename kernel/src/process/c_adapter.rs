@@ -1,4 +1,4 @@
-use crate::smp::core::smp_get_processor_id;
+use crate::{mm::VirtAddr, smp::core::smp_get_processor_id};
 
 use super::{kthread::kthread_init, process_init, ProcessManager, __PROCESS_MANAGEMENT_INIT_DONE};
 
@@ -59,6 +59,19 @@ pub extern "C" fn rs_preempt_enable() {
     return ProcessManager::preempt_enable();
 }
 
+/// 用户态缺页时，尝试扩展当前进程的用户栈
+///
+/// 只处理用户栈下方有限距离以内的缺页。成功时返回0；缺页不能通过扩展用户栈来处理时返回-1，
+/// 此时当前进程已经被发送了SIGSEGV
+#[no_mangle]
+pub extern "C" fn rs_user_stack_fault(addr: u64) -> i32 {
+    let pcb = ProcessManager::current_pcb();
+    return match ProcessManager::handle_user_stack_fault(&pcb, VirtAddr::new(addr as usize)) {
+        Ok(_) => 0,
+        Err(_) => -1,
+    };
+}
+
 #[no_mangle]
 pub extern "C" fn rs_process_do_exit(exit_code: usize) -> usize {
     ProcessManager::exit(exit_code);
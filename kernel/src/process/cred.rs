@@ -0,0 +1,128 @@
+bitflags! {
+    /// 进程的能力（capabilities），位的定义与Linux一致
+    pub struct CAPFlags: u64 {
+        const CAP_CHOWN = 1 << 0;
+        const CAP_DAC_OVERRIDE = 1 << 1;
+        const CAP_FOWNER = 1 << 3;
+        const CAP_KILL = 1 << 5;
+        const CAP_SETGID = 1 << 6;
+        const CAP_SETUID = 1 << 7;
+        const CAP_SYS_ADMIN = 1 << 21;
+        const CAP_SYS_NICE = 1 << 23;
+        const CAP_SYS_RESOURCE = 1 << 24;
+    }
+}
+
+/// 进程的身份凭证
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Credentials {
+    /// 实际用户id
+    pub uid: usize,
+    /// 有效用户id
+    pub euid: usize,
+    /// 保存的用户id
+    pub suid: usize,
+    /// 实际组id
+    pub gid: usize,
+    /// 有效组id
+    pub egid: usize,
+    /// 保存的组id
+    pub sgid: usize,
+    /// 进程当前拥有的能力
+    pub cap_effective: CAPFlags,
+}
+
+impl Credentials {
+    /// 创建一个拥有全部能力的root用户凭证
+    pub const fn root() -> Self {
+        Self {
+            uid: 0,
+            euid: 0,
+            suid: 0,
+            gid: 0,
+            egid: 0,
+            sgid: 0,
+            cap_effective: CAPFlags::all(),
+        }
+    }
+
+    /// 创建一个不拥有任何能力的普通用户凭证
+    pub const fn user(uid: usize, gid: usize) -> Self {
+        Self {
+            uid,
+            euid: uid,
+            suid: uid,
+            gid,
+            egid: gid,
+            sgid: gid,
+            cap_effective: CAPFlags::empty(),
+        }
+    }
+
+    /// 判断凭证是否拥有某项能力
+    pub fn has_capability(&self, cap: CAPFlags) -> bool {
+        return self.cap_effective.contains(cap);
+    }
+
+    /// 判断是否能够向拥有`target`凭证的进程发送信号（不考虑SIGCONT的会话例外）
+    ///
+    /// 拥有`CAP_KILL`能力，或者实际用户id、有效用户id等于接收者的实际用户id或保存的用户id时允许发送
+    pub fn can_signal(&self, target: &Credentials) -> bool {
+        if self.has_capability(CAPFlags::CAP_KILL) {
+            return true;
+        }
+        return [self.uid, self.euid]
+            .iter()
+            .any(|uid| *uid == target.uid || *uid == target.suid);
+    }
+
+    /// 判断是否能够修改拥有`target`凭证的进程的nice值
+    ///
+    /// 拥有`CAP_SYS_NICE`能力，或者有效用户id等于接收者的实际用户id或有效用户id时允许修改
+    pub fn can_renice(&self, target: &Credentials) -> bool {
+        return self.has_capability(CAPFlags::CAP_SYS_NICE)
+            || self.euid == target.uid
+            || self.euid == target.euid;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CAPFlags, Credentials};
+
+    /// 向其他用户的进程发送信号需要CAP_KILL能力；接收者的保存的用户id与发送者相同时同样允许
+    #[test]
+    fn test_can_signal() {
+        let user = Credentials::user(1000, 1000);
+        let other = Credentials::user(2000, 2000);
+        assert!(user.can_signal(&Credentials::user(1000, 1000)));
+        assert!(!user.can_signal(&other));
+        assert!(Credentials::root().can_signal(&other));
+
+        let mut saved = other;
+        saved.suid = 1000;
+        assert!(user.can_signal(&saved));
+
+        // 有效用户id同样可以作为发送者的身份
+        let mut setuid = Credentials::user(3000, 3000);
+        setuid.euid = 2000;
+        assert!(setuid.can_signal(&other));
+
+        let mut killer = user;
+        killer.cap_effective = CAPFlags::CAP_KILL;
+        assert!(killer.can_signal(&other));
+    }
+
+    /// 没有CAP_SYS_NICE能力时，只能修改有效用户id与自己相同的进程的nice值
+    #[test]
+    fn test_can_renice() {
+        let user = Credentials::user(1000, 1000);
+        assert!(user.can_renice(&Credentials::user(1000, 1000)));
+        assert!(!user.can_renice(&Credentials::user(1001, 1001)));
+        assert!(Credentials::root().can_renice(&Credentials::user(1001, 1001)));
+
+        let mut target = Credentials::user(1001, 1001);
+        target.euid = 1000;
+        assert!(user.can_renice(&target));
+    }
+}
@@ -2,7 +2,7 @@ use alloc::{string::ToString, sync::Arc};
 
 use crate::{
     arch::interrupt::TrapFrame, filesystem::procfs::procfs_register_pid, libs::rwlock::RwLock,
-    process::ProcessFlags, syscall::SystemError,
+    mm::VirtAddr, process::ProcessFlags, sched::core::sched_fork, syscall::SystemError,
 };
 
 use super::{
@@ -27,6 +27,8 @@ bitflags! {
         const CLONE_THREAD = (1 << 5);
         /// 共享打开的文件
         const CLONE_FILES = (1 << 6);
+        /// 使用传入的地址设置新线程的线程局部存储（TLS）
+        const CLONE_SETTLS = (1 << 7);
     }
 }
 
@@ -49,13 +51,31 @@ impl ProcessManager {
     pub fn fork(
         current_trapframe: &mut TrapFrame,
         clone_flags: CloneFlags,
+    ) -> Result<Pid, SystemError> {
+        return Self::clone(current_trapframe, clone_flags, VirtAddr::new(0));
+    }
+
+    /// 创建一个新进程，与`fork`相同，但是可以指定新线程的线程局部存储
+    ///
+    /// ## 参数
+    ///
+    /// - `current_trapframe`: 当前进程的trapframe
+    /// - `clone_flags`: 进程克隆标志
+    /// - `tls`: 新线程的线程局部存储的地址，仅在指定了`CLONE_SETTLS`时生效
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：返回新进程的pid
+    /// - 失败：返回Err(SystemError)，失败的话，子线程不会执行。
+    pub fn clone(
+        current_trapframe: &mut TrapFrame,
+        clone_flags: CloneFlags,
+        tls: VirtAddr,
     ) -> Result<Pid, SystemError> {
         let current_pcb = ProcessManager::current_pcb();
         let new_kstack = KernelStack::new()?;
         let name = current_pcb.basic().name().to_string();
-        let pcb = ProcessControlBlock::new(name, new_kstack);
-        let mut se = pcb.sched_info().se();
-        se.set_pcb(pcb);
+        let pcb = ProcessControlBlock::new(name, new_kstack)?;
 
         // 克隆架构相关信息
         *pcb.arch_info() = current_pcb.arch_info_irqsave().clone();
@@ -73,6 +93,11 @@ impl ProcessManager {
             )
         });
 
+        // 创建线程时，新线程与当前线程属于同一个线程组
+        if clone_flags.contains(CloneFlags::CLONE_THREAD) {
+            pcb.set_tgid(current_pcb.tgid());
+        }
+
         // 拷贝用户地址空间
         ProcessManager::copy_mm(&clone_flags, &current_pcb, &pcb).unwrap_or_else(|e| {
             panic!(
@@ -92,14 +117,18 @@ impl ProcessManager {
         // todo: 拷贝信号相关数据
 
         // 拷贝线程
-        ProcessManager::copy_thread(&clone_flags, &current_pcb, &pcb, &current_trapframe).unwrap_or_else(|e| {
+        ProcessManager::copy_thread(&clone_flags, &current_pcb, &pcb, &current_trapframe, tls).unwrap_or_else(|e| {
             panic!(
                 "fork: Failed to copy thread from current process, current pid: [{:?}], new pid: [{:?}]. Error: {:?}",
                 current_pcb.pid(), pcb.pid(), e
             )
         });
 
-        ProcessManager::add_pcb(pcb.clone());
+        // 确定子进程的初始虚拟运行时间
+        sched_fork(&current_pcb, &pcb);
+
+        // 加入进程表失败时，新进程还没有被任何人看到，pcb被析构时会归还pid
+        ProcessManager::add_pcb(pcb.clone())?;
 
         // 向procfs注册进程
         procfs_register_pid(pcb.pid()).unwrap_or_else(|e| {
@@ -110,7 +139,7 @@ impl ProcessManager {
             )
         });
 
-        ProcessManager::wakeup(&pcb).unwrap_or_else(|e| {
+        ProcessManager::wakeup_new(&pcb).unwrap_or_else(|e| {
             panic!(
                 "fork: Failed to wakeup new process, pid: [{:?}]. Error: {:?}",
                 pcb.pid(),
@@ -161,6 +190,8 @@ impl ProcessManager {
 
         if clone_flags.contains(CloneFlags::CLONE_VM) {
             unsafe { new_pcb.basic_mut().set_user_vm(Some(old_address_space)) };
+            current_pcb.mark_shared(CloneFlags::CLONE_VM);
+            new_pcb.mark_shared(CloneFlags::CLONE_VM);
             return Ok(());
         }
 
@@ -184,12 +215,15 @@ impl ProcessManager {
             let new_fd_table = current_pcb.basic().fd_table().unwrap().read().clone();
             let new_fd_table = Arc::new(RwLock::new(new_fd_table));
             new_pcb.basic_mut().set_fd_table(Some(new_fd_table));
+            return Ok(());
         }
 
-        // 如果共享文件描述符表，则直接拷贝指针
+        // 如果共享文件描述符表，则直接拷贝指针，并且两个进程都记录下共享关系
         new_pcb
             .basic_mut()
             .set_fd_table(current_pcb.basic().fd_table().clone());
+        current_pcb.mark_shared(CloneFlags::CLONE_FILES);
+        new_pcb.mark_shared(CloneFlags::CLONE_FILES);
 
         return Ok(());
     }
@@ -204,3 +238,61 @@ impl ProcessManager {
         return Ok(());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, string::ToString};
+
+    use crate::{
+        arch::interrupt::TrapFrame,
+        mm::VirtAddr,
+        process::{
+            kthread::{KernelThreadClosure, KernelThreadMechanism},
+            ProcessManager,
+        },
+    };
+
+    use super::CloneFlags;
+
+    /// 指定CLONE_SETTLS时，新线程的TLS寄存器被设置为传入的地址，并在它第一次运行时被恢复
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_clone_settls() {
+        let current = ProcessManager::current_pcb();
+        let tls = VirtAddr::new(0x7fff_0000_1000);
+        let new_pcb = |name: &str| {
+            KernelThreadMechanism::create(
+                KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                name.to_string(),
+            )
+            .unwrap()
+        };
+
+        let with_tls = new_pcb("clone-settls");
+        ProcessManager::copy_thread(
+            &CloneFlags::CLONE_SETTLS,
+            &current,
+            &with_tls,
+            &TrapFrame::new(),
+            tls,
+        )
+        .unwrap();
+        // 切换到新线程时，switch_process从arch info中恢复fsbase
+        assert_eq!(with_tls.arch_info_irqsave().fsbase(), tls.data());
+
+        // 没有CLONE_SETTLS时，新线程继承当前线程的TLS
+        let inherited = new_pcb("clone-no-settls");
+        ProcessManager::copy_thread(
+            &CloneFlags::empty(),
+            &current,
+            &inherited,
+            &TrapFrame::new(),
+            tls,
+        )
+        .unwrap();
+        assert_eq!(
+            inherited.arch_info_irqsave().fsbase(),
+            current.arch_info_irqsave().fsbase()
+        );
+    }
+}
@@ -45,7 +45,7 @@ impl ProcessManager {
                 })
             };
 
-            let idle_pcb = ProcessControlBlock::new_idle(smp_get_processor_id(), kstack);
+            let idle_pcb = ProcessControlBlock::new_idle(i as u32, kstack);
 
             assert!(idle_pcb.basic().user_vm().is_none());
             unsafe {
@@ -56,6 +56,9 @@ impl ProcessManager {
 
             assert!(idle_pcb.sched_info().on_cpu().is_none());
             idle_pcb.sched_info().set_on_cpu(Some(i as u32));
+            // 每个cpu最初运行的是它的idle进程。内核栈只持有pcb的弱引用，
+            // 由当前进程的缓存持有强引用，使得正在运行的进程不会被析构
+            Self::set_current_pcb_cache(i, &idle_pcb);
             v.push(idle_pcb);
         }
 
@@ -72,8 +75,39 @@ impl ProcessManager {
         return VirtAddr::new(x86::current::registers::rsp() as usize);
     }
 
-    /// 获取idle进程数组的引用
-    pub fn idle_pcb() -> &'static Vec<Arc<ProcessControlBlock>> {
-        unsafe { __IDLE_PCB.as_ref().unwrap() }
+    /// 获取每个核的idle进程
+    ///
+    /// ## 返回值
+    ///
+    /// 以cpu id为下标的idle进程的pcb数组，长度为`PerCpu::MAX_CPU_NUM`
+    ///
+    /// ## Panics
+    ///
+    /// 如果idle进程尚未通过`init_idle`初始化，则panic
+    pub fn idle_pcb() -> &'static [Arc<ProcessControlBlock>] {
+        unsafe {
+            __IDLE_PCB
+                .as_ref()
+                .expect("Idle process has not been initialized")
+                .as_slice()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{mm::percpu::PerCpu, process::Pid};
+
+    use super::ProcessManager;
+
+    /// 每个cpu都应当有且只有一个绑定在该cpu上的idle进程
+    #[test]
+    fn test_idle_pcb_per_cpu() {
+        let idle = ProcessManager::idle_pcb();
+        assert_eq!(idle.len(), PerCpu::MAX_CPU_NUM);
+        for (i, pcb) in idle.iter().enumerate() {
+            assert_eq!(pcb.pid(), Pid::new(0));
+            assert_eq!(pcb.sched_info().on_cpu(), Some(i as u32));
+        }
     }
 }
@@ -1,18 +1,30 @@
 //! 这个文件内放置初始内核线程的代码。
 
-use alloc::string::String;
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
 
 use crate::{
     arch::process::arch_switch_to_user,
     driver::{disk::ahci::ahci_init, virtio::virtio::virtio_probe},
     filesystem::vfs::core::mount_root_fs,
     kdebug, kerror,
+    libs::spinlock::SpinLock,
     net::net_core::net_init,
-    process::{kthread::KernelThreadMechanism, process::stdio_init},
+    process::{
+        kthread::{KernelThreadMechanism, KernelWorker},
+        process::stdio_init,
+        Pid, ProcessManager,
+    },
 };
 
+/// 回收孤儿进程退出状态的内核线程
+static ZOMBIE_REAPER: SpinLock<Option<Arc<KernelWorker>>> = SpinLock::new(None);
+
 pub fn initial_kernel_thread() -> i32 {
     KernelThreadMechanism::init_stage2();
+    zombie_reaper_init();
     // 由于目前加锁，速度过慢，所以先不开启双缓冲
     // scm_enable_double_buffer().expect("Failed to enable double buffer");
     stdio_init().expect("Failed to initialize stdio");
@@ -34,6 +46,38 @@ pub fn initial_kernel_thread() -> i32 {
     loop {}
 }
 
+/// 创建回收孤儿进程退出状态的内核线程
+///
+/// 父进程退出后，子进程被init进程收养，但init进程不一定会等待它们。被收养的孤儿进程退出时向init进程发送SIGCHLD，
+/// 回收线程随之被唤醒，代替init进程以WNOHANG的方式等待它们。init进程阻塞SIGCHLD时，由它自己等待。
+/// 回收线程在没有孤儿进程退出时睡眠
+fn zombie_reaper_init() {
+    let pcb = ProcessManager::create_kthread("zombie_reaper".to_string(), reap_orphans)
+        .expect("Failed to create zombie reaper");
+    let worker = pcb
+        .worker_private()
+        .as_ref()
+        .and_then(|x| x.kernel_thread())
+        .and_then(|x| x.worker())
+        .expect("zombie reaper: worker is none");
+    ZOMBIE_REAPER.lock_irqsave().replace(worker);
+}
+
+/// 回收线程的工作函数
+fn reap_orphans() {
+    if let Some(init_pcb) = ProcessManager::find(Pid::new(1)) {
+        init_pcb.reap_orphans();
+    }
+}
+
+/// init进程收到SIGCHLD时调用，唤醒回收线程
+pub fn wake_zombie_reaper() {
+    let worker = ZOMBIE_REAPER.lock_irqsave().clone();
+    if let Some(worker) = worker {
+        worker.queue_work();
+    }
+}
+
 /// 切换到用户态
 fn switch_to_user() {
     let path = String::from("/bin/shell.elf");
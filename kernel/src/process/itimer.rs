@@ -0,0 +1,334 @@
+use alloc::{
+    boxed::Box,
+    sync::{Arc, Weak},
+};
+
+use crate::{
+    ipc::signal::Signal,
+    syscall::SystemError,
+    time::timer::{clock, Timer, TimerFunction},
+};
+
+use super::{ProcessControlBlock, ProcessManager};
+
+/// 间隔定时器的种类，编号与Linux一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum ITimerWhich {
+    /// 按照真实时间计时，到期时发送SIGALRM
+    Real = 0,
+    /// 按照进程在用户态运行的时间计时，到期时发送SIGVTALRM
+    Virtual = 1,
+    /// 按照进程在用户态与内核态运行的时间计时，到期时发送SIGPROF
+    Prof = 2,
+}
+
+impl ITimerWhich {
+    /// 定时器到期时发送的信号
+    pub fn signal(&self) -> Signal {
+        return match self {
+            ITimerWhich::Real => Signal::SIGALRM,
+            ITimerWhich::Virtual => Signal::SIGVTALRM,
+            ITimerWhich::Prof => Signal::SIGPROF,
+        };
+    }
+}
+
+impl TryFrom<usize> for ITimerWhich {
+    type Error = SystemError;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        return match value {
+            0 => Ok(ITimerWhich::Real),
+            1 => Ok(ITimerWhich::Virtual),
+            2 => Ok(ITimerWhich::Prof),
+            _ => Err(SystemError::EINVAL),
+        };
+    }
+}
+
+/// 间隔定时器的值（单位：微秒）
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ITimerVal {
+    /// 定时器到期后重新装载的值，为0表示只触发一次
+    pub interval_us: u64,
+    /// 距离定时器到期的时间，为0表示定时器没有启用
+    pub value_us: u64,
+}
+
+impl ITimerVal {
+    pub const fn new(interval_us: u64, value_us: u64) -> Self {
+        Self {
+            interval_us,
+            value_us,
+        }
+    }
+}
+
+/// 进程的间隔定时器
+#[derive(Debug, Default)]
+pub struct ITimers {
+    /// ITIMER_REAL到期的时刻（单位：jiffies），为0表示没有启用
+    real_expire: u64,
+    /// ITIMER_REAL的重新装载值（单位：微秒）
+    real_interval: u64,
+    /// 为ITIMER_REAL设置的内核定时器
+    real_timer: Option<Arc<Timer>>,
+    /// ITIMER_VIRTUAL
+    virt: ITimerVal,
+    /// ITIMER_PROF
+    prof: ITimerVal,
+}
+
+impl ITimers {
+    /// 按照运行时间计时的定时器
+    fn cpu_timer(&mut self, which: ITimerWhich) -> &mut ITimerVal {
+        return match which {
+            ITimerWhich::Virtual => &mut self.virt,
+            ITimerWhich::Prof => &mut self.prof,
+            ITimerWhich::Real => unreachable!("ITIMER_REAL is not a cpu timer"),
+        };
+    }
+
+    /// 获取定时器的当前值
+    ///
+    /// ## 参数
+    ///
+    /// - `which` : 定时器的种类
+    /// - `now` : 当前时刻（单位：jiffies），用于计算ITIMER_REAL的剩余时间
+    pub fn get(&mut self, which: ITimerWhich, now: u64) -> ITimerVal {
+        if which == ITimerWhich::Real {
+            let value = if self.real_expire == 0 {
+                0
+            } else {
+                // 已经到期但还没有被重新装载时，返回最小的非零值，表示定时器仍然启用
+                core::cmp::max(self.real_expire.saturating_sub(now), 1)
+            };
+            return ITimerVal::new(self.real_interval, value);
+        }
+        return *self.cpu_timer(which);
+    }
+
+    /// 将一段运行时间计入按照运行时间计时的定时器
+    ///
+    /// ## 返回值
+    ///
+    /// 定时器是否在这段时间内到期。到期后按照重新装载值继续计时，重新装载值为0时停止
+    pub fn tick(&mut self, which: ITimerWhich, delta_us: u64) -> bool {
+        let timer = self.cpu_timer(which);
+        if timer.value_us == 0 {
+            return false;
+        }
+        if timer.value_us > delta_us {
+            timer.value_us -= delta_us;
+            return false;
+        }
+        timer.value_us = timer.interval_us;
+        return true;
+    }
+}
+
+/// ITIMER_REAL到期时，由内核定时器执行的函数
+#[derive(Debug)]
+struct ITimerRealHelper {
+    pcb: Weak<ProcessControlBlock>,
+}
+
+impl TimerFunction for ITimerRealHelper {
+    fn run(&mut self) -> Result<(), SystemError> {
+        if let Some(pcb) = self.pcb.upgrade() {
+            ProcessManager::itimer_real_expired(&pcb);
+        }
+        return Ok(());
+    }
+}
+
+impl ProcessManager {
+    /// 设置进程的间隔定时器
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 要设置定时器的进程
+    /// - `which` : 定时器的种类
+    /// - `new` : 新的值，`value_us`为0表示停止定时器
+    ///
+    /// ## 返回值
+    ///
+    /// 定时器原来的值
+    pub fn setitimer(
+        pcb: &Arc<ProcessControlBlock>,
+        which: ITimerWhich,
+        new: ITimerVal,
+    ) -> Result<ITimerVal, SystemError> {
+        if pcb.sched_info().state().has_exited() {
+            return Err(SystemError::ESRCH);
+        }
+        let now = clock();
+        let mut itimers = pcb.itimers();
+        let old = itimers.get(which, now);
+        if which != ITimerWhich::Real {
+            *itimers.cpu_timer(which) = new;
+            return Ok(old);
+        }
+
+        if let Some(timer) = itimers.real_timer.take() {
+            timer.cancel();
+        }
+        itimers.real_interval = new.interval_us;
+        if new.value_us == 0 {
+            itimers.real_expire = 0;
+        } else {
+            itimers.real_expire = now + new.value_us;
+            itimers.real_timer = Some(Self::arm_itimer_real(pcb, itimers.real_expire));
+        }
+        return Ok(old);
+    }
+
+    /// 获取进程的间隔定时器的当前值
+    pub fn getitimer(pcb: &Arc<ProcessControlBlock>, which: ITimerWhich) -> ITimerVal {
+        return pcb.itimers().get(which, clock());
+    }
+
+    /// 时钟中断时，将这个时钟周期计入当前进程按照运行时间计时的定时器，并发送到期的定时器的信号
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 当前进程
+    /// - `user_mode` : 这个时钟周期是否运行在用户态
+    /// - `delta_us` : 时钟周期的长度（单位：微秒）
+    pub fn itimer_tick(pcb: &Arc<ProcessControlBlock>, user_mode: bool, delta_us: u64) {
+        let (virt_expired, prof_expired) = {
+            let mut itimers = pcb.itimers();
+            let virt = user_mode && itimers.tick(ITimerWhich::Virtual, delta_us);
+            (virt, itimers.tick(ITimerWhich::Prof, delta_us))
+        };
+        if virt_expired {
+            Self::send_signal_to_thread(pcb, ITimerWhich::Virtual.signal()).ok();
+        }
+        if prof_expired {
+            Self::send_signal_to_thread(pcb, ITimerWhich::Prof.signal()).ok();
+        }
+    }
+
+    /// ITIMER_REAL到期：发送SIGALRM，并按照重新装载值再次启动定时器
+    fn itimer_real_expired(pcb: &Arc<ProcessControlBlock>) {
+        {
+            let mut itimers = pcb.itimers();
+            itimers.real_timer = None;
+            if itimers.real_interval == 0 {
+                itimers.real_expire = 0;
+            } else {
+                itimers.real_expire = clock() + itimers.real_interval;
+                itimers.real_timer = Some(Self::arm_itimer_real(pcb, itimers.real_expire));
+            }
+        }
+        Self::send_signal_to_thread(pcb, ITimerWhich::Real.signal()).ok();
+    }
+
+    /// 创建并启动一个在`expire`时刻为进程触发ITIMER_REAL的内核定时器
+    fn arm_itimer_real(pcb: &Arc<ProcessControlBlock>, expire: u64) -> Arc<Timer> {
+        let timer = Timer::new(
+            Box::new(ITimerRealHelper {
+                pcb: Arc::downgrade(pcb),
+            }),
+            expire,
+        );
+        timer.activate();
+        return timer;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, string::ToString};
+
+    use crate::{
+        process::{
+            kthread::{KernelThreadClosure, KernelThreadMechanism},
+            ProcessManager,
+        },
+        time::timer::schedule_timeout,
+    };
+
+    use super::{ITimerVal, ITimerWhich};
+
+    /// 短暂的ITIMER_REAL到期后，SIGALRM成为待处理的信号，只触发一次的定时器随之停止
+    #[test]
+    fn test_itimer_real() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "itimer-real".to_string(),
+        )
+        .unwrap();
+        let old =
+            ProcessManager::setitimer(&pcb, ITimerWhich::Real, ITimerVal::new(0, 1000)).unwrap();
+        assert_eq!(old, ITimerVal::default());
+        let armed = ProcessManager::getitimer(&pcb, ITimerWhich::Real);
+        assert!(armed.value_us > 0 && armed.value_us <= 1000);
+
+        for _ in 0..100 {
+            if pcb
+                .sig_state()
+                .pending()
+                .contains(ITimerWhich::Real.signal().mask())
+            {
+                break;
+            }
+            schedule_timeout(1000).ok();
+        }
+        assert!(pcb
+            .sig_state()
+            .pending()
+            .contains(ITimerWhich::Real.signal().mask()));
+        assert_eq!(
+            ProcessManager::getitimer(&pcb, ITimerWhich::Real),
+            ITimerVal::default()
+        );
+    }
+
+    /// ITIMER_VIRTUAL只计入用户态的时间，ITIMER_PROF计入所有的运行时间，到期后按照重新装载值继续计时
+    #[test]
+    fn test_itimer_cpu() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "itimer-cpu".to_string(),
+        )
+        .unwrap();
+        ProcessManager::setitimer(&pcb, ITimerWhich::Virtual, ITimerVal::new(0, 10_000)).unwrap();
+        ProcessManager::setitimer(&pcb, ITimerWhich::Prof, ITimerVal::new(5_000, 10_000)).unwrap();
+
+        ProcessManager::itimer_tick(&pcb, false, 5_000);
+        ProcessManager::itimer_tick(&pcb, true, 5_000);
+        assert_eq!(
+            ProcessManager::getitimer(&pcb, ITimerWhich::Virtual),
+            ITimerVal::new(0, 5_000)
+        );
+        assert_eq!(
+            ProcessManager::getitimer(&pcb, ITimerWhich::Prof),
+            ITimerVal::new(5_000, 5_000)
+        );
+        assert!(pcb
+            .sig_state()
+            .pending()
+            .contains(ITimerWhich::Prof.signal().mask()));
+        assert!(!pcb
+            .sig_state()
+            .pending()
+            .contains(ITimerWhich::Virtual.signal().mask()));
+
+        ProcessManager::itimer_tick(&pcb, true, 5_000);
+        assert!(pcb
+            .sig_state()
+            .pending()
+            .contains(ITimerWhich::Virtual.signal().mask()));
+        assert_eq!(
+            ProcessManager::getitimer(&pcb, ITimerWhich::Virtual),
+            ITimerVal::default()
+        );
+
+        assert_eq!(
+            ITimerWhich::try_from(3),
+            Err(crate::syscall::SystemError::EINVAL)
+        );
+    }
+}
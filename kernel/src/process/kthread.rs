@@ -14,8 +14,8 @@ use atomic_enum::atomic_enum;
 use crate::{
     arch::{sched::sched, CurrentIrqArch},
     exception::InterruptArch,
-    kdebug, kinfo,
-    libs::{once::Once, spinlock::SpinLock},
+    kdebug, kinfo, kwarn,
+    libs::{once::Once, spinlock::SpinLock, wait_queue::WaitQueue},
     process::{ProcessManager, ProcessState},
     syscall::SystemError,
 };
@@ -61,6 +61,8 @@ bitflags! {
 #[derive(Debug)]
 pub struct KernelThreadPcbPrivate {
     flags: KernelThreadFlags,
+    /// 由`ProcessManager::create_kthread`创建的工作线程的数据
+    worker: Option<Arc<KernelWorker>>,
 }
 
 #[allow(dead_code)]
@@ -68,6 +70,7 @@ impl KernelThreadPcbPrivate {
     pub fn new() -> Self {
         Self {
             flags: KernelThreadFlags::empty(),
+            worker: None,
         }
     }
 
@@ -78,6 +81,59 @@ impl KernelThreadPcbPrivate {
     pub fn flags_mut(&mut self) -> &mut KernelThreadFlags {
         &mut self.flags
     }
+
+    pub fn worker(&self) -> Option<Arc<KernelWorker>> {
+        self.worker.clone()
+    }
+}
+
+/// 内核工作线程的数据
+///
+/// 工作线程循环执行工作函数，没有待处理的工作时，在自己的等待队列上睡眠
+#[derive(Debug)]
+pub struct KernelWorker {
+    /// 工作函数
+    func: fn(),
+    /// 待处理的工作数量
+    pending: SpinLock<usize>,
+    /// 没有待处理的工作时，工作线程在这个等待队列上睡眠
+    wait_queue: WaitQueue,
+}
+
+impl KernelWorker {
+    fn new(func: fn()) -> Arc<Self> {
+        return Arc::new(Self {
+            func,
+            // 工作线程启动后先执行一次工作函数
+            pending: SpinLock::new(1),
+            wait_queue: WaitQueue::INIT,
+        });
+    }
+
+    /// 增加一个待处理的工作，并唤醒工作线程
+    pub fn queue_work(&self) {
+        *self.pending.lock_irqsave() += 1;
+        self.wait_queue.wakeup(None);
+    }
+
+    /// 工作线程的主循环
+    fn run(&self) -> i32 {
+        let pcb = ProcessManager::current_pcb();
+        loop {
+            let mut pending = self.pending.lock_irqsave();
+            if KernelThreadMechanism::should_stop(&pcb) {
+                return 0;
+            }
+            if *pending == 0 {
+                // 在加入等待队列之后才释放锁，避免丢失唤醒
+                self.wait_queue.sleep_unlock(pending);
+                continue;
+            }
+            *pending -= 1;
+            drop(pending);
+            (self.func)();
+        }
+    }
 }
 
 /// 内核线程的闭包，参数必须与闭包的参数一致，返回值必须是i32
@@ -318,6 +374,22 @@ impl KernelThreadMechanism {
         return info.poll_result();
     }
 
+    /// 由当前内核线程直接创建一个新的内核线程
+    ///
+    /// 与`create`不同，不经过kthreadd，新线程是当前线程的子进程。创建结果同样通过`info.poll_result()`获取
+    ///
+    /// ## 返回值
+    ///
+    /// - Ok(Pid) 新创建的内核线程的pid
+    pub fn create_child(info: &Arc<KernelThreadCreateInfo>) -> Result<Pid, SystemError> {
+        let result = Self::__inner_create(info, CloneFlags::CLONE_FS | CloneFlags::CLONE_SIGNAL);
+        if result.is_err() {
+            info.created
+                .store(KernelThreadCreateStatus::ErrorOccured, Ordering::SeqCst);
+        }
+        return result;
+    }
+
     /// 创建并运行一个新的内核线程
     ///
     /// ## 参数
@@ -454,6 +526,40 @@ impl KernelThreadMechanism {
     }
 }
 
+impl ProcessManager {
+    /// 创建一个内核工作线程，并将其加入调度队列
+    ///
+    /// 工作线程启动后执行一次工作函数，之后每当`KernelWorker::queue_work`被调用时再执行一次，
+    /// 没有待处理的工作时，工作线程会睡眠
+    ///
+    /// ## 参数
+    ///
+    /// - name: 内核线程的名字
+    /// - f: 工作函数
+    ///
+    /// ## 返回值
+    ///
+    /// - Some(Arc<ProcessControlBlock>) 创建成功，返回新创建的内核线程的PCB
+    /// - None 创建或唤醒内核线程失败
+    pub fn create_kthread(name: String, f: fn()) -> Option<Arc<ProcessControlBlock>> {
+        let worker = KernelWorker::new(f);
+        let w = worker.clone();
+        let closure = KernelThreadClosure::EmptyClosure((Box::new(move || w.run()), ()));
+        let pcb = KernelThreadMechanism::create(closure, name)?;
+        // 新创建的内核线程处于睡眠状态，在唤醒之前设置好worker，使得调用者可以通过pcb找到它
+        pcb.worker_private()
+            .as_mut()
+            .and_then(|x| x.kernel_thread_mut())
+            .expect("kthread: worker_private is none")
+            .worker = Some(worker);
+        if let Err(e) = ProcessManager::wakeup(&pcb) {
+            kwarn!("Failed to wakeup kthread {:?}: {:?}", pcb.pid(), e);
+            return None;
+        }
+        return Some(pcb);
+    }
+}
+
 /// 内核线程启动的第二阶段
 ///
 /// 该函数只能被`kernel_thread_bootstrap_stage1`调用（jmp到该函数）
@@ -492,3 +598,50 @@ pub fn kthread_init() {
         KernelThreadMechanism::init_stage1();
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        hint::spin_loop,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use alloc::string::ToString;
+
+    use crate::process::{ProcessFlags, ProcessManager};
+
+    use super::KernelThreadMechanism;
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn increase_counter() {
+        COUNTER.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn wait_counter(expected: usize) {
+        while COUNTER.load(Ordering::SeqCst) < expected {
+            spin_loop();
+        }
+    }
+
+    /// 工作线程启动后执行一次工作函数，之后每增加一个工作就再执行一次
+    #[test]
+    fn test_create_kthread() {
+        let pcb = ProcessManager::create_kthread("test-worker".to_string(), increase_counter)
+            .expect("Failed to create kthread");
+        assert!(pcb.flags().contains(ProcessFlags::KTHREAD));
+        wait_counter(1);
+
+        let worker = pcb
+            .worker_private()
+            .as_ref()
+            .and_then(|x| x.kernel_thread())
+            .and_then(|x| x.worker())
+            .unwrap();
+        worker.queue_work();
+        wait_counter(2);
+
+        assert_eq!(KernelThreadMechanism::stop(&pcb), Ok(0));
+        assert_eq!(COUNTER.load(Ordering::SeqCst), 2);
+    }
+}
@@ -2,11 +2,15 @@ use core::{
     hash::{Hash, Hasher},
     intrinsics::{likely, unlikely},
     mem::ManuallyDrop,
-    sync::atomic::{compiler_fence, AtomicBool, AtomicI32, AtomicIsize, AtomicUsize, Ordering},
+    sync::atomic::{
+        compiler_fence, AtomicBool, AtomicI32, AtomicIsize, AtomicPtr, AtomicU64, AtomicU8,
+        AtomicUsize, Ordering,
+    },
 };
 
 use alloc::{
-    collections::BTreeMap,
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, VecDeque},
     string::{String, ToString},
     sync::{Arc, Weak},
     vec::Vec,
@@ -14,61 +18,167 @@ use alloc::{
 use hashbrown::HashMap;
 
 use crate::{
-    arch::{process::ArchPCBInfo, sched::sched, CurrentIrqArch},
+    arch::{
+        cpu::cpu_idle,
+        process::ArchPCBInfo,
+        sched::sched,
+        CurrentIrqArch,
+    },
     exception::InterruptArch,
     filesystem::{
         procfs::procfs_unregister_pid,
         vfs::{file::FileDescriptorVec, FileType},
     },
-    include::bindings::bindings::MAX_CPU_NUM,
-    kdebug, kinfo,
+    ipc::signal::{SigSet, Signal, SignalState},
+    kdebug, kerror, kinfo, kwarn,
     libs::{
         align::AlignedBox,
         casting::DowncastArc,
+        futex::Futex,
+        lazy_init::Lazy,
         rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard},
         spinlock::{SpinLock, SpinLockGuard},
         wait_queue::WaitQueue,
     },
-    mm::{percpu::PerCpuVar, set_INITIAL_PROCESS_ADDRESS_SPACE, ucontext::AddressSpace, VirtAddr},
+    mm::{
+        percpu::{PerCpu, PerCpuVar},
+        set_INITIAL_PROCESS_ADDRESS_SPACE,
+        ucontext::AddressSpace,
+        VirtAddr,
+    },
     net::socket::SocketInode,
     sched::{
-        cfs::{CFSQueue, SchedulerCFS},
-        core::{enqueue_se, sched_enqueue, CPU_EXECUTING},
+        cfs::{__get_cfs_scheduler, CFSQueue, SchedulerCFS, NICE_0_LOAD, SCHED_SLEEP_AVG_MAX_US},
+        clock::sched_clock,
+        core::{enqueue_se, loads_balance, sched_dequeue, sched_enqueue, CPU_EXECUTING},
+        deadline::{__get_dl_scheduler, DeadlineEntity, DeadlineParams},
+        loadavg::{dec_nr_uninterruptible, inc_nr_uninterruptible},
+        rt::SchedulerRT,
+        syscall::SchedParam,
         SchedPolicy, SchedPriority,
     },
-    smp::kick_cpu,
-    syscall::SystemError,
+    smp::{core::smp_get_processor_id, cpumask::CpuMask, kick_cpu},
+    syscall::{user_access::UserBufferWriter, SystemError},
+    time::timer::{clock, schedule_timeout},
 };
 
-use self::kthread::WorkerPrivate;
+use self::{
+    cred::{CAPFlags, Credentials},
+    fork::CloneFlags,
+    itimer::ITimers,
+    kthread::{KernelThreadClosure, KernelThreadCreateInfo, KernelThreadMechanism, WorkerPrivate},
+    oom::{OOM_SCORE_ADJ_MAX, OOM_SCORE_ADJ_MIN},
+    resource::{RLimit, RLimitID, RLimitTable, RLIM_INFINITY},
+};
 
 pub mod abi;
+pub mod acct;
 pub mod c_adapter;
+pub mod cred;
 pub mod exec;
 pub mod fork;
 pub mod idle;
 pub mod init;
+pub mod itimer;
 pub mod kthread;
+pub mod oom;
+pub mod pid;
 pub mod process;
+pub mod resource;
 pub mod syscall;
 
-/// 系统中所有进程的pcb
-static ALL_PROCESS: SpinLock<Option<HashMap<Pid, Arc<ProcessControlBlock>>>> = SpinLock::new(None);
+/// 进程表的分片数量
+const ALL_PROCESS_SHARDS: usize = 16;
+
+/// 进程表预留的初始容量（所有分片之和）
+///
+/// 进程数超过容量时哈希表需要在持有分片的锁的情况下扩容并重新散列，导致创建进程的延迟出现尖峰。
+/// 预留的容量使得常见的负载下不会发生扩容
+pub const ALL_PROCESS_INITIAL_CAPACITY: usize = 1024;
+
+lazy_static! {
+    /// 系统中所有进程的pcb
+    ///
+    /// 进程表按照`pid % ALL_PROCESS_SHARDS`分片，每个分片由单独的锁保护，以减少多核下的锁竞争
+    static ref ALL_PROCESS: [SpinLock<HashMap<Pid, Arc<ProcessControlBlock>>>; ALL_PROCESS_SHARDS] =
+        core::array::from_fn(|_| {
+            SpinLock::new(HashMap::with_capacity(
+                ALL_PROCESS_INITIAL_CAPACITY / ALL_PROCESS_SHARDS,
+            ))
+        });
+}
+/// init进程退出时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum InitExitAction {
+    /// 内核panic
+    Panic = 0,
+    /// 重启系统
+    Reboot = 1,
+}
+
+impl InitExitAction {
+    /// 指定init进程退出时的处理方式的启动参数
+    const BOOT_PARAM: &'static str = "init_exit=";
+
+    /// 从内核启动参数中解析`init_exit=panic|reboot`
+    ///
+    /// ## 返回值
+    ///
+    /// 没有指定该参数或者参数值无法识别时返回None，参数被指定多次时以最后一次为准
+    pub fn from_cmdline(cmdline: &str) -> Option<Self> {
+        return cmdline
+            .split_whitespace()
+            .filter_map(|arg| arg.strip_prefix(Self::BOOT_PARAM))
+            .last()
+            .and_then(|value| match value {
+                "panic" => Some(Self::Panic),
+                "reboot" => Some(Self::Reboot),
+                _ => None,
+            });
+    }
+}
+
+/// init进程退出时的处理方式，默认为panic
+static INIT_EXIT_ACTION: AtomicU8 = AtomicU8::new(InitExitAction::Panic as u8);
+
+/// 进程组默认的最大嵌套层数
+pub const TASK_GROUP_DEFAULT_MAX_DEPTH: usize = 8;
+
+/// 优先级继承沿着阻塞链传递的最大深度
+const PI_MAX_CHAIN_DEPTH: usize = 16;
+
+/// 进程组的最大嵌套层数，根进程组的层数为0
+///
+/// 层次化选择下一个进程时需要逐层遍历进程组，限制层数以避免开销过大以及过深的递归
+static TASK_GROUP_MAX_DEPTH: AtomicUsize = AtomicUsize::new(TASK_GROUP_DEFAULT_MAX_DEPTH);
+
 ///系统中所有进程组的TaskGroup
 static ALL_PROCESS_GROUP: SpinLock<Option<HashMap<Pid, Arc<TaskGroup>>>> = SpinLock::new(None);
-pub static mut SWITCH_RESULT: Option<PerCpuVar<SwitchResult>> = None;
+/// 每个cpu上正在进行的进程切换的前后两个进程，由switch_finish_hook在切换完成后取出
+static SWITCH_RESULT: Lazy<PerCpuVar<SpinLock<SwitchResult>>> = PerCpuVar::define_lazy();
+/// 每个cpu上正在运行的进程，由switch_finish_hook在切换完成后更新
+///
+/// 槽位持有进程的一个强引用（`Arc::into_raw`得到的指针），为空表示该cpu还没有进行过进程切换
+static CURRENT_PCB: Lazy<PerCpuVar<AtomicPtr<ProcessControlBlock>>> = PerCpuVar::define_lazy();
+
+/// 最近一次成功加入进程表的进程的pid
+static LAST_PID: AtomicPid = AtomicPid::new(Pid(0));
+
+/// 进程管理器初始化时的时间戳（单位：微秒），作为计算系统运行时间的起点
+static BOOT_TIMESTAMP_US: AtomicU64 = AtomicU64::new(0);
 
 /// 一个只改变1次的全局变量，标志进程管理器是否已经初始化完成
 static mut __PROCESS_MANAGEMENT_INIT_DONE: bool = false;
 
 #[derive(Debug)]
-pub struct SwitchResult {
-    pub prev_pcb: Option<Arc<ProcessControlBlock>>,
-    pub next_pcb: Option<Arc<ProcessControlBlock>>,
+struct SwitchResult {
+    prev_pcb: Option<Arc<ProcessControlBlock>>,
+    next_pcb: Option<Arc<ProcessControlBlock>>,
 }
 
 impl SwitchResult {
-    pub fn new() -> Self {
+    const fn new() -> Self {
         Self {
             prev_pcb: None,
             next_pcb: None,
@@ -99,7 +209,11 @@ impl ProcessManager {
             compiler_fence(Ordering::SeqCst);
         };
 
-        ALL_PROCESS.lock().replace(HashMap::new());
+        BOOT_TIMESTAMP_US.store(clock(), Ordering::SeqCst);
+        // 在初始化阶段为进程表分配空间，而不是在第一次创建进程时
+        lazy_static::initialize(&ALL_PROCESS);
+        Self::init_switch_result();
+        Self::init_current_pcb_cache();
         Self::arch_init();
         kdebug!("process arch init done.");
         Self::init_idle();
@@ -111,9 +225,86 @@ impl ProcessManager {
         kinfo!("Process Manager initialized.");
     }
 
+    /// 初始化进程切换结果 per cpu变量
+    fn init_switch_result() {
+        let switch_res_vec: Vec<SpinLock<SwitchResult>> = (0..PerCpu::MAX_CPU_NUM)
+            .map(|_| SpinLock::new(SwitchResult::new()))
+            .collect();
+        SWITCH_RESULT.init(PerCpuVar::new(switch_res_vec).unwrap());
+    }
+
+    /// 初始化每个cpu上正在运行的进程的缓存
+    fn init_current_pcb_cache() {
+        let slots: Vec<AtomicPtr<ProcessControlBlock>> = (0..PerCpu::MAX_CPU_NUM)
+            .map(|_| AtomicPtr::new(core::ptr::null_mut()))
+            .collect();
+        CURRENT_PCB.init(PerCpuVar::new(slots).unwrap());
+    }
+
+    /// 设置cpu上正在运行的进程的缓存，缓存持有进程的一个强引用
+    ///
+    /// 只在cpu开始运行它的第一个进程（idle进程）之前调用，此后由`switch_finish_hook`更新
+    fn set_current_pcb_cache(cpu_id: usize, pcb: &Arc<ProcessControlBlock>) {
+        let cached = Arc::into_raw(pcb.clone()) as *mut ProcessControlBlock;
+        let old = CURRENT_PCB.force_get(cpu_id).swap(cached, Ordering::AcqRel);
+        if !old.is_null() {
+            drop(unsafe { Arc::from_raw(old) });
+        }
+    }
+
+    /// 获取系统自进程管理器初始化以来的运行时间（单位：微秒）
+    pub fn uptime_us() -> u64 {
+        return clock().saturating_sub(BOOT_TIMESTAMP_US.load(Ordering::SeqCst));
+    }
+
     /// 获取当前进程的pcb
+    ///
+    /// 读取当前cpu上缓存的pcb，只需要增加一次引用计数。缓存持有正在运行的进程的强引用，
+    /// 因此即使进程已经退出、不再被进程表持有，在切换出去之前也总能获取到它的pcb。
+    /// 进程管理器初始化之前，从内核栈中取出pcb
     pub fn current_pcb() -> Arc<ProcessControlBlock> {
-        return ProcessControlBlock::arch_current_pcb();
+        // 关中断使得读取cpu号与读取缓存之间不会发生进程切换，从而不会读到其他cpu的缓存
+        let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let cached = CURRENT_PCB
+            .try_get()
+            .map(|slots| slots.get().load(Ordering::Acquire))
+            .filter(|p| !p.is_null());
+        let pcb = match cached {
+            Some(p) => unsafe {
+                // 槽位持有的引用在下一次进程切换之前不会被释放
+                Arc::increment_strong_count(p);
+                Arc::from_raw(p)
+            },
+            None => ProcessControlBlock::arch_current_pcb(),
+        };
+        drop(irq_guard);
+        return pcb;
+    }
+
+    /// 获取当前进程所在线程组的id（getpid），即线程组中第一个线程的pid
+    ///
+    /// 单线程的进程的线程组id与它的pid相同
+    pub fn getpid() -> Pid {
+        return Self::current_pcb().tgid();
+    }
+
+    /// 获取当前线程自己的id（gettid），即pcb中的pid，与线程组无关
+    pub fn gettid() -> Pid {
+        return Self::current_pcb().pid();
+    }
+
+    /// 获取当前正在执行的cpu的id
+    ///
+    /// 如果当前进程可能被迁移，返回值只在调用的时刻有效
+    #[inline(always)]
+    pub fn current_cpu() -> u32 {
+        return smp_get_processor_id();
+    }
+
+    /// 进程管理是否已经初始化完成
+    #[inline(always)]
+    pub fn initialized() -> bool {
+        return unsafe { __PROCESS_MANAGEMENT_INIT_DONE };
     }
 
     /// 增加当前进程的锁持有计数
@@ -132,6 +323,32 @@ impl ProcessManager {
         }
     }
 
+    /// 检查当前进程是否能够进入睡眠
+    ///
+    /// 持有自旋锁时（锁持有计数不为0）进行调度，会导致死锁等难以排查的问题。
+    /// 所有的阻塞原语都通过`sched()`发起调度，由它在发起调度之前调用本函数，只在debug构建中进行检查。
+    ///
+    /// ## Panics
+    ///
+    /// 当前进程的锁持有计数不为0
+    #[inline(always)]
+    pub fn might_sleep() {
+        #[cfg(debug_assertions)]
+        {
+            if likely(unsafe { __PROCESS_MANAGEMENT_INIT_DONE }) {
+                let pcb = ProcessManager::current_pcb();
+                let count = pcb.preempt_count();
+                if count != 0 {
+                    panic!(
+                        "scheduling while atomic: pid: {:?}, preempt_count: {}",
+                        pcb.pid(),
+                        count
+                    );
+                }
+            }
+        }
+    }
+
     /// 根据pid获取进程的pcb
     ///
     /// ## 参数
@@ -140,9 +357,84 @@ impl ProcessManager {
     ///
     /// ## 返回值
     ///
-    /// 如果找到了对应的进程，那么返回该进程的pcb，否则返回None
+    /// 如果找到了对应的进程，那么返回该进程的pcb，否则返回None。
+    ///
+    /// 每个cpu都有自己的idle进程，它们共用`Pid::IDLE`，因此`Pid::IDLE`总是返回None，
+    /// 需要idle进程时应当使用`idle_pcb`
     pub fn find(pid: Pid) -> Option<Arc<ProcessControlBlock>> {
-        return ALL_PROCESS.lock().as_ref()?.get(&pid).cloned();
+        if pid.is_idle() {
+            return None;
+        }
+        return Self::all_process_shard(pid).lock().get(&pid).cloned();
+    }
+
+    /// 获取pid所在的进程表分片
+    #[inline(always)]
+    fn all_process_shard(pid: Pid) -> &'static SpinLock<HashMap<Pid, Arc<ProcessControlBlock>>> {
+        return &ALL_PROCESS[pid.into() % ALL_PROCESS_SHARDS];
+    }
+
+    /// 遍历系统中的所有进程
+    ///
+    /// ## 注意
+    ///
+    /// 遍历时会依次持有每个分片的锁，因此闭包中不能再访问进程表（例如调用`find`）
+    pub fn for_each<F>(mut f: F)
+    where
+        F: FnMut(&Arc<ProcessControlBlock>),
+    {
+        for shard in ALL_PROCESS.iter() {
+            shard.lock().values().for_each(&mut f);
+        }
+    }
+
+    /// 获取进程树，用于诊断
+    ///
+    /// 从根进程开始，沿着每个进程的子进程表进行深度优先遍历。父进程不在进程表中的进程被视为根进程。
+    /// 正常情况下父子关系不会成环，但为了防止损坏的父进程指针导致死循环，每个进程只会被访问一次，
+    /// 因成环而无法从根进程到达的进程同样被视为根进程
+    ///
+    /// ## 返回值
+    ///
+    /// 每个进程的`(pid, ppid, 名字)`，按照深度优先的顺序排列，父进程总是在子进程之前，兄弟进程按照pid排序
+    pub fn process_tree() -> Vec<(Pid, Pid, String)> {
+        let mut all: Vec<Arc<ProcessControlBlock>> = Vec::new();
+        Self::for_each(|pcb| all.push(pcb.clone()));
+        all.sort_by_key(|pcb| pcb.pid());
+        let pids: BTreeSet<Pid> = all.iter().map(|pcb| pcb.pid()).collect();
+
+        let is_root = |pcb: &Arc<ProcessControlBlock>| match pcb.parent_pcb.read().upgrade() {
+            Some(parent) => parent.pid() == pcb.pid() || !pids.contains(&parent.pid()),
+            None => true,
+        };
+        let (roots, others): (Vec<_>, Vec<_>) = all.iter().partition(|pcb| is_root(pcb));
+
+        let mut tree = Vec::with_capacity(all.len());
+        let mut visited = BTreeSet::new();
+        for root in roots.into_iter().chain(others) {
+            if visited.contains(&root.pid()) {
+                continue;
+            }
+            let mut stack = Vec::from([(root.clone(), root.basic().ppid())]);
+            while let Some((pcb, ppid)) = stack.pop() {
+                if !visited.insert(pcb.pid()) {
+                    continue;
+                }
+                tree.push((pcb.pid(), ppid, pcb.basic().name().to_string()));
+
+                let mut children: Vec<Arc<ProcessControlBlock>> = pcb
+                    .children
+                    .read()
+                    .values()
+                    .filter(|child| pids.contains(&child.pid()))
+                    .cloned()
+                    .collect();
+                // 逆序入栈，使得pid较小的子进程先被访问
+                children.sort_by_key(|child| core::cmp::Reverse(child.pid()));
+                stack.extend(children.into_iter().map(|child| (child, pcb.pid())));
+            }
+        }
+        return tree;
     }
 
     /// 向系统中添加一个进程的pcb
@@ -153,1002 +445,5286 @@ impl ProcessManager {
     ///
     /// ## 返回值
     ///
-    /// 无
-    pub fn add_pcb(pcb: Arc<ProcessControlBlock>) {
-        ALL_PROCESS
-            .lock()
-            .as_mut()
-            .unwrap()
-            .insert(pcb.pid(), pcb.clone());
+    /// 如果进程表中已经存在相同pid的进程，返回`EEXIST`；idle进程不能被加入进程表，返回`EINVAL`
+    pub fn add_pcb(pcb: Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+        if pcb.pid().is_idle() {
+            return Err(SystemError::EINVAL);
+        }
+        let mut shard = Self::all_process_shard(pcb.pid()).lock();
+        if shard.contains_key(&pcb.pid()) {
+            return Err(SystemError::EEXIST);
+        }
+        shard.insert(pcb.pid(), pcb.clone());
+        LAST_PID.store(pcb.pid(), Ordering::SeqCst);
+        return Ok(());
+    }
+
+    /// 获取系统中的进程总数
+    pub fn process_count() -> usize {
+        return ALL_PROCESS.iter().map(|shard| shard.lock().len()).sum();
+    }
+
+    /// 获取系统中处于可运行状态的进程数
+    pub fn runnable_count() -> usize {
+        let mut count = 0;
+        Self::for_each(|pcb| {
+            if pcb.sched_info().state().is_runnable() {
+                count += 1;
+            }
+        });
+        return count;
+    }
+
+    /// 获取系统中处于不可中断睡眠状态的进程数
+    pub fn uninterruptible_count() -> usize {
+        let mut count = 0;
+        Self::for_each(|pcb| {
+            if pcb.sched_info().state() == ProcessState::Blocked(false) {
+                count += 1;
+            }
+        });
+        return count;
+    }
+
+    /// 获取最近一次成功加入进程表的进程的pid
+    ///
+    /// 分配了pid但是创建失败的进程不会被计入
+    pub fn last_pid() -> Pid {
+        return LAST_PID.load(Ordering::SeqCst);
     }
 
     /// 唤醒一个进程
     pub fn wakeup(pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
-        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
-        let state = pcb.sched_info().state();
-        if state.is_blocked() {
-            let mut writer = pcb.sched_info_mut();
-            let state = writer.state();
-            if state.is_blocked() {
-                writer.set_state(ProcessState::Runnable);
-                // avoid deadlock
-                drop(writer);
+        return Self::try_to_wake_up(pcb).map(|_| ());
+    }
 
-                sched_enqueue(pcb.clone(), true);
-                return Ok(());
-            } else if state.is_exited() {
-                return Err(SystemError::EINVAL);
-            } else {
-                return Ok(());
-            }
-        } else if state.is_exited() {
-            return Err(SystemError::EINVAL);
-        } else {
-            return Ok(());
+    /// 由于信号而唤醒一个进程
+    ///
+    /// 进程会被标记为有待处理的信号，只有处于可中断睡眠的进程才会被唤醒，
+    /// 它醒来后能通过`sleep_outcome`得知自己是被信号打断的
+    ///
+    /// ## 返回值
+    ///
+    /// - Ok(true): 进程被唤醒
+    /// - Ok(false): 进程不处于可中断的睡眠中，只进行了标记
+    /// - Err(SystemError): 进程已经退出
+    pub fn signal_wakeup(pcb: &Arc<ProcessControlBlock>) -> Result<bool, SystemError> {
+        pcb.flags().insert(ProcessFlags::HAS_PENDING_SIGNAL);
+        if pcb.sched_info().state() != ProcessState::Blocked(true) {
+            return Ok(false);
         }
+        return Self::try_to_wake_up(pcb);
     }
 
-    /// 标志当前进程永久睡眠，但是发起调度的工作，应该由调用者完成
+    /// 向指定的线程发送信号
     ///
-    /// ## 注意
+    /// 信号只会被加入该线程的待处理信号集。信号没有被阻塞时，唤醒处于可中断睡眠的线程。
+    /// 收到SIGKILL的线程会被标记为`WAKEKILL`，被停止的线程也会恢复运行，它在下一次返回用户态时退出
     ///
-    /// - 进入当前函数之前，不能持有sched_info的锁
-    /// - 进入当前函数之前，必须关闭中断
-    pub fn mark_sleep(interruptable: bool) -> Result<(), SystemError> {
-        assert_eq!(
-            CurrentIrqArch::is_irq_enabled(),
-            false,
-            "interrupt must be disabled before enter ProcessManager::mark_sleep()"
-        );
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：线程已经退出时，返回`ESRCH`
+    pub fn send_signal_to_thread(
+        pcb: &Arc<ProcessControlBlock>,
+        sig: Signal,
+    ) -> Result<(), SystemError> {
+        if pcb.sched_info().state().has_exited() {
+            return Err(SystemError::ESRCH);
+        }
+        let deliverable = pcb.sig_state().add_pending(sig);
+        if sig.is_fatal() {
+            pcb.flags().insert(ProcessFlags::WAKEKILL);
+            pcb.sig_state().remove_pending(SigSet::STOP);
+            Self::cont(pcb);
+        }
+        if deliverable {
+            Self::signal_wakeup(pcb)?;
+        }
+        return Ok(());
+    }
 
-        let pcb = ProcessManager::current_pcb();
-        let mut writer = pcb.sched_info_mut_irqsave();
-        if writer.state() != ProcessState::Exited(0) {
-            writer.set_state(ProcessState::Blocked(interruptable));
-            pcb.flags().insert(ProcessFlags::NEED_SCHEDULE);
-            drop(writer);
+    /// 向指定的进程发送信号，并处理停止信号与SIGCONT之间的相互作用
+    ///
+    /// - SIGCONT：清除所有待处理的停止信号，被停止的进程恢复为可运行的状态，并重新加入运行队列
+    /// - 停止信号：清除待处理的SIGCONT。信号没有被阻塞时，可运行的进程（包括因为该信号而从可中断睡眠中
+    ///   醒来的进程）立即被停止，信号随之被处理；否则信号保持待处理，直到被SIGCONT清除
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：进程已经退出时，返回`ESRCH`；当前进程没有权限向其发送信号时，返回`EPERM`
+    pub fn send_signal(pcb: &Arc<ProcessControlBlock>, sig: Signal) -> Result<(), SystemError> {
+        if pcb.sched_info().state().has_exited() {
+            return Err(SystemError::ESRCH);
+        }
+        Self::check_kill_permission(&Self::current_pcb(), pcb, sig)?;
+        if sig == Signal::SIGCONT {
+            pcb.sig_state().remove_pending(SigSet::STOP);
+            Self::cont(pcb);
+        } else if sig.is_stop() {
+            pcb.sig_state().remove_pending(Signal::SIGCONT.mask());
+        }
+
+        Self::send_signal_to_thread(pcb, sig)?;
+
+        if sig.is_stop() {
+            let blocked = pcb.sig_state().blocked().contains(sig.mask());
+            if !blocked && Self::stop(pcb) {
+                pcb.sig_state().remove_pending(sig.mask());
+            }
+        }
+        return Ok(());
+    }
 
+    /// 检查`sender`是否有权限向`target`发送信号
+    ///
+    /// 与POSIX的规定相同，满足以下任意条件时允许发送：
+    /// - 发送者拥有`CAP_KILL`能力
+    /// - 发送者的实际用户id或有效用户id，等于接收者的实际用户id或保存的用户id
+    /// - 信号为SIGCONT，并且发送者与接收者处于同一会话中
+    ///
+    /// ## 返回值
+    ///
+    /// 没有权限时，返回`EPERM`
+    pub fn check_kill_permission(
+        sender: &ProcessControlBlock,
+        target: &ProcessControlBlock,
+        sig: Signal,
+    ) -> Result<(), SystemError> {
+        if sender.cred().can_signal(&target.cred()) {
             return Ok(());
         }
-        return Err(SystemError::EINTR);
+        if sig == Signal::SIGCONT && sender.basic().sid() == target.basic().sid() {
+            return Ok(());
+        }
+        return Err(SystemError::EPERM);
     }
 
-    /// 当子进程退出后向父进程发送通知
-    fn exit_notify() {
-        let current = ProcessManager::current_pcb();
-        // 让INIT进程收养所有子进程
-        if current.pid() != Pid(1) {
-            unsafe {
-                current
-                    .adopt_childen()
-                    .unwrap_or_else(|e| panic!("adopte_childen failed: error: {e:?}"))
-            };
-            // todo: 当信号机制重写后，这里需要向父进程发送SIGCHLD信号
+    /// 向进程组中的所有进程发送信号，见`send_signal`
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：收到信号的进程数
+    /// - 失败：进程组中没有未退出的进程时，返回`ESRCH`；没有权限向其中任何一个进程发送信号时，返回`EPERM`
+    pub fn send_signal_to_group(pgid: Pid, sig: Signal) -> Result<usize, SystemError> {
+        let mut all: Vec<Arc<ProcessControlBlock>> = Vec::new();
+        Self::for_each(|pcb| all.push(pcb.clone()));
+        let mut sent = 0;
+        let mut err = SystemError::ESRCH;
+        for pcb in all
+            .iter()
+            .filter(|pcb| PROCESS_GROUP_MANAGER.is_member(pgid, pcb))
+        {
+            match Self::send_signal(pcb, sig) {
+                Ok(()) => sent += 1,
+                Err(SystemError::EPERM) => err = SystemError::EPERM,
+                Err(_) => {}
+            }
         }
+        if sent == 0 {
+            return Err(err);
+        }
+        return Ok(sent);
     }
 
-    /// 退出当前进程
+    /// 向指定的线程发送信号（tkill）
     ///
     /// ## 参数
     ///
-    /// - `exit_code` : 进程的退出码
-    pub fn exit(exit_code: usize) -> ! {
-        // 关中断
-        let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
-        let pcb = ProcessManager::current_pcb();
-        pcb.sched_info
-            .write()
-            .set_state(ProcessState::Exited(exit_code));
-        pcb.wait_queue.wakeup(Some(ProcessState::Blocked(true)));
-        drop(pcb);
-        ProcessManager::exit_notify();
-        drop(irq_guard);
-        sched();
-        loop {}
+    /// - `tid` : 线程的id
+    /// - `sig` : 要发送的信号
+    ///
+    /// ## 返回值
+    ///
+    /// 线程不存在时，返回`ESRCH`
+    pub fn tkill(tid: Pid, sig: Signal) -> Result<(), SystemError> {
+        let pcb = Self::find(tid).ok_or(SystemError::ESRCH)?;
+        return Self::send_signal_to_thread(&pcb, sig);
+    }
+
+    /// 向指定线程组中的指定线程发送信号（tgkill）
+    ///
+    /// 与tkill相比，额外检查线程是否属于该线程组，避免线程退出、tid被重用后，信号被发送给了无关的线程
+    ///
+    /// ## 参数
+    ///
+    /// - `tgid` : 线程组的id
+    /// - `tid` : 线程的id
+    /// - `sig` : 要发送的信号
+    ///
+    /// ## 返回值
+    ///
+    /// 线程不存在，或者不属于该线程组时，返回`ESRCH`
+    pub fn tgkill(tgid: Pid, tid: Pid, sig: Signal) -> Result<(), SystemError> {
+        let pcb = Self::find(tid).ok_or(SystemError::ESRCH)?;
+        if pcb.tgid() != tgid {
+            return Err(SystemError::ESRCH);
+        }
+        return Self::send_signal_to_thread(&pcb, sig);
     }
 
-    pub unsafe fn release(pid: Pid) {
-        let pcb = ProcessManager::find(pid);
-        if !pcb.is_none() {
-            let pcb = pcb.unwrap();
-            // 判断该pcb是否在全局没有任何引用
-            if Arc::strong_count(&pcb) <= 1 {
-                drop(pcb);
-                ALL_PROCESS.lock().as_mut().unwrap().remove(&pid);
+    /// 检查进程使用的cpu时间是否超出了`RLIMIT_CPU`，在时钟中断中对当前进程调用
+    ///
+    /// - 达到软限制时，发送SIGXCPU，并将软限制增加一秒，使得此后每多用一秒cpu时间就再发送一次
+    /// - 达到硬限制时，发送SIGKILL，进程在下一次返回用户态时退出
+    ///
+    /// ## 返回值
+    ///
+    /// 发送的信号，没有超出限制时返回None
+    pub fn check_cpu_rlimit(pcb: &Arc<ProcessControlBlock>) -> Option<Signal> {
+        let cpu_secs = {
+            let sched_info = pcb.sched_info();
+            (sched_info.utime() + sched_info.stime()) / 1_000_000
+        };
+
+        let sig = {
+            let mut rlimits = pcb.rlimits.lock_irqsave();
+            let limit = rlimits.get(RLimitID::Cpu);
+            if limit.max != RLIM_INFINITY && cpu_secs >= limit.max {
+                Signal::SIGKILL
+            } else if limit.cur != RLIM_INFINITY && cpu_secs >= limit.cur {
+                if limit.cur < limit.max {
+                    // 增加软限制不会超过硬限制，因此不需要特权
+                    rlimits
+                        .set(RLimitID::Cpu, RLimit::new(limit.cur + 1, limit.max), false)
+                        .ok();
+                }
+                Signal::SIGXCPU
             } else {
-                // 如果不为1就panic
-                panic!("pcb is still referenced");
+                return None;
             }
+        };
+
+        Self::send_signal_to_thread(pcb, sig).ok()?;
+        return Some(sig);
+    }
+
+    /// 处理用户栈下方的缺页
+    ///
+    /// 只有紧邻用户栈下方（不超过`UserStack::MAX_GROW_GAP`）的缺页才会扩展用户栈，更远的缺页是非法访问。
+    /// 在`RLIMIT_STACK`的软限制以内时，向下扩展用户栈；否则不扩展，并向进程发送SIGSEGV
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 发生缺页的进程
+    /// - `addr` : 发生缺页的地址
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())，缺页已经被处理
+    /// - 失败：缺页不能通过扩展用户栈来处理时，返回对应的错误码
+    pub fn handle_user_stack_fault(
+        pcb: &Arc<ProcessControlBlock>,
+        addr: VirtAddr,
+    ) -> Result<(), SystemError> {
+        let vm = pcb.basic().user_vm().ok_or(SystemError::EFAULT)?;
+        let limit = usize::try_from(pcb.rlimit(RLimitID::Stack).cur).unwrap_or(usize::MAX);
+        let r = vm.write().grow_user_stack(addr, limit);
+        if r.is_err() {
+            Self::send_signal_to_thread(pcb, Signal::SIGSEGV).ok();
         }
+        return r;
     }
 
-    /// 上下文切换完成后的钩子函数
-    unsafe fn switch_finish_hook() {
-        // kdebug!("switch_finish_hook");
-        let prev_pcb = SWITCH_RESULT
-            .as_mut()
-            .unwrap()
-            .get_mut()
-            .prev_pcb
-            .take()
-            .expect("prev_pcb is None");
-        let next_pcb = SWITCH_RESULT
-            .as_mut()
-            .unwrap()
-            .get_mut()
-            .next_pcb
-            .take()
-            .expect("next_pcb is None");
+    /// 清除进程的待处理信号标记
+    pub fn clear_pending_signal(pcb: &Arc<ProcessControlBlock>) {
+        pcb.flags().remove(ProcessFlags::HAS_PENDING_SIGNAL);
+    }
 
-        // 由于进程切换前使用了SpinLockGuard::leak()，所以这里需要手动释放锁
-        prev_pcb.arch_info.force_unlock();
-        next_pcb.arch_info.force_unlock();
+    /// 获取当前进程从睡眠中返回的原因
+    pub fn sleep_outcome() -> SleepOutcome {
+        if ProcessManager::current_pcb()
+            .flags()
+            .contains(ProcessFlags::HAS_PENDING_SIGNAL)
+        {
+            return SleepOutcome::Interrupted;
+        }
+        return SleepOutcome::Event;
     }
 
-    /// 如果目标进程正在目标CPU上运行，那么就让这个cpu陷入内核态
+    /// 尝试唤醒一个进程
+    ///
+    /// 在持有sched_info写锁的情况下检查并转换进程的状态，然后：
+    /// - 如果进程仍然在某个cpu上执行（还没有完成切换），那么只修改状态，
+    ///   由该cpu上的调度器在调度时发现进程可运行，并将其留在运行队列中；
+    /// - 否则，根据负载均衡选择目标cpu并加入其运行队列，如果需要抢占目标cpu上正在运行的进程，
+    ///   就向目标cpu发送IPI。
     ///
     /// ## 参数
     ///
-    /// - `pcb` : 进程的pcb
-    #[allow(dead_code)]
-    pub fn kick(pcb: &Arc<ProcessControlBlock>) {
-        ProcessManager::current_pcb().preempt_disable();
-        let cpu_id = pcb.sched_info().on_cpu();
-
-        if let Some(cpu_id) = cpu_id {
-            let cpu_id = cpu_id;
+    /// - `pcb` : 要唤醒的进程
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(true)` : 进程从阻塞态被唤醒
+    /// - `Ok(false)` : 进程已经是可运行的，什么都没有做
+    /// - `Err(EINVAL)` : 进程已经退出
+    pub fn try_to_wake_up(pcb: &Arc<ProcessControlBlock>) -> Result<bool, SystemError> {
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let mut writer = pcb.sched_info_mut();
+        let state = writer.state();
+        if state.has_exited() {
+            return Err(SystemError::EINVAL);
+        } else if !state.is_blocked() {
+            return Ok(false);
+        }
+        // 阻塞的进程不在任何队列中。即使迁移标志仍然被其他cpu持有，也要唤醒它，否则这次唤醒会丢失；
+        // 对方随后会因为在队列中找不到它而放弃迁移
+        if pcb.flags().contains(ProcessFlags::FROZEN) {
+            // 在睡眠中被冻结的进程醒来后不能运行，直到被解冻
+            writer.try_set_state(ProcessState::Frozen)?;
+            return Ok(true);
+        }
+        writer.try_set_state(ProcessState::Runnable)?;
+        writer.account_sleep(clock());
+        let on_cpu = writer.on_cpu();
+        // avoid deadlock
+        drop(writer);
 
-            if pcb.pid() == CPU_EXECUTING.get(cpu_id) {
-                kick_cpu(cpu_id).expect("ProcessManager::kick(): Failed to kick cpu");
+        if let Some(cpu_id) = on_cpu {
+            if CPU_EXECUTING.get(cpu_id) == pcb.pid() {
+                // 进程仍在cpu上执行，不需要重新入队，让远端cpu尽快进行调度即可
+                if cpu_id != smp_get_processor_id() {
+                    kick_cpu(cpu_id).expect("try_to_wake_up: Failed to kick cpu");
+                }
+                return Ok(true);
             }
         }
 
-        ProcessManager::current_pcb().preempt_enable();
+        // sched_enqueue会根据负载均衡为进程选择目标cpu，并在需要时抢占目标cpu
+        sched_enqueue(pcb.clone(), true);
+        return Ok(true);
     }
-}
-
-/// 上下文切换的钩子函数,当这个函数return的时候,将会发生上下文切换
-pub unsafe extern "sysv64" fn switch_finish_hook() {
-    ProcessManager::switch_finish_hook();
-}
 
-int_like!(Pid, AtomicPid, usize, AtomicUsize);
+    /// 冻结一个进程
+    ///
+    /// - 可运行的进程会被移出运行队列；如果它正在某个cpu上运行，那么会在该cpu下一次调度时停止运行
+    /// - 正在睡眠的进程会继续睡眠，被唤醒时才进入冻结状态
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())，已经被冻结的进程也返回成功
+    /// - 失败：进程不存在时，返回`ESRCH`；进程带有`NOFREEZE`标志时，返回`EPERM`；
+    ///   进程已经退出时，返回`EINVAL`
+    pub fn freeze(pid: Pid) -> Result<(), SystemError> {
+        let pcb = Self::find(pid).ok_or(SystemError::ESRCH)?;
+        if pcb.flags().contains(ProcessFlags::NOFREEZE) {
+            return Err(SystemError::EPERM);
+        }
 
-impl Hash for Pid {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.0.hash(state);
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let mut writer = pcb.sched_info_mut();
+        let state = writer.state();
+        if state.has_exited() {
+            return Err(SystemError::EINVAL);
+        }
+        pcb.flags().insert(ProcessFlags::FROZEN);
+        if !state.is_runnable() {
+            return Ok(());
+        }
+        writer.try_set_state(ProcessState::Frozen)?;
+        let on_cpu = writer.on_cpu();
+        drop(writer);
+
+        if let Some(cpu_id) = on_cpu {
+            if CPU_EXECUTING.get(cpu_id) == pcb.pid() {
+                // 进程正在运行，让它所在的cpu尽快进行调度。由于它已经不是可运行的，调度时不会被重新入队
+                pcb.set_need_schedule();
+                if cpu_id != smp_get_processor_id() {
+                    kick_cpu(cpu_id).expect("freeze: Failed to kick cpu");
+                }
+                return Ok(());
+            }
+        }
+        sched_dequeue(&pcb);
+        return Ok(());
     }
-}
 
-impl Pid {
-    pub fn to_string(&self) -> String {
-        self.0.to_string()
+    /// 解冻一个进程，使其恢复到被冻结之前的状态
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())，没有被冻结的进程也返回成功
+    /// - 失败：进程不存在时，返回`ESRCH`
+    pub fn thaw(pid: Pid) -> Result<(), SystemError> {
+        let pcb = Self::find(pid).ok_or(SystemError::ESRCH)?;
+
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let mut writer = pcb.sched_info_mut();
+        pcb.flags().remove(ProcessFlags::FROZEN);
+        if !writer.state().is_frozen() {
+            // 进程仍在睡眠中，或者还没有来得及停止运行
+            return Ok(());
+        }
+        writer.try_set_state(ProcessState::Runnable)?;
+        drop(writer);
+
+        sched_enqueue(pcb, false);
+        return Ok(());
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ProcessState {
-    /// The process is running on a CPU or in a run queue.
-    Runnable,
-    /// The process is waiting for an event to occur.
-    /// 其中的bool表示该等待过程是否可以被打断。
-    /// - 如果该bool为true,那么，硬件中断/信号/其他系统事件都可以打断该等待过程，使得该进程重新进入Runnable状态。
-    /// - 如果该bool为false,那么，这个进程必须被显式的唤醒，才能重新进入Runnable状态。
-    Blocked(bool),
-    /// 进程被信号终止
-    // Stopped(SignalNumber),
-    /// 进程已经退出，usize表示进程的退出码
-    Exited(usize),
-}
+    /// 停止一个可运行的进程
+    ///
+    /// 进程会被移出运行队列；如果它正在某个cpu上运行，那么会在该cpu下一次调度时停止运行
+    ///
+    /// ## 返回值
+    ///
+    /// 进程是否被停止。不可运行的进程（例如正在睡眠、已经被停止）不会被停止
+    fn stop(pcb: &Arc<ProcessControlBlock>) -> bool {
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let mut writer = pcb.sched_info_mut();
+        if !writer.state().is_runnable() || writer.try_set_state(ProcessState::Stopped).is_err() {
+            return false;
+        }
+        let on_cpu = writer.on_cpu();
+        drop(writer);
 
-#[allow(dead_code)]
-impl ProcessState {
-    #[inline(always)]
-    pub fn is_runnable(&self) -> bool {
-        return matches!(self, ProcessState::Runnable);
+        if let Some(cpu_id) = on_cpu {
+            if CPU_EXECUTING.get(cpu_id) == pcb.pid() {
+                // 进程正在运行，让它所在的cpu尽快进行调度。由于它已经不是可运行的，调度时不会被重新入队
+                pcb.set_need_schedule();
+                if cpu_id != smp_get_processor_id() {
+                    kick_cpu(cpu_id).expect("stop: Failed to kick cpu");
+                }
+                return true;
+            }
+        }
+        sched_dequeue(pcb);
+        return true;
     }
 
-    #[inline(always)]
-    pub fn is_blocked(&self) -> bool {
-        return matches!(self, ProcessState::Blocked(_));
+    /// 让一个被停止的进程继续运行，没有被停止的进程不受影响
+    fn cont(pcb: &Arc<ProcessControlBlock>) {
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let mut writer = pcb.sched_info_mut();
+        if !writer.state().is_stopped() || writer.try_set_state(ProcessState::Runnable).is_err() {
+            return;
+        }
+        let on_cpu = writer.on_cpu();
+        drop(writer);
+
+        if let Some(cpu_id) = on_cpu {
+            if CPU_EXECUTING.get(cpu_id) == pcb.pid() {
+                // 进程还没有来得及被切换出去，它会继续运行，不需要重新入队
+                return;
+            }
+        }
+        sched_enqueue(pcb.clone(), true);
     }
 
-    #[inline(always)]
-    pub fn is_exited(&self) -> bool {
-        return matches!(self, ProcessState::Exited(_));
+    /// 改变进程的调度策略与优先级（sched_setscheduler）
+    ///
+    /// 进程在运行队列中时，先从旧的调度策略的队列中移出，再加入新的调度策略的队列；
+    /// 进程正在某个cpu上运行时，让该cpu尽快进行调度，进程在被切换出去时会加入新的调度策略的队列
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 进程的pid
+    /// - `policy` : 新的调度策略
+    /// - `sched_priority` : 用户态sched_param中的优先级，见`SchedPolicy::priority_from_param`
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：进程不存在或者已经退出时，返回`ESRCH`；优先级不合法时，返回`EINVAL`；
+    ///   设置实时调度策略而没有`CAP_SYS_NICE`能力时，返回`EPERM`
+    pub fn sched_setscheduler(
+        pid: Pid,
+        policy: SchedPolicy,
+        sched_priority: i32,
+    ) -> Result<(), SystemError> {
+        let pcb = Self::find(pid).ok_or(SystemError::ESRCH)?;
+        let priority = policy.priority_from_param(sched_priority)?;
+        return Self::change_policy(&pcb, policy, priority, None);
     }
-}
 
-bitflags! {
-    /// pcb的标志位
-    pub struct ProcessFlags: usize {
-        /// 当前pcb表示一个内核线程
-        const KTHREAD = 1 << 0;
-        /// 当前进程需要被调度
-        const NEED_SCHEDULE = 1 << 1;
-        /// 进程由于vfork而与父进程存在资源共享
-        const VFORK = 1 << 2;
-        /// 进程不可被冻结
-        const NOFREEZE = 1 << 3;
-        /// 进程正在退出
-        const EXITING = 1 << 4;
-        /// 进程由于接收到终止信号唤醒
-        const WAKEKILL = 1 << 5;
-        /// 进程由于接收到信号而退出.(Killed by a signal)
-        const SIGNALED = 1 << 6;
-        /// 进程需要迁移到其他cpu上
-        const NEED_MIGRATE = 1 << 7;
+    /// 获取进程的调度参数（sched_getparam）
+    ///
+    /// 返回的是进程自己设置的优先级，不包括优先级继承带来的临时提升
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：实时进程返回`[RT_PARAM_MIN, RT_PARAM_MAX]`内的优先级，其他进程返回0
+    /// - 失败：进程不存在时，返回`ESRCH`
+    pub fn sched_getparam(pid: Pid) -> Result<SchedParam, SystemError> {
+        let pcb = Self::find(pid).ok_or(SystemError::ESRCH)?;
+        let (policy, priority) = Self::normal_policy(&pcb);
+        return Ok(SchedParam {
+            sched_priority: policy.param_from_priority(priority),
+        });
     }
-}
 
-#[derive(Debug)]
-pub struct ProcessControlBlock {
-    /// 当前进程的pid
-    pid: Pid,
+    /// 在不改变调度策略的情况下，修改进程的优先级（sched_setparam）
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：进程不存在或者已经退出时，返回`ESRCH`；优先级不在当前调度策略允许的范围内
+    ///   （实时调度策略为`[RT_PARAM_MIN, RT_PARAM_MAX]`，其他为0）时，返回`EINVAL`；
+    ///   修改实时进程的优先级时没有`CAP_SYS_NICE`能力，返回`EPERM`
+    pub fn sched_setparam(pid: Pid, param: SchedParam) -> Result<(), SystemError> {
+        let pcb = Self::find(pid).ok_or(SystemError::ESRCH)?;
+        let (policy, _) = Self::normal_policy(&pcb);
+        let priority = policy.priority_from_param(param.sched_priority)?;
+        return Self::change_policy(&pcb, policy, priority, None);
+    }
 
-    basic: RwLock<ProcessBasicInfo>,
-    /// 当前进程的自旋锁持有计数
-    preempt_count: AtomicUsize,
+    /// 获取进程自己设置的调度策略与优先级，进程被优先级继承提升时，返回提升之前的值
+    fn normal_policy(pcb: &Arc<ProcessControlBlock>) -> (SchedPolicy, SchedPriority) {
+        let sched_info = pcb.sched_info();
+        return sched_info
+            .pi_saved
+            .unwrap_or((sched_info.policy(), sched_info.priority()));
+    }
 
-    flags: SpinLock<ProcessFlags>,
-    worker_private: SpinLock<Option<WorkerPrivate>>,
-    /// 进程的内核栈
-    kernel_stack: RwLock<KernelStack>,
+    /// 将进程设置为deadline调度策略（sched_setattr）
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 进程的pid
+    /// - `params` : deadline调度参数
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：进程不存在或者已经退出时，返回`ESRCH`；参数不合法时，返回`EINVAL`；
+    ///   没有`CAP_SYS_NICE`能力时，返回`EPERM`；deadline进程的总带宽超过容量时，返回`EBUSY`
+    pub fn sched_setdeadline(pid: Pid, params: DeadlineParams) -> Result<(), SystemError> {
+        let pcb = Self::find(pid).ok_or(SystemError::ESRCH)?;
+        let params = params.validate()?;
+        return Self::change_policy(
+            &pcb,
+            SchedPolicy::DEADLINE,
+            SchedPriority::DEFAULT,
+            Some(params),
+        );
+    }
 
-    /// 与调度相关的信息
-    sched_info: RwLock<ProcessSchedulerInfo>,
-    /// 与处理器架构相关的信息
-    arch_info: SpinLock<ArchPCBInfo>,
+    /// 设置进程的nice值（setpriority）
+    ///
+    /// 与Linux相同，超出`[NICE_MIN, NICE_MAX]`的值会被截断到范围之内。没有`CAP_SYS_NICE`能力时，
+    /// 只能修改有效用户id与自己的有效用户id相同的进程，并且只能提高nice值（降低优先级）
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 进程的pid
+    /// - `nice` : 新的nice值
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：进程不存在时，返回`ESRCH`；没有权限修改其他用户的进程时，返回`EPERM`；
+    ///   没有权限降低nice值时，返回`EACCES`
+    pub fn setpriority(pid: Pid, nice: i32) -> Result<(), SystemError> {
+        let pcb = Self::find(pid).ok_or(SystemError::ESRCH)?;
+        let cred = Self::current_pcb().cred();
+        let privileged = cred.has_capability(CAPFlags::CAP_SYS_NICE);
+        if !cred.can_renice(&pcb.cred()) {
+            return Err(SystemError::EPERM);
+        }
 
-    /// 父进程指针
-    parent_pcb: RwLock<Weak<ProcessControlBlock>>,
+        let nice = nice.clamp(
+            ProcessSchedulerInfo::NICE_MIN,
+            ProcessSchedulerInfo::NICE_MAX,
+        );
+        if !privileged && nice < pcb.sched_info().nice() {
+            return Err(SystemError::EACCES);
+        }
+        return pcb.sched_info().set_nice(nice);
+    }
 
-    /// 子进程链表
-    children: RwLock<HashMap<Pid, Arc<ProcessControlBlock>>>,
+    /// 改变进程的调度策略与优先级，见`sched_setscheduler`
+    ///
+    /// ## 参数
+    ///
+    /// - `dl_params` : 新的调度策略为deadline时的调度参数，此时必须不为空
+    fn change_policy(
+        pcb: &Arc<ProcessControlBlock>,
+        policy: SchedPolicy,
+        priority: SchedPriority,
+        dl_params: Option<DeadlineParams>,
+    ) -> Result<(), SystemError> {
+        if policy.is_privileged()
+            && !Self::current_pcb()
+                .cred()
+                .has_capability(CAPFlags::CAP_SYS_NICE)
+        {
+            return Err(SystemError::EPERM);
+        }
 
-    /// 等待队列
-    wait_queue: WaitQueue,
-}
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        if pcb.sched_info().state().has_exited() {
+            return Err(SystemError::ESRCH);
+        }
+        // 先进行带宽的准入控制，失败时进程的调度策略不变
+        let old_bw = Self::dl_bandwidth(pcb);
+        let new_bw = dl_params.map_or(0, |params| params.bandwidth());
+        __get_dl_scheduler().admit(old_bw, new_bw)?;
+
+        // 显式设置的调度策略取代优先级继承带来的提升
+        pcb.sched_info_mut().pi_saved = None;
+        Self::apply_policy(pcb, policy, priority, dl_params);
+        return Ok(());
+    }
 
-impl ProcessControlBlock {
-    /// Generate a new pcb.
+    /// 修改进程的调度策略与优先级，并将进程移动到新的调度策略的队列中
+    ///
+    /// 调用者需要关闭中断，并且已经完成了权限检查与deadline带宽的准入控制
+    fn apply_policy(
+        pcb: &Arc<ProcessControlBlock>,
+        policy: SchedPolicy,
+        priority: SchedPriority,
+        dl_params: Option<DeadlineParams>,
+    ) {
+        // 必须在修改调度策略之前移出，因为进程所在的队列由旧的调度策略与优先级决定
+        let queued = sched_dequeue(pcb);
+
+        let mut writer = pcb.sched_info_mut();
+        let old_policy = writer.policy();
+        writer.set_policy(policy, priority);
+        if let Some(params) = dl_params {
+            writer.dl().set_params(params, clock() * 1000);
+        } else if policy.is_rt() {
+            writer.set_rt_time_slice(SchedulerRT::RR_TIMESLICE);
+        } else if !old_policy.is_fair() {
+            // 实时进程与deadline进程的虚拟运行时间在它们运行期间没有增加，
+            // 不能让它们回到cfs后长期占用cpu
+            if let Some(min) = writer
+                .on_cpu()
+                .and_then(|cpu_id| __get_cfs_scheduler().min_vruntime(cpu_id as usize))
+            {
+                if writer.virtual_runtime() < min {
+                    writer.set_virtual_runtime(min);
+                }
+            }
+        }
+        let on_cpu = writer.on_cpu();
+        drop(writer);
+
+        if queued {
+            sched_enqueue(pcb.clone(), false);
+            return;
+        }
+        if let Some(cpu_id) = on_cpu {
+            if CPU_EXECUTING.get(cpu_id) == pcb.pid() {
+                pcb.set_need_schedule();
+                if cpu_id != smp_get_processor_id() {
+                    kick_cpu(cpu_id).expect("apply_policy: Failed to kick cpu");
+                }
+            }
+        }
+    }
+
+    /// 优先级继承：实时进程`waiter`因为等待`owner`持有的锁`lock`而阻塞时，将`owner`的调度策略与优先级
+    /// 临时提升到与`waiter`相同，避免`owner`被优先级介于两者之间的进程抢占，导致`waiter`无限期地等待
+    ///
+    /// 等待者按照它所等待的锁记录在`owner`上，`owner`释放某个锁时只撤销这个锁的等待者带来的提升。
+    /// 如果`owner`也在等待其他进程持有的锁，提升会沿着阻塞链传递下去
+    ///
+    /// 调用者不能持有锁内部用于保护等待队列的自旋锁
     ///
     /// ## 参数
     ///
-    /// - `name` : 进程的名字
-    /// - `kstack` : 进程的内核栈
+    /// - `owner` : 锁的持有者
+    /// - `waiter` : 等待锁的进程
+    /// - `lock` : 锁的标识（锁的地址）
+    pub fn pi_boost(
+        owner: &Arc<ProcessControlBlock>,
+        waiter: &Arc<ProcessControlBlock>,
+        lock: usize,
+    ) {
+        if Arc::ptr_eq(owner, waiter) {
+            return;
+        }
+        owner.pi_waiters.lock_irqsave().push(PiWaiter {
+            lock,
+            waiter: Arc::downgrade(waiter),
+        });
+        Self::pi_adjust_chain(owner);
+    }
+
+    /// 进程释放了锁`lock`，移除这个锁的等待者，并根据它持有的其他锁的等待者重新计算调度策略与优先级
+    ///
+    /// 这些等待者会在锁的下一个持有者获得锁时，通过`pi_boost`重新记录到新的持有者上
+    pub fn pi_release(owner: &Arc<ProcessControlBlock>, lock: usize) {
+        owner
+            .pi_waiters
+            .lock_irqsave()
+            .retain(|w| w.lock != lock && w.waiter.strong_count() > 0);
+        Self::pi_adjust_chain(owner);
+    }
+
+    /// 从`pcb`开始，沿着阻塞链重新计算每个进程由于优先级继承而得到的调度策略与优先级
+    fn pi_adjust_chain(pcb: &Arc<ProcessControlBlock>) {
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let mut next = Some(pcb.clone());
+        // 阻塞链成环说明发生了死锁，提升无法解决问题，限制传递的深度避免死循环
+        for _ in 0..PI_MAX_CHAIN_DEPTH {
+            let pcb = match next {
+                Some(pcb) => pcb,
+                None => break,
+            };
+            if !Self::pi_adjust(&pcb) {
+                break;
+            }
+            next = pcb.pi_blocked_on();
+        }
+    }
+
+    /// 根据进程持有的锁的等待者，重新计算进程的调度策略与优先级
+    ///
+    /// 调用者需要关闭中断
     ///
     /// ## 返回值
     ///
-    /// 返回一个新的pcb
-    pub fn new(name: String, kstack: KernelStack) -> Arc<Self> {
-        return Self::do_create_pcb(name, kstack, false);
+    /// 进程的调度策略或优先级是否发生了改变
+    fn pi_adjust(pcb: &Arc<ProcessControlBlock>) -> bool {
+        let waiters: Vec<(SchedPolicy, SchedPriority)> = pcb
+            .pi_waiters
+            .lock_irqsave()
+            .iter()
+            .filter_map(|w| w.waiter.upgrade())
+            .map(|waiter| {
+                let sched_info = waiter.sched_info();
+                (sched_info.policy(), sched_info.priority())
+            })
+            .collect();
+
+        let normal = Self::normal_policy(pcb);
+        let (policy, priority) = pi_effective_policy(normal, &waiters).unwrap_or(normal);
+        {
+            let mut writer = pcb.sched_info_mut();
+            if writer.policy() == policy && writer.priority() == priority {
+                return false;
+            }
+            writer.pi_saved = if (policy, priority) == normal {
+                None
+            } else {
+                Some(normal)
+            };
+        }
+        Self::apply_policy(pcb, policy, priority, None);
+        return true;
     }
 
-    /// 创建一个新的idle进程
+    /// 获取进程占用的deadline带宽，不是deadline进程时为0
+    fn dl_bandwidth(pcb: &Arc<ProcessControlBlock>) -> u64 {
+        let sched_info = pcb.sched_info();
+        if sched_info.policy() != SchedPolicy::DEADLINE {
+            return 0;
+        }
+        return sched_info.dl().params().bandwidth();
+    }
+
+    /// 获取进程设置的cpu亲和性（sched_getaffinity）
     ///
-    /// 请注意，这个函数只能在进程管理初始化的时候调用。
-    pub fn new_idle(cpu_id: u32, kstack: KernelStack) -> Arc<Self> {
-        let name = format!("idle-{}", cpu_id);
-        return Self::do_create_pcb(name, kstack, true);
+    /// 返回的是进程设置的集合，而不是被进程组（cpuset）限制之后的集合，见`effective_affinity`
+    ///
+    /// ## 返回值
+    ///
+    /// 进程不存在时，返回`ESRCH`
+    pub fn sched_getaffinity(pid: Pid) -> Result<CpuMask, SystemError> {
+        let pcb = Self::find(pid).ok_or(SystemError::ESRCH)?;
+        return Ok(pcb.sched_info().cpu_affinity());
     }
 
-    fn do_create_pcb(name: String, kstack: KernelStack, is_idle: bool) -> Arc<Self> {
-        let (pid, ppid, cwd) = if is_idle {
-            (Pid(0), Pid(0), "/".to_string())
-        } else {
-            (
-                Self::generate_pid(),
-                ProcessManager::current_pcb().pid(),
-                ProcessManager::current_pcb().basic().cwd(),
-            )
-        };
-        let pgid = ProcessManager::current_pcb().basic().pgid();
-        let tg: Arc<TaskGroup> = ProcessManager::current_pcb().basic().tg();
-        let basic_info = ProcessBasicInfo::new(pgid, ppid, name, cwd, None, Some(tg));
-        let preempt_count = AtomicUsize::new(0);
-        let flags = SpinLock::new(ProcessFlags::empty());
+    /// 获取进程的调度策略（sched_getscheduler）
+    ///
+    /// ## 返回值
+    ///
+    /// 进程不存在时，返回`ESRCH`
+    pub fn sched_getscheduler(pid: Pid) -> Result<SchedPolicy, SystemError> {
+        let pcb = Self::find(pid).ok_or(SystemError::ESRCH)?;
+        return Ok(pcb.sched_info().policy());
+    }
 
-        let sched_info = ProcessSchedulerInfo::new(None);
-        let arch_info = SpinLock::new(ArchPCBInfo::new(Some(&kstack)));
+    /// 让出cpu，由调度器决定接下来运行哪个进程
+    pub fn yield_now() {
+        Self::current_pcb().set_need_schedule();
+        sched();
+    }
 
-        let ppcb: Weak<ProcessControlBlock> = ProcessManager::find(ppid)
-            .map(|p| Arc::downgrade(&p))
-            .unwrap_or_else(|| Weak::new());
+    /// 将当前进程剩余的时间片让给指定的进程，使其成为当前cpu上下一个运行的进程
+    ///
+    /// 用于让持有自旋锁的进程、或者半虚拟化客户机的vcpu尽快运行。
+    /// 如果目标进程不是可运行的cfs进程、正在其他cpu上运行，或者不允许在当前cpu上运行，
+    /// 则退化为`yield_now`。
+    ///
+    /// ## 返回值
+    ///
+    /// 是否成功地将时间片让给了目标进程
+    pub fn yield_to(target: Pid) -> bool {
+        let donated = Self::donate_slice(&Self::current_pcb(), target);
+        Self::yield_now();
+        return donated;
+    }
 
-        let pcb = Self {
-            pid,
-            basic: basic_info,
-            preempt_count,
-            flags,
-            kernel_stack: RwLock::new(kstack),
-            worker_private: SpinLock::new(None),
-            sched_info,
-            arch_info,
-            parent_pcb: RwLock::new(ppcb),
-            children: RwLock::new(HashMap::new()),
-            wait_queue: WaitQueue::INIT,
+    /// 将目标进程移到当前cpu的队列中，并使其虚拟运行时间小于当前进程以及队列中的其他进程
+    ///
+    /// 目标进程将使用当前cpu上剩余的时间片运行
+    fn donate_slice(current: &Arc<ProcessControlBlock>, target: Pid) -> bool {
+        let target = match Self::find(target) {
+            Some(target) => target,
+            None => return false,
         };
+        if Arc::ptr_eq(&target, current) {
+            return false;
+        }
+
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let cpu_id = smp_get_processor_id();
+        {
+            let sched_info = target.sched_info();
+            if !sched_info.state().is_runnable()
+                || !sched_info.policy().is_fair()
+                || !current.sched_info().policy().is_fair()
+                || !target.effective_affinity().contains(cpu_id as usize)
+            {
+                return false;
+            }
+        }
+        // 正在运行的进程不在任何队列中，无法被移到当前cpu
+        if !sched_dequeue(&target) {
+            return false;
+        }
+
+        let cfs = __get_cfs_scheduler();
+        let current_vruntime = current.sched_info().virtual_runtime();
+        let bound = cfs
+            .min_vruntime(cpu_id as usize)
+            .map_or(current_vruntime, |min| {
+                core::cmp::min(min, current_vruntime)
+            });
+        let sched_info = target.sched_info();
+        sched_info.set_virtual_runtime(bound - 1);
+        sched_info.set_on_cpu(Some(cpu_id));
+        drop(sched_info);
+        cfs.enqueue_on(target, cpu_id as usize);
+        return true;
+    }
+
+    /// 让进程创建一个新的会话，并成为新会话以及新进程组的组长
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：新会话的id，即进程的pid
+    /// - 失败：进程已经是进程组的组长时，返回`EPERM`
+    pub fn setsid(pcb: &Arc<ProcessControlBlock>) -> Result<Pid, SystemError> {
+        let pid = pcb.pid();
+        let mut basic = pcb.basic_mut();
+        if basic.pgid() == pid {
+            return Err(SystemError::EPERM);
+        }
+        // 原来的进程组可能没有被进程组管理器记录（例如内核线程所在的进程组），此时只需创建新的进程组
+        if PROCESS_GROUP_MANAGER
+            .set_pgid_by_pid(pid, pid, basic.pgid())
+            .is_err()
+        {
+            PROCESS_GROUP_MANAGER.add_group(pid)?;
+        }
+        basic.set_pgid(pid);
+        basic.set_sid(pid);
+        return Ok(pid);
+    }
+
+    /// 以守护进程的方式运行一个内核服务
+    ///
+    /// 与经典的daemon()一样通过两次fork使服务进程脱离原来的父进程与控制终端：
+    ///
+    /// 1. 创建中间进程，由它fork出服务进程，此时服务进程还没有开始运行
+    /// 2. 中间进程退出，服务进程成为孤儿进程，被init进程收养
+    /// 3. 调用`setsid`，使服务进程脱离原来的会话，也就不再有控制终端
+    /// 4. 将工作目录切换到根目录，避免占用原来的工作目录所在的文件系统
+    /// 5. 唤醒服务进程
+    ///
+    /// 所有设置都在服务进程开始运行之前完成，因此服务进程不会观察到中间状态。
+    ///
+    /// ## 参数
+    ///
+    /// - `func` : 服务进程的入口函数、传入参数
+    /// - `name` : 服务进程的名字
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：服务进程的pid
+    /// - 失败：无法创建进程时，返回`ENOMEM`；init进程不存在时，返回`ECHILD`
+    pub fn daemonize(func: KernelThreadClosure, name: String) -> Result<Pid, SystemError> {
+        // init进程不存在时，孤儿进程无法被收养
+        Self::find(Pid(1)).ok_or(SystemError::ECHILD)?;
+
+        let intermediate_name = format!("{}-daemonize", name);
+        let info = KernelThreadCreateInfo::new(func, name);
+        let daemon_info = info.clone();
+        let intermediate = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((
+                Box::new(move || {
+                    // 中间进程fork出服务进程之后立即退出
+                    return match KernelThreadMechanism::create_child(&daemon_info) {
+                        Ok(_) => 0,
+                        Err(e) => e.to_posix_errno(),
+                    };
+                }),
+                (),
+            )),
+            intermediate_name,
+        )
+        .ok_or(SystemError::ENOMEM)?;
+        Self::wakeup(&intermediate)?;
+        drop(intermediate);
+
+        let pcb = info.poll_result().ok_or(SystemError::ENOMEM)?;
+        // 等待中间进程退出，服务进程被init进程收养
+        while pcb.basic().ppid() != Pid(1) {
+            Self::yield_now();
+        }
+        Self::setsid(&pcb)?;
+        pcb.basic_mut().set_cwd("/".to_string());
+        Self::wakeup(&pcb)?;
+        return Ok(pcb.pid());
+    }
+
+    /// 唤醒一个新创建的进程
+    ///
+    /// 与`wakeup`不同，新进程的虚拟运行时间已经在fork时确定，入队时不会被重设
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：进程不处于阻塞状态时，返回`EINVAL`
+    pub fn wakeup_new(pcb: &Arc<ProcessControlBlock>) -> Result<(), SystemError> {
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let mut writer = pcb.sched_info_mut();
+        if !writer.state().is_blocked() {
+            return Err(SystemError::EINVAL);
+        }
+        writer.try_set_state(ProcessState::Runnable)?;
+        // avoid deadlock
+        drop(writer);
+
+        sched_enqueue(pcb.clone(), false);
+        return Ok(());
+    }
+
+    /// 检查被唤醒的进程是否应当抢占其目标cpu上正在运行的进程，如果是，则向目标cpu发送IPI
+    ///
+    /// ## 返回值
+    ///
+    /// 是否向目标cpu发送了IPI
+    pub fn wakeup_preempt(pcb: &Arc<ProcessControlBlock>) -> bool {
+        // 迁移期间on_cpu可能已经过时，由完成迁移的一方在进程入队时检查抢占
+        if pcb.sched_info().is_migrating() {
+            return false;
+        }
+        let target_cpu = match pcb.sched_info().on_cpu() {
+            Some(cpu_id) => cpu_id,
+            None => return false,
+        };
+
+        let should_preempt = Self::should_preempt(pcb, target_cpu);
+        if should_preempt {
+            kick_cpu(target_cpu).expect("wakeup_preempt: Failed to kick cpu");
+        }
+        return should_preempt;
+    }
+
+    /// 判断加入某个cpu的运行队列的进程，是否需要通过IPI抢占该cpu
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 被加入运行队列的进程
+    /// - `target_cpu` : 进程所在的运行队列对应的cpu
+    ///
+    /// ## 返回值
+    ///
+    /// 目标cpu正在运行idle进程，或者进程的虚拟运行时间小于目标cpu上正在运行的进程时，返回true。
+    /// 目标cpu为当前cpu时总是返回false，因为当前cpu会在中断返回或者下一次时钟中断时检查是否需要调度。
+    /// 进程正在被迁移时也返回false，`target_cpu`可能已经不是它所在的cpu。
+    pub fn should_preempt(pcb: &Arc<ProcessControlBlock>, target_cpu: u32) -> bool {
+        if target_cpu == smp_get_processor_id() || pcb.sched_info().is_migrating() {
+            return false;
+        }
+
+        let running = CPU_EXECUTING.get(target_cpu);
+        if running.is_idle() {
+            // 目标cpu正在运行idle进程
+            return true;
+        }
+        return match ProcessManager::find(running) {
+            Some(running) => {
+                running.sched_info().virtual_runtime() > pcb.sched_info().virtual_runtime()
+            }
+            None => true,
+        };
+    }
+
+    /// 批量唤醒一组进程
+    ///
+    /// 只关闭一次中断，在一次遍历中对每个进程调用`try_to_wake_up`。
+    /// 已经退出、已经处于可运行状态或者被冻结的进程会被跳过。
+    ///
+    /// ## 参数
+    ///
+    /// - `pcbs` : 要唤醒的进程
+    ///
+    /// ## 返回值
+    ///
+    /// 实际被唤醒的进程数
+    pub fn wakeup_all(pcbs: &[Arc<ProcessControlBlock>]) -> usize {
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        return pcbs
+            .iter()
+            .filter(|pcb| matches!(Self::try_to_wake_up(pcb), Ok(true)))
+            .count();
+    }
+
+    /// 让当前进程可被中断地睡眠一段时间
+    ///
+    /// 当前进程被标记为可中断的阻塞态，并设置一个定时器在`timeout`个jiffies之后唤醒它。
+    /// 进程可能被信号或者显式的唤醒提前唤醒，此时定时器会被取消。
+    ///
+    /// ## 参数
+    ///
+    /// - `timeout` : 睡眠的时间（单位：jiffies）
+    ///
+    /// ## 返回值
+    ///
+    /// 剩余未睡眠的jiffies数，如果是因为超时而被唤醒，返回0。
+    /// 如果睡眠被信号打断，返回`ERESTARTSYS`
+    pub fn schedule_timeout(timeout: i64) -> Result<i64, SystemError> {
+        return schedule_timeout(timeout);
+    }
+
+    /// 标志当前进程永久睡眠，但是发起调度的工作，应该由调用者完成
+    ///
+    /// ## 返回值
+    ///
+    /// - Ok(SleepOutcome::Event): 进程已被标记为睡眠
+    /// - Ok(SleepOutcome::Interrupted): 要求可中断的睡眠，但进程已有待处理的信号，因此不会睡眠
+    ///
+    /// ## 注意
+    ///
+    /// - 进入当前函数之前，不能持有sched_info的锁
+    /// - 进入当前函数之前，必须关闭中断
+    pub fn mark_sleep(interruptable: bool) -> Result<SleepOutcome, SystemError> {
+        assert_eq!(
+            CurrentIrqArch::is_irq_enabled(),
+            false,
+            "interrupt must be disabled before enter ProcessManager::mark_sleep()"
+        );
+
+        let pcb = ProcessManager::current_pcb();
+        if interruptable && pcb.flags().contains(ProcessFlags::HAS_PENDING_SIGNAL) {
+            return Ok(SleepOutcome::Interrupted);
+        }
+
+        let mut writer = pcb.sched_info_mut_irqsave();
+        if writer
+            .try_set_state(ProcessState::Blocked(interruptable))
+            .is_ok()
+        {
+            writer.mark_sleep_start(clock());
+            writer.mark_resched(sched_clock());
+            pcb.flags().insert(ProcessFlags::NEED_SCHEDULE);
+            drop(writer);
+
+            return Ok(SleepOutcome::Event);
+        }
+        return Err(SystemError::EINTR);
+    }
+
+    /// 当子进程退出后向父进程发送通知
+    ///
+    /// 退出状态被放入父进程的队列，由父进程的wait收集。内核线程的退出码通过`kthread_stop`获取，
+    /// 不会被等待，因此不保留它们的退出状态
+    fn exit_notify(exit_code: usize) {
+        Self::do_exit_notify(&ProcessManager::current_pcb(), exit_code);
+    }
+
+    fn do_exit_notify(current: &Arc<ProcessControlBlock>, exit_code: usize) {
+        // INIT进程退出之后，孤儿进程将无法被回收，系统处于不确定的状态
+        if current.pid() == Pid(1) {
+            Self::init_exited(exit_code);
+        }
+        // 让INIT进程收养所有子进程
+        {
+            unsafe {
+                current
+                    .adopt_childen()
+                    .unwrap_or_else(|e| panic!("adopte_childen failed: error: {e:?}"))
+            };
+            let is_kthread = current.flags().contains(ProcessFlags::KTHREAD);
+            let parent = current.parent_pcb.read().upgrade();
+            current.report_exit((!is_kthread).then_some(exit_code));
+
+            // init进程通过SIGCHLD得知有子进程退出，由回收线程代替它等待被收养的孤儿进程
+            // todo: 当信号机制支持SIGCHLD的默认行为（忽略）之后，向所有父进程发送SIGCHLD
+            if let Some(parent) = parent.filter(|parent| parent.pid() == Pid(1)) {
+                if !is_kthread
+                    && ProcessManager::send_signal_to_thread(&parent, Signal::SIGCHLD).is_ok()
+                {
+                    init::wake_zombie_reaper();
+                }
+            }
+        }
+    }
+
+    /// 解析内核启动参数中与进程管理相关的参数，在初始化阶段调用
+    ///
+    /// ## 参数
+    ///
+    /// - `cmdline` : 内核启动参数
+    pub fn parse_boot_params(cmdline: &str) {
+        if let Some(action) = InitExitAction::from_cmdline(cmdline) {
+            Self::set_init_exit_action(action);
+        }
+    }
+
+    /// 获取init进程退出时的处理方式
+    pub fn init_exit_action() -> InitExitAction {
+        match INIT_EXIT_ACTION.load(Ordering::SeqCst) {
+            1 => InitExitAction::Reboot,
+            _ => InitExitAction::Panic,
+        }
+    }
+
+    /// 设置init进程退出时的处理方式
+    pub fn set_init_exit_action(action: InitExitAction) {
+        INIT_EXIT_ACTION.store(action as u8, Ordering::SeqCst);
+    }
+
+    /// init进程退出时调用，根据启动参数panic或者重启系统
+    fn init_exited(exit_code: usize) -> ! {
+        let action = Self::init_exit_action();
+        kerror!("init exited, exit code: {exit_code}, action: {action:?}");
+        match action {
+            InitExitAction::Panic => panic!("init exited, exit code: {exit_code}"),
+            InitExitAction::Reboot => crate::arch::cpu::cpu_reset(),
+        }
+    }
+
+    /// 线程退出时，将set_tid_address设置的地址清零，并唤醒一个在该地址上等待的线程（pthread_join）
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 退出的线程
+    /// - `from_user` : 地址是否来自用户空间
+    pub fn clear_child_tid(pcb: &Arc<ProcessControlBlock>, from_user: bool) {
+        let uaddr = match pcb.take_clear_child_tid() {
+            Some(uaddr) => uaddr,
+            None => return,
+        };
+        let mut writer = match UserBufferWriter::new(
+            uaddr.data() as *mut i32,
+            core::mem::size_of::<i32>(),
+            from_user,
+        ) {
+            Ok(writer) => writer,
+            Err(_) => return,
+        };
+        if writer.copy_one_to_user(&0i32, 0).is_ok() {
+            Futex::wake(uaddr, 1);
+        }
+    }
+
+    /// 退出当前进程
+    ///
+    /// ## 参数
+    ///
+    /// - `exit_code` : 进程的退出码
+    pub fn exit(exit_code: usize) -> ! {
+        let current = ProcessManager::current_pcb();
+        let from_user = !current.flags().contains(ProcessFlags::KTHREAD);
+        Self::clear_child_tid(&current, from_user);
+        // 关闭文件可能需要睡眠，因此在关中断之前进行
+        current.close_all_fds();
+        if from_user {
+            current.drop_user_vm();
+        }
+        Self::acct_process(&current, exit_code);
+        drop(current);
+
+        // 关中断
+        let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let pcb = ProcessManager::current_pcb();
+        pcb.sched_info
+            .write()
+            .try_set_state(ProcessState::Exited(exit_code))
+            .unwrap_or_else(|e| panic!("exit: failed to set process state to exited: {e:?}"));
+        // 归还deadline进程占用的带宽
+        __get_dl_scheduler().admit(Self::dl_bandwidth(&pcb), 0).ok();
+        let pid = pcb.pid();
+
+        // 先将退出状态交给父进程，再唤醒等待者，使得被唤醒的父进程一定能收集到退出状态
+        ProcessManager::exit_notify(exit_code);
+        pcb.wait_queue.wakeup(Some(ProcessState::Blocked(true)));
+        drop(pcb);
+        drop(irq_guard);
+        sched();
+
+        // 已经退出的进程不会再被加入调度队列，sched()不应该返回
+        debug_assert!(false, "exited process {:?} returned from sched()", pid);
+        loop {
+            // 开中断并停机，等待下一次时钟中断触发调度，而不是空转
+            cpu_idle();
+            sched();
+        }
+    }
+
+    /// 将一个已经被回收的进程从进程表中移除
+    ///
+    /// 进程表持有的引用被移除后，pcb会在最后一个引用被释放时析构，并在析构时归还pid。
+    /// 因此其他地方仍然持有pcb的引用是安全的，它们只会推迟pcb的析构。
+    ///
+    /// ## 返回值
+    ///
+    /// 被移除的pcb。如果进程已经不在进程表中（例如已经被释放过），返回None
+    pub fn release(pid: Pid) -> Option<Arc<ProcessControlBlock>> {
+        // 返回被移除的pcb，使得它在进程表的锁释放之后才可能析构
+        return Self::all_process_shard(pid).lock().remove(&pid);
+    }
+
+    /// 记录当前cpu上即将进行的进程切换，切换完成后由`switch_finish_hook`释放两个进程的arch_info锁
+    ///
+    /// ## Safety
+    ///
+    /// - 调用者必须已经通过`SpinLockGuard::leak()`持有了`prev`和`next`的arch_info锁
+    /// - 调用之后必须立即切换上下文，中间不能再发生进程切换
+    pub unsafe fn prepare_switch(prev: &Arc<ProcessControlBlock>, next: &Arc<ProcessControlBlock>) {
+        let mut switch_result = SWITCH_RESULT.get().get().lock();
+        switch_result.prev_pcb = Some(prev.clone());
+        switch_result.next_pcb = Some(next.clone());
+    }
+
+    /// 上下文切换完成后的钩子函数
+    unsafe fn switch_finish_hook() {
+        // kdebug!("switch_finish_hook");
+        let mut switch_result = SWITCH_RESULT.get().get().lock();
+        let prev_pcb = switch_result.prev_pcb.take().expect("prev_pcb is None");
+        let next_pcb = switch_result.next_pcb.take().expect("next_pcb is None");
+        drop(switch_result);
+
+        // 更新当前cpu上正在运行的进程的缓存，并释放缓存原先持有的引用
+        let cached = Arc::into_raw(next_pcb.clone()) as *mut ProcessControlBlock;
+        let old = CURRENT_PCB.get().get().swap(cached, Ordering::AcqRel);
+        if !old.is_null() {
+            drop(Arc::from_raw(old));
+        }
+
+        // 由于进程切换前使用了SpinLockGuard::leak()，所以这里需要手动释放锁
+        prev_pcb.arch_info.force_unlock();
+        next_pcb.arch_info.force_unlock();
+    }
+
+    /// 如果目标进程正在目标CPU上运行，那么就让这个cpu陷入内核态
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 进程的pcb
+    #[allow(dead_code)]
+    pub fn kick(pcb: &Arc<ProcessControlBlock>) {
+        ProcessManager::current_pcb().preempt_disable();
+        // 正在被迁移的进程不在任何cpu上运行
+        let cpu_id = pcb
+            .sched_info()
+            .on_cpu()
+            .filter(|_| !pcb.sched_info().is_migrating());
+
+        if let Some(cpu_id) = cpu_id {
+            let cpu_id = cpu_id;
+
+            if pcb.pid() == CPU_EXECUTING.get(cpu_id) {
+                kick_cpu(cpu_id).expect("ProcessManager::kick(): Failed to kick cpu");
+            }
+        }
+
+        ProcessManager::current_pcb().preempt_enable();
+    }
+}
+
+/// 上下文切换的钩子函数,当这个函数return的时候,将会发生上下文切换
+pub unsafe extern "sysv64" fn switch_finish_hook() {
+    ProcessManager::switch_finish_hook();
+}
+
+int_like!(Pid, AtomicPid, usize, AtomicUsize);
+
+impl Hash for Pid {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl Pid {
+    /// idle进程的pid
+    ///
+    /// pid 0被保留给每个cpu上的idle进程：它不会被分配给其他进程，不在进程表中，也不能作为进程组id
+    pub const IDLE: Pid = Pid(0);
+
+    /// 是否是idle进程的pid
+    #[inline(always)]
+    pub const fn is_idle(&self) -> bool {
+        return self.0 == Self::IDLE.0;
+    }
+
+    pub fn to_string(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    /// The process is running on a CPU or in a run queue.
+    Runnable,
+    /// The process is waiting for an event to occur.
+    /// 其中的bool表示该等待过程是否可以被打断。
+    /// - 如果该bool为true,那么，硬件中断/信号/其他系统事件都可以打断该等待过程，使得该进程重新进入Runnable状态。
+    /// - 如果该bool为false,那么，这个进程必须被显式的唤醒，才能重新进入Runnable状态。
+    Blocked(bool),
+    /// 进程被暂停执行，需要被显式地恢复才能重新进入Runnable状态
+    Stopped,
+    /// 进程被冻结，只有解冻后才能重新进入Runnable状态
+    Frozen,
+    /// 进程已经退出，退出状态还没有被收集，usize表示进程的退出码
+    Exited(usize),
+    /// 进程的退出状态已经交给父进程，正在等待被回收
+    Dead,
+}
+
+/// 进程从可中断的睡眠中返回的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepOutcome {
+    /// 所等待的事件已经发生（或者已经超时）
+    Event,
+    /// 睡眠被信号打断
+    Interrupted,
+}
+
+impl SleepOutcome {
+    /// 转换为系统调用层使用的返回值，被信号打断时返回`ERESTARTSYS`，
+    /// 由系统调用层决定重新执行系统调用，还是向用户程序返回`EINTR`
+    pub fn into_result(self) -> Result<(), SystemError> {
+        match self {
+            SleepOutcome::Event => return Ok(()),
+            SleepOutcome::Interrupted => return Err(SystemError::ERESTARTSYS),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl ProcessState {
+    #[inline(always)]
+    pub fn is_runnable(&self) -> bool {
+        return matches!(self, ProcessState::Runnable);
+    }
+
+    #[inline(always)]
+    pub fn is_blocked(&self) -> bool {
+        return matches!(self, ProcessState::Blocked(_));
+    }
+
+    #[inline(always)]
+    pub fn is_exited(&self) -> bool {
+        return matches!(self, ProcessState::Exited(_));
+    }
+
+    #[inline(always)]
+    pub fn is_dead(&self) -> bool {
+        return matches!(self, ProcessState::Dead);
+    }
+
+    /// 进程是否已经退出，无论它的退出状态是否已经被收集
+    #[inline(always)]
+    pub fn has_exited(&self) -> bool {
+        return self.is_exited() || self.is_dead();
+    }
+
+    #[inline(always)]
+    pub fn is_stopped(&self) -> bool {
+        return matches!(self, ProcessState::Stopped);
+    }
+
+    #[inline(always)]
+    pub fn is_frozen(&self) -> bool {
+        return matches!(self, ProcessState::Frozen);
+    }
+
+    /// 判断从当前状态转换到`new`状态是否合法
+    ///
+    /// 合法的状态转换：
+    /// - Runnable <-> Blocked
+    /// - Runnable <-> Stopped
+    /// - Runnable <-> Frozen
+    /// - Blocked -> Frozen（被冻结的进程在睡眠中被唤醒）
+    /// - Runnable -> Exited
+    /// - Blocked -> Exited
+    /// - Exited -> Dead（退出状态被交给父进程）
+    ///
+    /// 死亡的进程不能再转换到任何状态
+    pub fn can_transition_to(&self, new: ProcessState) -> bool {
+        match (self, new) {
+            (ProcessState::Runnable, ProcessState::Blocked(_))
+            | (ProcessState::Blocked(_), ProcessState::Runnable)
+            | (ProcessState::Runnable, ProcessState::Stopped)
+            | (ProcessState::Stopped, ProcessState::Runnable)
+            | (ProcessState::Runnable, ProcessState::Frozen)
+            | (ProcessState::Blocked(_), ProcessState::Frozen)
+            | (ProcessState::Frozen, ProcessState::Runnable)
+            | (ProcessState::Runnable, ProcessState::Exited(_))
+            | (ProcessState::Blocked(_), ProcessState::Exited(_))
+            | (ProcessState::Exited(_), ProcessState::Dead) => true,
+            _ => false,
+        }
+    }
+}
+
+bitflags! {
+    /// pcb的标志位
+    pub struct ProcessFlags: usize {
+        /// 当前pcb表示一个内核线程
+        const KTHREAD = 1 << 0;
+        /// 当前进程需要被调度
+        const NEED_SCHEDULE = 1 << 1;
+        /// 进程由于vfork而与父进程存在资源共享
+        const VFORK = 1 << 2;
+        /// 进程不可被冻结
+        const NOFREEZE = 1 << 3;
+        /// 进程正在退出
+        const EXITING = 1 << 4;
+        /// 进程由于接收到终止信号唤醒
+        const WAKEKILL = 1 << 5;
+        /// 进程由于接收到信号而退出.(Killed by a signal)
+        const SIGNALED = 1 << 6;
+        /// 进程需要迁移到其他cpu上
+        const NEED_MIGRATE = 1 << 7;
+        /// 进程有待处理的信号
+        const HAS_PENDING_SIGNAL = 1 << 8;
+        /// 进程被要求冻结
+        const FROZEN = 1 << 9;
+    }
+}
+
+/// 已经退出、等待父进程通过wait收集的子进程的退出状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChildExitStatus {
+    /// 子进程的pid
+    pub pid: Pid,
+    /// 子进程退出时所在的进程组
+    pub pgid: Pid,
+    /// 子进程的退出码
+    pub status: usize,
+    /// 子进程是否是被init收养的孤儿进程，这类退出状态可以由回收线程收集
+    pub orphan: bool,
+}
+
+impl ChildExitStatus {
+    /// 判断退出状态是否符合wait4的pid语义
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 要等待的子进程，含义见`ProcessControlBlock::wait_candidates`
+    /// - `pgid` : 等待者所在的进程组，`pid`等于0时使用
+    pub fn matches(&self, pid: i64, pgid: Pid) -> bool {
+        return match pid {
+            pid if pid > 0 => self.pid == Pid(pid as usize),
+            -1 => true,
+            0 => self.pgid == pgid,
+            _ => self.pgid == Pid(pid.unsigned_abs() as usize),
+        };
+    }
+}
+
+/// 等待某个支持优先级继承的锁的进程，记录在锁的持有者上
+#[derive(Debug)]
+struct PiWaiter {
+    /// 等待的锁的标识
+    lock: usize,
+    /// 等待锁的进程
+    waiter: Weak<ProcessControlBlock>,
+}
+
+/// 计算锁的持有者由于优先级继承而应当使用的调度策略与优先级
+///
+/// ## 参数
+///
+/// - `normal` : 持有者自己设置的调度策略与优先级
+/// - `waiters` : 等待持有者所持有的锁的进程的调度策略与优先级
+///
+/// ## 返回值
+///
+/// 等待者中优先级最高的实时进程比持有者的优先级更高时，返回该等待者的调度策略与优先级；
+/// 否则持有者不需要被提升，返回None。deadline进程永远不会被提升
+fn pi_effective_policy(
+    normal: (SchedPolicy, SchedPriority),
+    waiters: &[(SchedPolicy, SchedPriority)],
+) -> Option<(SchedPolicy, SchedPriority)> {
+    if normal.0 == SchedPolicy::DEADLINE {
+        return None;
+    }
+    // 优先级的数值越小，优先级越高
+    let top = waiters
+        .iter()
+        .filter(|(policy, _)| policy.is_rt())
+        .min_by_key(|(_, priority)| *priority)?;
+    if normal.0.is_rt() && normal.1 <= top.1 {
+        return None;
+    }
+    return Some(*top);
+}
+
+#[derive(Debug)]
+pub struct ProcessControlBlock {
+    /// 当前进程的pid
+    pid: Pid,
+    /// 所在线程组的id，即线程组中第一个线程的pid
+    tgid: AtomicPid,
+
+    basic: RwLock<ProcessBasicInfo>,
+    /// 当前进程的自旋锁持有计数
+    preempt_count: AtomicUsize,
+
+    flags: SpinLock<ProcessFlags>,
+    worker_private: SpinLock<Option<WorkerPrivate>>,
+    /// 进程的内核栈
+    kernel_stack: RwLock<KernelStack>,
+
+    /// 与调度相关的信息
+    sched_info: RwLock<ProcessSchedulerInfo>,
+    /// 与处理器架构相关的信息
+    arch_info: SpinLock<ArchPCBInfo>,
+
+    /// 父进程指针
+    parent_pcb: RwLock<Weak<ProcessControlBlock>>,
+
+    /// 子进程链表
+    children: RwLock<HashMap<Pid, Arc<ProcessControlBlock>>>,
+
+    /// 等待队列
+    wait_queue: WaitQueue,
+
+    /// 进程的身份凭证
+    cred: SpinLock<Credentials>,
+
+    /// 内存不足时，用于调整选择被杀死的进程的评分，范围为`[OOM_SCORE_ADJ_MIN, OOM_SCORE_ADJ_MAX]`
+    oom_score_adj: AtomicI32,
+    /// 没有`CAP_SYS_RESOURCE`能力时，oom_score_adj所能设置的最小值
+    oom_score_adj_min: AtomicI32,
+
+    /// 线程的待处理信号与被阻塞的信号
+    sig_state: SpinLock<SignalState>,
+
+    /// 进程的资源限制
+    rlimits: SpinLock<RLimitTable>,
+
+    /// 进程的间隔定时器
+    itimers: SpinLock<ITimers>,
+
+    /// 线程退出时需要清零并唤醒的用户地址（set_tid_address），为0表示没有设置
+    clear_child_tid: AtomicUsize,
+
+    /// 已经退出、还没有被wait收集的子进程的退出状态，按照退出的先后顺序排列
+    child_exit_statuses: SpinLock<VecDeque<ChildExitStatus>>,
+
+    /// 进程的父进程退出后，是否被init进程收养
+    adopted: AtomicBool,
+
+    /// 进程正在等待获取的支持优先级继承的锁的持有者
+    pi_blocked_on: SpinLock<Weak<ProcessControlBlock>>,
+
+    /// 等待进程所持有的、支持优先级继承的锁的进程
+    pi_waiters: SpinLock<Vec<PiWaiter>>,
+
+    /// 进程被创建时的时间（单位：微秒）
+    start_time: u64,
+
+    /// fork时与其他进程共享的资源，只记录`CLONE_FILES`与`CLONE_VM`
+    shared_resources: SpinLock<CloneFlags>,
+}
+
+impl ProcessControlBlock {
+    /// Generate a new pcb.
+    ///
+    /// ## 参数
+    ///
+    /// - `name` : 进程的名字
+    /// - `kstack` : 进程的内核栈
+    ///
+    /// ## 返回值
+    ///
+    /// 返回一个新的pcb，没有空闲的pid时返回`EAGAIN`
+    pub fn new(name: String, kstack: KernelStack) -> Result<Arc<Self>, SystemError> {
+        return Self::do_create_pcb(name, kstack, false);
+    }
+
+    /// 创建一个新的idle进程
+    ///
+    /// 请注意，这个函数只能在进程管理初始化的时候调用。
+    pub fn new_idle(cpu_id: u32, kstack: KernelStack) -> Arc<Self> {
+        let name = format!("idle-{}", cpu_id);
+        return Self::do_create_pcb(name, kstack, true)
+            .expect("Failed to create pcb for idle process");
+    }
+
+    fn do_create_pcb(
+        name: String,
+        kstack: KernelStack,
+        is_idle: bool,
+    ) -> Result<Arc<Self>, SystemError> {
+        let (pid, ppid, cwd) = if is_idle {
+            (Pid::IDLE, Pid::IDLE, "/".to_string())
+        } else {
+            (
+                Self::generate_pid()?,
+                ProcessManager::current_pcb().pid(),
+                ProcessManager::current_pcb().basic().cwd(),
+            )
+        };
+        let pgid = ProcessManager::current_pcb().basic().pgid();
+        // 子进程与父进程处于同一个会话中
+        let sid = if is_idle {
+            Pid::IDLE
+        } else {
+            ProcessManager::current_pcb().basic().sid()
+        };
+        let tg: Arc<TaskGroup> = ProcessManager::current_pcb().basic().tg();
+        let basic_info = ProcessBasicInfo::new(pgid, sid, ppid, name, cwd, None, Some(tg));
+        let preempt_count = AtomicUsize::new(0);
+        let flags = SpinLock::new(ProcessFlags::empty());
+
+        let sched_info = ProcessSchedulerInfo::new(None);
+        let (cred, oom_score_adj, rlimits) = if is_idle {
+            (Credentials::root(), 0, RLimitTable::default())
+        } else {
+            // 子进程继承父进程（也就是当前进程）的nice值、cpu亲和性、凭证、oom_score_adj以及资源限制
+            let current = ProcessManager::current_pcb();
+            sched_info.read().inherit_from(&current.sched_info());
+            (
+                current.cred(),
+                current.oom_score_adj(),
+                *current.rlimits.lock_irqsave(),
+            )
+        };
+        let arch_info = SpinLock::new(ArchPCBInfo::new(Some(&kstack)));
+
+        let ppcb: Weak<ProcessControlBlock> = ProcessManager::find(ppid)
+            .map(|p| Arc::downgrade(&p))
+            .unwrap_or_else(|| Weak::new());
+
+        let pcb = Self {
+            pid,
+            tgid: AtomicPid::new(pid),
+            basic: basic_info,
+            preempt_count,
+            flags,
+            kernel_stack: RwLock::new(kstack),
+            worker_private: SpinLock::new(None),
+            sched_info,
+            arch_info,
+            parent_pcb: RwLock::new(ppcb),
+            children: RwLock::new(HashMap::new()),
+            wait_queue: WaitQueue::INIT,
+            cred: SpinLock::new(cred),
+            oom_score_adj: AtomicI32::new(oom_score_adj),
+            oom_score_adj_min: AtomicI32::new(oom_score_adj),
+            sig_state: SpinLock::new(SignalState::default()),
+            rlimits: SpinLock::new(rlimits),
+            itimers: SpinLock::new(ITimers::default()),
+            clear_child_tid: AtomicUsize::new(0),
+            child_exit_statuses: SpinLock::new(VecDeque::new()),
+            adopted: AtomicBool::new(false),
+            pi_blocked_on: SpinLock::new(Weak::new()),
+            pi_waiters: SpinLock::new(Vec::new()),
+            start_time: clock(),
+            shared_resources: SpinLock::new(CloneFlags::empty()),
+        };
+
+        let pcb = Arc::new(pcb);
+
+        // 设置调度实体对应的进程
+        if let Some(se) = pcb.sched_info().se() {
+            se.set_pcb(&pcb);
+        }
+
+        // 设置进程的弱引用到内核栈的最低地址处
+        unsafe {
+            pcb.kernel_stack
+                .write()
+                .set_pcb(Arc::downgrade(&pcb))
+                .unwrap()
+        };
+
+        // 将当前pcb加入父进程的子进程哈希表中
+        if pcb.pid() > Pid(1) {
+            if let Some(ppcb_arc) = pcb.parent_pcb.read().upgrade() {
+                let mut children = ppcb_arc.children.write();
+                children.insert(pcb.pid(), pcb.clone());
+            } else {
+                panic!("parent pcb is None");
+            }
+        }
+
+        return Ok(pcb);
+    }
+
+    /// 生成一个新的pid
+    ///
+    /// ## 返回值
+    ///
+    /// 没有空闲的pid时，返回`EAGAIN`
+    #[inline(always)]
+    fn generate_pid() -> Result<Pid, SystemError> {
+        return pid::alloc_pid();
+    }
+
+    /// 返回当前进程的锁持有计数
+    #[inline(always)]
+    pub fn preempt_count(&self) -> usize {
+        return self.preempt_count.load(Ordering::SeqCst);
+    }
+
+    /// 增加当前进程的锁持有计数
+    #[inline(always)]
+    pub fn preempt_disable(&self) {
+        self.preempt_count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// 减少当前进程的锁持有计数
+    ///
+    /// 计数已经为0时，说明加锁与解锁不配对：debug构建中panic，release构建中打印警告，并且计数保持为0
+    #[inline(always)]
+    pub fn preempt_enable(&self) {
+        let r = self
+            .preempt_count
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                count.checked_sub(1)
+            });
+        if unlikely(r.is_err()) {
+            self.preempt_count_underflow();
+        }
+    }
+
+    #[cold]
+    fn preempt_count_underflow(&self) {
+        #[cfg(debug_assertions)]
+        panic!("preempt_count underflow: pid: {:?}", self.pid);
+        #[cfg(not(debug_assertions))]
+        kwarn!("preempt_count underflow: pid: {:?}", self.pid);
+    }
+
+    #[inline(always)]
+    pub unsafe fn set_preempt_count(&self, count: usize) {
+        self.preempt_count.store(count, Ordering::SeqCst);
+    }
+
+    /// 获取进程的常驻内存（RSS）页数，没有用户地址空间的进程为0
+    ///
+    /// 共享同一个地址空间的线程，返回的是整个地址空间的常驻内存
+    pub fn rss(&self) -> usize {
+        return self.basic().user_vm().map_or(0, |vm| vm.read().rss());
+    }
+
+    pub fn oom_score_adj(&self) -> i32 {
+        return self.oom_score_adj.load(Ordering::SeqCst);
+    }
+
+    /// 设置进程的oom_score_adj
+    ///
+    /// 没有`CAP_SYS_RESOURCE`能力时，不能将其设置得比fork时继承的值（或者特权进程设置的值）更小；
+    /// 拥有该能力时，设置的值同时成为新的下限。
+    ///
+    /// ## 参数
+    ///
+    /// - `adj` : 新的oom_score_adj
+    /// - `privileged` : 设置者是否拥有`CAP_SYS_RESOURCE`能力
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：值不在`[OOM_SCORE_ADJ_MIN, OOM_SCORE_ADJ_MAX]`范围内时，返回`EINVAL`；
+    ///   没有权限降低oom_score_adj时，返回`EACCES`
+    pub fn set_oom_score_adj(&self, adj: i32, privileged: bool) -> Result<(), SystemError> {
+        if adj < OOM_SCORE_ADJ_MIN || adj > OOM_SCORE_ADJ_MAX {
+            return Err(SystemError::EINVAL);
+        }
+        if privileged {
+            self.oom_score_adj_min.store(adj, Ordering::SeqCst);
+        } else if adj < self.oom_score_adj_min.load(Ordering::SeqCst) {
+            return Err(SystemError::EACCES);
+        }
+        self.oom_score_adj.store(adj, Ordering::SeqCst);
+        return Ok(());
+    }
+
+    /// 获取线程组的id
+    pub fn tgid(&self) -> Pid {
+        return self.tgid.load(Ordering::SeqCst);
+    }
+
+    /// 将进程加入指定的线程组，只能在进程开始运行之前调用
+    pub fn set_tgid(&self, tgid: Pid) {
+        self.tgid.store(tgid, Ordering::SeqCst);
+    }
+
+    /// 获取线程的信号状态
+    pub fn sig_state(&self) -> SpinLockGuard<SignalState> {
+        return self.sig_state.lock_irqsave();
+    }
+
+    /// 获取进程的间隔定时器
+    pub fn itimers(&self) -> SpinLockGuard<ITimers> {
+        return self.itimers.lock_irqsave();
+    }
+
+    /// 获取进程的某项资源限制
+    pub fn rlimit(&self, id: RLimitID) -> RLimit {
+        return self.rlimits.lock_irqsave().get(id);
+    }
+
+    /// 设置进程的某项资源限制
+    ///
+    /// ## 参数
+    ///
+    /// - `id` : 资源限制的种类
+    /// - `limit` : 新的资源限制
+    /// - `privileged` : 设置者是否拥有`CAP_SYS_RESOURCE`能力
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：软限制大于硬限制时，返回`EINVAL`；没有权限提高硬限制时，返回`EPERM`
+    pub fn set_rlimit(
+        &self,
+        id: RLimitID,
+        limit: RLimit,
+        privileged: bool,
+    ) -> Result<(), SystemError> {
+        return self.rlimits.lock_irqsave().set(id, limit, privileged);
+    }
+
+    /// 将进程运行的时间计入它所在的进程组
+    ///
+    /// 在时钟中断中调用，不能等待`basic`的写锁，因此写锁恰好被持有时，这段时间不计入进程组
+    pub fn account_group_cpu_usage(&self, delta_ns: u64) {
+        if let Some(tg) = self.basic.try_read().and_then(|basic| basic.tg()) {
+            tg.account_cpu_usage(delta_ns);
+        }
+    }
+
+    /// 设置线程退出时需要清零并唤醒的用户地址
+    pub fn set_clear_child_tid(&self, uaddr: VirtAddr) {
+        self.clear_child_tid.store(uaddr.data(), Ordering::SeqCst);
+    }
+
+    /// 取出线程退出时需要清零并唤醒的用户地址，取出后该地址被清除
+    pub fn take_clear_child_tid(&self) -> Option<VirtAddr> {
+        let uaddr = self.clear_child_tid.swap(0, Ordering::SeqCst);
+        return (uaddr != 0).then(|| VirtAddr::new(uaddr));
+    }
+
+    /// 获取进程正在等待获取的支持优先级继承的锁的持有者，没有在等待时返回None
+    pub fn pi_blocked_on(&self) -> Option<Arc<ProcessControlBlock>> {
+        return self.pi_blocked_on.lock_irqsave().upgrade();
+    }
+
+    /// 设置进程正在等待获取的支持优先级继承的锁的持有者，为None表示不再等待
+    pub fn set_pi_blocked_on(&self, owner: Option<&Arc<ProcessControlBlock>>) {
+        *self.pi_blocked_on.lock_irqsave() = owner.map_or(Weak::new(), Arc::downgrade);
+    }
+
+    /// 获取进程实际允许运行的cpu集合，即进程设置的cpu亲和性与它所在进程组（cpuset）允许的cpu集合的交集
+    ///
+    /// 交集为空时，以进程组允许的cpu集合为准。进程的放置与迁移使用该集合，
+    /// 而`sched_getaffinity`仍然返回进程设置的cpu亲和性。
+    ///
+    /// 需要获取`basic`的读锁，因此调用者不能持有该进程的`basic`的写锁
+    pub fn effective_affinity(&self) -> CpuMask {
+        let requested = self.sched_info().cpu_affinity();
+        let cpuset = match self.basic().tg() {
+            Some(tg) => tg.cpus_allowed(),
+            None => return requested,
+        };
+        let effective = requested & cpuset;
+        if effective.is_empty() {
+            return cpuset;
+        }
+        return effective;
+    }
+
+    /// 获取进程的身份凭证
+    pub fn cred(&self) -> Credentials {
+        return *self.cred.lock();
+    }
+
+    /// 设置进程的身份凭证
+    pub fn set_cred(&self, cred: Credentials) {
+        *self.cred.lock() = cred;
+    }
+
+    #[inline(always)]
+    pub fn flags(&self) -> SpinLockGuard<ProcessFlags> {
+        return self.flags.lock();
+    }
+
+    /// 标记进程需要被调度，并记录标记的时刻，用于统计进程切换的延迟
+    ///
+    /// 不能在持有进程的`sched_info`写锁时调用
+    pub fn set_need_schedule(&self) {
+        self.sched_info().mark_resched(sched_clock());
+        self.flags().insert(ProcessFlags::NEED_SCHEDULE);
+    }
+
+    #[inline(always)]
+    pub fn basic(&self) -> RwLockReadGuard<ProcessBasicInfo> {
+        return self.basic.read();
+    }
+
+    #[inline(always)]
+    pub fn set_name(&self, name: String) {
+        self.basic.write().set_name(name);
+    }
+
+    #[inline(always)]
+    pub fn basic_mut(&self) -> RwLockWriteGuard<ProcessBasicInfo> {
+        return self.basic.write();
+    }
+
+    #[inline(always)]
+    pub fn arch_info(&self) -> SpinLockGuard<ArchPCBInfo> {
+        return self.arch_info.lock();
+    }
+
+    #[inline(always)]
+    pub fn arch_info_irqsave(&self) -> SpinLockGuard<ArchPCBInfo> {
+        return self.arch_info.lock_irqsave();
+    }
+
+    #[inline(always)]
+    pub fn kernel_stack(&self) -> RwLockReadGuard<KernelStack> {
+        return self.kernel_stack.read();
+    }
+
+    #[inline(always)]
+    #[allow(dead_code)]
+    pub fn kernel_stack_mut(&self) -> RwLockWriteGuard<KernelStack> {
+        return self.kernel_stack.write();
+    }
+
+    #[inline(always)]
+    pub fn sched_info(&self) -> RwLockReadGuard<ProcessSchedulerInfo> {
+        return self.sched_info.read();
+    }
+
+    #[inline(always)]
+    pub fn sched_info_mut(&self) -> RwLockWriteGuard<ProcessSchedulerInfo> {
+        return self.sched_info.write();
+    }
+
+    #[inline(always)]
+    pub fn sched_info_mut_irqsave(&self) -> RwLockWriteGuard<ProcessSchedulerInfo> {
+        return self.sched_info.write_irqsave();
+    }
+
+    #[inline(always)]
+    pub fn worker_private(&self) -> SpinLockGuard<Option<WorkerPrivate>> {
+        return self.worker_private.lock();
+    }
+
+    #[inline(always)]
+    pub fn pid(&self) -> Pid {
+        return self.pid;
+    }
+
+    /// 获取进程被创建时的时间（单位：微秒）
+    pub fn start_time(&self) -> u64 {
+        return self.start_time;
+    }
+
+    /// 获取文件描述符表的Arc指针
+    #[inline(always)]
+    pub fn fd_table(&self) -> Arc<RwLock<FileDescriptorVec>> {
+        return self.basic.read().fd_table().unwrap();
+    }
+
+    /// 记录进程在fork时与其他进程共享的资源
+    ///
+    /// 共享关系是双向的，因此父进程与子进程都需要记录。除了execve换用新的地址空间之外，记录不会被清除，
+    /// 即使共享的另一方已经退出，资源也只在pcb析构时才被释放
+    pub fn mark_shared(&self, clone_flags: CloneFlags) {
+        let shared = clone_flags & (CloneFlags::CLONE_FILES | CloneFlags::CLONE_VM);
+        self.shared_resources.lock_irqsave().insert(shared);
+    }
+
+    /// 进程换用了只属于自己的`resource`（例如execve之后的`CLONE_VM`）时，清除共享的记录
+    pub fn clear_shared(&self, resource: CloneFlags) {
+        self.shared_resources.lock_irqsave().remove(resource);
+    }
+
+    /// 判断进程的`resource`（例如`CLONE_FILES`）是否与其他进程共享
+    fn is_shared(&self, resource: CloneFlags) -> bool {
+        return self.shared_resources.lock_irqsave().contains(resource);
+    }
+
+    /// 进程退出时关闭它打开的所有文件，使得socket、管道等资源被及时释放，而不是等到pcb析构
+    ///
+    /// 如果文件描述符表通过CLONE_FILES与其他进程共享，那么其他进程仍然在使用这些文件，不会关闭它们
+    pub fn close_all_fds(&self) {
+        if self.is_shared(CloneFlags::CLONE_FILES) {
+            return;
+        }
+        let fd_table = match self.basic.read().fd_table() {
+            Some(fd_table) => fd_table,
+            None => return,
+        };
+        let files = fd_table.write().take_all();
+        // 在释放文件描述符表的锁之后再关闭文件
+        drop(files);
+    }
+
+    /// 进程退出时取消用户地址空间内的所有映射并归还物理页，而不是等到地址空间的最后一个引用被释放
+    ///
+    /// 如果地址空间通过CLONE_VM与其他进程共享，那么不做任何操作。fork时子进程会拷贝所有的页，
+    /// 因此独占的地址空间中的页都只属于当前进程。地址空间本身仍然保留（为空），
+    /// 使得进程被回收之前仍然可以查询它的内存信息
+    pub fn drop_user_vm(&self) {
+        if self.is_shared(CloneFlags::CLONE_VM) {
+            return;
+        }
+        let user_vm = match self.basic.read().user_vm() {
+            Some(user_vm) => user_vm,
+            None => return,
+        };
+        unsafe { user_vm.write().unmap_all() };
+    }
+
+    /// 根据文件描述符序号，获取socket对象的Arc指针
+    ///
+    /// ## 参数
+    ///
+    /// - `fd` 文件描述符序号
+    ///
+    /// ## 返回值
+    ///
+    /// Option(&mut Box<dyn Socket>) socket对象的可变引用. 如果文件描述符不是socket，那么返回None
+    pub fn get_socket(&self, fd: i32) -> Option<Arc<SocketInode>> {
+        let binding = ProcessManager::current_pcb().fd_table();
+        let fd_table_guard = binding.read();
+
+        let f = fd_table_guard.get_file_by_fd(fd)?;
+        drop(fd_table_guard);
+
+        let guard = f.lock();
+        if guard.file_type() != FileType::Socket {
+            return None;
+        }
+        let socket: Arc<SocketInode> = guard
+            .inode()
+            .downcast_arc::<SocketInode>()
+            .expect("Not a socket inode");
+        return Some(socket);
+    }
+
+    /// 当前进程退出时,让初始进程收养所有子进程
+    unsafe fn adopt_childen(&self) -> Result<(), SystemError> {
+        match ProcessManager::find(Pid(1)) {
+            Some(init_pcb) => {
+                let mut childen_guard = self.children.write();
+                let mut init_childen_guard = init_pcb.children.write();
+                let mut reaped = Vec::new();
+
+                childen_guard.drain().for_each(|(pid, child)| {
+                    if child.sched_info().state().has_exited() {
+                        // 已经退出的子进程不会再被等待，由INIT进程直接回收
+                        init_pcb
+                            .sched_info()
+                            .absorb_child_times(&child.sched_info());
+                        ProcessManager::release(pid);
+                        reaped.push(child);
+                    } else {
+                        child.adopted.store(true, Ordering::SeqCst);
+                        // 在持有子进程表的锁时更新父进程，使之后退出的子进程把退出状态交给init进程
+                        *child.parent_pcb.write() = Arc::downgrade(&init_pcb);
+                        child.basic_mut().set_ppid(Pid(1));
+                        init_childen_guard.insert(pid, child);
+                    }
+                });
+
+                // 被回收的子进程析构时会访问父进程的子进程表，因此要在释放锁之后才能析构
+                drop(init_childen_guard);
+                drop(childen_guard);
+                drop(reaped);
+
+                return Ok(());
+            }
+            _ => Err(SystemError::ECHILD),
+        }
+    }
+
+    /// 按照wait4的pid语义，获取当前进程中可以被等待的子进程
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 大于0时等待该子进程；等于0时等待与当前进程处于同一进程组的任意子进程；
+    /// 等于-1时等待任意子进程；小于-1时等待进程组`-pid`中的任意子进程
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：所有符合条件的子进程
+    /// - 失败：没有符合条件的子进程时，返回`ECHILD`
+    fn wait_candidates(&self, pid: i64) -> Result<Vec<Arc<ProcessControlBlock>>, SystemError> {
+        let children = self.children.read();
+        let candidates: Vec<Arc<ProcessControlBlock>> = match pid {
+            pid if pid > 0 => children
+                .get(&Pid(pid as usize))
+                .cloned()
+                .into_iter()
+                .collect(),
+            -1 => children.values().cloned().collect(),
+            _ => {
+                let pgid = if pid == 0 {
+                    self.basic().pgid()
+                } else {
+                    Pid(pid.unsigned_abs() as usize)
+                };
+                children
+                    .values()
+                    .filter(|child| PROCESS_GROUP_MANAGER.is_member(pgid, child))
+                    .cloned()
+                    .collect()
+            }
+        };
+        if candidates.is_empty() {
+            return Err(SystemError::ECHILD);
+        }
+        return Ok(candidates);
+    }
+
+    /// 按照wait4的pid语义，收集一个已经退出的子进程的退出状态。没有子进程退出时，
+    /// 当前进程在所有符合条件的子进程的等待队列上睡眠
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 要等待的子进程，含义见`wait_candidates`
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(Some(exited))` : 最早退出的符合条件的子进程的退出状态，它已经被移出队列
+    /// - `Ok(None)` : 没有子进程退出，当前进程已经进入睡眠状态（有待处理的信号时不会睡眠）
+    /// - `Err(ECHILD)` : 既没有符合条件的子进程，也没有等待收集的退出状态
+    ///
+    /// ## Safety
+    ///
+    /// 调用者必须已经关闭中断，并且在返回`Ok(None)`之后进行调度
+    unsafe fn wait_child(&self, pid: i64) -> Result<Option<ChildExitStatus>, SystemError> {
+        // 子进程在持有该锁时放入退出状态并脱离父进程，因此在锁内检查队列并睡眠不会错过子进程的退出
+        let mut statuses = self.child_exit_statuses.lock();
+        if let Some(exited) = self.take_exit_status(&mut statuses, pid) {
+            return Ok(Some(exited));
+        }
+
+        let candidates = self.wait_candidates(pid)?;
+        // 在所有子进程的等待队列上等待，但只标记一次睡眠：任意一个子进程退出都能唤醒当前进程
+        if ProcessManager::mark_sleep(true)? == SleepOutcome::Interrupted {
+            return Ok(None);
+        }
+        for pcb in candidates {
+            pcb.wait_queue.add_current_without_sleep();
+        }
+        return Ok(None);
+    }
+
+    /// 以WNOHANG的方式等待子进程：收集一个已经退出的子进程的退出状态，没有子进程退出时不睡眠
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 要等待的子进程，含义见`wait_candidates`
+    ///
+    /// ## 返回值
+    ///
+    /// - `Ok(Some(exited))` : 最早退出的符合条件的子进程的退出状态，它已经被移出队列
+    /// - `Ok(None)` : 有符合条件的子进程，但它们都还没有退出
+    /// - `Err(ECHILD)` : 既没有符合条件的子进程，也没有等待收集的退出状态
+    pub fn try_wait_child(&self, pid: i64) -> Result<Option<ChildExitStatus>, SystemError> {
+        let mut statuses = self.child_exit_statuses.lock_irqsave();
+        if let Some(exited) = self.take_exit_status(&mut statuses, pid) {
+            return Ok(Some(exited));
+        }
+        self.wait_candidates(pid)?;
+        return Ok(None);
+    }
+
+    /// 从退出状态队列中取出最早退出的、符合wait4的pid语义的子进程的退出状态
+    fn take_exit_status(
+        &self,
+        statuses: &mut VecDeque<ChildExitStatus>,
+        pid: i64,
+    ) -> Option<ChildExitStatus> {
+        let pgid = self.basic().pgid();
+        let index = statuses
+            .iter()
+            .position(|exited| exited.matches(pid, pgid))?;
+        return statuses.remove(index);
+    }
+
+    /// 退出的进程将退出状态交给父进程，并脱离父进程
+    ///
+    /// 退出状态被放入父进程的队列之后，父进程不再需要子进程的pcb，因此子进程进入`Dead`状态并立即被回收，
+    /// pcb会在最后一个引用被释放时析构
+    ///
+    /// ## 参数
+    ///
+    /// - `status` : 退出码，为None时不保留退出状态，进程保持`Exited`状态，退出码仍然可以从pcb中获取
+    fn report_exit(self: &Arc<Self>, status: Option<usize>) {
+        let parent = match self.parent_pcb.read().upgrade() {
+            Some(parent) => parent,
+            None => return,
+        };
+        let orphan = self.adopted.load(Ordering::SeqCst);
+        let mut statuses = parent.child_exit_statuses.lock_irqsave();
+        if let Some(status) = status {
+            statuses.push_back(ChildExitStatus {
+                pid: self.pid(),
+                pgid: self.basic().pgid(),
+                status,
+                orphan,
+            });
+            // 退出状态已经被交给父进程，进程只剩下等待被回收
+            self.sched_info_mut()
+                .try_set_state(ProcessState::Dead)
+                .unwrap_or_else(|e| panic!("report_exit: failed to set state to dead: {e:?}"));
+        }
+        parent.reap_child(self);
+    }
+
+    /// 代替init进程，以WNOHANG的方式等待所有已经退出的被收养的孤儿进程，避免它们的退出状态在init进程中无限堆积
+    ///
+    /// init进程阻塞了SIGCHLD时，表示它自己通过wait收集子进程的退出状态，此时不会收集任何退出状态。
+    /// 否则消耗待处理的SIGCHLD，并逐个等待退出的孤儿进程；init进程自己创建的子进程的退出状态仍然留给它自己等待
+    ///
+    /// ## 返回值
+    ///
+    /// 被收集的退出状态的数量
+    pub fn reap_orphans(self: &Arc<Self>) -> usize {
+        {
+            let mut sig_state = self.sig_state();
+            if sig_state.blocked().contains(Signal::SIGCHLD.mask()) {
+                return 0;
+            }
+            // 在收集之前消耗SIGCHLD：之后退出的孤儿进程会再次发送SIGCHLD
+            sig_state.remove_pending(Signal::SIGCHLD.mask());
+            if !sig_state.has_deliverable() {
+                ProcessManager::clear_pending_signal(self);
+            }
+        }
+
+        let orphans: Vec<Pid> = self
+            .child_exit_statuses
+            .lock_irqsave()
+            .iter()
+            .filter(|exited| exited.orphan)
+            .map(|exited| exited.pid)
+            .collect();
+        return orphans
+            .into_iter()
+            .filter(|pid| matches!(self.try_wait_child(pid.0 as i64), Ok(Some(_))))
+            .count();
+    }
+
+    /// 回收一个已经退出的子进程
+    ///
+    /// 将子进程从子进程表以及进程表中移除，并将其运行时间累加到当前进程的子进程运行时间中。
+    /// 子进程的pcb会在最后一个引用被释放时析构
+    fn reap_child(&self, child: &Arc<ProcessControlBlock>) {
+        if self.children.write().remove(&child.pid()).is_some() {
+            self.sched_info().absorb_child_times(&child.sched_info());
+            ProcessManager::release(child.pid());
+        }
+    }
+
+    /// 生成进程的名字
+    pub fn generate_name(_program_path: &str, args: &Vec<String>) -> String {
+        let mut name = "".to_string();
+        for arg in args {
+            name.push_str(arg);
+            name.push(' ');
+        }
+        return name;
+    }
+}
+
+impl Drop for ProcessControlBlock {
+    fn drop(&mut self) {
+        // 在ProcFS中,解除进程的注册。进程在fork的过程中失败时还没有被注册，此时没有需要解除注册的目录
+        match procfs_unregister_pid(self.pid()) {
+            Ok(()) | Err(SystemError::ENOENT) => {}
+            Err(e) => panic!("procfs_unregister_pid failed: error: {e:?}"),
+        }
+
+        if let Some(ppcb) = self.parent_pcb.read().upgrade() {
+            ppcb.children.write().remove(&self.pid());
+        }
+
+        // 进程表持有pcb的引用，因此能够析构时，进程一定已经被ProcessManager::release()移除，
+        // 此时才能归还pid，避免新进程与旧进程的pid同时可见
+        pid::free_pid(self.pid());
+    }
+}
+/// 进程的基本信息
+///
+/// 这个结构体保存进程的基本信息，主要是那些不会随着进程的运行而经常改变的信息。
+#[derive(Debug)]
+pub struct ProcessBasicInfo {
+    /// 当前进程的进程组id
+    pgid: Pid,
+    /// 当前进程所在会话的id
+    sid: Pid,
+    /// 当前进程的父进程的pid
+    ppid: Pid,
+    /// 进程的名字
+    name: String,
+
+    /// 当前进程的工作目录
+    cwd: String,
+
+    /// 用户地址空间
+    user_vm: Option<Arc<AddressSpace>>,
+
+    /// 文件描述符表
+    fd_table: Option<Arc<RwLock<FileDescriptorVec>>>,
+
+    /// 所在的进程组tg
+    sched_tg: Option<Arc<TaskGroup>>,
+}
+
+impl ProcessBasicInfo {
+    pub fn new(
+        pgid: Pid,
+        sid: Pid,
+        ppid: Pid,
+        name: String,
+        cwd: String,
+        user_vm: Option<Arc<AddressSpace>>,
+        sched_tg: Option<Arc<TaskGroup>>,
+    ) -> RwLock<Self> {
+        let fd_table = Arc::new(RwLock::new(FileDescriptorVec::new()));
+        return RwLock::new(Self {
+            pgid,
+            sid,
+            ppid,
+            name,
+            cwd,
+            user_vm,
+            fd_table: Some(fd_table),
+            sched_tg: Some(sched_tg),
+        });
+    }
+
+    pub fn pgid(&self) -> Pid {
+        return self.pgid;
+    }
+
+    pub fn set_pgid(&self, npgid: Pid) -> Pid {
+        self.pgid = npgid;
+    }
+
+    pub fn sid(&self) -> Pid {
+        return self.sid;
+    }
+
+    pub fn set_sid(&mut self, sid: Pid) {
+        self.sid = sid;
+    }
+
+    pub fn ppid(&self) -> Pid {
+        return self.ppid;
+    }
+
+    pub fn set_ppid(&mut self, ppid: Pid) {
+        self.ppid = ppid;
+    }
+
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn cwd(&self) -> String {
+        return self.cwd.clone();
+    }
+    pub fn set_cwd(&mut self, path: String) {
+        return self.cwd = path;
+    }
+
+    pub fn user_vm(&self) -> Option<Arc<AddressSpace>> {
+        return self.user_vm.clone();
+    }
+
+    pub unsafe fn set_user_vm(&mut self, user_vm: Option<Arc<AddressSpace>>) {
+        self.user_vm = user_vm;
+    }
+
+    pub fn fd_table(&self) -> Option<Arc<RwLock<FileDescriptorVec>>> {
+        return self.fd_table.clone();
+    }
+
+    pub fn set_fd_table(&mut self, fd_table: Option<Arc<RwLock<FileDescriptorVec>>>) {
+        self.fd_table = fd_table;
+    }
+
+    pub fn set_tg(&mut self, tg: Option<Arc<TaskGroup>>) {
+        self.sched_tg = tg;
+    }
+
+    pub fn tg(&self) -> Option<Arc<TaskGroup>> {
+        return self.sched_tg.clone();
+    }
+}
+
+#[derive(Debug)]
+pub struct ProcessSchedulerInfo {
+    /// 当前进程所在的cpu
+    on_cpu: AtomicI32,
+    /// 如果当前进程等待被迁移到另一个cpu核心上（也就是flags中的PF_NEED_MIGRATE被置位），
+    /// 该字段存储要被迁移到的目标处理器核心号
+    migrate_to: AtomicI32,
+    /// 进程上一次被切换出去时所在的cpu，进程从未被切换出去时为-1
+    last_cpu: AtomicI32,
+    /// 进程正在被迁移：已经选定了目标cpu，但`on_cpu`还没有被更新为目标cpu
+    migrating: AtomicBool,
+
+    /// 当前进程的状态
+    state: ProcessState,
+    /// 进程的调度策略
+    sched_policy: SchedPolicy,
+    /// 进程的调度优先级
+    priority: SchedPriority,
+    /// 进程的nice值，范围为`[NICE_MIN, NICE_MAX]`
+    nice: AtomicI32,
+    /// 进程允许运行的cpu集合
+    cpu_affinity: SpinLock<CpuMask>,
+    /// 当前进程的虚拟运行时间
+    virtual_runtime: AtomicIsize,
+    /// 由实时调度器管理的时间片
+    rt_time_slice: AtomicIsize,
+    //调度实体
+    se: Option<Arc<SchedEntity>>,
+    /// 进程在用户态运行的时间（单位：微秒）
+    utime: AtomicU64,
+    /// 进程在内核态运行的时间（单位：微秒）
+    stime: AtomicU64,
+    /// 已被回收的子进程（包括它们回收的子进程）在用户态运行的时间之和（单位：微秒）
+    cutime: AtomicU64,
+    /// 已被回收的子进程（包括它们回收的子进程）在内核态运行的时间之和（单位：微秒）
+    cstime: AtomicU64,
+    /// 进程实际运行的总时间（单位：纳秒），由调度器根据调度器时钟计算
+    sum_exec_runtime: AtomicU64,
+    /// 进程最近一次开始睡眠的时间（单位：微秒）
+    sleep_start: AtomicU64,
+    /// 进程的平均睡眠时间（单位：微秒），睡眠时增加、运行时减少，范围为`[0, SCHED_SLEEP_AVG_MAX_US]`
+    sleep_avg: AtomicU64,
+    /// deadline调度策略的参数与当前周期的状态
+    dl: SpinLock<DeadlineEntity>,
+    /// 进程被标记为需要调度时，调度器时钟的读数（单位：纳秒），为0表示没有被标记
+    resched_at: AtomicU64,
+    /// 进程正处于不可中断睡眠，并且已经被计入了某个cpu的不可中断睡眠进程数
+    contributes_to_load: bool,
+    /// 进程因为优先级继承而被提升时，保存它原来的调度策略与优先级
+    pi_saved: Option<(SchedPolicy, SchedPriority)>,
+}
+
+impl ProcessSchedulerInfo {
+    /// 最小的nice值（优先级最高）
+    pub const NICE_MIN: i32 = -20;
+    /// 最大的nice值（优先级最低）
+    pub const NICE_MAX: i32 = 19;
+
+    pub fn new(on_cpu: Option<u32>) -> RwLock<Self> {
+        let cpu_id = match on_cpu {
+            Some(cpu_id) => cpu_id as i32,
+            None => -1,
+        };
+        return RwLock::new(Self {
+            on_cpu: AtomicI32::new(cpu_id),
+            migrate_to: AtomicI32::new(-1),
+            last_cpu: AtomicI32::new(-1),
+            migrating: AtomicBool::new(false),
+            state: ProcessState::Blocked(false),
+            sched_policy: SchedPolicy::CFS,
+            virtual_runtime: AtomicIsize::new(0),
+            rt_time_slice: AtomicIsize::new(0),
+            priority: SchedPriority::DEFAULT,
+            nice: AtomicI32::new(0),
+            cpu_affinity: SpinLock::new(CpuMask::full()),
+            se: Some(SchedEntity::new()),
+            utime: AtomicU64::new(0),
+            stime: AtomicU64::new(0),
+            cutime: AtomicU64::new(0),
+            cstime: AtomicU64::new(0),
+            sum_exec_runtime: AtomicU64::new(0),
+            sleep_start: AtomicU64::new(0),
+            sleep_avg: AtomicU64::new(0),
+            dl: SpinLock::new(DeadlineEntity::default()),
+            resched_at: AtomicU64::new(0),
+            contributes_to_load: false,
+            pi_saved: None,
+        });
+    }
+
+    pub fn on_cpu(&self) -> Option<u32> {
+        let on_cpu = self.on_cpu.load(Ordering::SeqCst);
+        if on_cpu == -1 {
+            return None;
+        } else {
+            return Some(on_cpu as u32);
+        }
+    }
+
+    pub fn set_on_cpu(&self, on_cpu: Option<u32>) {
+        if let Some(cpu_id) = on_cpu {
+            self.on_cpu.store(cpu_id as i32, Ordering::SeqCst);
+        } else {
+            self.on_cpu.store(-1, Ordering::SeqCst);
+        }
+    }
+
+    /// 进程是否正在被迁移
+    ///
+    /// 迁移期间`on_cpu`与`last_cpu`可能已经过时，负载均衡等依赖它们的操作应当跳过这个进程
+    pub fn is_migrating(&self) -> bool {
+        return self.migrating.load(Ordering::SeqCst);
+    }
+
+    /// 标记进程开始迁移
+    ///
+    /// ## 返回值
+    ///
+    /// 如果进程已经在被迁移，返回false，调用者不能再迁移这个进程
+    pub fn start_migration(&self) -> bool {
+        return self
+            .migrating
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok();
+    }
+
+    /// 完成迁移：先将`on_cpu`更新为目标cpu，再清除迁移标志
+    ///
+    /// 只能由成功调用了`start_migration`的一方调用
+    pub fn finish_migration(&self, on_cpu: Option<u32>) {
+        self.set_on_cpu(on_cpu);
+        self.migrating.store(false, Ordering::SeqCst);
+    }
+
+    /// 放弃迁移，`on_cpu`保持不变
+    ///
+    /// 只能由成功调用了`start_migration`的一方调用
+    pub fn cancel_migration(&self) {
+        self.migrating.store(false, Ordering::SeqCst);
+    }
+
+    pub fn migrate_to(&self) -> Option<u32> {
+        let migrate_to = self.migrate_to.load(Ordering::SeqCst);
+        if migrate_to == -1 {
+            return None;
+        } else {
+            return Some(migrate_to as u32);
+        }
+    }
+
+    pub fn set_migrate_to(&self, migrate_to: Option<u32>) {
+        if let Some(data) = migrate_to {
+            self.migrate_to.store(data as i32, Ordering::SeqCst);
+        } else {
+            self.migrate_to.store(-1, Ordering::SeqCst)
+        }
+    }
+
+    pub fn nice(&self) -> i32 {
+        return self.nice.load(Ordering::SeqCst);
+    }
+
+    /// 设置进程的nice值
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：nice值不在`[NICE_MIN, NICE_MAX]`范围内时，返回`EINVAL`
+    pub fn set_nice(&self, nice: i32) -> Result<(), SystemError> {
+        if nice < Self::NICE_MIN || nice > Self::NICE_MAX {
+            return Err(SystemError::EINVAL);
+        }
+        self.nice.store(nice, Ordering::SeqCst);
+        return Ok(());
+    }
+
+    pub fn cpu_affinity(&self) -> CpuMask {
+        return *self.cpu_affinity.lock();
+    }
+
+    /// 设置进程允许运行的cpu集合
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：集合为空时，返回`EINVAL`
+    pub fn set_cpu_affinity(&self, mask: CpuMask) -> Result<(), SystemError> {
+        if mask.is_empty() {
+            return Err(SystemError::EINVAL);
+        }
+        *self.cpu_affinity.lock() = mask;
+        return Ok(());
+    }
+
+    /// fork时，从父进程继承nice值与cpu亲和性
+    pub fn inherit_from(&self, parent: &ProcessSchedulerInfo) {
+        self.nice.store(parent.nice(), Ordering::SeqCst);
+        *self.cpu_affinity.lock() = parent.cpu_affinity();
+    }
+
+    /// 获取进程上一次被切换出去时所在的cpu
+    ///
+    /// 进程在这个cpu上的缓存可能仍然是热的，负载均衡时优先让进程回到这个cpu上运行
+    pub fn last_cpu(&self) -> Option<u32> {
+        let last_cpu = self.last_cpu.load(Ordering::SeqCst);
+        if last_cpu == -1 {
+            return None;
+        } else {
+            return Some(last_cpu as u32);
+        }
+    }
+
+    /// 记录进程被切换出去时所在的cpu，由调度器在进程切换时调用
+    pub fn set_last_cpu(&self, cpu_id: u32) {
+        self.last_cpu.store(cpu_id as i32, Ordering::SeqCst);
+    }
+
+    pub fn state(&self) -> ProcessState {
+        return self.state;
+    }
+
+    /// 改变进程的状态，只允许进行合法的状态转换
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：状态转换不合法（例如让已经退出的进程重新运行）时，返回`EINVAL`，进程的状态不变
+    ///
+    /// 进程进入不可中断睡眠时被计入当前cpu的不可中断睡眠进程数，离开时从当前cpu的计数中减去
+    pub fn try_set_state(&mut self, state: ProcessState) -> Result<(), SystemError> {
+        if !self.state.can_transition_to(state) {
+            return Err(SystemError::EINVAL);
+        }
+        if state == ProcessState::Blocked(false) {
+            if !self.contributes_to_load {
+                inc_nr_uninterruptible(smp_get_processor_id());
+                self.contributes_to_load = true;
+            }
+        } else if self.contributes_to_load {
+            dec_nr_uninterruptible(smp_get_processor_id());
+            self.contributes_to_load = false;
+        }
+        self.state = state;
+        return Ok(());
+    }
+
+    pub fn policy(&self) -> SchedPolicy {
+        return self.sched_policy;
+    }
+
+    pub fn virtual_runtime(&self) -> isize {
+        return self.virtual_runtime.load(Ordering::SeqCst);
+    }
+
+    pub fn set_virtual_runtime(&self, virtual_runtime: isize) {
+        self.virtual_runtime
+            .store(virtual_runtime, Ordering::SeqCst);
+    }
+    pub fn increase_virtual_runtime(&self, delta: isize) {
+        self.virtual_runtime.fetch_add(delta, Ordering::SeqCst);
+    }
+
+    pub fn rt_time_slice(&self) -> isize {
+        return self.rt_time_slice.load(Ordering::SeqCst);
+    }
+
+    pub fn set_rt_time_slice(&self, rt_time_slice: isize) {
+        self.rt_time_slice.store(rt_time_slice, Ordering::SeqCst);
+    }
+
+    pub fn increase_rt_time_slice(&self, delta: isize) {
+        self.rt_time_slice.fetch_add(delta, Ordering::SeqCst);
+    }
+
+    pub fn priority(&self) -> SchedPriority {
+        return self.priority;
+    }
+
+    /// 获取进程的deadline调度状态
+    pub fn dl(&self) -> SpinLockGuard<DeadlineEntity> {
+        return self.dl.lock_irqsave();
+    }
+
+    /// 设置进程的调度策略与优先级
+    ///
+    /// 调用者需要保证进程不在任何运行队列中，否则进程会留在旧的调度策略的队列里
+    pub fn set_policy(&mut self, policy: SchedPolicy, priority: SchedPriority) {
+        self.sched_policy = policy;
+        self.priority = priority;
+    }
+
+    pub fn se(&self) -> Option<Arc<SchedEntity>> {
+        return self.se.clone();
+    }
+
+    pub fn utime(&self) -> u64 {
+        return self.utime.load(Ordering::SeqCst);
+    }
+
+    pub fn stime(&self) -> u64 {
+        return self.stime.load(Ordering::SeqCst);
+    }
+
+    pub fn cutime(&self) -> u64 {
+        return self.cutime.load(Ordering::SeqCst);
+    }
+
+    pub fn cstime(&self) -> u64 {
+        return self.cstime.load(Ordering::SeqCst);
+    }
+
+    /// 将一段运行时间计入进程的用户态或内核态运行时间
+    ///
+    /// ## 参数
+    ///
+    /// - `user_mode` : 这段时间是否运行在用户态
+    /// - `delta_us` : 运行的时间（单位：微秒）
+    pub fn account_time(&self, user_mode: bool, delta_us: u64) {
+        if user_mode {
+            self.utime.fetch_add(delta_us, Ordering::SeqCst);
+        } else {
+            self.stime.fetch_add(delta_us, Ordering::SeqCst);
+        }
+        // 运行的时间抵消睡眠的时间，变为cpu密集型的进程逐渐失去交互式补偿
+        self.sleep_avg
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |avg| {
+                Some(avg.saturating_sub(delta_us))
+            })
+            .ok();
+    }
+
+    /// 获取进程实际运行的总时间（单位：纳秒）
+    ///
+    /// utime与stime在时钟中断时按整个时钟周期采样，而该值在每次调度时按照调度器时钟精确计算
+    pub fn sum_exec_runtime(&self) -> u64 {
+        return self.sum_exec_runtime.load(Ordering::SeqCst);
+    }
+
+    /// 将一段实际运行的时间计入进程的运行总时间
+    pub fn add_exec_runtime(&self, delta_ns: u64) {
+        self.sum_exec_runtime.fetch_add(delta_ns, Ordering::SeqCst);
+    }
+
+    /// 获取进程的平均睡眠时间（单位：微秒）
+    pub fn sleep_avg(&self) -> u64 {
+        return self.sleep_avg.load(Ordering::SeqCst);
+    }
+
+    /// 记录进程开始睡眠的时间
+    pub fn mark_sleep_start(&self, now_us: u64) {
+        self.sleep_start.store(now_us, Ordering::SeqCst);
+    }
+
+    /// 进程被唤醒时，将这次睡眠的时间计入平均睡眠时间
+    ///
+    /// ## 参数
+    ///
+    /// - `now_us` : 被唤醒的时间（单位：微秒）
+    pub fn account_sleep(&self, now_us: u64) {
+        let slept = now_us.saturating_sub(self.sleep_start.load(Ordering::SeqCst));
+        self.sleep_avg
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |avg| {
+                Some(core::cmp::min(
+                    avg.saturating_add(slept),
+                    SCHED_SLEEP_AVG_MAX_US,
+                ))
+            })
+            .ok();
+    }
+
+    /// 记录进程被标记为需要调度的时刻，进程已经被标记时保留最早的时刻
+    ///
+    /// ## 参数
+    ///
+    /// - `now_ns` : 调度器时钟的读数（单位：纳秒）
+    pub fn mark_resched(&self, now_ns: u64) {
+        self.resched_at
+            .compare_exchange(0, now_ns, Ordering::SeqCst, Ordering::SeqCst)
+            .ok();
+    }
+
+    /// 获取进程被标记为需要调度的时刻，没有被标记时返回None
+    pub fn resched_at(&self) -> Option<u64> {
+        match self.resched_at.load(Ordering::SeqCst) {
+            0 => None,
+            resched_at => Some(resched_at),
+        }
+    }
+
+    /// 取出进程被标记为需要调度的时刻，并清除该记录
+    pub fn take_resched_at(&self) -> Option<u64> {
+        match self.resched_at.swap(0, Ordering::SeqCst) {
+            0 => None,
+            resched_at => Some(resched_at),
+        }
+    }
+
+    /// 回收子进程时，将子进程自身以及它回收的子进程的运行时间累加到当前进程的子进程运行时间中
+    pub fn absorb_child_times(&self, child: &ProcessSchedulerInfo) {
+        self.cutime
+            .fetch_add(child.utime() + child.cutime(), Ordering::SeqCst);
+        self.cstime
+            .fetch_add(child.stime() + child.cstime(), Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug)]
+pub struct KernelStack {
+    stack: Option<AlignedBox<[u8; KernelStack::SIZE], { KernelStack::ALIGN }>>,
+    /// 标记该内核栈是否可以被释放
+    can_be_freed: bool,
+}
+
+impl KernelStack {
+    pub const SIZE: usize = 0x4000;
+    pub const ALIGN: usize = 0x4000;
+
+    pub fn new() -> Result<Self, SystemError> {
+        return Ok(Self {
+            stack: Some(
+                AlignedBox::<[u8; KernelStack::SIZE], { KernelStack::ALIGN }>::new_zeroed()?,
+            ),
+            can_be_freed: true,
+        });
+    }
+
+    /// 根据已有的空间，构造一个内核栈结构体
+    ///
+    /// 仅仅用于BSP启动时，为idle进程构造内核栈。其他时候使用这个函数，很可能造成错误！
+    pub unsafe fn from_existed(base: VirtAddr) -> Result<Self, SystemError> {
+        if base.is_null() || base.check_aligned(Self::ALIGN) == false {
+            return Err(SystemError::EFAULT);
+        }
+
+        return Ok(Self {
+            stack: Some(
+                AlignedBox::<[u8; KernelStack::SIZE], { KernelStack::ALIGN }>::new_unchecked(
+                    base.data() as *mut [u8; KernelStack::SIZE],
+                ),
+            ),
+            can_be_freed: false,
+        });
+    }
+
+    /// 返回内核栈的起始虚拟地址(低地址)
+    pub fn start_address(&self) -> VirtAddr {
+        return VirtAddr::new(self.stack.as_ref().unwrap().as_ptr() as usize);
+    }
+
+    /// 返回内核栈的结束虚拟地址(高地址)(不包含该地址)
+    pub fn stack_max_address(&self) -> VirtAddr {
+        return VirtAddr::new(self.stack.as_ref().unwrap().as_ptr() as usize + Self::SIZE);
+    }
+
+    /// 将pcb的弱引用放到内核栈的最低地址处，用于获取当前进程的pcb
+    ///
+    /// 内核栈属于pcb，如果这里持有强引用，pcb与内核栈会互相引用，导致pcb永远无法析构
+    pub unsafe fn set_pcb(&mut self, pcb: Weak<ProcessControlBlock>) -> Result<(), SystemError> {
+        let p: *const ProcessControlBlock = Weak::into_raw(pcb);
+        let stack_bottom_ptr = self.start_address().data() as *mut *const ProcessControlBlock;
+
+        // 如果内核栈的最低地址处已经有了一个pcb，那么，这里就不再设置,直接返回错误
+        if unlikely(unsafe { !(*stack_bottom_ptr).is_null() }) {
+            drop(Weak::from_raw(p));
+            return Err(SystemError::EPERM);
+        }
+        // 将pcb的地址放到内核栈的最低地址处
+        unsafe {
+            *stack_bottom_ptr = p;
+        }
+
+        return Ok(());
+    }
+
+    /// 返回指向当前内核栈pcb的Arc指针，pcb已经析构时返回None
+    #[allow(dead_code)]
+    pub unsafe fn pcb(&self) -> Option<Arc<ProcessControlBlock>> {
+        // 从内核栈的最低地址处取出pcb的地址
+        let p = *(self.start_address().data() as *const *const ProcessControlBlock);
+        if unlikely(p.is_null()) {
+            return None;
+        }
+
+        // 为了防止内核栈的pcb指针被释放，这里需要将其包装一下，使得Weak的drop不会被调用
+        let weak_wrapper: ManuallyDrop<Weak<ProcessControlBlock>> =
+            ManuallyDrop::new(Weak::from_raw(p));
+        return weak_wrapper.upgrade();
+    }
+}
+
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        if !self.stack.is_none() {
+            let p = unsafe { *(self.start_address().data() as *const *const ProcessControlBlock) };
+            if !p.is_null() {
+                drop(unsafe { Weak::from_raw(p) });
+            }
+        }
+        // 如果该内核栈不可以被释放，那么，这里就forget，不调用AlignedBox的drop函数
+        if !self.can_be_freed {
+            let bx = self.stack.take();
+            core::mem::forget(bx);
+        }
+    }
+}
+
+pub fn process_init() {
+    ProcessManager::init();
+}
+
+#[derive(Debug)]
+pub struct SchedEntity {
+    ///如果是进程它将对应一个pcb，进程组的调度实体则为空
+    pcb: SpinLock<Weak<ProcessControlBlock>>,
+    /// 进程的调度优先级
+    priority: SchedPriority,
+    //parent:SchedEntity,
+    /// 当前进程的虚拟运行时间
+    virtual_runtime: AtomicIsize,
+    /// 调度实体在队列中的权重，进程的调度实体在入队时按照调度策略设置
+    load_weight: AtomicU64,
+    /// 调度实体所在的cfs队列。队列持有其中的调度实体，因此这里只持有队列的弱引用
+    cfs_rq: SpinLock<Weak<SpinLock<CFSQueue>>>,
+    /// 进程组的调度实体所管理的cfs队列，进程的调度实体则为空
+    my_q: SpinLock<Option<Arc<SpinLock<CFSQueue>>>>,
+}
+///! CFSQueue 需要处理
+///! parent 当为task se 指向进程组
+///!        当为group se 指向父进程组
+
+impl SchedEntity {
+    /// 创建一个新的调度实体
+    ///
+    /// 新的调度实体不对应任何进程，也不属于任何cfs队列
+    pub fn new() -> Arc<Self> {
+        return Arc::new(Self {
+            pcb: SpinLock::new(Weak::new()),
+            virtual_runtime: AtomicIsize::new(0),
+            load_weight: AtomicU64::new(NICE_0_LOAD),
+            priority: SchedPriority::DEFAULT,
+            my_q: SpinLock::new(None),
+            cfs_rq: SpinLock::new(Weak::new()),
+        });
+    }
+    pub fn virtual_runtime(&self) -> isize {
+        return self.virtual_runtime.load(Ordering::SeqCst);
+    }
+
+    pub fn set_virtual_runtime(&self, virtual_runtime: isize) {
+        self.virtual_runtime
+            .store(virtual_runtime, Ordering::SeqCst);
+    }
+
+    pub fn load_weight(&self) -> u64 {
+        return self.load_weight.load(Ordering::SeqCst);
+    }
+
+    pub fn set_load_weight(&self, load_weight: u64) {
+        self.load_weight.store(load_weight, Ordering::SeqCst);
+    }
+
+    fn set_cfs_rq(&self, cfs_rq: Option<&Arc<SpinLock<CFSQueue>>>) {
+        *self.cfs_rq.lock() = cfs_rq.map_or(Weak::new(), Arc::downgrade);
+    }
+
+    fn cfs_rq(&self) -> Option<Arc<SpinLock<CFSQueue>>> {
+        return self.cfs_rq.lock().upgrade();
+    }
+
+    fn set_my_q(&self, my_q: Option<Arc<SpinLock<CFSQueue>>>) {
+        *self.my_q.lock() = my_q;
+    }
+
+    /// 获取进程组的调度实体所管理的cfs队列，如果是进程的调度实体，则返回None
+    pub fn group_cfs_rq(&self) -> Option<Arc<SpinLock<CFSQueue>>> {
+        return self.my_q.lock().clone();
+    }
+
+    pub fn priority(&self) -> SchedPriority {
+        return self.priority;
+    }
+
+    /// 设置调度实体对应的进程
+    ///
+    /// 调度实体只持有pcb的弱引用，避免pcb与调度实体之间形成循环引用
+    pub fn set_pcb(&self, pcb: &Arc<ProcessControlBlock>) {
+        *self.pcb.lock() = Arc::downgrade(pcb);
+    }
+
+    /// 获取调度实体对应的进程，如果是进程组的调度实体，则返回None
+    pub fn pcb(&self) -> Option<Arc<ProcessControlBlock>> {
+        return self.pcb.lock().upgrade();
+    }
+}
+/// 进程组的cpu带宽控制统计信息，即cgroup的cpu.stat
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TaskGroupCpuStat {
+    /// 已经开始的带宽控制周期数
+    nr_periods: u64,
+    /// 进程组被限流的次数
+    nr_throttled: u64,
+    /// 进程组被限流的总时间（纳秒）
+    throttled_time: u64,
+    /// 进程组当前被限流的开始时刻（纳秒），没有被限流时为None
+    throttled_since: Option<u64>,
+}
+
+impl TaskGroupCpuStat {
+    pub fn nr_periods(&self) -> u64 {
+        return self.nr_periods;
+    }
 
-        let pcb = Arc::new(pcb);
+    pub fn nr_throttled(&self) -> u64 {
+        return self.nr_throttled;
+    }
 
-        // 设置进程的arc指针到内核栈的最低地址处
-        unsafe { pcb.kernel_stack.write().set_pcb(Arc::clone(&pcb)).unwrap() };
+    pub fn throttled_time(&self) -> u64 {
+        return self.throttled_time;
+    }
 
-        // 将当前pcb加入父进程的子进程哈希表中
-        if pcb.pid() > Pid(1) {
-            if let Some(ppcb_arc) = pcb.parent_pcb.read().upgrade() {
-                let mut children = ppcb_arc.children.write();
-                children.insert(pcb.pid(), pcb.clone());
-            } else {
-                panic!("parent pcb is None");
-            }
+    /// 按照cgroup的cpu.stat格式输出
+    pub fn render(&self) -> String {
+        return format!(
+            "nr_periods {}\nnr_throttled {}\nthrottled_time {}\n",
+            self.nr_periods, self.nr_throttled, self.throttled_time
+        );
+    }
+}
+
+#[derive(Debug)]
+struct TaskGroup {
+    se: Vec<Arc<SchedEntity>>, // 调度实体(每个CPU分配一个)
+    cfs: SchedulerCFS,         // 完全公平调度运行队列(每个CPU分配一个)
+    /// 父进程指针
+    parent_tg: RwLock<Weak<TaskGroup>>,
+    /// 子进程组链表
+    children: RwLock<HashMap<Pid, Arc<TaskGroup>>>,
+    /// 进程组自身的成员消耗的cpu时间（纳秒），不包括子进程组
+    cpu_usage: AtomicU64,
+    /// 带宽控制的统计信息
+    cpu_stat: SpinLock<TaskGroupCpuStat>,
+    /// 进程组（cpuset）的成员允许运行的cpu集合
+    cpus_allowed: SpinLock<CpuMask>,
+    /// 嵌套层数，根进程组为0
+    depth: usize,
+}
+
+impl TaskGroup {
+    /// 创建一个子进程组
+    ///
+    /// ## 参数
+    ///
+    /// - `ptg`: 父进程组
+    ///
+    /// ## 返回值
+    ///
+    /// 子进程组的层数超过`TASK_GROUP_MAX_DEPTH`时返回`EINVAL`
+    pub fn new(ptg: Arc<TaskGroup>) -> Result<Arc<Self>, SystemError> {
+        let depth = ptg.depth + 1;
+        if depth > Self::max_depth() {
+            return Err(SystemError::EINVAL);
         }
+        return Ok(Self::do_create(Arc::downgrade(&ptg), depth));
+    }
 
-        return pcb;
+    /// 创建一个没有父进程组的根进程组
+    pub fn new_root() -> Arc<Self> {
+        return Self::do_create(Weak::new(), 0);
     }
 
-    /// 生成一个新的pid
-    #[inline(always)]
-    fn generate_pid() -> Pid {
-        static NEXT_PID: AtomicPid = AtomicPid::new(Pid(1));
-        return NEXT_PID.fetch_add(Pid(1), Ordering::SeqCst);
+    /// 获取进程组的最大嵌套层数
+    pub fn max_depth() -> usize {
+        return TASK_GROUP_MAX_DEPTH.load(Ordering::Relaxed);
     }
 
-    /// 返回当前进程的锁持有计数
-    #[inline(always)]
-    pub fn preempt_count(&self) -> usize {
-        return self.preempt_count.load(Ordering::SeqCst);
+    /// 设置进程组的最大嵌套层数，只影响之后创建的进程组
+    pub fn set_max_depth(depth: usize) {
+        TASK_GROUP_MAX_DEPTH.store(depth, Ordering::Relaxed);
     }
 
-    /// 增加当前进程的锁持有计数
-    #[inline(always)]
-    pub fn preempt_disable(&self) {
-        self.preempt_count.fetch_add(1, Ordering::SeqCst);
+    pub fn depth(&self) -> usize {
+        return self.depth;
     }
 
-    /// 减少当前进程的锁持有计数
-    #[inline(always)]
-    pub fn preempt_enable(&self) {
-        self.preempt_count.fetch_sub(1, Ordering::SeqCst);
+    fn do_create(parent_tg: Weak<TaskGroup>, depth: usize) -> Arc<Self> {
+        let cfs = SchedulerCFS::new();
+        let cpu_num = cfs.cpu_num();
+        return Arc::new(Self {
+            cfs,
+            se: (0..cpu_num).map(|_| SchedEntity::new()).collect(),
+            parent_tg: RwLock::new(parent_tg),
+            children: RwLock::new(HashMap::new()),
+            cpu_usage: AtomicU64::new(0),
+            cpu_stat: SpinLock::new(TaskGroupCpuStat::default()),
+            cpus_allowed: SpinLock::new(CpuMask::full()),
+            depth,
+        });
+    }
+    /// ! 在创建新的进程组时，应该将se的cfsrq指向父进程组的cfsrq，cfsrq[cpu]
+    /// !   同时my_q应指向本进程组创建的cfsrq
+    /// !根进程组的创建时机  struct task_group root_task_group;
+    /// ! 创建好关系之后 ，group se 和时task se 加到对应的cfs_rq
+    pub fn init_group_se(&self, ptg: Arc<TaskGroup>, ntg: Arc<TaskGroup>) {
+        for (cpu, per_se) in self.se.iter().enumerate() {
+            per_se.set_cfs_rq(Some(&ptg.cfs.shared_cpu_queue(cpu)));
+            per_se.set_my_q(Some(ntg.cfs.shared_cpu_queue(cpu)));
+            // 进程组的调度实体加入到父进程组的cfs队列中
+            ptg.cfs.enqueue_group_se(per_se.clone(), cpu);
+        }
     }
 
-    #[inline(always)]
-    pub unsafe fn set_preempt_count(&self, count: usize) {
-        self.preempt_count.store(count, Ordering::SeqCst);
+    pub fn add_tg(pgid: Pid, tg: Arc<TaskGroup>) {
+        ALL_PROCESS_GROUP
+            .lock()
+            .as_mut()
+            .unwrap()
+            .insert(pgid, tg.clone());
     }
 
-    #[inline(always)]
-    pub fn flags(&self) -> SpinLockGuard<ProcessFlags> {
-        return self.flags.lock();
+    pub fn cfs(&self) -> &SchedulerCFS {
+        return &self.cfs;
     }
 
-    #[inline(always)]
-    pub fn basic(&self) -> RwLockReadGuard<ProcessBasicInfo> {
-        return self.basic.read();
+    /// 将成员运行的时间计入进程组
+    pub fn account_cpu_usage(&self, delta_ns: u64) {
+        self.cpu_usage.fetch_add(delta_ns, Ordering::Relaxed);
     }
 
-    #[inline(always)]
-    pub fn set_name(&self, name: String) {
-        self.basic.write().set_name(name);
+    /// 获取进程组及其所有子进程组的成员消耗的cpu时间（纳秒），即cpuacct.usage
+    pub fn cpu_usage(&self) -> u64 {
+        let children: u64 = self
+            .children
+            .read()
+            .values()
+            .map(|child| child.cpu_usage())
+            .sum();
+        return self.cpu_usage.load(Ordering::Relaxed) + children;
     }
 
-    #[inline(always)]
-    pub fn basic_mut(&self) -> RwLockWriteGuard<ProcessBasicInfo> {
-        return self.basic.write();
+    /// 获取进程组（cpuset）的成员允许运行的cpu集合
+    pub fn cpus_allowed(&self) -> CpuMask {
+        return *self.cpus_allowed.lock_irqsave();
     }
 
-    #[inline(always)]
-    pub fn arch_info(&self) -> SpinLockGuard<ArchPCBInfo> {
-        return self.arch_info.lock();
+    /// 设置进程组（cpuset）的成员允许运行的cpu集合
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：集合为空时，返回`EINVAL`
+    pub fn set_cpus_allowed(&self, mask: CpuMask) -> Result<(), SystemError> {
+        if mask.is_empty() {
+            return Err(SystemError::EINVAL);
+        }
+        *self.cpus_allowed.lock_irqsave() = mask;
+        return Ok(());
     }
 
-    #[inline(always)]
-    pub fn arch_info_irqsave(&self) -> SpinLockGuard<ArchPCBInfo> {
-        return self.arch_info.lock_irqsave();
+    /// 修改进程组（cpuset）允许运行的cpu集合，并迁移位于不再被允许的cpu上的成员
+    ///
+    /// 在队列中的成员立即按照负载被重新放置；正在运行的成员被设置`NEED_MIGRATE`并要求重新调度，
+    /// 在被切换出去、重新入队时迁移；睡眠的成员在被唤醒时才会被放置，不需要处理。
+    /// 成员设置的cpu亲和性与新的集合没有交集时，给出一次警告，此后该成员以新的集合为准
+    ///
+    /// ## 参数
+    ///
+    /// - `mask` : 新的cpu集合
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：需要迁移的成员的数目
+    /// - 失败：集合为空时，返回`EINVAL`
+    pub fn update_cpus(self: &Arc<Self>, mask: CpuMask) -> Result<usize, SystemError> {
+        self.set_cpus_allowed(mask)?;
+
+        let mut members = Vec::new();
+        ProcessManager::for_each(|pcb| {
+            if pcb.basic().tg().is_some_and(|tg| Arc::ptr_eq(&tg, self)) {
+                members.push(pcb.clone());
+            }
+        });
+
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let mut migrated = 0;
+        for pcb in members {
+            if (pcb.sched_info().cpu_affinity() & mask).is_empty() {
+                kwarn!(
+                    "pid {:?}: cpu affinity is disjoint from its cpuset, use the cpuset instead",
+                    pcb.pid()
+                );
+            }
+            let cpu_id = match pcb.sched_info().on_cpu() {
+                Some(cpu_id) => cpu_id,
+                None => continue,
+            };
+            if pcb.effective_affinity().contains(cpu_id as usize) {
+                continue;
+            }
+            if sched_dequeue(&pcb) {
+                // 重新入队时，负载均衡只会选择允许进程运行的cpu
+                sched_enqueue(pcb, false);
+                migrated += 1;
+            } else if CPU_EXECUTING.get(cpu_id) == pcb.pid() {
+                loads_balance(pcb.clone());
+                pcb.set_need_schedule();
+                if cpu_id != smp_get_processor_id() {
+                    kick_cpu(cpu_id).expect("update_cpus: Failed to kick cpu");
+                }
+                migrated += 1;
+            }
+        }
+        return Ok(migrated);
     }
 
-    #[inline(always)]
-    pub fn kernel_stack(&self) -> RwLockReadGuard<KernelStack> {
-        return self.kernel_stack.read();
+    /// 获取带宽控制的统计信息
+    pub fn cpu_stat(&self) -> TaskGroupCpuStat {
+        return *self.cpu_stat.lock_irqsave();
     }
 
-    #[inline(always)]
-    #[allow(dead_code)]
-    pub fn kernel_stack_mut(&self) -> RwLockWriteGuard<KernelStack> {
-        return self.kernel_stack.write();
+    /// 带宽控制的一个新周期开始时调用
+    pub fn account_period(&self) {
+        self.cpu_stat.lock_irqsave().nr_periods += 1;
     }
 
-    #[inline(always)]
-    pub fn sched_info(&self) -> RwLockReadGuard<ProcessSchedulerInfo> {
-        return self.sched_info.read();
+    /// 进程组用完了本周期的配额，开始被限流时调用。已经被限流时不重复计数
+    pub fn throttle(&self, now_ns: u64) {
+        let mut stat = self.cpu_stat.lock_irqsave();
+        if stat.throttled_since.is_none() {
+            stat.nr_throttled += 1;
+            stat.throttled_since = Some(now_ns);
+        }
     }
 
-    #[inline(always)]
-    pub fn sched_info_mut(&self) -> RwLockWriteGuard<ProcessSchedulerInfo> {
-        return self.sched_info.write();
+    /// 进程组的配额被补充，解除限流时调用，被限流的时间计入`throttled_time`
+    pub fn unthrottle(&self, now_ns: u64) {
+        let mut stat = self.cpu_stat.lock_irqsave();
+        if let Some(since) = stat.throttled_since.take() {
+            stat.throttled_time += now_ns.saturating_sub(since);
+        }
     }
 
-    #[inline(always)]
-    pub fn sched_info_mut_irqsave(&self) -> RwLockWriteGuard<ProcessSchedulerInfo> {
-        return self.sched_info.write_irqsave();
+    /// 获取属于该进程组的所有进程
+    fn members(self: &Arc<Self>) -> Vec<Pid> {
+        let mut members = Vec::new();
+        ProcessManager::for_each(|pcb| {
+            if pcb.basic().tg().map_or(false, |tg| Arc::ptr_eq(&tg, self)) {
+                members.push(pcb.pid());
+            }
+        });
+        return members;
     }
 
-    #[inline(always)]
-    pub fn worker_private(&self) -> SpinLockGuard<Option<WorkerPrivate>> {
-        return self.worker_private.lock();
+    /// 冻结属于该进程组的所有进程，带有`NOFREEZE`标志的进程会被跳过
+    ///
+    /// ## 返回值
+    ///
+    /// 被冻结的进程数
+    pub fn freeze(self: &Arc<Self>) -> usize {
+        return self
+            .members()
+            .into_iter()
+            .filter(|pid| ProcessManager::freeze(*pid).is_ok())
+            .count();
     }
 
-    #[inline(always)]
-    pub fn pid(&self) -> Pid {
-        return self.pid;
+    /// 解冻属于该进程组的所有进程
+    pub fn thaw(self: &Arc<Self>) {
+        for pid in self.members() {
+            ProcessManager::thaw(pid).ok();
+        }
     }
+}
 
-    /// 获取文件描述符表的Arc指针
-    #[inline(always)]
-    pub fn fd_table(&self) -> Arc<RwLock<FileDescriptorVec>> {
-        return self.basic.read().fd_table().unwrap();
+pub static PROCESS_GROUP_MANAGER: ProcessGroupManager = ProcessGroupManager::new();
+
+pub struct ProcessGroupManager(pub SpinLock<BTreeMap<Pid, Vec<Pid>>>);
+
+impl ProcessGroupManager {
+    pub const fn new() -> Self {
+        Self(SpinLock::new(BTreeMap::new()))
     }
 
-    /// 根据文件描述符序号，获取socket对象的Arc指针
+    /// 根据pgid获取进程组的tg
     ///
     /// ## 参数
+    /// - `pgid` : 进程的pgid
     ///
-    /// - `fd` 文件描述符序号
+    /// ## 返回值
+    /// 如果找到了对应的进程组，那么返回该进程组，否则返回None
+    pub fn find(pgid: Pid) -> Option<Arc<TaskGroup>> {
+        return ALL_PROCESS_GROUP.lock().as_ref()?.get(&pgid).cloned();
+    }
+
+    /// 将进程加入到已存在的进程组中
+    ///
+    /// ## 参数
+    /// - `pgid` : 进程组id
+    /// - `pid` : 要加入的进程的pid
     ///
     /// ## 返回值
+    /// 如果进程组不存在，返回`ESRCH`
+    pub fn add_process(&self, pgid: Pid, pid: Pid) -> Result<(), SystemError> {
+        let mut inner = self.0.lock();
+        let group = inner.get_mut(&pgid).ok_or(SystemError::ESRCH)?;
+        group.push(pid);
+        return Ok(());
+    }
+
+    /// 创建一个以`pgid`为组长的进程组
     ///
-    /// Option(&mut Box<dyn Socket>) socket对象的可变引用. 如果文件描述符不是socket，那么返回None
-    pub fn get_socket(&self, fd: i32) -> Option<Arc<SocketInode>> {
-        let binding = ProcessManager::current_pcb().fd_table();
-        let fd_table_guard = binding.read();
+    /// ## 返回值
+    ///
+    /// `Pid::IDLE`不能作为进程组id，返回`EINVAL`
+    pub fn add_group(&self, pgid: Pid) -> Result<(), SystemError> {
+        if pgid.is_idle() {
+            return Err(SystemError::EINVAL);
+        }
+        self.0.lock().insert(pgid, vec![pgid]);
+        return Ok(());
+    }
 
-        let f = fd_table_guard.get_file_by_fd(fd)?;
-        drop(fd_table_guard);
+    /// 冻结进程组中的所有进程，带有`NOFREEZE`标志的进程会被跳过
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：被冻结的进程数
+    /// - 失败：进程组不存在时，返回`ESRCH`
+    pub fn freeze(&self, pgid: Pid) -> Result<usize, SystemError> {
+        let members = self.members(pgid)?;
+        return Ok(members
+            .into_iter()
+            .filter(|pid| ProcessManager::freeze(*pid).is_ok())
+            .count());
+    }
 
-        let guard = f.lock();
-        if guard.file_type() != FileType::Socket {
-            return None;
+    /// 解冻进程组中的所有进程
+    ///
+    /// ## 返回值
+    ///
+    /// 进程组不存在时，返回`ESRCH`
+    pub fn thaw(&self, pgid: Pid) -> Result<(), SystemError> {
+        for pid in self.members(pgid)? {
+            ProcessManager::thaw(pid).ok();
         }
-        let socket: Arc<SocketInode> = guard
-            .inode()
-            .downcast_arc::<SocketInode>()
-            .expect("Not a socket inode");
-        return Some(socket);
+        return Ok(());
     }
 
-    /// 当前进程退出时,让初始进程收养所有子进程
-    unsafe fn adopt_childen(&self) -> Result<(), SystemError> {
-        match ProcessManager::find(Pid(1)) {
-            Some(init_pcb) => {
-                let mut childen_guard = self.children.write();
-                let mut init_childen_guard = init_pcb.children.write();
+    /// 对进程组中的每个成员调用`f`，进程组不存在时不调用
+    ///
+    /// 遍历期间持有进程组的锁，`f`不能再访问进程组管理器，也不能睡眠
+    pub fn for_each_member<F: FnMut(Pid)>(&self, pgid: Pid, mut f: F) {
+        if let Some(group) = self.0.lock().get(&pgid) {
+            group.iter().for_each(|pid| f(*pid));
+        }
+    }
 
-                childen_guard.drain().for_each(|(pid, child)| {
-                    init_childen_guard.insert(pid, child);
-                });
+    /// 获取进程组的组长，也就是pid等于pgid的成员
+    ///
+    /// ## 返回值
+    ///
+    /// 进程组不存在、组长已经离开进程组或者已经退出时，返回None。
+    /// 组长退出之后，只要还有其他成员，进程组就继续存在
+    pub fn leader(&self, pgid: Pid) -> Option<Pid> {
+        if !self.0.lock().get(&pgid)?.contains(&pgid) {
+            return None;
+        }
+        let state = ProcessManager::find(pgid)?.sched_info().state();
+        if state.is_exited() || state.is_dead() {
+            return None;
+        }
+        return Some(pgid);
+    }
 
-                return Ok(());
-            }
-            _ => Err(SystemError::ECHILD),
+    fn members(&self, pgid: Pid) -> Result<Vec<Pid>, SystemError> {
+        return self.0.lock().get(&pgid).cloned().ok_or(SystemError::ESRCH);
+    }
+
+    /// 判断进程是否属于进程组
+    ///
+    /// fork出的子进程继承父进程的pgid，但不会被进程组管理器记录，因此同时检查进程自身的pgid
+    pub fn is_member(&self, pgid: Pid, pcb: &ProcessControlBlock) -> bool {
+        if pcb.basic().pgid() == pgid {
+            return true;
         }
+        return self
+            .0
+            .lock()
+            .get(&pgid)
+            .map_or(false, |group| group.contains(&pcb.pid()));
     }
 
-    /// 生成进程的名字
-    pub fn generate_name(_program_path: &str, args: &Vec<String>) -> String {
-        let mut name = "".to_string();
-        for arg in args {
-            name.push_str(arg);
-            name.push(' ');
+    pub fn get_group_by_pgid(&self, pgid: Pid) -> Vec<Pid> {
+        self.0.lock().get(&pgid).cloned().unwrap()
+    }
+
+    /// 将进程从原来的进程组移动到新的进程组
+    ///
+    /// ## 参数
+    /// - `pid` : 要移动的进程的pid
+    /// - `new_pgid` : 新的进程组id
+    /// - `old_pgid` : 原来的进程组id
+    ///
+    /// ## 返回值
+    /// - `Ok(true)` : 新的进程组原先不存在，已被创建（ornewtg）
+    /// - `Ok(false)` : 进程被加入了已存在的进程组
+    /// - `Err(ESRCH)` : 原来的进程组不存在
+    /// - `Err(EINVAL)` : 新的进程组id为`Pid::IDLE`
+    pub fn set_pgid_by_pid(
+        &self,
+        pid: Pid,
+        new_pgid: Pid,
+        old_pgid: Pid,
+    ) -> Result<bool, SystemError> {
+        if new_pgid.is_idle() {
+            return Err(SystemError::EINVAL);
+        }
+        let mut inner = self.0.lock();
+        let old_group_vec = inner.get_mut(&old_pgid).ok_or(SystemError::ESRCH)?;
+        old_group_vec.retain(|&x| x != pid);
+        if let Some(new_group_vec) = inner.get_mut(&new_pgid) {
+            new_group_vec.push(pid);
+            return Ok(false);
+        } else {
+            let new_group: Vec<Pid> = vec![new_pgid];
+            inner.insert(new_pgid, new_group);
+            return Ok(true);
         }
-        return name;
     }
 }
 
-impl Drop for ProcessControlBlock {
-    fn drop(&mut self) {
-        // 在ProcFS中,解除进程的注册
-        procfs_unregister_pid(self.pid())
-            .unwrap_or_else(|e| panic!("procfs_unregister_pid failed: error: {e:?}"));
+#[cfg(test)]
+mod tests {
+    use core::{
+        hint::spin_loop,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use alloc::{
+        boxed::Box,
+        format,
+        string::{String, ToString},
+        sync::Arc,
+        vec::Vec,
+    };
+
+    use crate::{
+        arch::{CurrentIrqArch, MMArch},
+        exception::InterruptArch,
+        filesystem::vfs::file::{File, FileMode},
+        include::bindings::bindings::smp_get_total_cpu,
+        ipc::pipe::LockedPipeInode,
+        kinfo,
+        libs::spinlock::SpinLock,
+        mm::{
+            allocator::page_frame::PageFrameCount,
+            syscall::{MapFlags, ProtFlags},
+            ucontext::{AddressSpace, UserStack},
+            MemoryManagementArch, VirtAddr,
+        },
+        sched::{
+            cfs::__get_cfs_scheduler, clock::sched_clock, core::task_allowed_cpus,
+            syscall::SchedParam, SchedPolicy,
+        },
+        smp::{core::smp_get_processor_id, cpumask::CpuMask},
+        syscall::SystemError,
+    };
+
+    use super::{
+        fork::CloneFlags,
+        kthread::{KernelThreadClosure, KernelThreadMechanism},
+        pi_effective_policy,
+        resource::{RLimit, RLimitID, RLIM_INFINITY},
+        sched_dequeue, sched_enqueue, ChildExitStatus, Credentials, InitExitAction, Pid,
+        ProcessControlBlock, ProcessFlags, ProcessGroupManager, ProcessManager,
+        ProcessSchedulerInfo, ProcessState, SchedEntity, SchedPriority, Signal, SleepOutcome,
+        TaskGroup, ALL_PROCESS, ALL_PROCESS_INITIAL_CAPACITY, ALL_PROCESS_SHARDS, CPU_EXECUTING,
+        PROCESS_GROUP_MANAGER,
+    };
+
+    /// 向同一个进程组中加入大量进程，成员应当按加入顺序全部保留
+    #[test]
+    fn test_add_process_stress() {
+        let manager = ProcessGroupManager::new();
+        let pgid = Pid::new(1);
+        manager.add_group(pgid).unwrap();
+
+        for i in 2..10000 {
+            manager.add_process(pgid, Pid::new(i)).unwrap();
+        }
+
+        let group = manager.get_group_by_pgid(pgid);
+        assert_eq!(group.len(), 9999);
+        assert_eq!(group, (1..10000).map(Pid::new).collect::<Vec<_>>());
+    }
+
+    /// 加入或移出不存在的进程组时，应当返回ESRCH
+    #[test]
+    fn test_missing_group() {
+        let manager = ProcessGroupManager::new();
+        assert_eq!(
+            manager.add_process(Pid::new(5), Pid::new(6)),
+            Err(SystemError::ESRCH)
+        );
+        assert_eq!(
+            manager.set_pgid_by_pid(Pid::new(6), Pid::new(7), Pid::new(5)),
+            Err(SystemError::ESRCH)
+        );
+
+        manager.add_group(Pid::new(5)).unwrap();
+        manager.add_process(Pid::new(5), Pid::new(6)).unwrap();
+        assert_eq!(
+            manager.set_pgid_by_pid(Pid::new(6), Pid::new(7), Pid::new(5)),
+            Ok(true)
+        );
+        assert_eq!(manager.get_group_by_pgid(Pid::new(5)), [Pid::new(5)]);
+    }
 
-        if let Some(ppcb) = self.parent_pcb.read().upgrade() {
-            ppcb.children.write().remove(&self.pid());
+    /// 遍历进程组的成员并查找组长；组长退出后进程组仍然存在，但不再有组长
+    #[test]
+    fn test_group_members_and_leader() {
+        let manager = ProcessGroupManager::new();
+        let leader = new_test_kthread("pgrp-leader");
+        let pgid = leader.pid();
+        let members: Vec<_> = (0..3).map(|_| new_test_kthread("pgrp-member")).collect();
+        manager.add_group(pgid).unwrap();
+        for pcb in members.iter() {
+            manager.add_process(pgid, pcb.pid()).unwrap();
         }
 
-        unsafe { ProcessManager::release(self.pid()) };
+        let mut visited = Vec::new();
+        manager.for_each_member(pgid, |pid| visited.push(pid));
+        let mut expected = vec![pgid];
+        expected.extend(members.iter().map(|pcb| pcb.pid()));
+        assert_eq!(visited, expected);
+        assert_eq!(manager.leader(pgid), Some(pgid));
+        // 成员作为pgid时不是任何进程组的组长
+        assert_eq!(manager.leader(members[0].pid()), None);
+
+        leader
+            .sched_info_mut()
+            .try_set_state(ProcessState::Exited(0))
+            .unwrap();
+        assert_eq!(manager.leader(pgid), None);
+        ProcessManager::release(pgid);
+        assert_eq!(manager.leader(pgid), None);
+        let mut count = 0;
+        manager.for_each_member(pgid, |_| count += 1);
+        assert_eq!(count, members.len() + 1);
+
+        // 组长离开进程组之后，其余成员仍然可以被遍历
+        manager.set_pgid_by_pid(pgid, members[0].pid(), pgid).unwrap();
+        let mut visited = Vec::new();
+        manager.for_each_member(pgid, |pid| visited.push(pid));
+        assert_eq!(visited, expected[1..]);
+        assert_eq!(manager.leader(pgid), None);
     }
-}
-/// 进程的基本信息
-///
-/// 这个结构体保存进程的基本信息，主要是那些不会随着进程的运行而经常改变的信息。
-#[derive(Debug)]
-pub struct ProcessBasicInfo {
-    /// 当前进程的进程组id
-    pgid: Pid,
-    /// 当前进程的父进程的pid
-    ppid: Pid,
-    /// 进程的名字
-    name: String,
 
-    /// 当前进程的工作目录
-    cwd: String,
-
-    /// 用户地址空间
-    user_vm: Option<Arc<AddressSpace>>,
+    /// 不存在的进程组：闭包不会被调用，也没有组长
+    #[test]
+    fn test_missing_group_members() {
+        let manager = ProcessGroupManager::new();
+        let mut count = 0;
+        manager.for_each_member(Pid::new(42), |_| count += 1);
+        assert_eq!(count, 0);
+        assert_eq!(manager.leader(Pid::new(42)), None);
+    }
 
-    /// 文件描述符表
-    fd_table: Option<Arc<RwLock<FileDescriptorVec>>>,
+    /// pid 0保留给idle进程：它不在进程表中，也不能作为进程组id
+    #[test]
+    fn test_idle_pid_reserved() {
+        assert!(Pid::IDLE.is_idle());
+        assert!(!Pid::new(1).is_idle());
+        for idle in ProcessManager::idle_pcb() {
+            assert_eq!(idle.pid(), Pid::IDLE);
+        }
 
-    /// 所在的进程组tg
-    sched_tg: Option<Arc<TaskGroup>>,
-}
+        // 每个cpu都有自己的idle进程，不能通过pid查找
+        assert!(ProcessManager::find(Pid::IDLE).is_none());
+        assert_eq!(
+            ProcessManager::add_pcb(ProcessManager::idle_pcb()[0].clone()),
+            Err(SystemError::EINVAL)
+        );
+        assert!(ProcessManager::find(Pid::IDLE).is_none());
 
-impl ProcessBasicInfo {
-    pub fn new(
-        pgid: Pid,
-        ppid: Pid,
-        name: String,
-        cwd: String,
-        user_vm: Option<Arc<AddressSpace>>,
-        sched_tg: Option<Arc<TaskGroup>>,
-    ) -> RwLock<Self> {
-        let fd_table = Arc::new(RwLock::new(FileDescriptorVec::new()));
-        return RwLock::new(Self {
-            pgid,
-            ppid,
-            name,
-            cwd,
-            user_vm,
-            fd_table: Some(fd_table),
-            sched_tg: Some(sched_tg),
-        });
+        let manager = ProcessGroupManager::new();
+        assert_eq!(manager.add_group(Pid::IDLE), Err(SystemError::EINVAL));
+        manager.add_group(Pid::new(5)).unwrap();
+        assert_eq!(manager.get_group_by_pgid(Pid::new(5)), [Pid::new(5)]);
+        assert_eq!(
+            manager.set_pgid_by_pid(Pid::new(5), Pid::IDLE, Pid::new(5)),
+            Err(SystemError::EINVAL)
+        );
+        // 被拒绝的操作不会改变原来的进程组
+        assert_eq!(manager.get_group_by_pgid(Pid::new(5)), [Pid::new(5)]);
     }
 
-    pub fn pgid(&self) -> Pid {
-        return self.pgid;
+    /// 创建一个父进程以及两个子进程：一个与父进程处于同一进程组，另一个是新进程组的组长
+    fn wait_family() -> (
+        Arc<ProcessControlBlock>,
+        Arc<ProcessControlBlock>,
+        Arc<ProcessControlBlock>,
+    ) {
+        let new_kthread = |name: &str| {
+            KernelThreadMechanism::create(
+                KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                name.to_string(),
+            )
+            .unwrap()
+        };
+        let parent = new_kthread("wait-parent");
+        let same_group = new_kthread("wait-same-group");
+        let other_group = new_kthread("wait-other-group");
+        same_group.basic_mut().set_pgid(parent.basic().pgid());
+        other_group.basic_mut().set_pgid(other_group.pid());
+        for child in [&same_group, &other_group] {
+            parent.children.write().insert(child.pid(), child.clone());
+        }
+        return (parent, same_group, other_group);
     }
 
-    pub fn set_pgid(&self, npgid: Pid) -> Pid {
-        self.pgid = npgid;
+    /// 获取可以被等待的子进程的pid，按照pid排序
+    fn wait_pids(parent: &ProcessControlBlock, pid: i64) -> Result<Vec<Pid>, SystemError> {
+        let mut pids: Vec<Pid> = parent
+            .wait_candidates(pid)?
+            .iter()
+            .map(|pcb| pcb.pid())
+            .collect();
+        pids.sort();
+        return Ok(pids);
     }
 
-    pub fn ppid(&self) -> Pid {
-        return self.ppid;
+    /// pid大于0时只等待该子进程，不是子进程时返回ECHILD
+    #[test]
+    fn test_wait_specific_child() {
+        let (parent, same_group, _other_group) = wait_family();
+        let pid = same_group.pid();
+        assert_eq!(wait_pids(&parent, pid.0 as i64), Ok([pid].to_vec()));
+        assert_eq!(
+            wait_pids(&parent, parent.pid().0 as i64),
+            Err(SystemError::ECHILD)
+        );
     }
 
-    pub fn name(&self) -> &str {
-        return &self.name;
+    /// pid等于0时等待与调用者处于同一进程组的子进程
+    #[test]
+    fn test_wait_own_group() {
+        let (parent, same_group, _other_group) = wait_family();
+        assert_eq!(wait_pids(&parent, 0), Ok([same_group.pid()].to_vec()));
     }
 
-    pub fn set_name(&mut self, name: String) {
-        self.name = name;
+    /// pid等于-1时等待任意子进程，没有子进程时返回ECHILD
+    #[test]
+    fn test_wait_any_child() {
+        let (parent, same_group, other_group) = wait_family();
+        let mut expected = [same_group.pid(), other_group.pid()].to_vec();
+        expected.sort();
+        assert_eq!(wait_pids(&parent, -1), Ok(expected));
+        assert_eq!(wait_pids(&same_group, -1), Err(SystemError::ECHILD));
     }
 
-    pub fn cwd(&self) -> String {
-        return self.cwd.clone();
+    /// pid小于-1时等待进程组`-pid`中的子进程，包括只被进程组管理器记录的子进程
+    #[test]
+    fn test_wait_other_group() {
+        let (parent, same_group, other_group) = wait_family();
+        let pgid = other_group.pid();
+        let selector = -(pgid.0 as i64);
+        assert_eq!(wait_pids(&parent, selector), Ok([pgid].to_vec()));
+
+        PROCESS_GROUP_MANAGER.add_group(pgid).unwrap();
+        PROCESS_GROUP_MANAGER
+            .add_process(pgid, same_group.pid())
+            .unwrap();
+        let mut expected = [pgid, same_group.pid()].to_vec();
+        expected.sort();
+        assert_eq!(wait_pids(&parent, selector), Ok(expected));
+        PROCESS_GROUP_MANAGER.0.lock().remove(&pgid);
+
+        // 不存在的进程组中没有子进程
+        assert_eq!(
+            wait_pids(&parent, -(parent.pid().0 as i64)),
+            Err(SystemError::ECHILD)
+        );
     }
-    pub fn set_cwd(&mut self, path: String) {
-        return self.cwd = path;
+
+    /// 子进程在父进程等待之前退出，退出状态被保留在父进程的队列中，之后仍然可以被收集
+    #[test]
+    fn test_collect_exit_status_later() {
+        let (parent, child, _other_group) = wait_family();
+        let pid = child.pid();
+        *child.parent_pcb.write() = Arc::downgrade(&parent);
+        child
+            .sched_info_mut()
+            .try_set_state(ProcessState::Exited(7))
+            .unwrap();
+        child.report_exit(Some(7));
+
+        // 子进程在退出时就已经被回收，不再需要它的pcb
+        assert!(!parent.children.read().contains_key(&pid));
+        assert!(ProcessManager::find(pid).is_none());
+        drop(child);
+
+        let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let exited = unsafe { parent.wait_child(pid.0 as i64) };
+        let again = unsafe { parent.wait_child(pid.0 as i64) };
+        drop(irq_guard);
+        assert_eq!(
+            exited,
+            Ok(Some(ChildExitStatus {
+                pid,
+                pgid: parent.basic().pgid(),
+                status: 7,
+                orphan: false,
+            }))
+        );
+        // 退出状态只能被收集一次
+        assert_eq!(again, Err(SystemError::ECHILD));
     }
 
-    pub fn user_vm(&self) -> Option<Arc<AddressSpace>> {
-        return self.user_vm.clone();
+    /// 退出状态被交给父进程后，进程进入Dead状态，不能被唤醒，并且已经被回收
+    #[test]
+    fn test_dead_then_reaped() {
+        let (parent, child, _other_group) = wait_family();
+        let pid = child.pid();
+        *child.parent_pcb.write() = Arc::downgrade(&parent);
+        child
+            .sched_info_mut()
+            .try_set_state(ProcessState::Exited(3))
+            .unwrap();
+        assert!(ProcessManager::find(pid).is_some());
+
+        child.report_exit(Some(3));
+        assert_eq!(child.sched_info().state(), ProcessState::Dead);
+        assert!(child.sched_info().state().has_exited());
+        assert!(!child.sched_info().state().is_exited());
+        assert_eq!(ProcessManager::wakeup(&child), Err(SystemError::EINVAL));
+        // 死亡的进程不会被加入运行队列，因此不会被调度器选中
+        sched_enqueue(child.clone(), false);
+        assert!(!sched_dequeue(&child));
+        // Dead状态的进程已经脱离父进程，并从进程表中移除
+        assert!(!parent.children.read().contains_key(&pid));
+        assert!(ProcessManager::find(pid).is_none());
     }
 
-    pub unsafe fn set_user_vm(&mut self, user_vm: Option<Arc<AddressSpace>>) {
-        self.user_vm = user_vm;
+    /// 不保留退出状态时（内核线程），进程保持Exited状态，退出码仍然可以被读取
+    #[test]
+    fn test_exit_without_status_stays_exited() {
+        let (parent, child, _other_group) = wait_family();
+        *child.parent_pcb.write() = Arc::downgrade(&parent);
+        child
+            .sched_info_mut()
+            .try_set_state(ProcessState::Exited(5))
+            .unwrap();
+        child.report_exit(None);
+        assert_eq!(child.sched_info().state(), ProcessState::Exited(5));
+        assert!(parent.child_exit_statuses.lock_irqsave().is_empty());
+        assert!(!parent.children.read().contains_key(&child.pid()));
     }
 
-    pub fn fd_table(&self) -> Option<Arc<RwLock<FileDescriptorVec>>> {
-        return self.fd_table.clone();
+    /// 退出状态按照wait4的pid语义匹配：指定的子进程、同一进程组、任意子进程或者指定的进程组
+    #[test]
+    fn test_exit_status_matches() {
+        let exited = ChildExitStatus {
+            pid: Pid::new(20),
+            pgid: Pid::new(7),
+            status: 0,
+            orphan: false,
+        };
+        assert!(exited.matches(20, Pid::new(1)));
+        assert!(!exited.matches(21, Pid::new(7)));
+        assert!(exited.matches(0, Pid::new(7)));
+        assert!(!exited.matches(0, Pid::new(1)));
+        assert!(exited.matches(-1, Pid::new(1)));
+        assert!(exited.matches(-7, Pid::new(1)));
+        assert!(!exited.matches(-20, Pid::new(7)));
     }
 
-    pub fn set_fd_table(&mut self, fd_table: Option<Arc<RwLock<FileDescriptorVec>>>) {
-        self.fd_table = fd_table;
+    /// 经典的优先级反转：低优先级的进程持有锁，高优先级的进程等待这个锁。
+    /// 持有者被提升到高于中优先级的进程；放开这个锁后，只保留它持有的其他锁的等待者带来的提升
+    #[test]
+    fn test_pi_inversion() {
+        let fifo = |param| {
+            (
+                SchedPolicy::FIFO,
+                SchedPolicy::FIFO.priority_from_param(param).unwrap(),
+            )
+        };
+        let low = fifo(10);
+        let medium = fifo(50);
+        let high = fifo(90);
+
+        let boosted = pi_effective_policy(low, &[high]).unwrap();
+        assert_eq!(boosted, high);
+        assert!(boosted.1 < medium.1);
+
+        // 持有者同时持有另一个锁，中优先级的进程在等待它
+        assert_eq!(pi_effective_policy(low, &[medium, high]), Some(high));
+        // 放开高优先级进程等待的锁后，优先级按照剩余的等待者重新计算
+        assert_eq!(pi_effective_policy(low, &[medium]), Some(medium));
+        assert_eq!(pi_effective_policy(low, &[]), None);
+
+        // 等待者的优先级不高于持有者、等待者不是实时进程，或者持有者是deadline进程时，不提升
+        assert_eq!(pi_effective_policy(high, &[medium]), None);
+        let normal = (SchedPolicy::CFS, SchedPolicy::CFS.priority_from_param(0).unwrap());
+        assert_eq!(pi_effective_policy(low, &[normal]), None);
+        assert_eq!(pi_effective_policy(normal, &[low]), Some(low));
+        assert_eq!(pi_effective_policy((SchedPolicy::DEADLINE, low.1), &[high]), None);
     }
 
-    pub fn set_tg(&mut self, tg: Option<Arc<TaskGroup>>) {
-        self.sched_tg = tg;
+    /// 启动参数`init_exit=`可以选择panic或者重启，参数缺失或无法识别时不生效
+    #[test]
+    fn test_init_exit_action_from_cmdline() {
+        assert_eq!(
+            InitExitAction::from_cmdline("root=/dev/sda1 init_exit=reboot"),
+            Some(InitExitAction::Reboot)
+        );
+        assert_eq!(
+            InitExitAction::from_cmdline("init_exit=reboot init_exit=panic"),
+            Some(InitExitAction::Panic)
+        );
+        assert_eq!(InitExitAction::from_cmdline("init_exit=halt"), None);
+        assert_eq!(InitExitAction::from_cmdline(""), None);
     }
 
-    pub fn tg(&self) -> Option<Arc<TaskGroup>> {
-        return self.sched_tg.clone();
+    /// 进程组的cpu使用时间包括它自身的成员以及所有子进程组的成员消耗的时间
+    #[test]
+    fn test_task_group_cpu_usage_rollup() {
+        let parent = TaskGroup::new_root();
+        let child = TaskGroup::new(parent.clone()).unwrap();
+        let grandchild = TaskGroup::new(child.clone()).unwrap();
+        parent.children.write().insert(Pid::new(100), child.clone());
+        child
+            .children
+            .write()
+            .insert(Pid::new(101), grandchild.clone());
+
+        parent.account_cpu_usage(1_000);
+        child.account_cpu_usage(200);
+        grandchild.account_cpu_usage(30);
+
+        assert_eq!(grandchild.cpu_usage(), 30);
+        assert_eq!(child.cpu_usage(), 230);
+        assert_eq!(parent.cpu_usage(), 1_230);
     }
-}
 
-#[derive(Debug)]
-pub struct ProcessSchedulerInfo {
-    /// 当前进程所在的cpu
-    on_cpu: AtomicI32,
-    /// 如果当前进程等待被迁移到另一个cpu核心上（也就是flags中的PF_NEED_MIGRATE被置位），
-    /// 该字段存储要被迁移到的目标处理器核心号
-    migrate_to: AtomicI32,
+    /// 父进程退出后被init收养的子进程退出时，它们的退出状态由回收线程以WNOHANG的方式等待，不会在init中堆积；
+    /// init阻塞SIGCHLD时，退出状态留给它自己等待
+    #[test]
+    fn test_reap_orphans() {
+        let init = ProcessManager::find(Pid::new(1)).unwrap();
+        let new_kthread = |name: &str| {
+            KernelThreadMechanism::create(
+                KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                name.to_string(),
+            )
+            .unwrap()
+        };
+        let parent = new_kthread("orphan-parent");
+        let orphans: Vec<Arc<ProcessControlBlock>> = (0..3)
+            .map(|i| {
+                let child = new_kthread(&format!("orphan-{}", i));
+                *child.parent_pcb.write() = Arc::downgrade(&parent);
+                parent.children.write().insert(child.pid(), child.clone());
+                child
+            })
+            .collect();
+
+        unsafe { parent.adopt_childen() }.unwrap();
+        let pids: Vec<Pid> = orphans.iter().map(|child| child.pid()).collect();
+        for (i, child) in orphans.into_iter().enumerate() {
+            *child.parent_pcb.write() = Arc::downgrade(&init);
+            child
+                .sched_info_mut()
+                .try_set_state(ProcessState::Exited(i))
+                .unwrap();
+            child.report_exit(Some(i));
+        }
 
-    /// 当前进程的状态
-    state: ProcessState,
-    /// 进程的调度策略
-    sched_policy: SchedPolicy,
-    /// 进程的调度优先级
-    priority: SchedPriority,
-    /// 当前进程的虚拟运行时间
-    virtual_runtime: AtomicIsize,
-    /// 由实时调度器管理的时间片
-    rt_time_slice: AtomicIsize,
-    //调度实体
-    se: Option<Arc<SchedEntity>>,
-}
+        // 回收线程可能已经先一步收集了一部分退出状态
+        init.reap_orphans();
+        {
+            let statuses = init.child_exit_statuses.lock_irqsave();
+            assert!(statuses.iter().all(|exited| !pids.contains(&exited.pid)));
+            assert!(statuses.iter().all(|exited| !exited.orphan));
+        }
+        for pid in pids {
+            assert_eq!(
+                init.try_wait_child(pid.0 as i64).unwrap_err(),
+                SystemError::ECHILD
+            );
+        }
 
-impl ProcessSchedulerInfo {
-    pub fn new(on_cpu: Option<u32>) -> RwLock<Self> {
-        let cpu_id = match on_cpu {
-            Some(cpu_id) => cpu_id as i32,
-            None => -1,
+        // init阻塞SIGCHLD时不会收集退出状态
+        let blocked = init.sig_state().blocked();
+        init.sig_state().set_blocked(blocked | Signal::SIGCHLD.mask());
+        let status = ChildExitStatus {
+            pid: Pid::new(usize::MAX - 1),
+            pgid: Pid::new(usize::MAX - 1),
+            status: 5,
+            orphan: true,
         };
-        return RwLock::new(Self {
-            on_cpu: AtomicI32::new(cpu_id),
-            migrate_to: AtomicI32::new(-1),
-            state: ProcessState::Blocked(false),
-            sched_policy: SchedPolicy::CFS,
-            virtual_runtime: AtomicIsize::new(0),
-            rt_time_slice: AtomicIsize::new(0),
-            priority: SchedPriority::new(100).unwrap(),
-            se: Some(Arc::new(SchedEntity::new())),
-        });
+        init.child_exit_statuses.lock_irqsave().push_back(status);
+        assert_eq!(init.reap_orphans(), 0);
+        let exited = init.try_wait_child(status.pid.0 as i64).unwrap().unwrap();
+        init.sig_state().set_blocked(blocked);
+        assert_eq!(exited.status, 5);
     }
 
-    pub fn on_cpu(&self) -> Option<u32> {
-        let on_cpu = self.on_cpu.load(Ordering::SeqCst);
-        if on_cpu == -1 {
-            return None;
-        } else {
-            return Some(on_cpu as u32);
-        }
+    /// 进程实际允许运行的cpu集合被cpuset限制，sched_getaffinity仍然返回进程设置的集合；
+    /// 两者没有交集时以cpuset为准
+    #[test]
+    fn test_effective_affinity() {
+        let pcb = new_test_kthread("effective-affinity");
+        let mut requested = CpuMask::new();
+        requested.set(0);
+        requested.set(1);
+        pcb.sched_info().set_cpu_affinity(requested).unwrap();
+        assert_eq!(pcb.effective_affinity(), requested);
+
+        let tg = TaskGroup::new_root();
+        let mut cpuset = CpuMask::new();
+        cpuset.set(1);
+        cpuset.set(2);
+        tg.set_cpus_allowed(cpuset).unwrap();
+        pcb.basic_mut().set_tg(Some(tg.clone()));
+
+        let mut effective = CpuMask::new();
+        effective.set(1);
+        assert_eq!(pcb.effective_affinity(), effective);
+        assert_eq!(ProcessManager::sched_getaffinity(pcb.pid()), Ok(requested));
+
+        let mut disjoint = CpuMask::new();
+        disjoint.set(3);
+        pcb.sched_info().set_cpu_affinity(disjoint).unwrap();
+        assert_eq!(pcb.effective_affinity(), cpuset);
+        assert_eq!(
+            tg.set_cpus_allowed(CpuMask::new()),
+            Err(SystemError::EINVAL)
+        );
+
+        // cpuset允许的cpu都不在线时，cpuset被重置为在线的cpu的集合，而不是一直不允许进程运行
+        let mut online = CpuMask::new();
+        online.set(0);
+        assert_eq!(task_allowed_cpus(&pcb, online, 4), online);
+        assert_eq!(tg.cpus_allowed(), online);
+        assert_eq!(pcb.effective_affinity(), online);
     }
 
-    pub fn set_on_cpu(&self, on_cpu: Option<u32>) {
-        if let Some(cpu_id) = on_cpu {
-            self.on_cpu.store(cpu_id as i32, Ordering::SeqCst);
-        } else {
-            self.on_cpu.store(-1, Ordering::SeqCst);
+    /// 缩小cpuset之后，所有成员最终都位于仍然被允许的cpu上
+    #[test]
+    fn test_update_cpus_migrates_members() {
+        let cpu_num = unsafe { smp_get_total_cpu() } as usize;
+        if cpu_num < 2 {
+            return;
+        }
+        let tg = TaskGroup::new_root();
+        let stop = Arc::new(AtomicBool::new(false));
+        let members: Vec<Arc<ProcessControlBlock>> = (0..cpu_num * 2)
+            .map(|i| {
+                let stop = stop.clone();
+                let pcb = KernelThreadMechanism::create(
+                    KernelThreadClosure::EmptyClosure((
+                        Box::new(move || {
+                            while !stop.load(Ordering::SeqCst) {
+                                spin_loop();
+                            }
+                            0
+                        }),
+                        (),
+                    )),
+                    format!("cpuset-member-{}", i),
+                )
+                .unwrap();
+                pcb.basic_mut().set_tg(Some(tg.clone()));
+                ProcessManager::wakeup(&pcb).unwrap();
+                pcb
+            })
+            .collect();
+
+        let mut mask = CpuMask::new();
+        mask.set(0);
+        tg.update_cpus(mask).unwrap();
+        for pcb in members.iter() {
+            while pcb.sched_info().on_cpu() != Some(0) {
+                spin_loop();
+            }
         }
+        stop.store(true, Ordering::SeqCst);
     }
 
-    pub fn migrate_to(&self) -> Option<u32> {
-        let migrate_to = self.migrate_to.load(Ordering::SeqCst);
-        if migrate_to == -1 {
-            return None;
-        } else {
-            return Some(migrate_to as u32);
+    /// 进程组可以嵌套到最大层数，超过最大层数时创建失败
+    #[test]
+    fn test_task_group_max_depth() {
+        let mut tg = TaskGroup::new_root();
+        for depth in 1..=TaskGroup::max_depth() {
+            tg = TaskGroup::new(tg).unwrap();
+            assert_eq!(tg.depth(), depth);
         }
+        assert_eq!(TaskGroup::new(tg).unwrap_err(), SystemError::EINVAL);
     }
 
-    pub fn set_migrate_to(&self, migrate_to: Option<u32>) {
-        if let Some(data) = migrate_to {
-            self.migrate_to.store(data as i32, Ordering::SeqCst);
-        } else {
-            self.migrate_to.store(-1, Ordering::SeqCst)
+    /// 进程组每次被限流、解除限流，cpu.stat中的计数都随之增加，重复限流不会重复计数
+    #[test]
+    fn test_task_group_cpu_stat() {
+        let tg = TaskGroup::new_root();
+        for i in 0..3u64 {
+            tg.account_period();
+            tg.throttle(i * 1_000);
+            // 已经被限流时再次限流不会重复计数
+            tg.throttle(i * 1_000 + 50);
+            tg.unthrottle(i * 1_000 + 100);
+            // 没有被限流时解除限流不会产生任何影响
+            tg.unthrottle(i * 1_000 + 500);
+
+            let stat = tg.cpu_stat();
+            assert_eq!(stat.nr_periods(), i + 1);
+            assert_eq!(stat.nr_throttled(), i + 1);
+            assert_eq!(stat.throttled_time(), (i + 1) * 100);
         }
+        assert_eq!(
+            tg.cpu_stat().render(),
+            "nr_periods 3\nnr_throttled 3\nthrottled_time 300\n"
+        );
     }
 
-    pub fn state(&self) -> ProcessState {
-        return self.state;
+    /// 进程表的每个分片都预留了容量，在容量之内加入进程不需要扩容
+    #[test]
+    fn test_all_process_capacity() {
+        let per_shard = ALL_PROCESS_INITIAL_CAPACITY / ALL_PROCESS_SHARDS;
+        assert!(per_shard > 0);
+        for shard in ALL_PROCESS.iter() {
+            let shard = shard.lock_irqsave();
+            assert!(shard.capacity() >= per_shard.max(shard.len()));
+        }
     }
 
-    fn set_state(&mut self, state: ProcessState) {
-        self.state = state;
+    /// 新建的调度实体使用默认的优先级，虚拟运行时间为0，且不对应任何进程
+    #[test]
+    fn test_sched_entity_default() {
+        let se = SchedEntity::new();
+        assert_eq!(se.priority().data(), 100);
+        assert_eq!(se.virtual_runtime(), 0);
+        assert!(se.pcb().is_none());
+        assert!(se.group_cfs_rq().is_none());
+
+        se.set_virtual_runtime(42);
+        assert_eq!(se.virtual_runtime(), 42);
     }
 
-    pub fn policy(&self) -> SchedPolicy {
-        return self.sched_policy;
+    /// 枚举所有的状态转换，只有合法的转换能够成功
+    #[test]
+    fn test_state_transitions() {
+        let states = [
+            ProcessState::Runnable,
+            ProcessState::Blocked(true),
+            ProcessState::Blocked(false),
+            ProcessState::Stopped,
+            ProcessState::Frozen,
+            ProcessState::Exited(0),
+            ProcessState::Exited(1),
+            ProcessState::Dead,
+        ];
+        let legal = |from: ProcessState, to: ProcessState| match (from, to) {
+            (ProcessState::Runnable, ProcessState::Blocked(_))
+            | (ProcessState::Runnable, ProcessState::Stopped)
+            | (ProcessState::Runnable, ProcessState::Frozen)
+            | (ProcessState::Blocked(_), ProcessState::Frozen)
+            | (ProcessState::Frozen, ProcessState::Runnable)
+            | (ProcessState::Runnable, ProcessState::Exited(_))
+            | (ProcessState::Blocked(_), ProcessState::Runnable)
+            | (ProcessState::Blocked(_), ProcessState::Exited(_))
+            | (ProcessState::Exited(_), ProcessState::Dead)
+            | (ProcessState::Stopped, ProcessState::Runnable) => true,
+            _ => false,
+        };
+
+        for from in states {
+            for to in states {
+                let sched_info = ProcessSchedulerInfo::new(None);
+                let mut writer = sched_info.write();
+                writer.state = from;
+
+                let r = writer.try_set_state(to);
+                if legal(from, to) {
+                    assert_eq!(r, Ok(()), "{:?} -> {:?}", from, to);
+                    assert_eq!(writer.state(), to);
+                } else {
+                    assert_eq!(r, Err(SystemError::EINVAL), "{:?} -> {:?}", from, to);
+                    assert_eq!(writer.state(), from);
+                }
+            }
+        }
+
+        // 已经退出的进程不能被唤醒
+        let sched_info = ProcessSchedulerInfo::new(None);
+        let mut writer = sched_info.write();
+        writer.try_set_state(ProcessState::Exited(0)).unwrap();
+        assert!(writer.try_set_state(ProcessState::Runnable).is_err());
+        assert!(writer.state().is_exited());
     }
 
-    pub fn virtual_runtime(&self) -> isize {
-        return self.virtual_runtime.load(Ordering::SeqCst);
+    /// 创建一个用于测试的内核线程
+    fn new_test_kthread(name: &str) -> Arc<ProcessControlBlock> {
+        return KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            name.to_string(),
+        )
+        .unwrap();
     }
 
-    pub fn set_virtual_runtime(&self, virtual_runtime: isize) {
-        self.virtual_runtime
-            .store(virtual_runtime, Ordering::SeqCst);
+    /// setpriority会将超出范围的nice值截断，而不是拒绝
+    #[test]
+    fn test_setpriority_clamp() {
+        let pcb = new_test_kthread("nice-clamp");
+        ProcessManager::setpriority(pcb.pid(), 100).unwrap();
+        assert_eq!(pcb.sched_info().nice(), ProcessSchedulerInfo::NICE_MAX);
+        ProcessManager::setpriority(pcb.pid(), -100).unwrap();
+        assert_eq!(pcb.sched_info().nice(), ProcessSchedulerInfo::NICE_MIN);
     }
-    pub fn increase_virtual_runtime(&self, delta: isize) {
-        self.virtual_runtime.fetch_add(delta, Ordering::SeqCst);
+
+    /// 没有CAP_SYS_NICE能力时，只能提高nice值，不能降低
+    #[test]
+    fn test_setpriority_unprivileged() {
+        let pcb = new_test_kthread("nice-unprivileged");
+        pcb.set_cred(Credentials::user(1000, 1000));
+        let current = ProcessManager::current_pcb();
+        let cred = current.cred();
+        current.set_cred(Credentials::user(1000, 1000));
+
+        assert_eq!(ProcessManager::setpriority(pcb.pid(), 5), Ok(()));
+        assert_eq!(pcb.sched_info().nice(), 5);
+        assert_eq!(
+            ProcessManager::setpriority(pcb.pid(), 4),
+            Err(SystemError::EACCES)
+        );
+        assert_eq!(
+            ProcessManager::setpriority(pcb.pid(), -20),
+            Err(SystemError::EACCES)
+        );
+        // 截断之后没有降低nice值
+        assert_eq!(ProcessManager::setpriority(pcb.pid(), 100), Ok(()));
+        assert_eq!(pcb.sched_info().nice(), ProcessSchedulerInfo::NICE_MAX);
+
+        current.set_cred(cred);
+        // 拥有CAP_SYS_NICE能力时，可以设置范围内的任意值
+        assert_eq!(ProcessManager::setpriority(pcb.pid(), -20), Ok(()));
+        assert_eq!(pcb.sched_info().nice(), ProcessSchedulerInfo::NICE_MIN);
     }
 
-    pub fn rt_time_slice(&self) -> isize {
-        return self.rt_time_slice.load(Ordering::SeqCst);
+    /// 没有CAP_SYS_NICE能力时，不能修改其他用户的进程的nice值
+    #[test]
+    fn test_setpriority_other_user() {
+        let pcb = new_test_kthread("nice-other-user");
+        pcb.set_cred(Credentials::user(1001, 1001));
+        let current = ProcessManager::current_pcb();
+        let cred = current.cred();
+        current.set_cred(Credentials::user(1000, 1000));
+
+        assert_eq!(
+            ProcessManager::setpriority(pcb.pid(), 10),
+            Err(SystemError::EPERM)
+        );
+        assert_eq!(pcb.sched_info().nice(), 0);
+
+        current.set_cred(cred);
     }
 
-    pub fn set_rt_time_slice(&self, rt_time_slice: isize) {
-        self.rt_time_slice.store(rt_time_slice, Ordering::SeqCst);
+    /// 进程在cpu 0上运行并被切换出去后，应当记录下cpu 0
+    #[test]
+    fn test_last_cpu() {
+        let info = ProcessSchedulerInfo::new(Some(0));
+        assert_eq!(info.read().last_cpu(), None);
+
+        info.read().set_last_cpu(info.read().on_cpu().unwrap());
+        assert_eq!(info.read().last_cpu(), Some(0));
+
+        // 迁移到另一个cpu之后，在被切换出去之前，last_cpu保持不变
+        info.read().set_on_cpu(Some(1));
+        assert_eq!(info.read().last_cpu(), Some(0));
     }
 
-    pub fn increase_rt_time_slice(&self, delta: isize) {
-        self.rt_time_slice.fetch_add(delta, Ordering::SeqCst);
+    /// 迁移标志只能被一方设置，只有完成或者放弃迁移才会清除，单纯修改on_cpu不会清除
+    #[test]
+    fn test_migration_fence() {
+        let info = ProcessSchedulerInfo::new(Some(0));
+        let info = info.read();
+        assert!(!info.is_migrating());
+
+        assert!(info.start_migration());
+        assert!(!info.start_migration());
+        info.set_on_cpu(Some(2));
+        assert!(info.is_migrating());
+        info.finish_migration(Some(1));
+        assert!(!info.is_migrating());
+        assert_eq!(info.on_cpu(), Some(1));
+
+        assert!(info.start_migration());
+        info.cancel_migration();
+        assert!(!info.is_migrating());
+        assert_eq!(info.on_cpu(), Some(1));
     }
 
-    pub fn priority(&self) -> SchedPriority {
-        return self.priority;
+    /// 回收子进程时，子进程及其已回收后代的运行时间应当累加到父进程中
+    #[test]
+    fn test_absorb_child_times() {
+        let parent = ProcessSchedulerInfo::new(None);
+        let child = ProcessSchedulerInfo::new(None);
+        let grandchild = ProcessSchedulerInfo::new(None);
+
+        grandchild.read().account_time(true, 300);
+        grandchild.read().account_time(false, 400);
+        child.read().absorb_child_times(&grandchild.read());
+
+        child.read().account_time(true, 1000);
+        child.read().account_time(true, 1000);
+        child.read().account_time(false, 500);
+        assert_eq!(child.read().utime(), 2000);
+        assert_eq!(child.read().stime(), 500);
+
+        parent.read().absorb_child_times(&child.read());
+        assert_eq!(parent.read().utime(), 0);
+        assert_eq!(parent.read().stime(), 0);
+        assert_eq!(parent.read().cutime(), 2300);
+        assert_eq!(parent.read().cstime(), 900);
     }
 
-    pub fn se(&self) -> Option<Arc<SchedEntity>> {
-        return self.se.clone();
+    /// 批量唤醒五个处于睡眠状态的内核线程，再次唤醒时不应重复唤醒
+    #[test]
+    fn test_wakeup_all() {
+        // 新创建的内核线程处于阻塞态，直到被唤醒
+        let sleepers: Vec<_> = (0..5)
+            .map(|i| {
+                KernelThreadMechanism::create(
+                    KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                    format!("wakeup-all-{}", i),
+                )
+                .unwrap()
+            })
+            .collect();
+        for pcb in sleepers.iter() {
+            assert!(pcb.sched_info().state().is_blocked());
+        }
+
+        assert_eq!(ProcessManager::wakeup_all(&sleepers), 5);
+        for pcb in sleepers.iter() {
+            assert!(!pcb.sched_info().state().is_blocked());
+        }
+        assert_eq!(ProcessManager::wakeup_all(&sleepers), 0);
     }
-}
 
-#[derive(Debug)]
-pub struct KernelStack {
-    stack: Option<AlignedBox<[u8; KernelStack::SIZE], { KernelStack::ALIGN }>>,
-    /// 标记该内核栈是否可以被释放
-    can_be_freed: bool,
-}
+    /// 唤醒一个已经处于可运行状态的进程，不应改变任何状态
+    #[test]
+    fn test_try_to_wake_up_runnable() {
+        let current = ProcessManager::current_pcb();
+        assert!(current.sched_info().state().is_runnable());
+        assert_eq!(ProcessManager::try_to_wake_up(&current), Ok(false));
+        assert!(current.sched_info().state().is_runnable());
+    }
 
-impl KernelStack {
-    pub const SIZE: usize = 0x4000;
-    pub const ALIGN: usize = 0x4000;
+    /// 唤醒一个位于其他正在运行idle进程的cpu上的进程，应当向该cpu发送IPI
+    #[test]
+    fn test_try_to_wake_up_remote() {
+        let this_cpu = smp_get_processor_id();
+        let remote_cpu = if this_cpu == 0 { 1 } else { 0 };
+        assert_eq!(CPU_EXECUTING.get(remote_cpu), Pid::new(0));
+
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "ttwu-remote".to_string(),
+        )
+        .unwrap();
+        pcb.sched_info().set_on_cpu(Some(remote_cpu));
+
+        assert_eq!(ProcessManager::try_to_wake_up(&pcb), Ok(true));
+        assert!(pcb.sched_info().state().is_runnable());
+        if pcb.sched_info().on_cpu() == Some(remote_cpu) {
+            assert!(ProcessManager::wakeup_preempt(&pcb));
+        }
+    }
 
-    pub fn new() -> Result<Self, SystemError> {
-        return Ok(Self {
-            stack: Some(
-                AlignedBox::<[u8; KernelStack::SIZE], { KernelStack::ALIGN }>::new_zeroed()?,
-            ),
-            can_be_freed: true,
-        });
+    /// 迁移标志被其他cpu持有时，阻塞的进程仍然会被唤醒
+    #[test]
+    fn test_try_to_wake_up_migrating() {
+        let pcb = new_test_kthread("ttwu-migrating");
+        assert!(pcb.sched_info().state().is_blocked());
+        assert!(pcb.sched_info().start_migration());
+        assert_eq!(ProcessManager::try_to_wake_up(&pcb), Ok(true));
+        pcb.sched_info().cancel_migration();
     }
 
-    /// 根据已有的空间，构造一个内核栈结构体
-    ///
-    /// 仅仅用于BSP启动时，为idle进程构造内核栈。其他时候使用这个函数，很可能造成错误！
-    pub unsafe fn from_existed(base: VirtAddr) -> Result<Self, SystemError> {
-        if base.is_null() || base.check_aligned(Self::ALIGN) == false {
-            return Err(SystemError::EFAULT);
+    /// 阻塞一个进程后向其发送信号，进程应被唤醒，并且睡眠的结果为被信号打断
+    #[test]
+    fn test_signal_wakeup_interrupted() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "signal-wakeup".to_string(),
+        )
+        .unwrap();
+
+        // 不可中断的睡眠只会被标记，不会被信号唤醒
+        pcb.sched_info_mut().state = ProcessState::Blocked(false);
+        assert_eq!(ProcessManager::signal_wakeup(&pcb), Ok(false));
+        assert!(pcb.sched_info().state().is_blocked());
+
+        pcb.sched_info_mut().state = ProcessState::Blocked(true);
+        assert_eq!(ProcessManager::signal_wakeup(&pcb), Ok(true));
+        assert!(pcb.sched_info().state().is_runnable());
+
+        // 当前进程有待处理的信号时，可中断的睡眠不会生效
+        let current = ProcessManager::current_pcb();
+        ProcessManager::signal_wakeup(&current).unwrap();
+        assert_eq!(ProcessManager::sleep_outcome(), SleepOutcome::Interrupted);
+        let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        assert_eq!(
+            ProcessManager::mark_sleep(true),
+            Ok(SleepOutcome::Interrupted)
+        );
+        drop(irq_guard);
+        assert!(current.sched_info().state().is_runnable());
+        assert_eq!(
+            SleepOutcome::Interrupted.into_result(),
+            Err(SystemError::ERESTARTSYS)
+        );
+
+        ProcessManager::clear_pending_signal(&current);
+        assert_eq!(ProcessManager::sleep_outcome(), SleepOutcome::Event);
+    }
+
+    /// 进程仍被其他地方引用时将其释放，只会从进程表中移除一次，不会panic
+    #[test]
+    fn test_release_with_outstanding_refs() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "release-refs".to_string(),
+        )
+        .unwrap();
+        let pid = pcb.pid();
+        let outstanding: Vec<_> = (0..4).map(|_| pcb.clone()).collect();
+
+        pcb.sched_info_mut()
+            .try_set_state(ProcessState::Exited(0))
+            .unwrap();
+        let released = ProcessManager::release(pid).unwrap();
+        assert!(Arc::ptr_eq(&released, &pcb));
+        assert!(ProcessManager::find(pid).is_none());
+        // 重复释放不会产生任何影响
+        assert!(ProcessManager::release(pid).is_none());
+
+        drop(released);
+        drop(pcb);
+        for r in outstanding.iter() {
+            assert_eq!(r.pid(), pid);
+            assert!(r.sched_info().state().is_exited());
         }
+    }
 
-        return Ok(Self {
-            stack: Some(
-                AlignedBox::<[u8; KernelStack::SIZE], { KernelStack::ALIGN }>::new_unchecked(
-                    base.data() as *mut [u8; KernelStack::SIZE],
-                ),
-            ),
-            can_be_freed: false,
-        });
+    /// 持有自旋锁时调用阻塞原语，应当panic
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "scheduling while atomic")]
+    fn test_might_sleep_while_atomic() {
+        // 没有持有锁时可以睡眠
+        ProcessManager::might_sleep();
+
+        let lock = SpinLock::new(());
+        let _guard = lock.lock();
+        ProcessManager::might_sleep();
     }
 
-    /// 返回内核栈的起始虚拟地址(低地址)
-    pub fn start_address(&self) -> VirtAddr {
-        return VirtAddr::new(self.stack.as_ref().unwrap().as_ptr() as usize);
+    /// 没有配对的preempt_disable时调用preempt_enable，应当panic，而不是让计数下溢
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "preempt_count underflow")]
+    fn test_preempt_count_underflow() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "preempt-underflow".to_string(),
+        )
+        .unwrap();
+        pcb.preempt_disable();
+        pcb.preempt_enable();
+        assert_eq!(pcb.preempt_count(), 0);
+
+        pcb.preempt_enable();
     }
 
-    /// 返回内核栈的结束虚拟地址(高地址)(不包含该地址)
-    pub fn stack_max_address(&self) -> VirtAddr {
-        return VirtAddr::new(self.stack.as_ref().unwrap().as_ptr() as usize + Self::SIZE);
+    /// 创建一个进程后，last_pid应当等于它的pid
+    #[test]
+    fn test_last_pid() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "last-pid".to_string(),
+        )
+        .unwrap();
+        assert_eq!(ProcessManager::last_pid(), pcb.pid());
     }
 
-    pub unsafe fn set_pcb(&mut self, pcb: Arc<ProcessControlBlock>) -> Result<(), SystemError> {
-        // 将一个Arc<ProcessControlBlock>放到内核栈的最低地址处
-        let p: *const ProcessControlBlock = Arc::into_raw(pcb);
-        let stack_bottom_ptr = self.start_address().data() as *mut *const ProcessControlBlock;
+    /// 重复加入相同pid的进程时返回EEXIST，进程表中原有的进程不会被覆盖
+    #[test]
+    fn test_add_pcb_duplicate() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "add-pcb-dup".to_string(),
+        )
+        .unwrap();
+        let pid = pcb.pid();
+        assert!(Arc::ptr_eq(&ProcessManager::find(pid).unwrap(), &pcb));
 
-        // 如果内核栈的最低地址处已经有了一个pcb，那么，这里就不再设置,直接返回错误
-        if unlikely(unsafe { !(*stack_bottom_ptr).is_null() }) {
-            return Err(SystemError::EPERM);
-        }
-        // 将pcb的地址放到内核栈的最低地址处
-        unsafe {
-            *stack_bottom_ptr = p;
-        }
+        assert_eq!(
+            ProcessManager::add_pcb(pcb.clone()),
+            Err(SystemError::EEXIST)
+        );
+        assert!(Arc::ptr_eq(&ProcessManager::find(pid).unwrap(), &pcb));
 
-        return Ok(());
+        // 被释放之后，才能再次加入
+        let released = ProcessManager::release(pid).unwrap();
+        assert_eq!(ProcessManager::add_pcb(released), Ok(()));
+        assert!(Arc::ptr_eq(&ProcessManager::find(pid).unwrap(), &pcb));
     }
 
-    /// 返回指向当前内核栈pcb的Arc指针
-    #[allow(dead_code)]
-    pub unsafe fn pcb(&self) -> Option<Arc<ProcessControlBlock>> {
-        // 从内核栈的最低地址处取出pcb的地址
-        let p = self.stack.as_ref().unwrap().as_ptr() as *const ProcessControlBlock;
-        if unlikely(p.is_null()) {
-            return None;
-        }
+    /// 子进程继承父进程的nice值与cpu亲和性
+    #[test]
+    fn test_fork_inherits_nice_and_affinity() {
+        let parent = ProcessManager::current_pcb();
+        let mut mask = CpuMask::new();
+        mask.set(0);
+        parent.sched_info().set_nice(5).unwrap();
+        parent.sched_info().set_cpu_affinity(mask).unwrap();
+
+        let child = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "nice-child".to_string(),
+        )
+        .unwrap();
+        assert_eq!(child.sched_info().nice(), 5);
+        assert_eq!(child.sched_info().cpu_affinity(), mask);
+
+        parent.sched_info().set_nice(0).unwrap();
+        parent
+            .sched_info()
+            .set_cpu_affinity(CpuMask::full())
+            .unwrap();
+        assert_eq!(parent.sched_info().set_nice(20), Err(SystemError::EINVAL));
+    }
+
+    /// 被冻结的进程离开运行队列，不再被调度，也就不再累积虚拟运行时间；解冻后重新回到运行队列
+    #[test]
+    fn test_freeze_thaw() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "freeze-busy".to_string(),
+        )
+        .unwrap();
+        ProcessManager::add_pcb(pcb.clone()).ok();
+        ProcessManager::wakeup(&pcb).unwrap();
+        let vruntime = pcb.sched_info().virtual_runtime();
+
+        ProcessManager::freeze(pcb.pid()).unwrap();
+        assert!(pcb.sched_info().state().is_frozen());
+        // 已经不在运行队列中，并且不能被重新加入
+        assert!(!sched_dequeue(&pcb));
+        sched_enqueue(pcb.clone(), false);
+        assert!(!sched_dequeue(&pcb));
+        assert_eq!(pcb.sched_info().virtual_runtime(), vruntime);
+
+        // 被冻结的进程不能被唤醒
+        assert_eq!(ProcessManager::try_to_wake_up(&pcb), Ok(false));
+
+        ProcessManager::thaw(pcb.pid()).unwrap();
+        assert!(pcb.sched_info().state().is_runnable());
+        assert!(sched_dequeue(&pcb));
+        sched_enqueue(pcb.clone(), false);
+
+        // 带有NOFREEZE标志的进程不能被冻结
+        pcb.flags().insert(ProcessFlags::NOFREEZE);
+        assert_eq!(ProcessManager::freeze(pcb.pid()), Err(SystemError::EPERM));
+        pcb.flags().remove(ProcessFlags::NOFREEZE);
+    }
+
+    /// 可运行的cfs进程切换为SCHED_FIFO后进入实时队列，切换回来后回到cfs队列
+    #[test]
+    fn test_sched_setscheduler() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "setscheduler".to_string(),
+        )
+        .unwrap();
+        ProcessManager::add_pcb(pcb.clone()).ok();
+        ProcessManager::wakeup(&pcb).unwrap();
+
+        assert_eq!(
+            ProcessManager::sched_setscheduler(pcb.pid(), SchedPolicy::FIFO, 0),
+            Err(SystemError::EINVAL)
+        );
+        assert_eq!(
+            ProcessManager::sched_setscheduler(pcb.pid(), SchedPolicy::CFS, 10),
+            Err(SystemError::EINVAL)
+        );
+
+        ProcessManager::sched_setscheduler(pcb.pid(), SchedPolicy::FIFO, 10).unwrap();
+        assert_eq!(
+            ProcessManager::sched_getscheduler(pcb.pid()),
+            Ok(SchedPolicy::FIFO)
+        );
+        assert_eq!(pcb.sched_info().priority().data(), 89);
+        // 进程已经在实时队列中
+        assert!(sched_dequeue(&pcb));
+        sched_enqueue(pcb.clone(), false);
+
+        ProcessManager::sched_setscheduler(pcb.pid(), SchedPolicy::CFS, 0).unwrap();
+        assert_eq!(
+            ProcessManager::sched_getscheduler(pcb.pid()),
+            Ok(SchedPolicy::CFS)
+        );
+        assert_eq!(pcb.sched_info().priority(), SchedPriority::DEFAULT);
+        assert!(sched_dequeue(&pcb));
+        sched_enqueue(pcb.clone(), false);
 
-        // 为了防止内核栈的pcb指针被释放，这里需要将其包装一下，使得Arc的drop不会被调用
-        let arc_wrapper: ManuallyDrop<Arc<ProcessControlBlock>> =
-            ManuallyDrop::new(Arc::from_raw(p));
+        // 没有CAP_SYS_NICE能力时，不能设置实时调度策略
+        let current = ProcessManager::current_pcb();
+        let cred = current.cred();
+        current.set_cred(Credentials::user(1000, 1000));
+        assert_eq!(
+            ProcessManager::sched_setscheduler(pcb.pid(), SchedPolicy::RR, 1),
+            Err(SystemError::EPERM)
+        );
+        current.set_cred(cred);
+    }
 
-        let new_arc: Arc<ProcessControlBlock> = Arc::clone(&arc_wrapper);
-        return Some(new_arc);
+    /// 通过sched_getparam获取进程在用户态的优先级
+    fn rt_param(pcb: &Arc<ProcessControlBlock>) -> i32 {
+        return ProcessManager::sched_getparam(pcb.pid())
+            .unwrap()
+            .sched_priority;
     }
-}
 
-impl Drop for KernelStack {
-    fn drop(&mut self) {
-        if !self.stack.is_none() {
-            let pcb_ptr: Arc<ProcessControlBlock> = unsafe {
-                Arc::from_raw(self.stack.as_ref().unwrap().as_ptr() as *const ProcessControlBlock)
-            };
-            drop(pcb_ptr);
+    /// sched_setparam只接受当前调度策略允许的优先级，sched_getparam返回用户态的优先级
+    #[test]
+    fn test_sched_setparam_range() {
+        let pcb = new_test_kthread("setparam-range");
+        let param = |sched_priority| SchedParam { sched_priority };
+
+        // cfs进程的优先级只能为0
+        assert_eq!(rt_param(&pcb), 0);
+        assert_eq!(ProcessManager::sched_setparam(pcb.pid(), param(0)), Ok(()));
+        assert_eq!(
+            ProcessManager::sched_setparam(pcb.pid(), param(1)),
+            Err(SystemError::EINVAL)
+        );
+
+        ProcessManager::sched_setscheduler(pcb.pid(), SchedPolicy::RR, 10).unwrap();
+        assert_eq!(rt_param(&pcb), 10);
+        for invalid in [0, SchedPolicy::RT_PARAM_MAX + 1, -1] {
+            assert_eq!(
+                ProcessManager::sched_setparam(pcb.pid(), param(invalid)),
+                Err(SystemError::EINVAL)
+            );
         }
-        // 如果该内核栈不可以被释放，那么，这里就forget，不调用AlignedBox的drop函数
-        if !self.can_be_freed {
-            let bx = self.stack.take();
-            core::mem::forget(bx);
+        for valid in [SchedPolicy::RT_PARAM_MIN, 42, SchedPolicy::RT_PARAM_MAX] {
+            assert_eq!(
+                ProcessManager::sched_setparam(pcb.pid(), param(valid)),
+                Ok(())
+            );
+            assert_eq!(rt_param(&pcb), valid);
+            // 调度策略保持不变
+            assert_eq!(
+                ProcessManager::sched_getscheduler(pcb.pid()),
+                Ok(SchedPolicy::RR)
+            );
         }
+        assert_eq!(
+            ProcessManager::sched_getparam(Pid::new(usize::MAX)).map(|p| p.sched_priority),
+            Err(SystemError::ESRCH)
+        );
     }
-}
 
-pub fn process_init() {
-    ProcessManager::init();
-}
+    /// 没有CAP_SYS_NICE能力时，不能修改实时进程的优先级，但可以为cfs进程设置0
+    #[test]
+    fn test_sched_setparam_unprivileged() {
+        let rt = new_test_kthread("setparam-rt");
+        let normal = new_test_kthread("setparam-normal");
+        ProcessManager::sched_setscheduler(rt.pid(), SchedPolicy::FIFO, 10).unwrap();
 
-#[derive(Debug)]
-pub struct SchedEntity {
-    ///如果是进程它将对应一个pcb
-    pcb: Option<Arc<ProcessControlBlock>>,
-    /// 进程的调度优先级
-    priority: SchedPriority,
-    //parent:SchedEntity,
-    /// 当前进程的虚拟运行时间
-    virtual_runtime: AtomicIsize,
-    cfs_rq: SpinLock<Option<CFSQueue>>,
-    ///是否判断是否有进程组
-    my_q: SpinLock<Option<CFSQueue>>,
-}
-///! CFSQueue 需要处理
-///! parent 当为task se 指向进程组
-///!        当为group se 指向父进程组
+        let current = ProcessManager::current_pcb();
+        let cred = current.cred();
+        current.set_cred(Credentials::user(1000, 1000));
+        assert_eq!(
+            ProcessManager::sched_setparam(rt.pid(), SchedParam { sched_priority: 20 }),
+            Err(SystemError::EPERM)
+        );
+        assert_eq!(
+            ProcessManager::sched_setparam(normal.pid(), SchedParam { sched_priority: 0 }),
+            Ok(())
+        );
+        current.set_cred(cred);
 
-impl SchedEntity {
-    pub fn new() -> RwLock<Self> {
-        return RwLock::new(Self {
-            pcb: None,
-            virtual_runtime: AtomicIsize::new(0),
-            priority: SchedPriority::new(100).unwrap(),
-            my_q: None,
-            cfs_rq: None,
-        });
-    }
-    pub fn virtual_runtime(&self) -> isize {
-        return self.virtual_runtime.load(Ordering::SeqCst);
+        assert_eq!(rt_param(&rt), 10);
+        assert_eq!(
+            ProcessManager::sched_setparam(rt.pid(), SchedParam { sched_priority: 20 }),
+            Ok(())
+        );
+        assert_eq!(rt_param(&rt), 20);
     }
 
-    pub fn set_virtual_runtime(&self, virtual_runtime: isize) {
-        self.virtual_runtime
-            .store(virtual_runtime, Ordering::SeqCst);
+    /// 将时间片让给指定的进程后，它被移到当前cpu的队列中，并且是下一个被调度的进程
+    #[test]
+    fn test_yield_to() {
+        let target = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "yield-target".to_string(),
+        )
+        .unwrap();
+        ProcessManager::add_pcb(target.clone()).ok();
+        ProcessManager::wakeup(&target).unwrap();
+
+        let current = ProcessManager::current_pcb();
+        let this_cpu = smp_get_processor_id();
+        assert!(!ProcessManager::donate_slice(&current, current.pid()));
+        assert!(ProcessManager::donate_slice(&current, target.pid()));
+        assert_eq!(target.sched_info().on_cpu(), Some(this_cpu));
+        assert!(target.sched_info().virtual_runtime() < current.sched_info().virtual_runtime());
+
+        let next = __get_cfs_scheduler().cpu_queue(this_cpu as usize).dequeue();
+        assert!(Arc::ptr_eq(&next, &target));
+        sched_enqueue(target.clone(), false);
+
+        // 不是可运行的进程时，不能将时间片让给它
+        ProcessManager::freeze(target.pid()).unwrap();
+        assert!(!ProcessManager::donate_slice(&current, target.pid()));
+        ProcessManager::thaw(target.pid()).unwrap();
     }
 
-    fn set_cfs_rq(&self, cfs_rq: Option<CFSQueue>) {
-        self.cfs_rq.lock().replace(cfs_rq);
+    /// 同一个线程组中的两个线程，gettid各不相同，getpid都是线程组的id
+    #[test]
+    fn test_gettid_in_thread_group() {
+        let reported: Arc<SpinLock<Vec<(Pid, Pid)>>> = Arc::new(SpinLock::new(Vec::new()));
+        let threads: Vec<Arc<ProcessControlBlock>> = (0..2)
+            .map(|i| {
+                let r = reported.clone();
+                KernelThreadMechanism::create(
+                    KernelThreadClosure::EmptyClosure((
+                        Box::new(move || {
+                            r.lock()
+                                .push((ProcessManager::gettid(), ProcessManager::getpid()));
+                            0
+                        }),
+                        (),
+                    )),
+                    format!("gettid-{}", i),
+                )
+                .unwrap()
+            })
+            .collect();
+        let tgid = threads[0].pid();
+        threads[1].set_tgid(tgid);
+        for pcb in threads.iter() {
+            ProcessManager::wakeup(pcb).unwrap();
+        }
+        while reported.lock().len() < 2 {
+            spin_loop();
+        }
+
+        let mut reported = reported.lock().clone();
+        reported.sort();
+        let mut tids: Vec<Pid> = threads.iter().map(|pcb| pcb.pid()).collect();
+        tids.sort();
+        assert_ne!(tids[0], tids[1]);
+        assert_eq!(reported, [(tids[0], tgid), (tids[1], tgid)].to_vec());
     }
 
-    fn cfs_rq(&self) -> SpinLockGuard<Option<CFSQueue>> {
-        return self.cfs_rq.lock();
+    /// 发送给线程组中某个线程的信号，只出现在该线程的待处理信号集中
+    #[test]
+    fn test_tgkill() {
+        let create = |name: &str| {
+            let pcb = KernelThreadMechanism::create(
+                KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                name.to_string(),
+            )
+            .unwrap();
+            ProcessManager::add_pcb(pcb.clone()).ok();
+            pcb
+        };
+        let leader = create("tgkill-leader");
+        let sibling = create("tgkill-sibling");
+        sibling.set_tgid(leader.pid());
+        let tgid = leader.pid();
+
+        ProcessManager::tgkill(tgid, sibling.pid(), Signal::SIGUSR1).unwrap();
+        assert!(sibling
+            .sig_state()
+            .pending()
+            .contains(Signal::SIGUSR1.mask()));
+        assert!(leader.sig_state().pending().is_empty());
+
+        // 被阻塞的信号保持待处理，但不会唤醒线程
+        leader.sig_state().set_blocked(Signal::SIGUSR2.mask());
+        leader.flags().remove(ProcessFlags::HAS_PENDING_SIGNAL);
+        ProcessManager::tkill(leader.pid(), Signal::SIGUSR2).unwrap();
+        assert!(leader
+            .sig_state()
+            .pending()
+            .contains(Signal::SIGUSR2.mask()));
+        assert!(!leader.sig_state().has_deliverable());
+        assert!(!leader.flags().contains(ProcessFlags::HAS_PENDING_SIGNAL));
+        assert_eq!(sibling.sig_state().dequeue(), Some(Signal::SIGUSR1));
+        assert!(sibling.sig_state().pending().is_empty());
+
+        // 线程不属于该线程组
+        assert_eq!(
+            ProcessManager::tgkill(sibling.pid(), leader.pid(), Signal::SIGUSR1),
+            Err(SystemError::ESRCH)
+        );
+        assert!(!leader
+            .sig_state()
+            .pending()
+            .contains(Signal::SIGUSR1.mask()));
     }
 
-    fn set_my_q(&self, my_q: Option<CFSQueue>) {
-        self.my_q.lock().replace(my_q);
+    /// 中间进程退出后，守护进程被init进程收养，它是新会话与新进程组的组长，工作目录为根目录
+    #[test]
+    fn test_daemonize() {
+        // 在检查完成之前，守护进程不能退出
+        let done = Arc::new(AtomicBool::new(false));
+        let daemon_done = done.clone();
+        let pid = ProcessManager::daemonize(
+            KernelThreadClosure::EmptyClosure((
+                Box::new(move || {
+                    while !daemon_done.load(Ordering::SeqCst) {
+                        spin_loop();
+                    }
+                    return 0;
+                }),
+                (),
+            )),
+            "daemon".to_string(),
+        )
+        .unwrap();
+        let pcb = ProcessManager::find(pid).unwrap();
+        let basic = pcb.basic();
+        assert_eq!(basic.sid(), pid);
+        assert_eq!(basic.pgid(), pid);
+        assert_eq!(basic.ppid(), Pid::new(1));
+        assert_eq!(basic.cwd(), "/");
+        drop(basic);
+
+        let init_pcb = ProcessManager::find(Pid::new(1)).unwrap();
+        assert!(init_pcb.children.read().contains_key(&pid));
+
+        // 已经是进程组组长的进程不能再创建新的会话
+        assert_eq!(ProcessManager::setsid(&pcb), Err(SystemError::EPERM));
+        done.store(true, Ordering::SeqCst);
     }
 
-    pub fn group_cfs_rq(&self) -> SpinLockGuard<Option<CFSQueue>> {
-        return self.my_q.lock();
+    /// 映射与取消映射页面时，进程的常驻内存页数随之变化
+    #[test]
+    fn test_rss_tracks_mappings() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "rss".to_string(),
+        )
+        .unwrap();
+        assert_eq!(pcb.rss(), 0);
+
+        // 用户栈同样计入常驻内存
+        let vm = AddressSpace::new(true).unwrap();
+        unsafe { pcb.basic_mut().set_user_vm(Some(vm.clone())) };
+        let base = pcb.rss();
+        assert!(base > 0);
+        let mut guard = vm.write();
+        let start = guard
+            .map_anonymous(
+                VirtAddr::new(0),
+                4 * MMArch::PAGE_SIZE,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS,
+                false,
+            )
+            .unwrap();
+        drop(guard);
+        assert_eq!(pcb.rss(), base + 4);
+
+        // 从中间取消映射一页，VMA被切分，但剩余的页仍然计入
+        vm.write()
+            .munmap(start.next(), PageFrameCount::new(1))
+            .unwrap();
+        assert_eq!(pcb.rss(), base + 3);
+
+        // fork得到的地址空间拷贝了所有的页，各自计数
+        let child_vm = vm.write().try_clone().unwrap();
+        assert_eq!(child_vm.read().rss(), base + 3);
+        vm.write().munmap(start, PageFrameCount::new(1)).unwrap();
+        assert_eq!(pcb.rss(), base + 2);
+        assert_eq!(child_vm.read().rss(), base + 3);
+
+        unsafe { pcb.basic_mut().set_user_vm(None) };
+        assert_eq!(pcb.rss(), 0);
     }
 
-    pub fn priority(&self) -> SchedPriority {
-        return self.priority;
+    /// 用户栈只能在RLIMIT_STACK的软限制以内扩展，超过时缺页得不到处理，进程收到SIGSEGV
+    #[test]
+    fn test_stack_rlimit() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "stack-rlimit".to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            pcb.rlimit(RLimitID::Stack).cur,
+            UserStack::DEFAULT_USER_STACK_SIZE as u64
+        );
+
+        let vm = AddressSpace::new(true).unwrap();
+        unsafe { pcb.basic_mut().set_user_vm(Some(vm.clone())) };
+        let (size, lowest) = {
+            let mut guard = vm.write();
+            let stack = guard.user_stack_mut().unwrap();
+            (stack.stack_size(), stack.lowest_addr())
+        };
+
+        // 栈已经达到软限制，不能再扩展
+        pcb.set_rlimit(
+            RLimitID::Stack,
+            RLimit::new(size as u64, RLIM_INFINITY),
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            ProcessManager::handle_user_stack_fault(&pcb, lowest - 1),
+            Err(SystemError::ENOMEM)
+        );
+        assert!(pcb.sig_state().pending().contains(Signal::SIGSEGV.mask()));
+        assert_eq!(vm.write().user_stack_mut().unwrap().stack_size(), size);
+
+        // 提高软限制后，可以扩展一页
+        pcb.sig_state().dequeue();
+        pcb.set_rlimit(
+            RLimitID::Stack,
+            RLimit::new((size + MMArch::PAGE_SIZE) as u64, RLIM_INFINITY),
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            ProcessManager::handle_user_stack_fault(&pcb, lowest - 1),
+            Ok(())
+        );
+        assert!(pcb.sig_state().pending().is_empty());
+        assert_eq!(
+            vm.write().user_stack_mut().unwrap().stack_size(),
+            size + MMArch::PAGE_SIZE
+        );
+        assert_eq!(
+            ProcessManager::handle_user_stack_fault(&pcb, lowest - MMArch::PAGE_SIZE - 1),
+            Err(SystemError::ENOMEM)
+        );
+
+        // 即使没有限制，远离用户栈的缺页也不会扩展用户栈
+        pcb.sig_state().dequeue();
+        pcb.set_rlimit(
+            RLimitID::Stack,
+            RLimit::new(RLIM_INFINITY, RLIM_INFINITY),
+            false,
+        )
+        .unwrap();
+        let lowest = vm.write().user_stack_mut().unwrap().lowest_addr();
+        assert_eq!(
+            ProcessManager::handle_user_stack_fault(
+                &pcb,
+                lowest - UserStack::MAX_GROW_GAP - MMArch::PAGE_SIZE,
+            ),
+            Err(SystemError::EFAULT)
+        );
+        assert!(pcb.sig_state().pending().contains(Signal::SIGSEGV.mask()));
+        assert_eq!(
+            vm.write().user_stack_mut().unwrap().stack_size(),
+            size + MMArch::PAGE_SIZE
+        );
+
+        unsafe { pcb.basic_mut().set_user_vm(None) };
     }
 
-    pub fn set_pcb(&mut self, pcb: Option<Arc<ProcessControlBlock>>) {
-        self.pcb = pcb;
+    /// 映射的总大小不能超过RLIMIT_AS的软限制
+    #[test]
+    fn test_as_rlimit() {
+        let current = ProcessManager::current_pcb();
+        let old = current.rlimit(RLimitID::As);
+
+        let vm = AddressSpace::new(true).unwrap();
+        let base = vm.read().total_vm();
+        let limit = (base + 4) * MMArch::PAGE_SIZE;
+        current
+            .set_rlimit(RLimitID::As, RLimit::new(limit as u64, old.max), false)
+            .unwrap();
+
+        let map = |pages: usize| {
+            vm.write().map_anonymous(
+                VirtAddr::new(0),
+                pages * MMArch::PAGE_SIZE,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS,
+                false,
+            )
+        };
+        let start = map(4).unwrap();
+        assert_eq!(vm.read().total_vm(), base + 4);
+        assert_eq!(map(1), Err(SystemError::ENOMEM));
+        assert_eq!(vm.read().total_vm(), base + 4);
+
+        // 取消映射之后，可以重新映射
+        vm.write().munmap(start, PageFrameCount::new(1)).unwrap();
+        assert_eq!(vm.read().total_vm(), base + 3);
+        assert!(map(1).is_ok());
+
+        current.set_rlimit(RLimitID::As, old, false).unwrap();
     }
 
-    pub fn pcb(&self) -> Option<Arc<ProcessControlBlock>> {
-        return self.pcb.as_ref();
+    /// cpu时间超出软限制时每秒发送一次SIGXCPU，达到硬限制时发送SIGKILL
+    #[test]
+    fn test_cpu_rlimit() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "cpu-rlimit".to_string(),
+        )
+        .unwrap();
+        pcb.set_rlimit(RLimitID::Cpu, RLimit::new(1, 3), false)
+            .unwrap();
+        assert_eq!(
+            pcb.set_rlimit(RLimitID::Cpu, RLimit::new(1, 4), false),
+            Err(SystemError::EPERM)
+        );
+
+        pcb.sched_info().account_time(false, 900_000);
+        assert_eq!(ProcessManager::check_cpu_rlimit(&pcb), None);
+
+        pcb.sched_info().account_time(true, 200_000);
+        assert_eq!(
+            ProcessManager::check_cpu_rlimit(&pcb),
+            Some(Signal::SIGXCPU)
+        );
+        assert!(pcb.sig_state().pending().contains(Signal::SIGXCPU.mask()));
+        assert_eq!(pcb.rlimit(RLimitID::Cpu), RLimit::new(2, 3));
+
+        // 同一秒之内不会重复发送
+        assert_eq!(pcb.sig_state().dequeue(), Some(Signal::SIGXCPU));
+        assert_eq!(ProcessManager::check_cpu_rlimit(&pcb), None);
+
+        pcb.sched_info().account_time(true, 1_000_000);
+        assert_eq!(
+            ProcessManager::check_cpu_rlimit(&pcb),
+            Some(Signal::SIGXCPU)
+        );
+
+        pcb.sched_info().account_time(true, 1_000_000);
+        assert_eq!(
+            ProcessManager::check_cpu_rlimit(&pcb),
+            Some(Signal::SIGKILL)
+        );
+        assert!(pcb.sig_state().pending().contains(Signal::SIGKILL.mask()));
+        assert!(pcb.flags().contains(ProcessFlags::WAKEKILL));
     }
-}
-#[derive(Debug)]
-struct TaskGroup {
-    se: Vec<Arc<SchedEntity>>, // 调度实体(每个CPU分配一个)
-    cfs: SchedulerCFS,         // 完全公平调度运行队列(每个CPU分配一个)
-    /// 父进程指针
-    parent_tg: RwLock<Weak<TaskGroup>>,
-    /// 子进程组链表
-    children: RwLock<HashMap<Pid, Arc<TaskGroup>>>,
-}
 
-impl TaskGroup {
-    pub fn new(ptg: Arc<TaskGroup>) -> Arc<Self> {
-        return Arc::new(Self {
-            cfs: SchedulerCFS::new(),
-            se: for i in 0..MAX_CPU_NUM {
-                Arc::new(SchedEntity::new())
-            },
-            parent_tg: RwLock::new(ptg),
-            children: RwLock::new(HashMap::new()),
-        });
+    /// 创建一个子进程，并将它的父进程修改为`parent`
+    fn tree_child(parent: &Arc<ProcessControlBlock>, name: &str) -> Arc<ProcessControlBlock> {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            name.to_string(),
+        )
+        .unwrap();
+        reparent(&pcb, parent);
+        return pcb;
     }
-    /// ! 在创建新的进程组时，应该将se的cfsrq指向父进程组的cfsrq，cfsrq[cpu]
-    /// !   同时my_q应指向本进程组创建的cfsrq
-    /// !根进程组的创建时机  struct task_group root_task_group;
-    /// ! 创建好关系之后 ，group se 和时task se 加到对应的cfs_rq
-    pub fn init_group_se(&self, ptg: Arc<TaskGroup>, ntg: Arc<TaskGroup>) {
-        let cfs_queue = ptg.cfs.get_cpu_queue();
-        let ncfs_queue = ntg.cfs.get_cpu_queue();
-        for cpu in 0..MAX_CPU_NUM {
-            if let Some(per_se) = self.se.get(cpu) {
-                per_se.set_cfs_rq(cfs_queue[cpu]);
-                per_se.set_my_q(ncfs_queue[cpu]);
-                ntg.cfs.enqueue_group_se(per_se, cpu);
-            }
+
+    fn reparent(pcb: &Arc<ProcessControlBlock>, parent: &Arc<ProcessControlBlock>) {
+        let mut parent_pcb = pcb.parent_pcb.write();
+        if let Some(old_parent) = parent_pcb.upgrade() {
+            old_parent.children.write().remove(&pcb.pid());
         }
+        parent.children.write().insert(pcb.pid(), pcb.clone());
+        *parent_pcb = Arc::downgrade(parent);
+        drop(parent_pcb);
+        pcb.basic_mut().set_ppid(parent.pid());
     }
 
-    pub fn add_tg(pgid: Pid, tg: Arc<TaskGroup>) {
-        ALL_PROCESS_GROUP
-            .lock()
-            .as_mut()
-            .unwrap()
-            .insert(pgid, tg.clone());
-    }
+    /// 进程树中每个进程只出现一次，父进程出现在子进程之前，并且报告的父进程与子进程表一致
+    #[test]
+    fn test_process_tree() {
+        let root = tree_child(&ProcessManager::current_pcb(), "tree-root");
+        let left = tree_child(&root, "tree-left");
+        let right = tree_child(&root, "tree-right");
+        let leaf = tree_child(&left, "tree-leaf");
+
+        let position = |tree: &Vec<(Pid, Pid, String)>, pid: Pid| {
+            let found: Vec<usize> = (0..tree.len()).filter(|&i| tree[i].0 == pid).collect();
+            assert_eq!(found.len(), 1);
+            return found[0];
+        };
 
-    pub fn cfs(&self) -> SchedulerCFS {
-        return self.cfs;
+        let tree = ProcessManager::process_tree();
+        let expected = [
+            (&root, ProcessManager::current_pcb().pid()),
+            (&left, root.pid()),
+            (&right, root.pid()),
+            (&leaf, left.pid()),
+        ];
+        for (pcb, ppid) in expected.iter() {
+            let entry = &tree[position(&tree, pcb.pid())];
+            assert_eq!(entry.1, *ppid);
+            assert_eq!(entry.2, pcb.basic().name());
+        }
+        assert!(position(&tree, root.pid()) < position(&tree, left.pid()));
+        assert!(position(&tree, left.pid()) < position(&tree, leaf.pid()));
+        assert!(position(&tree, leaf.pid()) < position(&tree, right.pid()));
+
+        // 损坏的父进程指针使得父子关系成环，遍历仍然能够结束，每个进程仍然只出现一次
+        reparent(&root, &leaf);
+        let tree = ProcessManager::process_tree();
+        for pcb in [&root, &left, &right, &leaf] {
+            position(&tree, pcb.pid());
+        }
     }
-}
-
-pub static PROCESS_GROUP_MANAGER: ProcessGroupManager = ProcessGroupManager::new();
 
-pub struct ProcessGroupManager(pub SpinLock<BTreeMap<Pid, Vec<Pid>>>);
+    /// 进程退出并被回收之后，pcb与它的调度实体都被析构，不会因为循环引用而一直存活
+    #[test]
+    fn test_exited_pcb_dropped() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "drop-pcb".to_string(),
+        )
+        .unwrap();
+        let pid = pcb.pid();
+        let weak_pcb = Arc::downgrade(&pcb);
+        let weak_se = Arc::downgrade(&pcb.sched_info().se().unwrap());
+        // 内核栈只持有pcb的弱引用
+        assert!(Arc::ptr_eq(
+            &unsafe { pcb.kernel_stack().pcb() }.unwrap(),
+            &pcb
+        ));
+
+        pcb.sched_info_mut()
+            .try_set_state(ProcessState::Exited(0))
+            .unwrap();
+        pcb.report_exit(None);
+        assert!(ProcessManager::find(pid).is_none());
+        drop(pcb);
 
-impl ProcessGroupManager {
-    pub const fn new() -> Self {
-        Self(SpinLock::new(BTreeMap::new()))
+        assert_eq!(weak_pcb.strong_count(), 0);
+        assert_eq!(weak_se.strong_count(), 0);
     }
 
-    /// 根据pgid获取进程组的tg
-    ///
-    /// ## 参数
-    /// - `pgid` : 进程的pgid
-    ///
-    /// ## 返回值
-    /// 如果找到了对应的进程组，那么返回该进程组，否则返回None
-    pub fn find(pgid: Pid) -> Option<Arc<TaskGroup>> {
-        return ALL_PROCESS_GROUP.lock().as_ref()?.get(&pgid).cloned();
+    /// 进程退出时立即关闭独占的文件，通过CLONE_FILES与其他进程共享的文件描述符表则保持不变
+    #[test]
+    fn test_close_all_fds() {
+        let open_pipe = |pcb: &Arc<ProcessControlBlock>| {
+            let pipe = LockedPipeInode::new(FileMode::O_RDONLY);
+            let fd = pcb
+                .fd_table()
+                .write()
+                .alloc_fd(File::new(pipe, FileMode::O_RDONLY).unwrap(), None)
+                .unwrap();
+            return Arc::downgrade(&pcb.fd_table().read().get_file_by_fd(fd).unwrap());
+        };
+
+        // 共享文件描述符表的进程仍然在使用文件，不能关闭
+        let shared = new_test_kthread("close-fds-shared");
+        let file = open_pipe(&shared);
+        shared.mark_shared(CloneFlags::CLONE_FILES);
+        shared.close_all_fds();
+        assert_eq!(file.strong_count(), 1);
+
+        // 临时持有文件描述符表的引用（例如解析/proc/<pid>/fd下的链接）不会使它被视为共享
+        let pcb = new_test_kthread("close-fds");
+        let file = open_pipe(&pcb);
+        let fd_table = pcb.fd_table();
+        pcb.close_all_fds();
+        assert_eq!(file.strong_count(), 0);
+        assert!(fd_table.read().opened_fds().is_empty());
     }
 
-    pub fn add_process(&self, pgid: Pid, pid: Pid) {
-        let mut inner = self.0.lock();
-        let vec = inner.get(&pgid);
-        let mut vec = vec.cloned().unwrap();
-        vec.push(pid);
-        inner.insert(pgid, vec);
+    /// 进程退出时立即归还独占的地址空间中的页，通过CLONE_VM与其他进程共享的地址空间则保持不变
+    #[test]
+    fn test_drop_user_vm() {
+        let pcb = new_test_kthread("drop-vm");
+        unsafe { pcb.basic_mut().set_user_vm(Some(AddressSpace::new(true).unwrap())) };
+        let vm = pcb.basic().user_vm().unwrap();
+        let start = vm
+            .write()
+            .map_anonymous(
+                VirtAddr::new(0),
+                4 * MMArch::PAGE_SIZE,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS,
+                false,
+            )
+            .unwrap();
+        let rss = vm.read().rss();
+
+        pcb.mark_shared(CloneFlags::CLONE_VM);
+        pcb.drop_user_vm();
+        assert_eq!(vm.read().rss(), rss);
+
+        // execve之后地址空间不再共享，测试中临时持有的引用不会使它被视为共享
+        pcb.clear_shared(CloneFlags::CLONE_VM);
+        pcb.drop_user_vm();
+        assert_eq!(vm.read().rss(), 0);
+        assert_eq!(vm.read().total_vm(), 0);
+        assert!(vm
+            .read()
+            .user_mapper
+            .utable
+            .translate(start.virt_address())
+            .is_none());
+        // 重复释放是安全的
+        pcb.drop_user_vm();
     }
 
-    pub fn add_group(&self, pgid: Pid) {
-        let mut inner = self.0.lock();
-        let mut vec: Vec<Pid> = Vec::new();
-        if pgid != Pid(0) {
-            //? 不清楚
-            vec.push(pgid);
+    /// 缓存的当前进程与从内核栈中取出的当前进程一致，并比较两种方式的开销
+    #[test]
+    fn test_current_pcb_cache() {
+        const ROUNDS: u64 = 10000;
+        let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        assert!(Arc::ptr_eq(
+            &ProcessManager::current_pcb(),
+            &ProcessControlBlock::arch_current_pcb()
+        ));
+        drop(irq_guard);
+
+        let start = sched_clock();
+        for _ in 0..ROUNDS {
+            drop(core::hint::black_box(ProcessControlBlock::arch_current_pcb()));
         }
-        inner.insert(pgid, vec);
+        let stack_ns = sched_clock() - start;
+        let start = sched_clock();
+        for _ in 0..ROUNDS {
+            drop(core::hint::black_box(ProcessManager::current_pcb()));
+        }
+        let cached_ns = sched_clock() - start;
+        kinfo!(
+            "current_pcb: {} ns/call from kernel stack, {} ns/call from per-cpu cache",
+            stack_ns / ROUNDS,
+            cached_ns / ROUNDS
+        );
     }
 
-    pub fn get_group_by_pgid(&self, pgid: Pid) -> Vec<Pid> {
-        self.0.lock().get(&pgid).cloned().unwrap()
-    }
+    /// 停止整个进程组后再让它继续运行，所有成员都重新进入运行队列，并且不会残留停止信号
+    #[test]
+    fn test_stop_cont_group() {
+        let a = new_test_kthread("stop-cont-a");
+        let b = new_test_kthread("stop-cont-b");
+        let pgid = a.pid();
+        for pcb in [&a, &b] {
+            pcb.basic_mut().set_pgid(pgid);
+            ProcessManager::add_pcb(pcb.clone()).ok();
+            ProcessManager::wakeup(pcb).unwrap();
+        }
 
-    ///return ornewtg
-    pub fn set_pgid_by_pid(&self, pid: Pid, new_pgid: Pid, old_pgid: Pid) -> bool {
-        let mut inner = self.0.lock();
-        let old_group_vec = inner.get_mut(&old_pgid).unwrap();
-        old_group_vec.retain(|&x| x != pid);
-        let new_group_vec = inner.get_mut(&new_pgid);
-        if let Some(new_group_vec) = new_group_vec {
-            new_group_vec.push(pid);
-            return false;
-        } else {
-            let new_group: Vec<Pid> = vec![new_pgid];
-            // let mut new_group = Vec::new();
-            // new_group.push(new_pgid);
-            inner.insert(new_pgid, new_group);
-            return true;
+        assert_eq!(
+            ProcessManager::send_signal_to_group(pgid, Signal::SIGSTOP),
+            Ok(2)
+        );
+        for pcb in [&a, &b] {
+            assert!(pcb.sched_info().state().is_stopped());
+            assert!(!sched_dequeue(pcb));
+            // 停止信号已经被处理，不再处于待处理状态
+            assert!(!pcb.sig_state().pending().contains(Signal::SIGSTOP.mask()));
+        }
+
+        // 已经被停止的进程收到的停止信号保持待处理
+        ProcessManager::send_signal(&b, Signal::SIGTSTP).unwrap();
+        assert!(b.sig_state().pending().contains(Signal::SIGTSTP.mask()));
+
+        assert_eq!(
+            ProcessManager::send_signal_to_group(pgid, Signal::SIGCONT),
+            Ok(2)
+        );
+        for pcb in [&a, &b] {
+            assert!(pcb.sched_info().state().is_runnable());
+            assert!(sched_dequeue(pcb));
+            sched_enqueue(pcb.clone(), false);
+            let pending = pcb.sig_state().pending();
+            assert!(!pending.contains(Signal::SIGTSTP.mask()));
+            assert!(pending.contains(Signal::SIGCONT.mask()));
         }
+
+        // 停止信号清除待处理的SIGCONT
+        ProcessManager::send_signal(&a, Signal::SIGSTOP).unwrap();
+        assert!(!a.sig_state().pending().contains(Signal::SIGCONT.mask()));
+        ProcessManager::send_signal(&a, Signal::SIGCONT).unwrap();
+        assert!(a.sched_info().state().is_runnable());
+    }
+
+    /// 向其他用户的进程发送信号需要CAP_KILL能力，SIGCONT在同一会话中总是允许发送
+    #[test]
+    fn test_send_signal_permission() {
+        let same_user = new_test_kthread("kill-same-user");
+        same_user.set_cred(Credentials::user(1000, 1000));
+        let other_user = new_test_kthread("kill-other-user");
+        other_user.set_cred(Credentials::user(2000, 2000));
+        let current = ProcessManager::current_pcb();
+        let cred = current.cred();
+        current.set_cred(Credentials::user(1000, 1000));
+
+        assert_eq!(
+            ProcessManager::send_signal(&same_user, Signal::SIGUSR1),
+            Ok(())
+        );
+        assert!(same_user
+            .sig_state()
+            .pending()
+            .contains(Signal::SIGUSR1.mask()));
+        assert_eq!(
+            ProcessManager::send_signal(&other_user, Signal::SIGUSR1),
+            Err(SystemError::EPERM)
+        );
+        // 权限检查在设置待处理信号之前进行
+        assert!(!other_user
+            .sig_state()
+            .pending()
+            .contains(Signal::SIGUSR1.mask()));
+
+        // 接收者的保存的用户id与发送者相同时，同样允许发送
+        let mut saved = Credentials::user(2000, 2000);
+        saved.suid = 1000;
+        other_user.set_cred(saved);
+        assert_eq!(
+            ProcessManager::send_signal(&other_user, Signal::SIGUSR2),
+            Ok(())
+        );
+        other_user.set_cred(Credentials::user(2000, 2000));
+
+        // 同一会话中的SIGCONT不受用户id的限制
+        other_user.basic_mut().set_sid(current.basic().sid());
+        assert_eq!(
+            ProcessManager::send_signal(&other_user, Signal::SIGCONT),
+            Ok(())
+        );
+
+        current.set_cred(cred);
+        // 拥有CAP_KILL能力时，可以向任何进程发送信号
+        assert_eq!(
+            ProcessManager::send_signal(&other_user, Signal::SIGUSR1),
+            Ok(())
+        );
+        assert!(other_user
+            .sig_state()
+            .pending()
+            .contains(Signal::SIGUSR1.mask()));
     }
 }
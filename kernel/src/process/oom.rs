@@ -0,0 +1,175 @@
+use alloc::vec::Vec;
+
+use crate::{ipc::signal::Signal, kwarn};
+
+use super::{Pid, ProcessControlBlock, ProcessFlags, ProcessManager};
+
+/// oom_score_adj的最小值，该值表示进程永远不会被OOM杀死
+pub const OOM_SCORE_ADJ_MIN: i32 = -1000;
+/// oom_score_adj的最大值
+pub const OOM_SCORE_ADJ_MAX: i32 = 1000;
+
+/// 选择OOM受害者时，一个候选进程的信息
+#[derive(Debug, Clone, Copy)]
+pub struct OomCandidate {
+    pub pid: Pid,
+    /// 是否为内核线程
+    pub kthread: bool,
+    /// 进程的常驻内存页数
+    pub rss_pages: usize,
+    pub oom_score_adj: i32,
+}
+
+impl OomCandidate {
+    fn from_pcb(pcb: &ProcessControlBlock) -> Self {
+        return Self {
+            pid: pcb.pid(),
+            kthread: pcb.flags().contains(ProcessFlags::KTHREAD),
+            rss_pages: pcb.rss(),
+            oom_score_adj: pcb.oom_score_adj(),
+        };
+    }
+}
+
+/// 收集系统中所有未退出的进程，作为OOM的候选进程
+fn collect_candidates() -> Vec<OomCandidate> {
+    let mut candidates = Vec::new();
+    ProcessManager::for_each(|pcb| {
+        if !pcb.sched_info().state().has_exited() {
+            candidates.push(OomCandidate::from_pcb(pcb));
+        }
+    });
+    return candidates;
+}
+
+/// 计算一个进程的坏度（badness），值越大越应该被杀死
+///
+/// 坏度以进程的常驻内存页数为基础，oom_score_adj以千分之一为单位，按`total_pages`的比例进行调整。
+///
+/// ## 参数
+///
+/// - `candidate` : 候选进程
+/// - `total_pages` : 作为oom_score_adj基准的总页数
+///
+/// ## 返回值
+///
+/// init进程、内核线程以及oom_score_adj为`OOM_SCORE_ADJ_MIN`的进程不能被杀死，返回None；
+/// 其余进程的坏度至少为1
+pub fn oom_badness(candidate: &OomCandidate, total_pages: usize) -> Option<usize> {
+    if candidate.pid <= Pid(1) || candidate.kthread || candidate.oom_score_adj == OOM_SCORE_ADJ_MIN
+    {
+        return None;
+    }
+
+    let adj = candidate.oom_score_adj as isize * total_pages as isize / 1000;
+    let points = candidate.rss_pages as isize + adj;
+    return Some(core::cmp::max(points, 1) as usize);
+}
+
+/// 从候选进程中选出坏度最大的进程
+///
+/// 由于还无法获取系统的总内存，以所有候选进程的常驻内存之和作为oom_score_adj的基准
+pub fn select_victim(candidates: &[OomCandidate]) -> Option<Pid> {
+    let total_pages: usize = candidates.iter().map(|c| c.rss_pages).sum();
+    return candidates
+        .iter()
+        .filter_map(|c| oom_badness(c, total_pages).map(|points| (points, c.pid)))
+        .max_by_key(|(points, _)| *points)
+        .map(|(_, pid)| pid);
+}
+
+/// 内存不足时，选择一个进程并将其杀死，以回收内存
+///
+/// 向受害者发送SIGKILL，它在下一次返回用户态时退出，父进程得到被SIGKILL终止的wait状态
+///
+/// ## 返回值
+///
+/// 被杀死的进程的pid。没有可以杀死的进程时返回None
+pub fn oom_kill() -> Option<Pid> {
+    let victim = select_victim(&collect_candidates())?;
+    let pcb = ProcessManager::find(victim)?;
+    kwarn!(
+        "Out of memory: killing process {:?} ({})",
+        victim,
+        pcb.basic().name()
+    );
+    ProcessManager::send_signal_to_thread(&pcb, Signal::SIGKILL).ok()?;
+    return Some(victim);
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, string::ToString};
+
+    use super::{
+        collect_candidates, select_victim, OomCandidate, OOM_SCORE_ADJ_MAX, OOM_SCORE_ADJ_MIN,
+    };
+    use crate::{
+        process::{
+            kthread::{KernelThreadClosure, KernelThreadMechanism},
+            Pid, ProcessManager,
+        },
+        syscall::SystemError,
+    };
+
+    fn candidate(pid: usize, kthread: bool, rss_pages: usize, oom_score_adj: i32) -> OomCandidate {
+        return OomCandidate {
+            pid: Pid::new(pid),
+            kthread,
+            rss_pages,
+            oom_score_adj,
+        };
+    }
+
+    /// 常驻内存最多的进程被选中，init进程、内核线程以及被豁免的进程永远不会被选中
+    #[test]
+    fn test_select_victim() {
+        let mut candidates = [
+            candidate(1, false, 10000, 0),
+            candidate(2, true, 8000, 0),
+            candidate(3, false, 100, 0),
+            candidate(4, false, 300, 0),
+            candidate(5, false, 5000, OOM_SCORE_ADJ_MIN),
+        ];
+        assert_eq!(select_victim(&candidates), Some(Pid::new(4)));
+
+        // oom_score_adj按总内存的比例调整坏度
+        candidates[2].oom_score_adj = OOM_SCORE_ADJ_MAX;
+        assert_eq!(select_victim(&candidates), Some(Pid::new(3)));
+        candidates[2].oom_score_adj = 0;
+        candidates[3].oom_score_adj = OOM_SCORE_ADJ_MIN + 1;
+        assert_eq!(select_victim(&candidates), Some(Pid::new(3)));
+
+        // 只剩下不能被杀死的进程
+        assert_eq!(select_victim(&candidates[..2]), None);
+    }
+
+    /// oom_score_adj为-1000的进程不会被oom_kill选中，并且没有特权时不能再降低oom_score_adj
+    #[test]
+    fn test_oom_score_adj_unkillable() {
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "oom-unkillable".to_string(),
+        )
+        .unwrap();
+        ProcessManager::add_pcb(pcb.clone()).ok();
+
+        // 没有特权时，不能比继承的值更低
+        assert_eq!(
+            pcb.set_oom_score_adj(OOM_SCORE_ADJ_MIN, false),
+            Err(SystemError::EACCES)
+        );
+        pcb.set_oom_score_adj(OOM_SCORE_ADJ_MIN, true).unwrap();
+
+        let mut candidates = collect_candidates();
+        for candidate in candidates.iter_mut().filter(|c| c.pid == pcb.pid()) {
+            // 将其视为占用内存最多的用户进程
+            candidate.kthread = false;
+            candidate.rss_pages = 1 << 40;
+        }
+        assert!(candidates.iter().any(|c| c.pid == pcb.pid()));
+        assert_ne!(select_victim(&candidates), Some(pcb.pid()));
+
+        assert_eq!(pcb.set_oom_score_adj(1001, true), Err(SystemError::EINVAL));
+    }
+}
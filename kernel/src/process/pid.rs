@@ -0,0 +1,219 @@
+use alloc::vec::Vec;
+
+use crate::{libs::spinlock::SpinLock, syscall::SystemError};
+
+use super::{Pid, ProcessManager};
+
+/// 系统中pid的上限（不含），分配的pid范围为`[1, PID_MAX)`
+pub const PID_MAX: usize = 32768;
+
+/// 全局的pid分配器
+static PID_ALLOCATOR: SpinLock<PidAllocator> = SpinLock::new(PidAllocator::new(PID_MAX));
+
+/// 基于位图的pid分配器
+///
+/// 从上一次分配的位置开始向后查找空闲的pid，到达`pid_max`后回绕到1，
+/// 使得被释放的pid能够被重新利用，同时尽量推迟pid的重用。
+///
+/// pid 0 被idle进程使用，永远不会被分配或释放。
+#[derive(Debug)]
+pub struct PidAllocator {
+    /// 已分配pid的位图，第i位为1表示pid i已被占用
+    bitmap: Vec<u64>,
+    /// 下一次开始查找的位置
+    cursor: usize,
+    /// 上一次分配出去的pid
+    last: usize,
+    /// pid的上限（不含）
+    pid_max: usize,
+}
+
+impl PidAllocator {
+    const BITS: usize = u64::BITS as usize;
+
+    /// 创建一个pid分配器
+    ///
+    /// ## 参数
+    ///
+    /// - `pid_max` : pid的上限（不含），必须大于1
+    pub const fn new(pid_max: usize) -> Self {
+        assert!(pid_max > 1);
+        Self {
+            bitmap: Vec::new(),
+            cursor: 1,
+            last: 0,
+            pid_max,
+        }
+    }
+
+    /// 分配一个空闲的pid
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：返回分配到的pid
+    /// - 失败：所有的pid都已被占用时，返回`EAGAIN`
+    pub fn alloc(&mut self) -> Result<Pid, SystemError> {
+        return self.alloc_skip(|_| false);
+    }
+
+    /// 分配一个空闲的pid，并跳过那些位图中空闲、但仍然被占用的pid
+    ///
+    /// ## 参数
+    ///
+    /// - `in_use` : 判断一个pid当前是否仍然被占用（例如仍在进程表中）
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：返回分配到的pid
+    /// - 失败：所有的pid都已被占用时，返回`EAGAIN`
+    pub fn alloc_skip<F: Fn(Pid) -> bool>(&mut self, in_use: F) -> Result<Pid, SystemError> {
+        if self.bitmap.is_empty() {
+            // 位图在第一次分配时才创建，以便分配器能够在编译期被构造
+            self.bitmap
+                .resize((self.pid_max + Self::BITS - 1) / Self::BITS, 0);
+            // pid 0 保留给idle进程
+            self.bitmap[0] |= 1;
+        }
+
+        for i in 0..self.pid_max - 1 {
+            // 在[1, pid_max)范围内回绕
+            let pid = (self.cursor - 1 + i) % (self.pid_max - 1) + 1;
+            if !self.test(pid) && !in_use(Pid::new(pid)) {
+                self.set(pid);
+                self.last = pid;
+                self.cursor = if pid + 1 >= self.pid_max { 1 } else { pid + 1 };
+                return Ok(Pid::new(pid));
+            }
+        }
+
+        return Err(SystemError::EAGAIN_OR_EWOULDBLOCK);
+    }
+
+    /// 释放一个pid，使其可以被重新分配
+    pub fn free(&mut self, pid: Pid) {
+        let pid: usize = pid.into();
+        if pid == 0 || pid >= self.pid_max || self.bitmap.is_empty() {
+            return;
+        }
+        self.bitmap[pid / Self::BITS] &= !(1 << (pid % Self::BITS));
+    }
+
+    /// 获取上一次分配出去的pid
+    pub fn last_pid(&self) -> Pid {
+        return Pid::new(self.last);
+    }
+
+    #[inline(always)]
+    fn test(&self, pid: usize) -> bool {
+        return self.bitmap[pid / Self::BITS] & (1 << (pid % Self::BITS)) != 0;
+    }
+
+    #[inline(always)]
+    fn set(&mut self, pid: usize) {
+        self.bitmap[pid / Self::BITS] |= 1 << (pid % Self::BITS);
+    }
+}
+
+/// 从全局的pid分配器中分配一个pid
+///
+/// 仍然存在于进程表中的pid会被跳过，保证不会与现有的进程冲突。
+/// 查询进程表需要获取进程表的锁，因此在pid分配器的锁之外进行
+pub fn alloc_pid() -> Result<Pid, SystemError> {
+    let mut skipped: Vec<Pid> = Vec::new();
+    let r = loop {
+        let pid = match PID_ALLOCATOR.lock_irqsave().alloc() {
+            Ok(pid) => pid,
+            Err(e) => break Err(e),
+        };
+        if ProcessManager::find(pid).is_none() {
+            break Ok(pid);
+        }
+        // 被跳过的pid暂时保持占用，避免下一次查找再次命中它
+        skipped.push(pid);
+    };
+
+    let mut allocator = PID_ALLOCATOR.lock_irqsave();
+    for pid in skipped {
+        allocator.free(pid);
+    }
+    return r;
+}
+
+/// 将pid归还给全局的pid分配器
+pub fn free_pid(pid: Pid) {
+    PID_ALLOCATOR.lock_irqsave().free(pid);
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{Pid, PidAllocator};
+    use crate::syscall::SystemError;
+
+    /// 被释放的pid应当在游标回绕后被重新分配
+    #[test]
+    fn test_pid_recycle() {
+        let mut allocator = PidAllocator::new(8);
+        for i in 1..8 {
+            assert_eq!(allocator.alloc(), Ok(Pid::new(i)));
+        }
+
+        allocator.free(Pid::new(3));
+        allocator.free(Pid::new(5));
+        assert_eq!(allocator.alloc(), Ok(Pid::new(3)));
+        assert_eq!(allocator.alloc(), Ok(Pid::new(5)));
+        assert_eq!(allocator.last_pid(), Pid::new(5));
+    }
+
+    /// 分配从游标处继续，而不是总是从最小的空闲pid开始
+    #[test]
+    fn test_pid_rotating_cursor() {
+        let mut allocator = PidAllocator::new(8);
+        assert_eq!(allocator.alloc(), Ok(Pid::new(1)));
+        assert_eq!(allocator.alloc(), Ok(Pid::new(2)));
+        allocator.free(Pid::new(1));
+        assert_eq!(allocator.alloc(), Ok(Pid::new(3)));
+    }
+
+    /// 所有pid都被占用时返回EAGAIN，释放后可以继续分配
+    #[test]
+    fn test_pid_exhaustion() {
+        let mut allocator = PidAllocator::new(130);
+        for i in 1..130 {
+            assert_eq!(allocator.alloc(), Ok(Pid::new(i)));
+        }
+        assert_eq!(allocator.alloc(), Err(SystemError::EAGAIN_OR_EWOULDBLOCK));
+
+        // pid 0 不能被释放
+        allocator.free(Pid::new(0));
+        assert_eq!(allocator.alloc(), Err(SystemError::EAGAIN_OR_EWOULDBLOCK));
+
+        allocator.free(Pid::new(64));
+        assert_eq!(allocator.alloc(), Ok(Pid::new(64)));
+    }
+
+    /// 模拟pid回绕：位图中空闲但仍在进程表中的pid不能被再次分配
+    #[test]
+    fn test_pid_wraparound_no_collision() {
+        let mut allocator = PidAllocator::new(16);
+        // 仍然存活的进程
+        let alive: Vec<Pid> = [2, 3, 7, 11].iter().map(|&x| Pid::new(x)).collect();
+        let in_use = |pid: Pid| alive.contains(&pid);
+
+        // 多次回绕，每次都把分配到的pid全部释放
+        for _ in 0..4 {
+            let mut allocated = Vec::new();
+            while let Ok(pid) = allocator.alloc_skip(in_use) {
+                assert!(!alive.contains(&pid));
+                assert!(!allocated.contains(&pid));
+                allocated.push(pid);
+            }
+            assert_eq!(allocated.len(), 15 - alive.len());
+
+            for pid in allocated {
+                allocator.free(pid);
+            }
+        }
+    }
+}
@@ -38,5 +38,6 @@ extern uint32_t rs_current_pcb_pid();
 extern uint32_t rs_current_pcb_preempt_count();
 extern uint32_t rs_current_pcb_flags();
 extern int64_t rs_current_pcb_thread_rbp();
+extern int rs_user_stack_fault(uint64_t addr);
 
 #define PF_NEED_SCHED (1UL << 1)
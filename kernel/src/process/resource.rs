@@ -0,0 +1,148 @@
+use crate::{mm::ucontext::UserStack, syscall::SystemError};
+
+/// 表示没有限制的资源限制值
+pub const RLIM_INFINITY: u64 = u64::MAX;
+/// 资源限制的种类数
+pub const RLIM_NLIMITS: usize = 16;
+/// 用户栈大小的默认软限制，与execve时创建的用户栈大小相同
+pub const DEFAULT_STACK_LIMIT: u64 = UserStack::DEFAULT_USER_STACK_SIZE as u64;
+
+/// 资源限制的种类，编号与Linux一致
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum RLimitID {
+    /// 进程能够使用的cpu时间（单位：秒）
+    Cpu = 0,
+    /// 进程能够创建的文件的最大大小
+    Fsize = 1,
+    /// 数据段的最大大小
+    Data = 2,
+    /// 用户栈的最大大小
+    Stack = 3,
+    /// core文件的最大大小
+    Core = 4,
+    /// 常驻内存的最大大小
+    Rss = 5,
+    /// 用户能够创建的进程数
+    Nproc = 6,
+    /// 能够打开的文件描述符数
+    Nofile = 7,
+    /// 能够锁定在内存中的最大字节数
+    Memlock = 8,
+    /// 地址空间的最大大小
+    As = 9,
+    /// 能够持有的文件锁数
+    Locks = 10,
+    /// 能够排队的信号数
+    Sigpending = 11,
+    /// POSIX消息队列的最大字节数
+    Msgqueue = 12,
+    /// nice值的上限
+    Nice = 13,
+    /// 实时优先级的上限
+    Rtprio = 14,
+    /// 实时进程在不阻塞的情况下能够使用的cpu时间（单位：微秒）
+    Rttime = 15,
+}
+
+/// 一项资源限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RLimit {
+    /// 软限制，即当前生效的限制
+    pub cur: u64,
+    /// 硬限制，即软限制所能设置的上限
+    pub max: u64,
+}
+
+impl RLimit {
+    pub const fn new(cur: u64, max: u64) -> Self {
+        Self { cur, max }
+    }
+
+    /// 没有限制
+    pub const fn infinity() -> Self {
+        Self::new(RLIM_INFINITY, RLIM_INFINITY)
+    }
+}
+
+/// 进程的资源限制表
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RLimitTable {
+    limits: [RLimit; RLIM_NLIMITS],
+}
+
+impl RLimitTable {
+    /// 获取某项资源限制
+    pub fn get(&self, id: RLimitID) -> RLimit {
+        return self.limits[id as usize];
+    }
+
+    /// 设置某项资源限制
+    ///
+    /// ## 参数
+    ///
+    /// - `id` : 资源限制的种类
+    /// - `limit` : 新的资源限制
+    /// - `privileged` : 设置者是否拥有`CAP_SYS_RESOURCE`能力
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：软限制大于硬限制时，返回`EINVAL`；没有权限提高硬限制时，返回`EPERM`
+    pub fn set(
+        &mut self,
+        id: RLimitID,
+        limit: RLimit,
+        privileged: bool,
+    ) -> Result<(), SystemError> {
+        if limit.cur > limit.max {
+            return Err(SystemError::EINVAL);
+        }
+        if limit.max > self.limits[id as usize].max && !privileged {
+            return Err(SystemError::EPERM);
+        }
+        self.limits[id as usize] = limit;
+        return Ok(());
+    }
+}
+
+impl Default for RLimitTable {
+    fn default() -> Self {
+        let mut limits = [RLimit::infinity(); RLIM_NLIMITS];
+        limits[RLimitID::Stack as usize] = RLimit::new(DEFAULT_STACK_LIMIT, RLIM_INFINITY);
+        return Self { limits };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::syscall::SystemError;
+
+    use super::{RLimit, RLimitID, RLimitTable, DEFAULT_STACK_LIMIT, RLIM_INFINITY};
+
+    /// 软限制不能大于硬限制；没有特权时可以降低硬限制，但不能提高
+    #[test]
+    fn test_set_rlimit() {
+        let mut table = RLimitTable::default();
+        assert_eq!(
+            table.get(RLimitID::Stack),
+            RLimit::new(DEFAULT_STACK_LIMIT, RLIM_INFINITY)
+        );
+        assert_eq!(table.get(RLimitID::Cpu), RLimit::infinity());
+
+        assert_eq!(
+            table.set(RLimitID::Cpu, RLimit::new(4, 3), true),
+            Err(SystemError::EINVAL)
+        );
+        assert_eq!(table.set(RLimitID::Cpu, RLimit::new(1, 3), false), Ok(()));
+        assert_eq!(
+            table.set(RLimitID::Cpu, RLimit::new(1, 4), false),
+            Err(SystemError::EPERM)
+        );
+        // 在硬限制以内提高软限制不需要特权
+        assert_eq!(table.set(RLimitID::Cpu, RLimit::new(3, 3), false), Ok(()));
+        assert_eq!(table.set(RLimitID::Cpu, RLimit::new(1, 4), true), Ok(()));
+        assert_eq!(table.get(RLimitID::Cpu), RLimit::new(1, 4));
+    }
+}
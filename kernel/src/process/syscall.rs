@@ -2,11 +2,12 @@ use core::ffi::c_void;
 
 use alloc::{string::String, sync::Arc, vec::Vec};
 
-use super::{fork::CloneFlags, Pid, ProcessManager, ProcessState};
+use super::{fork::CloneFlags, Pid, ProcessManager};
 use crate::{
     arch::{interrupt::TrapFrame, sched::sched, CurrentIrqArch},
     exception::InterruptArch,
     filesystem::vfs::MAX_PATHLEN,
+    mm::{verify_area, VirtAddr},
     process::{ProcessControlBlock, TaskGroup, PROCESS_GROUP_MANAGER},
     syscall::{
         user_access::{
@@ -16,6 +17,9 @@ use crate::{
     },
 };
 
+/// wait4的选项：没有子进程退出时立即返回
+const WNOHANG: i32 = 1;
+
 impl Syscall {
     pub fn fork(frame: &mut TrapFrame) -> Result<usize, SystemError> {
         let r = ProcessManager::fork(frame, CloneFlags::empty()).map(|pid| pid.into());
@@ -30,6 +34,30 @@ impl Syscall {
         .map(|pid| pid.into())
     }
 
+    /// 按照克隆标志创建一个新进程或线程
+    ///
+    /// ## 参数
+    ///
+    /// - `frame` : 当前进程的trapframe
+    /// - `flags` : 克隆标志，见`CloneFlags`
+    /// - `tls` : 指定了`CLONE_SETTLS`时，新线程的线程局部存储的地址
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：返回新进程的pid
+    /// - 失败：标志不合法时返回`EINVAL`；tls地址不在用户空间时返回`EPERM`
+    pub fn clone(frame: &mut TrapFrame, flags: u32, tls: usize) -> Result<usize, SystemError> {
+        let clone_flags = CloneFlags::from_bits(flags).ok_or(SystemError::EINVAL)?;
+        let tls = VirtAddr::new(tls);
+        if clone_flags.contains(CloneFlags::CLONE_SETTLS)
+            && frame.from_user()
+            && verify_area(tls, 0).is_err()
+        {
+            return Err(SystemError::EPERM);
+        }
+        return ProcessManager::clone(frame, clone_flags, tls).map(|pid| pid.into());
+    }
+
     pub fn execve(
         path: *const u8,
         argv: *const *const u8,
@@ -64,6 +92,12 @@ impl Syscall {
         return Self::do_execve(path, argv, envp, frame);
     }
 
+    /// 等待子进程退出，并收集它的退出状态
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：被收集的子进程的pid。指定了`WNOHANG`并且没有子进程退出时，返回0
+    /// - 失败：没有符合条件的子进程时，返回`ECHILD`；阻塞等待时被信号打断，返回`ERESTARTSYS`
     pub fn wait4(
         pid: i64,
         wstatus: *mut i32,
@@ -76,54 +110,37 @@ impl Syscall {
         let mut wstatus_buf =
             UserBufferWriter::new::<i32>(wstatus, core::mem::size_of::<i32>(), true)?;
 
-        // 暂时不支持options选项
-        if options != 0 {
+        // 暂时只支持WNOHANG选项
+        if options & !WNOHANG != 0 {
             return Err(SystemError::EINVAL);
         }
 
         let cur_pcb = ProcessManager::current_pcb();
-        let rd_childen = cur_pcb.children.read();
-
-        if pid > 0 {
-            let pid = Pid(pid as usize);
-            let child_pcb = rd_childen.get(&pid).ok_or(SystemError::ECHILD)?.clone();
-            drop(rd_childen);
-
-            // 获取退出码
-            if let ProcessState::Exited(status) = child_pcb.sched_info().state() {
+        if options & WNOHANG != 0 {
+            if let Some(exited) = cur_pcb.try_wait_child(pid)? {
                 if !wstatus.is_null() {
-                    wstatus_buf.copy_one_to_user(&status, 0)?;
+                    wstatus_buf.copy_one_to_user(&(exited.status as i32), 0)?;
                 }
-                return Ok(pid.into());
+                return Ok(exited.pid.into());
             }
-            // 等待指定进程
-            child_pcb.wait_queue.sleep();
-        } else if pid < -1 {
-            // TODO 判断是否pgid == -pid（等待指定组任意进程）
-            // 暂时不支持
-            return Err(SystemError::EINVAL);
-        } else if pid == 0 {
-            // TODO 判断是否pgid == current_pgid（等待当前组任意进程）
-            // 暂时不支持
-            return Err(SystemError::EINVAL);
-        } else {
-            // 等待任意子进程(这两)
+            return Ok(0);
+        }
+
+        // 阻塞等待时，直到收集到一个子进程的退出状态才返回
+        loop {
             let irq_guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
-            for (pid, pcb) in rd_childen.iter() {
-                if pcb.sched_info().state().is_exited() {
-                    if !wstatus.is_null() {
-                        wstatus_buf.copy_one_to_user(&0, 0)?;
-                    }
-                    return Ok(pid.clone().into());
-                } else {
-                    unsafe { pcb.wait_queue.sleep_without_schedule() };
+            if let Some(exited) = unsafe { cur_pcb.wait_child(pid)? } {
+                drop(irq_guard);
+                if !wstatus.is_null() {
+                    wstatus_buf.copy_one_to_user(&(exited.status as i32), 0)?;
                 }
+                return Ok(exited.pid.into());
             }
             drop(irq_guard);
             sched();
+            // 被信号唤醒时返回，由系统调用层决定是否重新执行
+            ProcessManager::sleep_outcome().into_result()?;
         }
-
-        return Ok(0);
     }
 
     /// # 退出进程
@@ -132,12 +149,40 @@ impl Syscall {
     ///
     /// - status: 退出状态
     pub fn exit(status: usize) -> ! {
-        ProcessManager::exit(status);
+        // 与Linux一致，正常退出时，退出码位于wait状态的第8~15位，与被信号终止的状态区分开
+        ProcessManager::exit((status & 0xff) << 8);
     }
 
-    /// @brief 获取当前进程的pid
+    /// @brief 获取当前进程的pid，即当前线程所在线程组的id
     pub fn getpid() -> Result<Pid, SystemError> {
+        return Ok(ProcessManager::getpid());
+    }
+
+    /// 获取当前线程的id
+    pub fn gettid() -> Result<Pid, SystemError> {
+        return Ok(ProcessManager::gettid());
+    }
+
+    /// 设置当前线程退出时需要清零并唤醒的地址
+    ///
+    /// 线程退出时，内核将该地址处的值清零，并在该地址上执行FUTEX_WAKE，从而唤醒pthread_join的线程
+    ///
+    /// ## 参数
+    ///
+    /// - `tidptr` : 线程id所在的地址，为空时表示退出时不做任何操作
+    /// - `from_user` : 地址是否来自用户空间
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：返回当前线程的id
+    /// - 失败：地址不合法时返回`EFAULT`
+    pub fn set_tid_address(tidptr: *mut i32, from_user: bool) -> Result<Pid, SystemError> {
+        if !tidptr.is_null() {
+            // 只检查地址是否合法，真正的写入发生在线程退出时
+            UserBufferWriter::new(tidptr, core::mem::size_of::<i32>(), from_user)?;
+        }
         let current_pcb = ProcessManager::current_pcb();
+        current_pcb.set_clear_child_tid(VirtAddr::new(tidptr as usize));
         return Ok(current_pcb.pid());
     }
 
@@ -165,10 +210,10 @@ impl Syscall {
         let target_proc = ProcessManager::find(pid).ok_or(SystemError::ESRCH)?;
         if pgid == 0 {
             let pgid = pid;
-            PROCESS_GROUP_MANAGER.add_process(pid, pid);
+            PROCESS_GROUP_MANAGER.add_process(pid, pid)?;
             let ptg: Arc<TaskGroup> = PROCESS_GROUP_MANAGER.find(pid);
 
-            let ntg = TaskGroup::new(ptg);
+            let ntg = TaskGroup::new(ptg)?;
             // 将当前TaskGroup加入父进程组的子进程哈希表中
 
             if let Some(ppcb_arc) = ntg.parent_tg.read().upgrade() {
@@ -183,10 +228,10 @@ impl Syscall {
             TaskGroup::add_tg(pid, ntg);
         } else {
             let old_pgid = target_proc.basic().pgid();
-            let ornewtg: bool = PROCESS_GROUP_MANAGER.set_pgid_by_pid(pid, pgid, old_pgid);
+            let ornewtg: bool = PROCESS_GROUP_MANAGER.set_pgid_by_pid(pid, pgid, old_pgid)?;
             if ornewtg == true {
                 let ptg: Arc<TaskGroup> = PROCESS_GROUP_MANAGER.find(old_pgid);
-                let ntg = TaskGroup::new(ptg);
+                let ntg = TaskGroup::new(ptg)?;
                 if let Some(ppcb_arc) = ntg.parent_tg.read().upgrade() {
                     let mut children = ppcb_arc.children.write();
                     children.insert(pgid, ntg.clone());
@@ -1,25 +1,33 @@
-use core::sync::atomic::compiler_fence;
+use core::sync::atomic::{compiler_fence, AtomicU64, AtomicUsize, Ordering};
 
 use alloc::{boxed::Box, sync::Arc, vec::Vec};
 
 use crate::{
     arch::CurrentIrqArch,
     exception::InterruptArch,
-    include::bindings::bindings::MAX_CPU_NUM,
     kBUG,
     libs::{
         rbtree::RBTree,
         spinlock::{SpinLock, SpinLockGuard},
     },
+    mm::percpu::{PerCpu, PerCpuVar},
     process::{
         ProcessControlBlock, ProcessFlags, ProcessManager, ProcessState, SchedEntity, TaskGroup,
     },
-    smp::core::smp_get_processor_id,
+    smp::{
+        core::{cpu_online_mask, smp_get_processor_id, smp_possible_cpu_num},
+        cpumask::CpuMask,
+        kick_cpu,
+    },
+    syscall::SystemError,
 };
 
 use super::{
-    core::{sched_enqueue, Scheduler},
-    SchedPriority,
+    clock::sched_clock,
+    core::{sched_enqueue, task_allowed_cpus, Scheduler, SCHED_TICK_INTERVAL_NS},
+    cpufreq::{cpu_util, update_util_avg, CpuFreqGovernor, NoopGovernor},
+    trace::trace_sched_migrate,
+    SchedPolicy,
 };
 
 /// 声明全局的cfs调度器实例
@@ -44,13 +52,52 @@ pub unsafe fn sched_cfs_init() {
     }
 }
 
+/// 分配给进程的时间片（单位：纳秒），为10个时钟周期
+pub const SCHED_SLICE_NS: i64 = 10 * SCHED_TICK_INTERVAL_NS as i64;
+
+/// SCHED_BATCH进程的时间片（单位：纳秒），为普通进程的4倍，减少批处理进程之间的切换
+pub const SCHED_BATCH_SLICE_NS: i64 = 4 * SCHED_SLICE_NS;
+
+/// 普通进程的权重
+pub const NICE_0_LOAD: u64 = 1024;
+/// SCHED_IDLE进程的权重，与Linux的WEIGHT_IDLEPRIO一致
+pub const WEIGHT_IDLEPRIO: u64 = 3;
+
+/// 被唤醒的进程最多能够获得的虚拟运行时间补偿（单位：纳秒），为一个时间片的一半
+pub const SCHED_SLEEPER_CREDIT: isize = 5 * SCHED_TICK_INTERVAL_NS as isize;
+
+/// 进程平均睡眠时间的上限（单位：微秒），达到上限的进程获得全部的交互式补偿
+pub const SCHED_SLEEP_AVG_MAX_US: u64 = 1_000_000;
+
+/// 经常睡眠的交互式进程被唤醒时，在`SCHED_SLEEPER_CREDIT`之外额外获得的补偿的上限（单位：纳秒）
+pub const SCHED_INTERACTIVE_BONUS_MAX: isize = 5 * SCHED_TICK_INTERVAL_NS as isize;
+
+/// 入队的调度实体的虚拟运行时间与队列最小虚拟运行时间之差的上限（单位：纳秒），为十个时间片
+pub const SCHED_VRUNTIME_LAG_MAX: isize = 100 * SCHED_TICK_INTERVAL_NS as isize;
+
+/// cpu负载平均值的衰减位数：每个时钟中断，旧的平均值保留 (2^LOAD_AVG_SHIFT - 1) / 2^LOAD_AVG_SHIFT
+const LOAD_AVG_SHIFT: u32 = 3;
+
+/// 即将空闲的cpu窃取进程时，最多检查的其他cpu的数目，用于限制窃取的开销
+pub const SCHED_STEAL_SCAN_MAX: usize = 8;
+
 /// @brief CFS队列（per-cpu的）
 #[derive(Debug)]
 pub struct CFSQueue {
-    /// 当前cpu上执行的进程剩余的时间片
-    cpu_exec_proc_jiffies: i64,
+    /// 当前cpu上执行的进程剩余的时间片（单位：纳秒）
+    cpu_exec_proc_ns: i64,
+    /// 上一次计算当前进程运行时间时，调度器时钟的读数（单位：纳秒），为0表示尚未开始计时
+    exec_start: u64,
     /// 自旋锁保护的队列
     locked_queue: SpinLock<RBTree<i64, Arc<SchedEntity>>>,
+    /// 队列中可运行的调度实体的数目，不包括IDLE进程
+    nr_running: AtomicUsize,
+    /// 队列中可运行的调度实体的权重之和，不包括IDLE进程
+    load_weight: AtomicU64,
+    /// cpu运行非IDLE进程的时间的滑动平均值，由时钟中断更新
+    util_avg: u64,
+    /// cpu上可运行的进程（包括正在运行的进程）的权重之和的滑动平均值，由时钟中断更新
+    load_avg: u64,
     /// 当前核心的队列专属的IDLE进程的pcb
     idle_pcb: Arc<ProcessControlBlock>,
 }
@@ -58,13 +105,18 @@ pub struct CFSQueue {
 impl CFSQueue {
     pub fn new(idle_pcb: Arc<ProcessControlBlock>) -> CFSQueue {
         CFSQueue {
-            cpu_exec_proc_jiffies: 0,
+            cpu_exec_proc_ns: 0,
+            exec_start: 0,
             locked_queue: SpinLock::new(RBTree::new()),
+            nr_running: AtomicUsize::new(0),
+            load_weight: AtomicU64::new(0),
+            util_avg: 0,
+            load_avg: 0,
             idle_pcb: idle_pcb,
         }
     }
 
-    /// @brief 将pcb加入队列
+    /// @brief 将pcb的调度实体加入队列
     pub fn enqueue(&mut self, pcb: Arc<ProcessControlBlock>) {
         let mut queue = self.locked_queue.lock_irqsave();
 
@@ -73,230 +125,905 @@ impl CFSQueue {
             return;
         }
 
-        queue.insert(pcb.sched_info().virtual_runtime() as i64, pcb.clone());
+        self.insert_pcb(&mut queue, &pcb);
+    }
+
+    /// 以进程的虚拟运行时间为键，将进程的调度实体插入队列
+    fn insert_pcb(
+        &self,
+        queue: &mut SpinLockGuard<RBTree<i64, Arc<SchedEntity>>>,
+        pcb: &Arc<ProcessControlBlock>,
+    ) {
+        let sched_info = pcb.sched_info();
+        let mut vruntime = sched_info.virtual_runtime();
+        if let Some(min_vruntime) = Self::min_vruntime(queue) {
+            let min_vruntime = min_vruntime as isize;
+            vruntime = Self::clamp_vruntime(vruntime, min_vruntime);
+            // 权重小的进程的虚拟运行时间增长得更快，领先的上限也按照权重放大，
+            // 否则SCHED_IDLE进程会因为上限而获得远超其权重的运行时间
+            let lag_max =
+                Self::calc_delta_fair(SCHED_VRUNTIME_LAG_MAX as u64, sched_info.policy()) as isize;
+            if sched_info.virtual_runtime() > vruntime {
+                vruntime = core::cmp::min(
+                    sched_info.virtual_runtime(),
+                    min_vruntime.saturating_add(lag_max),
+                );
+            }
+            sched_info.set_virtual_runtime(vruntime);
+        }
+        if let Some(se) = sched_info.se() {
+            se.set_virtual_runtime(vruntime);
+            se.set_load_weight(Self::sched_weight(sched_info.policy()));
+            self.account_enqueue(&se);
+            queue.insert(vruntime as i64, se);
+        }
+    }
+
+    /// 将虚拟运行时间限制在队列最小虚拟运行时间前后`SCHED_VRUNTIME_LAG_MAX`的范围内
+    ///
+    /// 虚拟运行时间远小于队列的进程会长期霸占cpu，远大于队列的进程则永远得不到调度，
+    /// 限制两者的差值，使得饥饿与霸占的时间都有上限
+    ///
+    /// ## 参数
+    ///
+    /// - `vruntime` : 要入队的调度实体的虚拟运行时间
+    /// - `min_vruntime` : 队列的最小虚拟运行时间
+    pub fn clamp_vruntime(vruntime: isize, min_vruntime: isize) -> isize {
+        return vruntime.clamp(
+            min_vruntime.saturating_sub(SCHED_VRUNTIME_LAG_MAX),
+            min_vruntime.saturating_add(SCHED_VRUNTIME_LAG_MAX),
+        );
+    }
+
+    /// 判断调度实体是否计入`nr_running`，IDLE进程的调度实体不计入
+    fn is_counted(se: &SchedEntity) -> bool {
+        return se.pcb().map_or(true, |pcb| pcb.pid().into() != 0);
+    }
+
+    /// 调度实体入队时，更新`nr_running`与`load_weight`
+    fn account_enqueue(&self, se: &SchedEntity) {
+        if Self::is_counted(se) {
+            self.nr_running.fetch_add(1, Ordering::SeqCst);
+            self.load_weight
+                .fetch_add(se.load_weight(), Ordering::SeqCst);
+        }
+    }
+
+    /// 调度实体出队时，更新`nr_running`与`load_weight`
+    fn account_dequeue(&self, se: &SchedEntity) {
+        if Self::is_counted(se) {
+            self.nr_running.fetch_sub(1, Ordering::SeqCst);
+            self.load_weight
+                .fetch_sub(se.load_weight(), Ordering::SeqCst);
+        }
+    }
+
+    /// 根据一次时钟中断的采样，更新cpu负载的滑动平均值
+    ///
+    /// ## 参数
+    ///
+    /// - `load_avg` : 原来的平均值
+    /// - `load` : 这次时钟中断时，cpu上可运行的进程的权重之和
+    ///
+    /// ## 返回值
+    ///
+    /// 新的平均值
+    pub fn update_load_avg(load_avg: u64, load: u64) -> u64 {
+        let kept = load_avg - (load_avg >> LOAD_AVG_SHIFT);
+        return kept + (load >> LOAD_AVG_SHIFT);
+    }
+
+    /// 获取队列中可运行的调度实体的数目，不包括IDLE进程
+    pub fn nr_running(&self) -> usize {
+        return self.nr_running.load(Ordering::SeqCst);
+    }
+
+    /// 将被唤醒的pcb加入队列，并且按照`place_entity`调整其虚拟运行时间
+    ///
+    /// 读取最小虚拟运行时间与插入在同一个锁守卫下完成，避免重复加锁导致死锁，
+    /// 也避免其他cpu在两者之间修改队列
+    pub fn enqueue_reset_vruntime(&mut self, pcb: Arc<ProcessControlBlock>) {
+        let mut queue = self.locked_queue.lock_irqsave();
+
+        // 如果进程是IDLE进程，那么就不加入队列
+        if pcb.pid().into() == 0 {
+            return;
+        }
+
+        if let Some(min_vruntime) = Self::min_vruntime(&queue) {
+            let sched_info = pcb.sched_info();
+            // 批处理进程被视为cpu密集型的进程，不获得交互式补偿
+            let sleep_avg = if sched_info.policy() == SchedPolicy::BATCH {
+                0
+            } else {
+                sched_info.sleep_avg()
+            };
+            sched_info.set_virtual_runtime(Self::place_entity(
+                sched_info.virtual_runtime(),
+                min_vruntime as isize,
+                sleep_avg,
+            ));
+        }
+        self.insert_pcb(&mut queue, &pcb);
+    }
+
+    /// 为被唤醒的进程确定虚拟运行时间
+    ///
+    /// 睡眠了很久的进程的虚拟运行时间远小于队列中的其他进程，若保持不变，它醒来后会长期霸占cpu；
+    /// 若直接设为队列的最小值，交互式进程又得不到任何优待。因此取
+    /// `max(原虚拟运行时间, 最小虚拟运行时间 - SCHED_SLEEPER_CREDIT - 交互式补偿)`，给予有上限的补偿。
+    /// 虚拟运行时间不会因为睡眠而增大。
+    ///
+    /// ## 参数
+    ///
+    /// - `vruntime` : 进程原来的虚拟运行时间
+    /// - `min_vruntime` : 队列的最小虚拟运行时间
+    /// - `sleep_avg` : 进程的平均睡眠时间（单位：微秒）
+    pub fn place_entity(vruntime: isize, min_vruntime: isize, sleep_avg: u64) -> isize {
+        let credit = SCHED_SLEEPER_CREDIT + Self::interactive_bonus(sleep_avg);
+        return core::cmp::max(vruntime, min_vruntime - credit);
+    }
+
+    /// 根据平均睡眠时间计算交互式补偿，与平均睡眠时间成正比，最多为`SCHED_INTERACTIVE_BONUS_MAX`
+    pub fn interactive_bonus(sleep_avg: u64) -> isize {
+        let sleep_avg = core::cmp::min(sleep_avg, SCHED_SLEEP_AVG_MAX_US);
+        return (SCHED_INTERACTIVE_BONUS_MAX as u64 * sleep_avg / SCHED_SLEEP_AVG_MAX_US) as isize;
+    }
+
+    /// 获取调度策略对应的权重
+    pub fn sched_weight(policy: SchedPolicy) -> u64 {
+        return match policy {
+            SchedPolicy::IDLE => WEIGHT_IDLEPRIO,
+            _ => NICE_0_LOAD,
+        };
+    }
+
+    /// 将实际运行的时间按照权重换算为虚拟运行时间，权重越小，虚拟运行时间增长得越快
+    pub fn calc_delta_fair(delta: u64, policy: SchedPolicy) -> u64 {
+        let weight = Self::sched_weight(policy);
+        if weight == NICE_0_LOAD {
+            return delta;
+        }
+        return (delta as u128 * NICE_0_LOAD as u128 / weight as u128) as u64;
+    }
+
+    /// 为新fork出的子进程确定初始的虚拟运行时间
+    ///
+    /// 父进程（即当前cpu上正在运行的进程）剩余时间片的一半被转交给子进程，
+    /// 这一半时间片同时计入父进程的虚拟运行时间，作为fork的代价；
+    /// 子进程则排在父进程之后，不会因为fork而获得比已有进程更小的虚拟运行时间。
+    ///
+    /// ## 参数
+    ///
+    /// - `parent` : 父进程的pcb
+    /// - `child` : 子进程的pcb
+    pub fn task_fork(
+        &mut self,
+        parent: &Arc<ProcessControlBlock>,
+        child: &Arc<ProcessControlBlock>,
+    ) {
+        // 至少收取1个时钟周期，避免时间片耗尽后fork不再产生代价
+        let half = core::cmp::max(self.cpu_exec_proc_ns / 2, SCHED_TICK_INTERVAL_NS as i64);
+        self.cpu_exec_proc_ns -= half;
+        if self.cpu_exec_proc_ns <= 0 {
+            parent.set_need_schedule();
+        }
+
+        let parent_info = parent.sched_info();
+        parent_info.increase_virtual_runtime(half as isize);
+        child
+            .sched_info()
+            .set_virtual_runtime(parent_info.virtual_runtime() + half as isize);
+    }
+
+    /// 按照调度器时钟，将上一次计时以来经过的时间计入当前进程
+    ///
+    /// 经过的时间按照进程的权重计入虚拟运行时间，并计入运行总时间，同时从剩余时间片中扣除，
+    /// 时间片耗尽时标记进程需要被调度。IDLE进程不计入。
+    ///
+    /// ## 参数
+    ///
+    /// - `current` : 当前cpu上正在运行的进程
+    /// - `now` : 调度器时钟的读数（单位：纳秒）
+    ///
+    /// ## 返回值
+    ///
+    /// 计入进程的时间（单位：纳秒）
+    pub fn update_curr(&mut self, current: &Arc<ProcessControlBlock>, now: u64) -> u64 {
+        let delta = if self.exec_start == 0 {
+            0
+        } else {
+            now.saturating_sub(self.exec_start)
+        };
+        self.exec_start = now;
+        if delta == 0 || current.pid().into() == 0 {
+            return 0;
+        }
+
+        let sched_info = current.sched_info();
+        sched_info
+            .increase_virtual_runtime(Self::calc_delta_fair(delta, sched_info.policy()) as isize);
+        sched_info.add_exec_runtime(delta);
+        drop(sched_info);
+        current.account_group_cpu_usage(delta);
+
+        self.cpu_exec_proc_ns -= delta as i64;
+        if self.cpu_exec_proc_ns <= 0 {
+            current.set_need_schedule();
+        }
+        return delta;
+    }
+
+    /// 将进程的调度实体从队列中移出
+    ///
+    /// 调度实体入队时的虚拟运行时间就是它在队列中的键，在队列中时不会改变。
+    /// 队列中可能存在相同的键，因此只在这些键相同的调度实体中比较
+    ///
+    /// ## 返回值
+    ///
+    /// 进程是否在队列中
+    pub fn dequeue_pcb(&mut self, pcb: &Arc<ProcessControlBlock>) -> bool {
+        let se = match pcb.sched_info().se() {
+            Some(se) => se,
+            None => return false,
+        };
+        let mut queue = self.locked_queue.lock_irqsave();
+        let removed = queue.remove_if(&(se.virtual_runtime() as i64), |queued| {
+            Arc::ptr_eq(queued, &se)
+        });
+        if let Some(se) = removed {
+            self.account_dequeue(&se);
+            return true;
+        }
+        return false;
     }
 
     /// @brief 将se加入队列
     pub fn enqueue_se(&mut self, se: Arc<SchedEntity>) {
         let mut queue = self.locked_queue.lock_irqsave();
-        queue.insert(se.virtual_runtime() as i64, se.clone());
+        if let Some(min_vruntime) = Self::min_vruntime(&queue) {
+            se.set_virtual_runtime(Self::clamp_vruntime(
+                se.virtual_runtime(),
+                min_vruntime as isize,
+            ));
+        }
+        self.account_enqueue(&se);
+        queue.insert(se.virtual_runtime() as i64, se);
     }
 
     /// @brief 将pcb从调度队列中弹出,若队列为空，则返回IDLE进程的pcb
     pub fn dequeue(&mut self) -> Arc<ProcessControlBlock> {
-        let res: Arc<ProcessControlBlock>;
         let mut queue = self.locked_queue.lock_irqsave();
-        if !queue.is_empty() {
-            // 队列不为空，返回下一个要执行的pcb
-            res = queue.pop_first().unwrap().1;
-        } else {
-            // 如果队列为空，则返回IDLE进程的pcb
-            res = self.idle_pcb.clone();
-        }
-        return res;
+        // 队列不为空，返回下一个要执行的pcb；否则返回IDLE进程的pcb，此时计数不变
+        return queue
+            .pop_first()
+            .and_then(|(_, se)| {
+                self.account_dequeue(&se);
+                se.pcb()
+            })
+            .unwrap_or_else(|| self.idle_pcb.clone());
     }
-    /// @brief 将pcb从调度队列中弹出
-    /// !没有考虑idle_pcb
-    pub fn dequeue_se(&mut self) -> Arc<SchedEntity> {
-        let res: Arc<ProcessControlBlock>;
+    /// 将虚拟运行时间最小的调度实体从调度队列中弹出
+    ///
+    /// ## 返回值
+    ///
+    /// 如果队列为空，返回None，由调用者决定是否运行IDLE进程
+    pub fn dequeue_se(&mut self) -> Option<Arc<SchedEntity>> {
         let mut queue = self.locked_queue.lock_irqsave();
-        if !queue.is_empty() {
-            // 队列不为空，返回下一个要执行的task se
-            res = queue.pop_first().unwrap().1;
-        }
-        return res;
+        return queue.pop_first().map(|(_, se)| {
+            self.account_dequeue(&se);
+            se
+        });
     }
 
     /// @brief 获取cfs队列的最小运行时间
     ///
+    /// 队列以虚拟运行时间为键，因此只需查看最左侧的键，而不需要关心队列中存放的是什么
+    ///
     /// @return Option<i64> 如果队列不为空，那么返回队列中，最小的虚拟运行时间；否则返回None
-    pub fn min_vruntime(
-        queue: &SpinLockGuard<RBTree<i64, Arc<ProcessControlBlock>>>,
-    ) -> Option<i64> {
-        if !queue.is_empty() {
-            return Some(queue.get_first().unwrap().1.sched_info().virtual_runtime() as i64);
-        } else {
-            return None;
-        }
+    pub fn min_vruntime<V>(queue: &SpinLockGuard<RBTree<i64, V>>) -> Option<i64> {
+        return queue.get_first().map(|(vruntime, _)| *vruntime);
     }
     /// 获取运行队列的长度
-    pub fn get_cfs_queue_size(
-        queue: &SpinLockGuard<RBTree<i64, Arc<ProcessControlBlock>>>,
-    ) -> usize {
+    pub fn get_cfs_queue_size<V>(queue: &SpinLockGuard<RBTree<i64, V>>) -> usize {
         return queue.len();
     }
 }
 
+/// 一个cpu上cfs调度器的统计信息，用于/proc/schedstat
+#[derive(Debug, Default)]
+pub struct CpuSchedStat {
+    /// 调用`sched()`的次数
+    sched_count: AtomicU64,
+    /// `sched()`选中IDLE进程的次数
+    sched_goidle: AtomicU64,
+    /// 迁入该cpu的进程数
+    migrations_in: AtomicU64,
+    /// 迁出该cpu的进程数
+    migrations_out: AtomicU64,
+    /// 非IDLE进程在该cpu上运行的时钟中断次数
+    run_ticks: AtomicU64,
+}
+
+impl CpuSchedStat {
+    pub fn sched_count(&self) -> u64 {
+        return self.sched_count.load(Ordering::SeqCst);
+    }
+
+    pub fn sched_goidle(&self) -> u64 {
+        return self.sched_goidle.load(Ordering::SeqCst);
+    }
+
+    pub fn migrations_in(&self) -> u64 {
+        return self.migrations_in.load(Ordering::SeqCst);
+    }
+
+    pub fn migrations_out(&self) -> u64 {
+        return self.migrations_out.load(Ordering::SeqCst);
+    }
+
+    pub fn run_ticks(&self) -> u64 {
+        return self.run_ticks.load(Ordering::SeqCst);
+    }
+}
+
 /// @brief CFS调度器类
 pub struct SchedulerCFS {
-    cpu_queue: Vec<&'static mut CFSQueue>,
+    /// 每个cpu的cfs队列
+    ///
+    /// 队列使用Arc共享，以便进程组的调度实体能够引用其所在的队列（cfs_rq）以及其管理的队列（my_q）。
+    /// 不存在的cpu没有队列。
+    cpu_queue: PerCpuVar<Option<Arc<SpinLock<CFSQueue>>>>,
+    /// 每个cpu的调度统计信息
+    stats: PerCpuVar<CpuSchedStat>,
+    /// cpu频率调节器，在每次时钟中断时接收cpu的利用率
+    governor: Box<dyn CpuFreqGovernor>,
+    /// 拥有队列的cpu的数目
+    cpu_num: usize,
 }
 
 impl SchedulerCFS {
     pub fn new() -> SchedulerCFS {
-        // 暂时手动指定核心数目
-        // todo: 从cpu模块来获取核心的数目
-        let mut result = SchedulerCFS {
-            cpu_queue: Default::default(),
+        return Self::with_cpu_num(smp_possible_cpu_num());
+    }
+
+    /// 创建一个cfs调度器，为cpu id位于`[0, cpu_num)`的每个cpu创建一个队列
+    ///
+    /// ## 参数
+    ///
+    /// - `cpu_num` : cpu的数目，不能超过idle进程的数目
+    pub fn with_cpu_num(cpu_num: usize) -> SchedulerCFS {
+        // 为每个cpu核心创建队列，idle进程必须在此之前由进程管理器初始化
+        let idle_pcbs = ProcessManager::idle_pcb();
+        assert!(
+            cpu_num > 0 && cpu_num <= idle_pcbs.len(),
+            "The number of cpus exceeds the number of idle processes"
+        );
+
+        let cpu_queue = idle_pcbs
+            .iter()
+            .take(PerCpu::MAX_CPU_NUM)
+            .enumerate()
+            .map(|(cpu, idle_pcb)| {
+                if cpu < cpu_num {
+                    Some(Arc::new(SpinLock::new(CFSQueue::new(idle_pcb.clone()))))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let stats = (0..cpu_queue.len())
+            .map(|_| CpuSchedStat::default())
+            .collect();
+        return SchedulerCFS {
+            cpu_queue: PerCpuVar::new(cpu_queue).expect("Failed to create cfs per cpu queues"),
+            stats: PerCpuVar::new(stats).expect("Failed to create cfs per cpu stats"),
+            governor: Box::new(NoopGovernor),
+            cpu_num,
         };
+    }
 
-        // 为每个cpu核心创建队列，进程重构后可以直接初始化Idle_pcb？
-        for i in 0..MAX_CPU_NUM {
-            let idle_pcb = ProcessManager::idle_pcb()[i as usize].clone();
-            result
-                .cpu_queue
-                .push(Box::leak(Box::new(CFSQueue::new(idle_pcb))));
-        }
+    /// 获取调度器管理的cpu的数目
+    pub fn cpu_num(&self) -> usize {
+        return self.cpu_num;
+    }
+
+    /// 获取所有cpu的队列中可运行的调度实体的总数
+    pub fn nr_running(&self) -> usize {
+        return (0..self.cpu_num)
+            .map(|cpu| self.queue_of(cpu).lock_irqsave().nr_running())
+            .sum();
+    }
+
+    /// 设置cpu频率调节器，替换原来的调节器
+    pub fn set_governor(&mut self, governor: Box<dyn CpuFreqGovernor>) {
+        self.governor = governor;
+    }
 
-        return result;
+    /// 获取某个cpu的队列的最小虚拟运行时间，队列为空时返回None
+    pub fn min_vruntime(&self, cpu: usize) -> Option<isize> {
+        let cpu_queue = self.cpu_queue(cpu);
+        let queue = cpu_queue.locked_queue.lock();
+        return CFSQueue::min_vruntime(&queue).map(|min| min as isize);
     }
 
-    pub fn get_cpu_queue(&self) -> Vec<&'static mut CFSQueue> {
-        return self.cpu_queue;
+    /// 获取某个cpu的负载，即该cpu上可运行的进程的权重之和随时间衰减的滑动平均值
+    ///
+    /// 一个持续运行的普通进程贡献`NICE_0_LOAD`。负载均衡、电源管理以及统计信息都应当使用这个值，
+    /// 而不是各自根据队列的长度计算负载
+    pub fn cpu_load(&self, cpu: usize) -> u64 {
+        return self.cpu_queue(cpu).load_avg;
+    }
+
+    /// 获取某个cpu的调度统计信息
+    pub fn cpu_stat(&self, cpu: usize) -> &CpuSchedStat {
+        return self.stats.force_get(cpu);
+    }
+
+    /// 获取某个cpu的队列
+    ///
+    /// ## Panics
+    ///
+    /// 该cpu没有队列（不存在）
+    #[inline]
+    fn queue_of(&self, cpu: usize) -> &Arc<SpinLock<CFSQueue>> {
+        return self
+            .cpu_queue
+            .force_get(cpu)
+            .as_ref()
+            .unwrap_or_else(|| panic!("cpu {} has no cfs queue", cpu));
+    }
+
+    /// 获取某个cpu的cfs队列
+    ///
+    /// ## 返回值
+    ///
+    /// 该cpu的cfs队列的锁守卫，持有期间中断被关闭
+    pub fn cpu_queue(&self, cpu: usize) -> SpinLockGuard<CFSQueue> {
+        return self.queue_of(cpu).lock_irqsave();
+    }
+
+    /// 获取当前cpu的cfs队列
+    ///
+    /// ## 返回值
+    ///
+    /// 当前cpu的cfs队列的锁守卫，持有期间中断被关闭
+    pub fn this_cpu_queue(&self) -> SpinLockGuard<CFSQueue> {
+        return self.cpu_queue(smp_get_processor_id() as usize);
+    }
+
+    /// 获取某个cpu的cfs队列的共享引用，用于让调度实体指向该队列
+    pub fn shared_cpu_queue(&self, cpu: usize) -> Arc<SpinLock<CFSQueue>> {
+        return self.queue_of(cpu).clone();
     }
 
     /// @brief 更新这个cpu上，这个进程的可执行时间。
     #[inline]
-    fn update_cpu_exec_proc_jiffies(
-        _priority: SchedPriority,
-        cfs_queue: &mut CFSQueue,
-    ) -> &mut CFSQueue {
+    fn update_cpu_exec_proc_time(policy: SchedPolicy, cfs_queue: &mut CFSQueue) -> &mut CFSQueue {
         // todo: 引入调度周期以及所有进程的优先权进行计算，然后设置分配给进程的可执行时间
-        cfs_queue.cpu_exec_proc_jiffies = 10;
+        cfs_queue.cpu_exec_proc_ns = match policy {
+            SchedPolicy::BATCH => SCHED_BATCH_SLICE_NS,
+            _ => SCHED_SLICE_NS,
+        };
 
         return cfs_queue;
     }
 
     /// @brief 时钟中断到来时，由sched的core模块中的函数，调用本函数，更新CFS进程的可执行时间
+    ///
+    /// 以调度器时钟的当前读数调用`timer_update`
     pub fn timer_update_jiffies(&mut self) {
-        let current_cpu_queue: &mut CFSQueue = self.cpu_queue[smp_get_processor_id() as usize];
+        self.timer_update(sched_clock());
+    }
+
+    /// 时钟中断到来时，将当前进程实际运行的时间计入它的虚拟运行时间，并更新cpu的利用率
+    ///
+    /// ## 参数
+    ///
+    /// - `now` : 调度器时钟的读数（单位：纳秒）
+    pub fn timer_update(&mut self, now: u64) {
+        let current = ProcessManager::current_pcb();
+        let mut current_cpu_queue = self.this_cpu_queue();
         // todo: 引入调度周期以及所有进程的优先权进行计算，然后设置进程的可执行时间
 
-        // 更新进程的剩余可执行时间
-        let queue = current_cpu_queue.locked_queue.lock();
-        current_cpu_queue.cpu_exec_proc_jiffies -= 1;
-        // 时间片耗尽，标记需要被调度
-        if current_cpu_queue.cpu_exec_proc_jiffies <= 0 {
-            ProcessManager::current_pcb()
-                .flags()
-                .insert(ProcessFlags::NEED_SCHEDULE);
+        // 更新进程的运行时间，时间片耗尽时标记需要被调度
+        current_cpu_queue.update_curr(&current, now);
+        let busy = current.pid().into() != 0;
+        current_cpu_queue.util_avg = update_util_avg(current_cpu_queue.util_avg, busy);
+        let current_weight = if busy {
+            CFSQueue::sched_weight(current.sched_info().policy())
+        } else {
+            0
+        };
+        let load = current_cpu_queue.load_weight.load(Ordering::SeqCst) + current_weight;
+        current_cpu_queue.load_avg = CFSQueue::update_load_avg(current_cpu_queue.load_avg, load);
+        let util = cpu_util(current_cpu_queue.util_avg, current_cpu_queue.nr_running());
+        drop(current_cpu_queue);
+        if busy {
+            self.stats.get().run_ticks.fetch_add(1, Ordering::SeqCst);
         }
-        drop(queue);
-
-        // 更新当前进程的虚拟运行时间
-        ProcessManager::current_pcb()
-            .sched_info()
-            .increase_virtual_runtime(1);
+        self.governor.update(smp_get_processor_id() as usize, util);
     }
 
-    /// @brief 将进程加入cpu的cfs调度队列，并且重设其虚拟运行时间为当前队列的最小值
+    /// @brief 将被唤醒的进程加入cpu的cfs调度队列，并且按照`place_entity`调整其虚拟运行时间
     pub fn enqueue_reset_vruntime(&mut self, pcb: Arc<ProcessControlBlock>) {
-        let cpu_queue = &mut self.cpu_queue[pcb.sched_info().on_cpu().unwrap() as usize];
-        let queue = cpu_queue.locked_queue.lock();
-        if queue.len() > 0 {
-            pcb.sched_info()
-                .set_virtual_runtime(CFSQueue::min_vruntime(&queue).unwrap_or(0) as isize)
+        let cpu_id = pcb.sched_info().on_cpu().unwrap() as usize;
+        self.cpu_queue(cpu_id).enqueue_reset_vruntime(pcb.clone());
+        Self::kick_if_preempt(&pcb, cpu_id);
+    }
+
+    /// 将进程加入指定cpu的cfs调度队列
+    ///
+    /// 唤醒进程的cpu不一定是进程所在队列的cpu。入队后，如果目标cpu正在运行idle进程，
+    /// 或者进程应当抢占目标cpu上正在运行的进程，就向目标cpu发送IPI，使其尽快进行调度。
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 要加入队列的进程
+    /// - `cpu_id` : 目标cpu
+    ///
+    /// ## 返回值
+    ///
+    /// 是否向目标cpu发送了IPI
+    pub fn enqueue_on(&self, pcb: Arc<ProcessControlBlock>, cpu_id: usize) -> bool {
+        self.cpu_queue(cpu_id).enqueue(pcb.clone());
+        return Self::kick_if_preempt(&pcb, cpu_id);
+    }
+
+    /// 判断刚刚加入队列的进程是否需要抢占目标cpu，如果是，则向目标cpu发送IPI
+    ///
+    /// 必须在释放目标cpu队列的锁之后调用
+    fn kick_if_preempt(pcb: &Arc<ProcessControlBlock>, cpu_id: usize) -> bool {
+        if !ProcessManager::should_preempt(pcb, cpu_id as u32) {
+            return false;
+        }
+        kick_cpu(cpu_id as u32).expect("SchedulerCFS: Failed to kick cpu");
+        return true;
+    }
+
+    /// 将进程从一个cpu的队列迁移到另一个cpu的队列时，调整它的虚拟运行时间
+    ///
+    /// 不同cpu的队列的虚拟运行时间互不相关，因此保留进程相对于源队列最小值的差值，
+    /// 再以目标队列的最小值为基准，保证进程在目标队列中既不会饿死，也不会长期霸占cpu。
+    /// 队列为空时没有可以参考的最小值，此时不改变进程的虚拟运行时间。
+    ///
+    /// 两个队列的锁分别获取，不会同时持有，避免两个cpu相互迁移时死锁。
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 要迁移的进程，不能位于任何队列中
+    /// - `src` : 源cpu
+    /// - `dst` : 目标cpu
+    pub fn migrate_entity(&self, pcb: &Arc<ProcessControlBlock>, src: usize, dst: usize) {
+        if src == dst {
+            return;
+        }
+        trace_sched_migrate(pcb, src, dst);
+        self.cpu_stat(src)
+            .migrations_out
+            .fetch_add(1, Ordering::SeqCst);
+        self.cpu_stat(dst)
+            .migrations_in
+            .fetch_add(1, Ordering::SeqCst);
+
+        let src_min = CFSQueue::min_vruntime(&self.cpu_queue(src).locked_queue.lock());
+        let dst_min = CFSQueue::min_vruntime(&self.cpu_queue(dst).locked_queue.lock());
+
+        if let (Some(src_min), Some(dst_min)) = (src_min, dst_min) {
+            let sched_info = pcb.sched_info();
+            let relative = sched_info.virtual_runtime() - src_min as isize;
+            sched_info.set_virtual_runtime(dst_min as isize + relative);
+        }
+    }
+
+    /// 将下线的cpu的队列中的进程迁移到其余的在线cpu上
+    ///
+    /// 每个进程被迁移到允许它运行的在线cpu中`nr_running`最小的一个（相同时选择id最小的），
+    /// 计数随着迁移而更新，因此进程会被均匀地分散。进程组的调度实体属于该cpu，留在队列中。
+    ///
+    /// ## 参数
+    ///
+    /// - `cpu` : 下线的cpu
+    /// - `online` : 在线的cpu的集合，不能包含`cpu`
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：被迁移的进程的数目
+    /// - 失败：没有其他在线的cpu能够接收进程时，返回`EBUSY`，无法迁移的进程留在原来的队列中
+    pub fn drain_cpu(&self, cpu: usize, online: CpuMask) -> Result<usize, SystemError> {
+        if !(0..self.cpu_num).any(|dst| dst != cpu && online.contains(dst)) {
+            return Err(SystemError::EBUSY);
+        }
+        let tasks = self
+            .cpu_queue(cpu)
+            .locked_queue
+            .lock()
+            .values()
+            .filter_map(|se| se.pcb())
+            .collect::<Vec<_>>();
+
+        let mut migrated = 0;
+        let mut stranded = false;
+        for pcb in tasks.iter() {
+            // 进程正在被其他cpu窃取，由窃取者负责它的去向
+            if !pcb.sched_info().start_migration() {
+                continue;
+            }
+            // 先选出目标cpu，找不到时进程留在队列中
+            let target = match self.migrate_target(pcb, Some(cpu), online) {
+                Some(target) => target,
+                None => {
+                    pcb.sched_info().cancel_migration();
+                    stranded = true;
+                    continue;
+                }
+            };
+            // 释放锁之后，进程可能已经被取出运行，此时不能再把它放入其他队列
+            if !self.dequeue_pcb(pcb, cpu) {
+                pcb.sched_info().cancel_migration();
+                continue;
+            }
+
+            self.migrate_entity(pcb, cpu, target);
+            pcb.sched_info().finish_migration(Some(target as u32));
+            self.enqueue_on(pcb.clone(), target);
+            migrated += 1;
+        }
+        if stranded {
+            return Err(SystemError::EBUSY);
+        }
+        return Ok(migrated);
+    }
+
+    /// 在允许进程运行的在线cpu中，选出`nr_running`最小的一个（相同时选择id最小的）
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 要迁移的进程
+    /// - `exclude` : 不能被选中的cpu
+    /// - `online` : 在线的cpu的集合
+    fn migrate_target(
+        &self,
+        pcb: &Arc<ProcessControlBlock>,
+        exclude: Option<usize>,
+        online: CpuMask,
+    ) -> Option<usize> {
+        let allowed = task_allowed_cpus(pcb, online, self.cpu_num);
+        return (0..self.cpu_num)
+            .filter(|dst| Some(*dst) != exclude && allowed.contains(*dst))
+            .min_by_key(|dst| self.cpu_queue(*dst).nr_running());
+    }
+
+    /// 将不再允许在`cpu`上运行的进程迁移到允许它运行的在线cpu中`nr_running`最小的一个
+    ///
+    /// 调用者不能持有任何cfs队列的锁
+    ///
+    /// ## 参数
+    ///
+    /// - `pcb` : 要迁移的进程，不能位于任何队列中
+    /// - `cpu` : 进程当前所在的cpu
+    /// - `online` : 在线的cpu的集合
+    ///
+    /// ## 返回值
+    ///
+    /// 进程被迁移到的cpu。没有允许它运行的在线cpu时，进程重新加入`cpu`的队列。
+    /// 进程正在被其他cpu迁移时，同样将进程重新加入`cpu`的队列并返回None，由对方决定进程的去向
+    pub fn migrate_disallowed(
+        &self,
+        pcb: &Arc<ProcessControlBlock>,
+        cpu: usize,
+        online: CpuMask,
+    ) -> Option<usize> {
+        if !pcb.sched_info().start_migration() {
+            // 进程已经被移出队列，对方只能在队列中找到它，不能在这里丢弃它
+            self.enqueue_on(pcb.clone(), cpu);
+            return None;
+        }
+        let target = self.migrate_target(pcb, None, online).unwrap_or(cpu);
+
+        if target != cpu {
+            self.migrate_entity(pcb, cpu, target);
+        }
+        pcb.sched_info().finish_migration(Some(target as u32));
+        self.enqueue_on(pcb.clone(), target);
+        return Some(target);
+    }
+
+    /// 从cpu的队列中选出下一个要运行的进程
+    ///
+    /// 如果最优可运行实体是一个进程组，则继续从该进程组的队列中选择，直到选到一个进程。
+    /// 进程入队之后，它的cpu亲和性可能被修改为不再包含该cpu，这样的进程不会被选中，
+    /// 而是被放入`misplaced`中，由调用者在释放队列的锁之后迁移
+    ///
+    /// ## 参数
+    ///
+    /// - `queue` : cpu的cfs队列
+    /// - `cpu` : 队列所属的cpu
+    /// - `misplaced` : 不再允许在该cpu上运行的进程，它们已经被移出队列
+    fn pick_next_task(
+        queue: &mut CFSQueue,
+        cpu: usize,
+        misplaced: &mut Vec<Arc<ProcessControlBlock>>,
+    ) -> Option<Arc<ProcessControlBlock>> {
+        // 调度实体从红黑树中被移出而不是被克隆。
+        // 进程的调度实体同时被pcb持有，因此即使这里的引用被释放，正在运行的进程的调度实体也不会被销毁
+        let mut next_se: Option<Arc<SchedEntity>> = queue.dequeue_se();
+        while let Some(se) = next_se {
+            if let Some(pcb) = se.pcb() {
+                if pcb.effective_affinity().contains(cpu) {
+                    return Some(pcb);
+                }
+                misplaced.push(pcb);
+                next_se = queue.dequeue_se();
+                continue;
+            }
+            next_se = se
+                .group_cfs_rq()
+                .and_then(|group_queue| group_queue.lock_irqsave().dequeue_se());
         }
-        drop(queue);
-        cpu_queue.enqueue(pcb);
+        return None;
+    }
+
+    /// 即将运行IDLE进程时，从最繁忙的cpu的队列中窃取一个进程
+    ///
+    /// 从`cpu`的下一个cpu开始，最多检查`SCHED_STEAL_SCAN_MAX`个cpu，选出`nr_running`最大的一个。
+    /// 它只有一个可运行的实体时，窃取只会让它变为空闲，因此不进行窃取。被窃取的是该队列中
+    /// 虚拟运行时间最小、并且允许在`cpu`上运行的进程，它的虚拟运行时间按照`migrate_entity`调整。
+    ///
+    /// 调用者不能持有任何cfs队列的锁
+    ///
+    /// ## 返回值
+    ///
+    /// 被窃取的进程，它已经被移出原来的队列，并且`on_cpu`被设置为`cpu`
+    pub fn steal_task(&self, cpu: usize) -> Option<Arc<ProcessControlBlock>> {
+        let (busiest, nr_running) = (1..self.cpu_num)
+            .take(SCHED_STEAL_SCAN_MAX)
+            .map(|offset| (cpu + offset) % self.cpu_num)
+            .map(|src| (src, self.cpu_queue(src).nr_running()))
+            .max_by_key(|(_, nr_running)| *nr_running)?;
+        if nr_running <= 1 {
+            return None;
+        }
+
+        let pcb = self
+            .cpu_queue(busiest)
+            .locked_queue
+            .lock()
+            .values()
+            .filter_map(|se| se.pcb())
+            .find(|pcb| {
+                !pcb.sched_info().is_migrating() && pcb.effective_affinity().contains(cpu)
+            })?;
+        // 其他cpu可能同时选中了这个进程，只有先标记迁移的一方能够窃取它
+        if !pcb.sched_info().start_migration() {
+            return None;
+        }
+        // 释放锁之后，进程可能已经被源cpu取出运行
+        if !self.dequeue_pcb(&pcb, busiest) {
+            pcb.sched_info().cancel_migration();
+            return None;
+        }
+        self.migrate_entity(&pcb, busiest, cpu);
+        pcb.sched_info().finish_migration(Some(cpu as u32));
+        return Some(pcb);
+    }
+
+    /// 将进程从指定cpu的cfs队列中移出
+    ///
+    /// ## 返回值
+    ///
+    /// 进程是否在队列中
+    pub fn dequeue_pcb(&self, pcb: &Arc<ProcessControlBlock>, cpu_id: usize) -> bool {
+        return self.cpu_queue(cpu_id).dequeue_pcb(pcb);
+    }
+
+    /// 在当前cpu的队列上，为新fork出的子进程确定初始的虚拟运行时间
+    pub fn task_fork(&self, parent: &Arc<ProcessControlBlock>, child: &Arc<ProcessControlBlock>) {
+        self.this_cpu_queue().task_fork(parent, child);
     }
 
     ///@brief 将某进程的se添加到cfsqueue
     /// ! 应该将pcb的se添加到自己进程组下的Scheduler 的cfsqueue[cpu]中
     pub fn enqueue_se(&mut self, pcb: Arc<ProcessControlBlock>) {
-        let cpu_queue = &mut self.cpu_queue[pcb.sched_info().on_cpu().unwrap() as usize];
+        let mut cpu_queue = self.cpu_queue(pcb.sched_info().on_cpu().unwrap() as usize);
         cpu_queue.enqueue_se(pcb.se());
     }
 
     ///@brief 在进程组创建时就将某进程组的se添加到cfsqueue
-    pub fn enqueue_group_se(&mut self, tg_se: Arc<SchedEntity>, cpu: usize) {
-        let cpu_queue = &mut self.cpu_queue[cpu];
-        cpu_queue.enqueue_se(tg_se);
+    pub fn enqueue_group_se(&self, tg_se: Arc<SchedEntity>, cpu: usize) {
+        self.cpu_queue(cpu).enqueue_se(tg_se);
     }
 
     /// @brief 设置cpu的队列的IDLE进程的pcb
     #[allow(dead_code)]
     pub fn set_cpu_idle(&mut self, cpu_id: usize, pcb: Arc<ProcessControlBlock>) {
         // kdebug!("set cpu idle: id={}", cpu_id);
-        self.cpu_queue[cpu_id].idle_pcb = pcb;
-    }
-    /// 获取某个cpu的运行队列中的进程数
-    pub fn get_cfs_queue_len(&mut self, cpu_id: u32) -> usize {
-        let queue = self.cpu_queue[cpu_id as usize].locked_queue.lock();
-        return CFSQueue::get_cfs_queue_size(&queue);
+        self.cpu_queue(cpu_id).idle_pcb = pcb;
     }
 }
 
 impl Scheduler for SchedulerCFS {
     /// @brief 在当前cpu上进行调度。
     /// 请注意，进入该函数之前，需要关中断
-    ///! 返回 se
     fn sched(&mut self) -> Option<Arc<ProcessControlBlock>> {
         assert!(CurrentIrqArch::is_irq_enabled() == false);
 
-        ProcessManager::current_pcb()
-            .flags()
-            .remove(ProcessFlags::NEED_SCHEDULE);
-
-        let current_cpu_id = smp_get_processor_id() as usize;
+        // 整个调度过程中只获取一次当前进程的pcb，避免反复增减引用计数
+        let current = ProcessManager::current_pcb();
+        current.flags().remove(ProcessFlags::NEED_SCHEDULE);
+        let stat = self.stats.get();
+        stat.sched_count.fetch_add(1, Ordering::SeqCst);
 
-        let current_cpu_queue: &mut CFSQueue = self.cpu_queue[current_cpu_id];
+        let mut current_cpu_queue = self.this_cpu_queue();
+        // 切换之前，将当前进程上一次计时以来运行的时间计入，下一个进程从此刻开始计时
+        current_cpu_queue.update_curr(&current, sched_clock());
 
-        //let proc: Arc<ProcessControlBlock> = current_cpu_queue.dequeue();
-        let se: Arc<SchedEntity> = current_cpu_queue.dequeue_se();
+        let cpu = smp_get_processor_id() as usize;
+        let mut misplaced = Vec::new();
+        let mut next_pcb = Self::pick_next_task(&mut current_cpu_queue, cpu, &mut misplaced);
         compiler_fence(core::sync::atomic::Ordering::SeqCst);
 
-        ///! se中的调度信息的设置
-        let mut cfs_queue: Option<CFSQueue> = None;
-        while let Some(_) = current_cpu_queue.dequeue() {
-            if cfs_queue.is_none() {
-                break; // 当 cfs_queue 为空时即选到了要调度的进程，则退出循环
+        // 迁移需要获取其他cpu的队列的锁，因此需要先释放当前cpu的队列的锁
+        if !misplaced.is_empty() {
+            drop(current_cpu_queue);
+            let online = cpu_online_mask();
+            for pcb in misplaced.iter() {
+                self.migrate_disallowed(pcb, cpu, online);
             }
-            // 如果最优可运行实体是一个进程组
-            cfs_queue = se.group_cfs_rq(); // 更新 cfs_queue 的值
+            current_cpu_queue = self.this_cpu_queue();
+        }
+
+        let current_runnable = current.sched_info().state() == ProcessState::Runnable;
+        // 即将运行IDLE进程时，先尝试从最繁忙的cpu上窃取一个进程。窃取需要获取其他cpu的队列的锁，
+        // 为了避免两个cpu相互窃取时死锁，需要先释放当前cpu的队列的锁
+        if next_pcb.is_none() && (current.pid().into() == 0 || !current_runnable) {
+            drop(current_cpu_queue);
+            next_pcb = self.steal_task(cpu);
+            current_cpu_queue = self.this_cpu_queue();
         }
 
-        let final_cpu_queue: &mut CFSQueue = cfs_queue[current_cpu_id];
-        let proc: Arc<ProcessControlBlock> = se.pcb();
+        // 没有可运行的实体，则运行IDLE进程
+        let proc: Arc<ProcessControlBlock> = next_pcb.unwrap_or_else(|| {
+            stat.sched_goidle.fetch_add(1, Ordering::SeqCst);
+            current_cpu_queue.idle_pcb.clone()
+        });
+
         // 如果当前不是running态，或者当前进程的虚拟运行时间大于等于下一个进程的，那就需要切换。
-        if (ProcessManager::current_pcb().sched_info().state() != ProcessState::Runnable)
-            || (ProcessManager::current_pcb().sched_info().virtual_runtime()
-                >= proc.sched_info().virtual_runtime())
+        if !current_runnable
+            || (current.sched_info().virtual_runtime() >= proc.sched_info().virtual_runtime())
         {
-            compiler_fence(core::sync::atomic::Ordering::SeqCst);
-            // 本次切换由于时间片到期引发，则再次加入就绪队列，否则交由其它功能模块进行管理
-            if ProcessManager::current_pcb().sched_info().state() == ProcessState::Runnable {
-                sched_enqueue(ProcessManager::current_pcb(), false);
-                compiler_fence(core::sync::atomic::Ordering::SeqCst);
-            }
-            compiler_fence(core::sync::atomic::Ordering::SeqCst);
-
             // 设置进程可以执行的时间
-            //if current_cpu_queue.cpu_exec_proc_jiffies <= 0 {
-            if final_cpu_queue.cpu_exec_proc_jiffies <= 0 {
-                SchedulerCFS::update_cpu_exec_proc_jiffies(
-                    proc.sched_info().priority(),
-                    current_cpu_queue,
+            if current_cpu_queue.cpu_exec_proc_ns <= 0 {
+                SchedulerCFS::update_cpu_exec_proc_time(
+                    proc.sched_info().policy(),
+                    &mut current_cpu_queue,
                 );
             }
+            // 重新入队时会再次获取队列的锁，因此需要先释放
+            drop(current_cpu_queue);
 
+            compiler_fence(core::sync::atomic::Ordering::SeqCst);
+            // 本次切换由于时间片到期引发，则再次加入就绪队列，否则交由其它功能模块进行管理
+            if current_runnable {
+                sched_enqueue(current, false);
+                compiler_fence(core::sync::atomic::Ordering::SeqCst);
+            }
             compiler_fence(core::sync::atomic::Ordering::SeqCst);
 
-            return Some(se);
+            return Some(proc);
         } else {
             // 不进行切换
 
             // 设置进程可以执行的时间
             compiler_fence(core::sync::atomic::Ordering::SeqCst);
-            //if current_cpu_queue.cpu_exec_proc_jiffies <= 0 {
-            if final_cpu_queue.cpu_exec_proc_jiffies <= 0 {
-                SchedulerCFS::update_cpu_exec_proc_jiffies(
-                    ProcessManager::current_pcb().sched_info().priority(),
-                    //current_cpu_queue,
-                    final_cpu_queue,
+            if current_cpu_queue.cpu_exec_proc_ns <= 0 {
+                SchedulerCFS::update_cpu_exec_proc_time(
+                    current.sched_info().policy(),
+                    &mut current_cpu_queue,
                 );
                 // kdebug!("cpu:{:?}",current_cpu_id);
             }
+            drop(current_cpu_queue);
 
             compiler_fence(core::sync::atomic::Ordering::SeqCst);
             sched_enqueue(proc, false);
@@ -308,8 +1035,871 @@ impl Scheduler for SchedulerCFS {
     }
 
     fn enqueue_pcb(&mut self, pcb: Arc<ProcessControlBlock>) {
-        let cpu_queue = &mut self.cpu_queue[pcb.sched_info().on_cpu().unwrap() as usize];
+        // 进程的目标cpu已经由负载均衡决定
+        let cpu_id = pcb.sched_info().on_cpu().unwrap() as usize;
+        self.enqueue_on(pcb, cpu_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        hint::spin_loop,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    };
+
+    use alloc::{boxed::Box, string::ToString, sync::Arc, vec::Vec};
+
+    use crate::{
+        arch::CurrentIrqArch,
+        exception::InterruptArch,
+        include::bindings::bindings::smp_get_total_cpu,
+        libs::spinlock::SpinLock,
+        process::{
+            kthread::{KernelThreadClosure, KernelThreadMechanism},
+            ProcessFlags, ProcessManager, SchedEntity,
+        },
+        sched::{
+            clock::sched_clock,
+            core::{need_resched, Scheduler, SCHED_TICK_INTERVAL_NS},
+            cpufreq::{CpuFreqGovernor, SCHED_CAPACITY_SCALE},
+            SchedPolicy, SchedPriority,
+        },
+        smp::{core::smp_get_processor_id, cpumask::CpuMask},
+        syscall::SystemError,
+    };
+
+    use super::{
+        CFSQueue, SchedulerCFS, NICE_0_LOAD, SCHED_INTERACTIVE_BONUS_MAX, SCHED_SLEEPER_CREDIT,
+        SCHED_SLEEP_AVG_MAX_US, SCHED_SLICE_NS, SCHED_VRUNTIME_LAG_MAX, WEIGHT_IDLEPRIO,
+    };
+
+    /// 忙碌的进程的虚拟运行时间（单位：纳秒），为1秒
+    const BUSY_VRUNTIME: isize = 1_000_000_000;
+
+    /// 睡眠了很久的进程醒来后能够尽快运行，但是补偿有上限，运行片刻后就要让出cpu
+    #[test]
+    fn test_place_entity_sleeper() {
+        let idle = ProcessManager::idle_pcb();
+        let mut queue = CFSQueue::new(idle[0].clone());
+        let busy = SchedEntity::new();
+        busy.set_virtual_runtime(BUSY_VRUNTIME);
+        queue.enqueue_se(busy.clone());
+
+        let sleeper = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "sleeper".to_string(),
+        )
+        .unwrap();
+        sleeper.sched_info().set_virtual_runtime(0);
+        queue.enqueue_reset_vruntime(sleeper.clone());
+        assert_eq!(
+            sleeper.sched_info().virtual_runtime(),
+            BUSY_VRUNTIME - SCHED_SLEEPER_CREDIT
+        );
+        assert!(Arc::ptr_eq(&queue.dequeue(), &sleeper));
+
+        // 用完补偿之后，忙碌的进程排在它前面
+        sleeper
+            .sched_info()
+            .increase_virtual_runtime(SCHED_SLEEPER_CREDIT + 1);
+        queue.enqueue(sleeper.clone());
+        assert!(Arc::ptr_eq(&queue.dequeue_se().unwrap(), &busy));
+
+        // 只睡眠了片刻的进程保持原来的虚拟运行时间
+        assert_eq!(CFSQueue::place_entity(998, 1000, 0), 998);
+    }
+
+    /// 经常睡眠的进程醒来后排在cpu密集型进程之前；变为cpu密集型之后，补偿随之消失
+    #[test]
+    fn test_interactive_bonus() {
+        let idle = ProcessManager::idle_pcb();
+        let mut queue = CFSQueue::new(idle[0].clone());
+        let busy = SchedEntity::new();
+        busy.set_virtual_runtime(BUSY_VRUNTIME);
+        queue.enqueue_se(busy);
+
+        let create = |name: &str| {
+            let pcb = KernelThreadMechanism::create(
+                KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                name.to_string(),
+            )
+            .unwrap();
+            pcb.sched_info().set_virtual_runtime(0);
+            pcb
+        };
+        let io_bound = create("io-bound");
+        let cpu_bound = create("cpu-bound");
+
+        // I/O密集型进程反复短暂运行后长时间睡眠
+        for i in 0..10 {
+            let start = i * 200_000;
+            io_bound.sched_info().account_time(false, 1_000);
+            io_bound.sched_info().mark_sleep_start(start);
+            io_bound.sched_info().account_sleep(start + 150_000);
+        }
+        assert_eq!(io_bound.sched_info().sleep_avg(), SCHED_SLEEP_AVG_MAX_US);
+        // cpu密集型进程几乎不睡眠
+        cpu_bound.sched_info().mark_sleep_start(0);
+        cpu_bound.sched_info().account_sleep(1_000);
+        cpu_bound.sched_info().account_time(true, 100_000);
+        assert_eq!(cpu_bound.sched_info().sleep_avg(), 0);
+
+        queue.enqueue_reset_vruntime(cpu_bound.clone());
+        queue.enqueue_reset_vruntime(io_bound.clone());
+        assert_eq!(
+            io_bound.sched_info().virtual_runtime(),
+            BUSY_VRUNTIME - SCHED_SLEEPER_CREDIT - SCHED_INTERACTIVE_BONUS_MAX
+        );
+        assert_eq!(
+            cpu_bound.sched_info().virtual_runtime(),
+            BUSY_VRUNTIME - SCHED_SLEEPER_CREDIT
+        );
+        // I/O密集型进程的调度延迟更小
+        assert!(Arc::ptr_eq(&queue.dequeue(), &io_bound));
+        assert!(Arc::ptr_eq(&queue.dequeue(), &cpu_bound));
+
+        // 持续运行之后，补偿衰减为0
+        io_bound
+            .sched_info()
+            .account_time(true, SCHED_SLEEP_AVG_MAX_US);
+        assert_eq!(
+            CFSQueue::interactive_bonus(io_bound.sched_info().sleep_avg()),
+            0
+        );
+    }
+
+    /// 无论入队时的虚拟运行时间多么极端，都被限制在队列最小虚拟运行时间附近
+    #[test]
+    fn test_clamp_vruntime_extremes() {
+        let idle = ProcessManager::idle_pcb();
+        let mut queue = CFSQueue::new(idle[0].clone());
+        let anchor = SchedEntity::new();
+        anchor.set_virtual_runtime(0);
+        queue.enqueue_se(anchor);
+
+        // 线性同余生成器产生的伪随机值，加上边界值
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut values = Vec::from([isize::MIN, isize::MIN + 1, -1, 0, 1, isize::MAX]);
+        for _ in 0..64 {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            values.push(seed as isize);
+        }
+
+        for vruntime in values {
+            let min = CFSQueue::min_vruntime(&queue.locked_queue.lock()).unwrap() as isize;
+            let se = SchedEntity::new();
+            se.set_virtual_runtime(vruntime);
+            queue.enqueue_se(se.clone());
+            let placed = se.virtual_runtime();
+            assert!((placed - min).abs() <= SCHED_VRUNTIME_LAG_MAX);
+            assert_eq!(
+                placed,
+                vruntime.clamp(min - SCHED_VRUNTIME_LAG_MAX, min + SCHED_VRUNTIME_LAG_MAX)
+            );
+        }
+
+        assert_eq!(CFSQueue::clamp_vruntime(isize::MAX, isize::MAX), isize::MAX);
+        assert_eq!(CFSQueue::clamp_vruntime(isize::MIN, isize::MIN), isize::MIN);
+    }
+
+    /// 可运行的进程数保持不变时，cpu负载收敛到它们的权重之和
+    #[test]
+    fn test_cpu_load_converges() {
+        let this_cpu = smp_get_processor_id() as usize;
+        let mut scheduler = SchedulerCFS::with_cpu_num(this_cpu + 1);
+        let current = ProcessManager::current_pcb();
+        assert_ne!(current.pid().into(), 0);
+        let pcbs: Vec<_> = (0..2)
+            .map(|_| {
+                let pcb = KernelThreadMechanism::create(
+                    KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                    "cpu-load".to_string(),
+                )
+                .unwrap();
+                scheduler.this_cpu_queue().enqueue(pcb.clone());
+                pcb
+            })
+            .collect();
+        // 当前进程与队列中的两个进程
+        let expected = 3 * NICE_0_LOAD;
+
+        let mut last = 0;
+        for _ in 0..64 {
+            scheduler.this_cpu_queue().cpu_exec_proc_ns = SCHED_SLICE_NS;
+            scheduler.timer_update_jiffies();
+            let load = scheduler.cpu_load(this_cpu);
+            assert!(load >= last && load <= expected);
+            last = load;
+        }
+        assert!(expected - last < expected / 100);
+        current.flags().remove(ProcessFlags::NEED_SCHEDULE);
+
+        for pcb in pcbs.iter() {
+            assert!(scheduler.this_cpu_queue().dequeue_pcb(pcb));
+        }
+        assert_eq!(
+            scheduler
+                .this_cpu_queue()
+                .load_weight
+                .load(Ordering::SeqCst),
+            0
+        );
+    }
+
+    /// 空队列弹出None，非空队列按照虚拟运行时间从小到大弹出调度实体
+    #[test]
+    fn test_dequeue_se() {
+        let idle = ProcessManager::idle_pcb();
+        let mut queue = CFSQueue::new(idle[0].clone());
+        assert!(queue.dequeue_se().is_none());
+
+        let se = idle[0].sched_info().se().unwrap();
+        queue.enqueue_se(se.clone());
+        let dequeued = queue.dequeue_se().unwrap();
+        assert!(Arc::ptr_eq(&dequeued, &se));
+        assert!(queue.dequeue_se().is_none());
+    }
+
+    /// 向两个不同cpu的队列中加入调度实体，两个队列互不影响
+    #[test]
+    fn test_enqueue_two_cpus() {
+        let scheduler = SchedulerCFS::with_cpu_num(2);
+        let se0 = SchedEntity::new();
+        let se1 = SchedEntity::new();
+        scheduler.enqueue_group_se(se0.clone(), 0);
+        scheduler.enqueue_group_se(se1.clone(), 1);
+
+        let dequeued1 = scheduler.cpu_queue(1).dequeue_se().unwrap();
+        assert!(Arc::ptr_eq(&dequeued1, &se1));
+        assert!(scheduler.cpu_queue(1).dequeue_se().is_none());
+
+        let dequeued0 = scheduler.cpu_queue(0).dequeue_se().unwrap();
+        assert!(Arc::ptr_eq(&dequeued0, &se0));
+    }
+
+    /// 向非空队列中加入进程时，进程的虚拟运行时间被重设为队列中的最小值
+    #[test]
+    fn test_enqueue_reset_vruntime() {
+        let idle = ProcessManager::idle_pcb();
+        let mut queue = CFSQueue::new(idle[0].clone());
+
+        let se = SchedEntity::new();
+        se.set_virtual_runtime(7);
+        queue.enqueue_se(se);
+
+        // 虚拟运行时间较大的进程不会因为睡眠而获得补偿
+        let pcb = ProcessManager::current_pcb();
+        pcb.sched_info().set_virtual_runtime(100);
+        queue.enqueue_reset_vruntime(pcb.clone());
+        assert_eq!(pcb.sched_info().virtual_runtime(), 100);
+        assert_eq!(queue.locked_queue.lock().len(), 2);
+    }
+
+    /// 最小虚拟运行时间等于插入的最小的键
+    #[test]
+    fn test_min_vruntime() {
+        let idle = ProcessManager::idle_pcb();
+        let mut queue = CFSQueue::new(idle[0].clone());
+        assert_eq!(CFSQueue::min_vruntime(&queue.locked_queue.lock()), None);
+
+        for vruntime in [30, 10, 20] {
+            let se = SchedEntity::new();
+            se.set_virtual_runtime(vruntime);
+            queue.enqueue_se(se);
+        }
+
+        let guard = queue.locked_queue.lock();
+        assert_eq!(CFSQueue::min_vruntime(&guard), Some(10));
+        assert_eq!(CFSQueue::get_cfs_queue_size(&guard), 3);
+    }
+
+    /// 父进程不断fork时，每次都要付出代价，而子进程总是排在父进程之后，
+    /// 因此父进程重新入队后仍然能够最先被调度
+    #[test]
+    fn test_task_fork_parent_progress() {
+        let idle = ProcessManager::idle_pcb();
+        let mut queue = CFSQueue::new(idle[0].clone());
+        queue.cpu_exec_proc_ns = SCHED_SLICE_NS;
+
+        let parent = ProcessManager::current_pcb();
+        parent.sched_info().set_virtual_runtime(0);
+
+        for _ in 0..64 {
+            let child = KernelThreadMechanism::create(
+                KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                "fork-child".to_string(),
+            )
+            .unwrap();
+            let before = parent.sched_info().virtual_runtime();
+            queue.task_fork(&parent, &child);
+
+            let after = parent.sched_info().virtual_runtime();
+            assert!(after > before);
+            assert!(child.sched_info().virtual_runtime() > after);
+            queue.enqueue(child);
+        }
+
+        queue.enqueue(parent.clone());
+        assert!(Arc::ptr_eq(&queue.dequeue(), &parent));
+    }
+
+    /// 迁移后，进程的虚拟运行时间以目标队列的最小值为基准，保持与源队列最小值的差值
+    #[test]
+    fn test_migrate_entity() {
+        let scheduler = SchedulerCFS::with_cpu_num(3);
+        for (cpu, vruntime) in [(0, 1000), (0, 1200), (1, 10)] {
+            let se = SchedEntity::new();
+            se.set_virtual_runtime(vruntime);
+            scheduler.enqueue_group_se(se, cpu);
+        }
+
+        let pcb = ProcessManager::current_pcb();
+        pcb.sched_info().set_virtual_runtime(1005);
+        scheduler.migrate_entity(&pcb, 0, 1);
+        assert_eq!(pcb.sched_info().virtual_runtime(), 15);
+
+        // 目标队列为空时，不改变虚拟运行时间
+        scheduler.migrate_entity(&pcb, 1, 2);
+        assert_eq!(pcb.sched_info().virtual_runtime(), 15);
+    }
+
+    /// 队列的数目等于指定的cpu数目，并且以cpu id为下标访问
+    #[test]
+    fn test_with_cpu_num() {
+        let idle = ProcessManager::idle_pcb();
+        for cpu_num in [1, 2, 4] {
+            let scheduler = SchedulerCFS::with_cpu_num(cpu_num);
+            assert_eq!(scheduler.cpu_num(), cpu_num);
+            for cpu in 0..cpu_num {
+                assert!(Arc::ptr_eq(&scheduler.cpu_queue(cpu).idle_pcb, &idle[cpu]));
+            }
+        }
+    }
+
+    /// 从一个cpu将进程加入另一个cpu的队列，进程只出现在目标cpu的队列中，
+    /// 并且由于目标cpu正在运行idle进程，需要向其发送IPI
+    #[test]
+    fn test_enqueue_on_remote_cpu() {
+        let this_cpu = smp_get_processor_id() as usize;
+        let remote_cpu = if this_cpu == 0 { 1 } else { 0 };
+        let scheduler = SchedulerCFS::with_cpu_num(2);
+
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "enqueue-remote".to_string(),
+        )
+        .unwrap();
+        pcb.sched_info().set_on_cpu(Some(remote_cpu as u32));
+
+        assert!(scheduler.enqueue_on(pcb.clone(), remote_cpu));
+        assert_eq!(
+            CFSQueue::get_cfs_queue_size(&scheduler.cpu_queue(this_cpu).locked_queue.lock()),
+            0
+        );
+        let dequeued = scheduler.cpu_queue(remote_cpu).dequeue();
+        assert!(Arc::ptr_eq(&dequeued, &pcb));
+
+        // 加入当前cpu的队列不需要IPI
+        assert!(!scheduler.enqueue_on(pcb, this_cpu));
+    }
+
+    /// 时间片耗尽的那一次时钟中断之后，中断返回时就应当发起调度，
+    /// 但是持有自旋锁时不能被抢占
+    #[test]
+    fn test_slice_expiry_resched() {
+        let mut scheduler = SchedulerCFS::with_cpu_num(smp_get_processor_id() as usize + 1);
+        let current = ProcessManager::current_pcb();
+        current.flags().remove(ProcessFlags::NEED_SCHEDULE);
+        scheduler.this_cpu_queue().cpu_exec_proc_ns = 2 * SCHED_TICK_INTERVAL_NS as i64;
+        scheduler.this_cpu_queue().exec_start = 1;
+
+        scheduler.timer_update(1 + SCHED_TICK_INTERVAL_NS);
+        assert!(!need_resched(&current));
+
+        scheduler.timer_update(1 + 2 * SCHED_TICK_INTERVAL_NS);
+        assert!(need_resched(&current));
+
+        current.preempt_disable();
+        assert!(!need_resched(&current));
+        current.preempt_enable();
+        assert!(need_resched(&current));
+        current.flags().remove(ProcessFlags::NEED_SCHEDULE);
+    }
+
+    /// 进程按照调度器时钟实际经过的时间计入运行时间，而不是按照整个时钟周期
+    #[test]
+    fn test_update_curr_proportional() {
+        let a = sched_clock();
+        let b = sched_clock();
+        assert!(b >= a);
+
+        let idle = ProcessManager::idle_pcb();
+        let mut queue = CFSQueue::new(idle[0].clone());
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "update-curr".to_string(),
+        )
+        .unwrap();
+        pcb.sched_info().set_virtual_runtime(0);
+        queue.cpu_exec_proc_ns = SCHED_SLICE_NS;
+
+        // 第一次计时只记录起点
+        let start = 1_000_000;
+        assert_eq!(queue.update_curr(&pcb, start), 0);
+
+        // 运行1.5个时钟周期
+        let t1 = start + SCHED_TICK_INTERVAL_NS * 3 / 2;
+        assert_eq!(queue.update_curr(&pcb, t1), SCHED_TICK_INTERVAL_NS * 3 / 2);
+        // 再运行3微秒
+        assert_eq!(queue.update_curr(&pcb, t1 + 3_000), 3_000);
+        let expected = SCHED_TICK_INTERVAL_NS * 3 / 2 + 3_000;
+        assert_eq!(pcb.sched_info().sum_exec_runtime(), expected);
+        assert_eq!(pcb.sched_info().virtual_runtime(), expected as isize);
+        assert_eq!(queue.cpu_exec_proc_ns, SCHED_SLICE_NS - expected as i64);
+        assert!(!pcb.flags().contains(ProcessFlags::NEED_SCHEDULE));
+
+        // 用完时间片
+        let t2 = t1 + 3_000 + queue.cpu_exec_proc_ns as u64;
+        queue.update_curr(&pcb, t2);
+        assert_eq!(pcb.sched_info().sum_exec_runtime(), SCHED_SLICE_NS as u64);
+        assert!(pcb.flags().contains(ProcessFlags::NEED_SCHEDULE));
+
+        // IDLE进程不计入
+        assert_eq!(queue.update_curr(&idle[0], t2 + 1_000), 0);
+    }
+
+    /// 与普通进程竞争时，SCHED_IDLE进程几乎得不到运行时间
+    #[test]
+    fn test_sched_idle_starved() {
+        let idle = ProcessManager::idle_pcb();
+        let mut queue = CFSQueue::new(idle[0].clone());
+        let create = |name: &str, policy: SchedPolicy| {
+            let pcb = KernelThreadMechanism::create(
+                KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                name.to_string(),
+            )
+            .unwrap();
+            pcb.sched_info_mut()
+                .set_policy(policy, SchedPriority::DEFAULT);
+            pcb.sched_info().set_virtual_runtime(0);
+            pcb
+        };
+        let normal = create("sched-normal", SchedPolicy::CFS);
+        let idle_task = create("sched-idle", SchedPolicy::IDLE);
+        queue.enqueue(normal.clone());
+        queue.enqueue(idle_task.clone());
+
+        // 每次选出虚拟运行时间最小的进程，让它运行一个完整的时间片
+        let mut now = 1_000_000;
+        queue.update_curr(&idle[0], now);
+        for _ in 0..200 {
+            let next = queue.dequeue();
+            now += SCHED_SLICE_NS as u64;
+            queue.update_curr(&next, now);
+            queue.enqueue(next);
+        }
+        queue.dequeue_pcb(&normal);
+        queue.dequeue_pcb(&idle_task);
+
+        let normal_runtime = normal.sched_info().sum_exec_runtime();
+        let idle_runtime = idle_task.sched_info().sum_exec_runtime();
+        assert_eq!(normal_runtime + idle_runtime, 200 * SCHED_SLICE_NS as u64);
+        assert!(idle_runtime * 100 <= normal_runtime);
+        assert_eq!(
+            CFSQueue::calc_delta_fair(WEIGHT_IDLEPRIO, SchedPolicy::IDLE),
+            NICE_0_LOAD
+        );
+        assert_eq!(CFSQueue::sched_weight(SchedPolicy::IDLE), WEIGHT_IDLEPRIO);
+    }
+
+    /// nr_running始终与队列中除IDLE进程以外的调度实体数目一致，队列为空时出队IDLE进程不改变计数
+    #[test]
+    fn test_nr_running() {
+        let scheduler = SchedulerCFS::with_cpu_num(2);
+        let idle = ProcessManager::idle_pcb();
+        let check = |cpu: usize, expected: usize| {
+            let queue = scheduler.cpu_queue(cpu);
+            assert_eq!(queue.nr_running(), expected);
+            assert_eq!(queue.locked_queue.lock().len(), expected);
+        };
+
+        let pcbs: Vec<_> = (0..3)
+            .map(|_| {
+                KernelThreadMechanism::create(
+                    KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                    "nr-running".to_string(),
+                )
+                .unwrap()
+            })
+            .collect();
+        for pcb in pcbs.iter() {
+            scheduler.cpu_queue(0).enqueue(pcb.clone());
+        }
+        scheduler.cpu_queue(0).enqueue(idle[0].clone());
+        scheduler.enqueue_group_se(SchedEntity::new(), 1);
+        check(0, 3);
+        check(1, 1);
+        assert_eq!(scheduler.nr_running(), 4);
+
+        assert!(scheduler.cpu_queue(0).dequeue_pcb(&pcbs[1]));
+        check(0, 2);
+        scheduler.cpu_queue(0).dequeue();
+        scheduler.cpu_queue(0).dequeue_se();
+        check(0, 0);
+        assert!(Arc::ptr_eq(&scheduler.cpu_queue(0).dequeue(), &idle[0]));
+        check(0, 0);
+        assert_eq!(scheduler.nr_running(), 1);
+    }
+
+    /// 调度、迁移以及时钟中断都会使对应cpu的统计信息增加
+    #[test]
+    fn test_sched_stat() {
+        let this_cpu = smp_get_processor_id() as usize;
+        let other_cpu = if this_cpu == 0 { 1 } else { 0 };
+        let mut scheduler = SchedulerCFS::with_cpu_num(core::cmp::max(this_cpu + 1, 2));
+        let idle = ProcessManager::idle_pcb();
+        let current = ProcessManager::current_pcb();
+
+        // 让当前进程的虚拟运行时间小于IDLE进程，使得调度时不发生切换
+        let vruntime = current.sched_info().virtual_runtime();
+        current
+            .sched_info()
+            .set_virtual_runtime(idle[this_cpu].sched_info().virtual_runtime() - 1);
+        {
+            let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+            for _ in 0..3 {
+                assert!(scheduler.sched().is_none());
+            }
+        }
+        let stat = scheduler.cpu_stat(this_cpu);
+        assert_eq!(stat.sched_count(), 3);
+        assert_eq!(stat.sched_goidle(), 3);
+
+        scheduler.migrate_entity(&current, this_cpu, other_cpu);
+        assert_eq!(scheduler.cpu_stat(this_cpu).migrations_out(), 1);
+        assert_eq!(scheduler.cpu_stat(other_cpu).migrations_in(), 1);
+        current.sched_info().set_virtual_runtime(vruntime);
+
+        scheduler.this_cpu_queue().cpu_exec_proc_ns = SCHED_SLICE_NS;
+        scheduler.timer_update_jiffies();
+        assert_eq!(scheduler.cpu_stat(this_cpu).run_ticks(), 1);
+        assert_eq!(scheduler.cpu_stat(other_cpu).run_ticks(), 0);
+    }
+
+    /// 记录每次收到的利用率的频率调节器
+    #[derive(Debug, Default)]
+    struct MockGovernor {
+        updates: Arc<SpinLock<Vec<(usize, u64)>>>,
+    }
+
+    impl CpuFreqGovernor for MockGovernor {
+        fn update(&self, cpu: usize, util: u64) {
+            self.updates.lock().push((cpu, util));
+        }
+    }
+
+    /// 忙碌的cpu的利用率逐渐上升，有进程等待时利用率为满值
+    #[test]
+    fn test_governor_util() {
+        let this_cpu = smp_get_processor_id() as usize;
+        let mut scheduler = SchedulerCFS::with_cpu_num(this_cpu + 1);
+        let governor = MockGovernor::default();
+        let updates = governor.updates.clone();
+        scheduler.set_governor(Box::new(governor));
+
+        let current = ProcessManager::current_pcb();
+        assert_ne!(current.pid().into(), 0);
+        for _ in 0..8 {
+            scheduler.this_cpu_queue().cpu_exec_proc_ns = SCHED_SLICE_NS;
+            scheduler.timer_update_jiffies();
+        }
+        let observed: Vec<u64> = updates.lock().iter().map(|(_, util)| *util).collect();
+        assert_eq!(observed.len(), 8);
+        assert!(updates.lock().iter().all(|(cpu, _)| *cpu == this_cpu));
+        assert!(observed.windows(2).all(|w| w[0] < w[1]));
+        assert!(observed[7] < SCHED_CAPACITY_SCALE);
+
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "governor-waiter".to_string(),
+        )
+        .unwrap();
+        scheduler.this_cpu_queue().enqueue(pcb);
+        scheduler.this_cpu_queue().cpu_exec_proc_ns = SCHED_SLICE_NS;
+        scheduler.timer_update_jiffies();
+        assert_eq!(
+            updates.lock().last().map(|(_, util)| *util),
+            Some(SCHED_CAPACITY_SCALE)
+        );
+        current.flags().remove(ProcessFlags::NEED_SCHEDULE);
+    }
+
+    /// 下线一个忙碌的cpu时，它的进程被均匀地分散到其余的cpu上，
+    /// 只允许在该cpu上运行的进程的亲和性被重置，进程组的调度实体留在原队列中
+    #[test]
+    fn test_drain_cpu() {
+        let scheduler = SchedulerCFS::with_cpu_num(3);
+        scheduler.enqueue_group_se(SchedEntity::new(), 0);
+        scheduler.enqueue_group_se(SchedEntity::new(), 2);
+        let pcbs: Vec<_> = (0..5)
+            .map(|_| {
+                let pcb = KernelThreadMechanism::create(
+                    KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                    "drain-cpu".to_string(),
+                )
+                .unwrap();
+                pcb.sched_info().set_on_cpu(Some(2));
+                scheduler.cpu_queue(2).enqueue(pcb.clone());
+                pcb
+            })
+            .collect();
+        let mut pinned = CpuMask::new();
+        pinned.set(2);
+        pcbs[0].sched_info().set_cpu_affinity(pinned).unwrap();
+
+        let mut online = CpuMask::full();
+        online.clear(2);
+        assert_eq!(scheduler.drain_cpu(2, online), Ok(5));
+
+        assert_eq!(scheduler.cpu_queue(0).nr_running(), 3);
+        assert_eq!(scheduler.cpu_queue(1).nr_running(), 3);
+        assert_eq!(scheduler.cpu_queue(2).nr_running(), 1);
+        assert_eq!(pcbs[0].sched_info().cpu_affinity(), online);
+        for pcb in pcbs.iter() {
+            let cpu = pcb.sched_info().on_cpu().unwrap() as usize;
+            assert!(cpu < 2);
+            assert!(scheduler.dequeue_pcb(pcb, cpu));
+        }
+
+        // 没有其他在线的cpu时不能下线
+        assert_eq!(
+            scheduler.drain_cpu(2, CpuMask::new()),
+            Err(SystemError::EBUSY)
+        );
+    }
+
+    /// 空闲的cpu从过载的cpu上窃取虚拟运行时间最小、并且允许在空闲cpu上运行的进程，
+    /// 最繁忙的cpu只有一个进程时不进行窃取
+    #[test]
+    fn test_steal_task() {
+        let scheduler = SchedulerCFS::with_cpu_num(2);
+        let pcbs: Vec<_> = [100, 200, 300]
+            .iter()
+            .map(|vruntime| {
+                let pcb = KernelThreadMechanism::create(
+                    KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                    "steal-task".to_string(),
+                )
+                .unwrap();
+                pcb.sched_info().set_virtual_runtime(*vruntime);
+                pcb.sched_info().set_on_cpu(Some(0));
+                scheduler.cpu_queue(0).enqueue(pcb.clone());
+                pcb
+            })
+            .collect();
+        let mut pinned = CpuMask::new();
+        pinned.set(0);
+        pcbs[0].sched_info().set_cpu_affinity(pinned).unwrap();
+
+        let stolen = scheduler.steal_task(1).unwrap();
+        assert!(Arc::ptr_eq(&stolen, &pcbs[1]));
+        assert_eq!(stolen.sched_info().on_cpu(), Some(1));
+        assert_eq!(scheduler.cpu_queue(0).nr_running(), 2);
+        assert_eq!(scheduler.cpu_stat(1).migrations_in(), 1);
+
+        assert!(scheduler.cpu_queue(0).dequeue_pcb(&pcbs[2]));
+        assert!(scheduler.steal_task(1).is_none());
+        assert!(scheduler.cpu_queue(0).dequeue_pcb(&pcbs[0]));
+    }
+
+    /// 正在被迁移的进程不会被窃取；迁移完成（on_cpu被更新）之后才能再次被窃取
+    #[test]
+    fn test_steal_skips_migrating() {
+        let scheduler = SchedulerCFS::with_cpu_num(2);
+        let pcbs: Vec<_> = (0..2)
+            .map(|_| {
+                let pcb = KernelThreadMechanism::create(
+                    KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                    "steal-migrating".to_string(),
+                )
+                .unwrap();
+                pcb.sched_info().set_on_cpu(Some(0));
+                scheduler.cpu_queue(0).enqueue(pcb.clone());
+                pcb
+            })
+            .collect();
+
+        for pcb in pcbs.iter() {
+            assert!(pcb.sched_info().start_migration());
+            assert!(!pcb.sched_info().start_migration());
+        }
+        assert!(scheduler.steal_task(1).is_none());
+        assert_eq!(scheduler.cpu_queue(0).nr_running(), 2);
+
+        // 修改on_cpu不会解除迁移标志，只有完成迁移才会
+        pcbs[1].sched_info().set_on_cpu(Some(0));
+        assert!(pcbs[1].sched_info().is_migrating());
+        assert!(scheduler.steal_task(1).is_none());
+        pcbs[1].sched_info().finish_migration(Some(0));
+        assert!(!pcbs[1].sched_info().is_migrating());
+        let stolen = scheduler.steal_task(1).unwrap();
+        assert!(Arc::ptr_eq(&stolen, &pcbs[1]));
+        assert!(!stolen.sched_info().is_migrating());
+
+        pcbs[0].sched_info().cancel_migration();
+        assert!(scheduler.cpu_queue(0).dequeue_pcb(&pcbs[0]));
+    }
+
+    /// 窃取进程，检查它的on_cpu之后，将它放回cpu0的队列
+    fn steal_and_put_back(scheduler: &SchedulerCFS, cpu: usize) -> bool {
+        let pcb = match scheduler.steal_task(cpu) {
+            Some(pcb) => pcb,
+            None => return true,
+        };
+        let ok =
+            pcb.sched_info().on_cpu() == Some(cpu as u32) && !pcb.sched_info().is_migrating();
+        pcb.sched_info().set_on_cpu(Some(0));
+        scheduler.cpu_queue(0).enqueue(pcb);
+        return ok;
+    }
+
+    /// 两个cpu同时从同一个队列中窃取进程，同时另一个线程不断读取进程的on_cpu：
+    /// 读到的总是有效的cpu，并且每个进程只会被一方取出，不会被重复放入队列
+    #[test]
+    fn test_concurrent_steal_no_double_enqueue() {
+        const NR_TASKS: usize = 4;
+        const ROUNDS: usize = 1000;
+        if unsafe { smp_get_total_cpu() } < 2 {
+            return;
+        }
+        let scheduler = Arc::new(SchedulerCFS::with_cpu_num(3));
+        let pcbs: Arc<Vec<_>> = Arc::new(
+            (0..NR_TASKS)
+                .map(|_| {
+                    let pcb = KernelThreadMechanism::create(
+                        KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                        "steal-concurrent".to_string(),
+                    )
+                    .unwrap();
+                    pcb.sched_info().set_on_cpu(Some(0));
+                    scheduler.cpu_queue(0).enqueue(pcb.clone());
+                    pcb
+                })
+                .collect(),
+        );
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let failed = Arc::new(AtomicBool::new(false));
+        let finished = Arc::new(AtomicUsize::new(0));
+
+        let stealer = {
+            let (scheduler, stop, failed, finished) = (
+                scheduler.clone(),
+                stop.clone(),
+                failed.clone(),
+                finished.clone(),
+            );
+            move || {
+                while !stop.load(Ordering::SeqCst) {
+                    if !steal_and_put_back(&scheduler, 2) {
+                        failed.store(true, Ordering::SeqCst);
+                    }
+                }
+                finished.fetch_add(1, Ordering::SeqCst);
+                0
+            }
+        };
+        let reader = {
+            let (pcbs, stop, failed, finished) =
+                (pcbs.clone(), stop.clone(), failed.clone(), finished.clone());
+            move || {
+                while !stop.load(Ordering::SeqCst) {
+                    for pcb in pcbs.iter() {
+                        match pcb.sched_info().on_cpu() {
+                            Some(cpu) if cpu < 3 => {}
+                            _ => failed.store(true, Ordering::SeqCst),
+                        }
+                    }
+                }
+                finished.fetch_add(1, Ordering::SeqCst);
+                0
+            }
+        };
+        for (closure, name) in [
+            (Box::new(stealer) as Box<dyn Fn() -> i32 + Send + Sync>, "stealer"),
+            (Box::new(reader), "on-cpu-reader"),
+        ] {
+            let pcb = KernelThreadMechanism::create(
+                KernelThreadClosure::EmptyClosure((closure, ())),
+                name.to_string(),
+            )
+            .unwrap();
+            ProcessManager::wakeup(&pcb).unwrap();
+        }
+
+        for _ in 0..ROUNDS {
+            assert!(steal_and_put_back(&scheduler, 1));
+        }
+        stop.store(true, Ordering::SeqCst);
+        while finished.load(Ordering::SeqCst) < 2 {
+            spin_loop();
+        }
+
+        assert!(!failed.load(Ordering::SeqCst));
+        assert_eq!(scheduler.cpu_queue(0).nr_running(), NR_TASKS);
+        for pcb in pcbs.iter() {
+            assert!(!pcb.sched_info().is_migrating());
+            assert!(scheduler.cpu_queue(0).dequeue_pcb(pcb));
+        }
+        assert_eq!(scheduler.cpu_queue(0).nr_running(), 0);
+    }
+
+    /// 进程入队之后亲和性被修改为不再包含当前cpu时，它不会被选中运行，而是被迁移到允许它运行的cpu上
+    #[test]
+    fn test_pick_next_skips_disallowed() {
+        let scheduler = SchedulerCFS::with_cpu_num(2);
+        let pcbs: Vec<_> = [100, 200]
+            .iter()
+            .map(|vruntime| {
+                let pcb = KernelThreadMechanism::create(
+                    KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                    "pick-affinity".to_string(),
+                )
+                .unwrap();
+                pcb.sched_info().set_virtual_runtime(*vruntime);
+                pcb.sched_info().set_on_cpu(Some(0));
+                scheduler.cpu_queue(0).enqueue(pcb.clone());
+                pcb
+            })
+            .collect();
+        let mut narrowed = CpuMask::new();
+        narrowed.set(1);
+        pcbs[0].sched_info().set_cpu_affinity(narrowed).unwrap();
+
+        let mut misplaced = Vec::new();
+        let next = SchedulerCFS::pick_next_task(&mut scheduler.cpu_queue(0), 0, &mut misplaced);
+        assert!(Arc::ptr_eq(&next.unwrap(), &pcbs[1]));
+        assert_eq!(misplaced.len(), 1);
+        assert!(Arc::ptr_eq(&misplaced[0], &pcbs[0]));
+
+        // 其他cpu持有迁移标志时，进程被放回原来的队列，而不是被丢弃
+        assert!(pcbs[0].sched_info().start_migration());
+        assert_eq!(
+            scheduler.migrate_disallowed(&pcbs[0], 0, CpuMask::full()),
+            None
+        );
+        assert!(scheduler.cpu_queue(0).dequeue_pcb(&pcbs[0]));
+        pcbs[0].sched_info().cancel_migration();
 
-        cpu_queue.enqueue(pcb);
+        assert_eq!(
+            scheduler.migrate_disallowed(&pcbs[0], 0, CpuMask::full()),
+            Some(1)
+        );
+        assert_eq!(pcbs[0].sched_info().on_cpu(), Some(1));
+        assert_eq!(scheduler.cpu_queue(0).nr_running(), 0);
+        assert!(scheduler.cpu_queue(1).dequeue_pcb(&pcbs[0]));
     }
 }
@@ -0,0 +1,16 @@
+use core::arch::x86_64::_rdtsc;
+
+use crate::{include::bindings::bindings::Cpu_tsc_freq, time::timer::clock};
+
+/// 调度器时钟，返回单调递增的时间戳（单位：纳秒）
+///
+/// 调度器在每次调度以及时钟中断时，以两次读数之差作为进程实际运行的时间。
+/// TSC的频率测量完成之前，退化为定时器的时钟，此时只有微秒级的精度。
+pub fn sched_clock() -> u64 {
+    let tsc_freq = unsafe { Cpu_tsc_freq };
+    if tsc_freq == 0 {
+        return clock() * 1000;
+    }
+    let tsc = unsafe { _rdtsc() };
+    return (tsc as u128 * 1_000_000_000 / tsc_freq as u128) as u64;
+}
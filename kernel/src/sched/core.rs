@@ -1,25 +1,36 @@
-use core::sync::atomic::{compiler_fence, Ordering};
+use core::sync::atomic::{compiler_fence, AtomicU64, AtomicUsize, Ordering};
 
 use alloc::{sync::Arc, vec::Vec};
 
 use crate::{
+    arch::{sched::sched, CurrentIrqArch},
+    exception::InterruptArch,
     include::bindings::bindings::smp_get_total_cpu,
-    kinfo,
+    kinfo, kwarn,
     mm::percpu::PerCpu,
     process::{
         AtomicPid, Pid, ProcessControlBlock, ProcessFlags, ProcessManager, ProcessState,
         SchedEntity,
     },
-    smp::core::smp_get_processor_id,
+    smp::{
+        core::{cpu_online, cpu_online_mask, set_cpu_online, smp_get_processor_id},
+        cpumask::CpuMask,
+        kick_cpu,
+    },
+    syscall::SystemError,
 };
 
 use super::{
-    cfs::CFSQueue,
-    rt::{sched_rt_init, SchedulerRT, __get_rt_scheduler},
+    cfs::{sched_cfs_init, SchedulerCFS, __get_cfs_scheduler, NICE_0_LOAD},
+    SchedPolicy,
 };
 use super::{
-    cfs::{sched_cfs_init, SchedulerCFS, __get_cfs_scheduler},
-    SchedPolicy,
+    clock::sched_clock,
+    deadline::{__get_dl_scheduler, sched_dl_init, SchedulerDeadline},
+    loadavg::calc_global_load_tick,
+    rt::{sched_rt_init, SchedulerRT, __get_rt_scheduler},
+    trace::{trace_sched_dequeue, trace_sched_enqueue, trace_sched_migrate},
+    watchdog::sched_stall_tick,
 };
 
 lazy_static! {
@@ -27,6 +38,67 @@ lazy_static! {
     pub static ref CPU_EXECUTING: CpuExecuting = CpuExecuting::new();
 }
 
+/// 调度器时钟中断的间隔（单位：微秒），与apic_timer.h中的APIC_TIMER_INTERVAL保持一致
+pub const SCHED_TICK_INTERVAL_US: u64 = 5000;
+/// 调度器时钟中断的间隔（单位：纳秒）
+pub const SCHED_TICK_INTERVAL_NS: u64 = SCHED_TICK_INTERVAL_US * 1000;
+
+/// 一个cpu在各种状态下经过的时钟中断次数，用于/proc/stat
+#[derive(Debug)]
+pub struct CpuTickStat {
+    /// 运行非idle进程的用户态代码
+    user: AtomicU64,
+    /// 运行非idle进程的内核态代码
+    system: AtomicU64,
+    /// 运行idle进程，并且没有进程在该cpu上等待io
+    idle: AtomicU64,
+    /// 运行idle进程，并且有进程在该cpu上等待io
+    iowait: AtomicU64,
+}
+
+impl CpuTickStat {
+    const fn new() -> Self {
+        Self {
+            user: AtomicU64::new(0),
+            system: AtomicU64::new(0),
+            idle: AtomicU64::new(0),
+            iowait: AtomicU64::new(0),
+        }
+    }
+
+    /// 运行用户态代码的时间（单位：微秒）
+    pub fn user_us(&self) -> u64 {
+        return self.user.load(Ordering::SeqCst) * SCHED_TICK_INTERVAL_US;
+    }
+
+    /// 运行内核态代码的时间（单位：微秒）
+    pub fn system_us(&self) -> u64 {
+        return self.system.load(Ordering::SeqCst) * SCHED_TICK_INTERVAL_US;
+    }
+
+    /// 空闲的时间（单位：微秒），不包括等待io的时间
+    pub fn idle_us(&self) -> u64 {
+        return self.idle.load(Ordering::SeqCst) * SCHED_TICK_INTERVAL_US;
+    }
+
+    /// 空闲并且有进程等待io的时间（单位：微秒）
+    pub fn iowait_us(&self) -> u64 {
+        return self.iowait.load(Ordering::SeqCst) * SCHED_TICK_INTERVAL_US;
+    }
+}
+
+/// 每个cpu在各种状态下经过的时钟中断次数
+static CPU_TICK_STAT: [CpuTickStat; PerCpu::MAX_CPU_NUM] = {
+    const ZERO: CpuTickStat = CpuTickStat::new();
+    [ZERO; PerCpu::MAX_CPU_NUM]
+};
+
+/// 每个cpu上正在通过`io_schedule`等待io的进程数
+static CPU_NR_IOWAIT: [AtomicUsize; PerCpu::MAX_CPU_NUM] = {
+    const ZERO: AtomicUsize = AtomicUsize::new(0);
+    [ZERO; PerCpu::MAX_CPU_NUM]
+};
+
 #[derive(Debug)]
 pub struct CpuExecuting {
     data: Vec<AtomicPid>,
@@ -36,7 +108,7 @@ impl CpuExecuting {
     pub fn new() -> Self {
         let mut data = Vec::new();
         for _ in 0..PerCpu::MAX_CPU_NUM {
-            data.push(AtomicPid::new(Pid::new(0)));
+            data.push(AtomicPid::new(Pid::IDLE));
         }
         Self { data }
     }
@@ -52,29 +124,221 @@ impl CpuExecuting {
     }
 }
 
+/// 一个cpu上进程切换的延迟，即进程被标记为需要调度到它被切换出去之间经过的时间
+///
+/// 延迟过大说明进程长时间处于不可抢占的状态，例如长时间持有自旋锁
+#[derive(Debug)]
+pub struct SwitchLatencyStat {
+    /// 统计的切换次数
+    count: AtomicU64,
+    /// 延迟之和（单位：纳秒）
+    total_ns: AtomicU64,
+    /// 最大的延迟（单位：纳秒）
+    max_ns: AtomicU64,
+}
+
+impl SwitchLatencyStat {
+    const fn new() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            total_ns: AtomicU64::new(0),
+            max_ns: AtomicU64::new(0),
+        }
+    }
+
+    /// 统计的切换次数
+    pub fn count(&self) -> u64 {
+        return self.count.load(Ordering::SeqCst);
+    }
+
+    /// 最大的延迟（单位：纳秒）
+    pub fn max_ns(&self) -> u64 {
+        return self.max_ns.load(Ordering::SeqCst);
+    }
+
+    /// 平均的延迟（单位：纳秒），没有发生过切换时为0
+    pub fn avg_ns(&self) -> u64 {
+        let count = self.count();
+        if count == 0 {
+            return 0;
+        }
+        return self.total_ns.load(Ordering::SeqCst) / count;
+    }
+
+    fn record(&self, latency_ns: u64) {
+        self.total_ns.fetch_add(latency_ns, Ordering::SeqCst);
+        self.max_ns.fetch_max(latency_ns, Ordering::SeqCst);
+        self.count.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// 每个cpu上进程切换的延迟
+static CPU_SWITCH_LATENCY: [SwitchLatencyStat; PerCpu::MAX_CPU_NUM] = {
+    const ZERO: SwitchLatencyStat = SwitchLatencyStat::new();
+    [ZERO; PerCpu::MAX_CPU_NUM]
+};
+
+/// 获取某个cpu上进程切换的延迟
+pub fn switch_latency(cpu_id: u32) -> &'static SwitchLatencyStat {
+    return &CPU_SWITCH_LATENCY[cpu_id as usize];
+}
+
+/// 调度结束时，统计当前cpu上进程切换的延迟
+///
+/// 进程被切换出去时，将它被标记为需要调度以来经过的时间计入统计；
+/// 进程没有被切换出去，但是标记已经被调度器清除时，丢弃记录的时刻
+///
+/// ## 参数
+///
+/// - `prev` : 调度之前当前cpu上正在运行的进程
+/// - `switched` : 是否切换到了其他进程
+pub fn account_switch_latency(prev: &Arc<ProcessControlBlock>, switched: bool) {
+    if !switched && prev.flags().contains(ProcessFlags::NEED_SCHEDULE) {
+        return;
+    }
+    let resched_at = match prev.sched_info().take_resched_at() {
+        Some(resched_at) => resched_at,
+        None => return,
+    };
+    if switched {
+        switch_latency(smp_get_processor_id()).record(sched_clock().saturating_sub(resched_at));
+    }
+}
+
+/// 获取某个cpu在各种状态下经过的时间
+pub fn cpu_tick_stat(cpu_id: u32) -> &'static CpuTickStat {
+    return &CPU_TICK_STAT[cpu_id as usize];
+}
+
+/// 获取某个cpu运行idle进程的时间（单位：微秒），包括等待io的时间
+pub fn cpu_idle_time_us(cpu_id: u32) -> u64 {
+    let stat = cpu_tick_stat(cpu_id);
+    return stat.idle_us() + stat.iowait_us();
+}
+
+/// 获取所有cpu运行idle进程的时间之和（单位：微秒），包括等待io的时间
+pub fn total_idle_time_us() -> u64 {
+    let cpu_num = unsafe { smp_get_total_cpu() };
+    return (0..cpu_num).map(cpu_idle_time_us).sum();
+}
+
+/// 获取所有cpu空闲并且有进程等待io的时间之和（单位：微秒）
+pub fn total_iowait_time_us() -> u64 {
+    let cpu_num = unsafe { smp_get_total_cpu() };
+    return (0..cpu_num).map(|cpu| cpu_tick_stat(cpu).iowait_us()).sum();
+}
+
+/// 将一个时钟周期计入cpu的某种状态
+///
+/// ## 参数
+///
+/// - `cpu_id` : 时钟中断发生的cpu
+/// - `idle` : cpu是否正在运行idle进程
+/// - `user_mode` : 时钟中断发生时，cpu是否处于用户态
+fn account_cpu_tick(cpu_id: u32, idle: bool, user_mode: bool) {
+    let stat = cpu_tick_stat(cpu_id);
+    let counter = if !idle {
+        if user_mode {
+            &stat.user
+        } else {
+            &stat.system
+        }
+    } else if CPU_NR_IOWAIT[cpu_id as usize].load(Ordering::SeqCst) > 0 {
+        &stat.iowait
+    } else {
+        &stat.idle
+    };
+    counter.fetch_add(1, Ordering::SeqCst);
+}
+
+/// 让出cpu以等待io完成
+///
+/// 调用者需要先将进程标记为睡眠。在进程睡眠期间，如果它最后运行的cpu空闲，那么空闲的时间被计为iowait
+pub fn io_schedule() {
+    let cpu_id = smp_get_processor_id() as usize;
+    CPU_NR_IOWAIT[cpu_id].fetch_add(1, Ordering::SeqCst);
+    sched();
+    CPU_NR_IOWAIT[cpu_id].fetch_sub(1, Ordering::SeqCst);
+}
+
 // 获取某个cpu的负载情况，返回当前负载，cpu_id 是获取负载的cpu的id
-// TODO:将获取负载情况调整为最近一段时间运行进程的数量
+// cfs进程的负载为最近一段时间的平均负载，按照普通进程的权重换算为进程数
 pub fn get_cpu_loads(cpu_id: u32) -> u32 {
     let cfs_scheduler = __get_cfs_scheduler();
     let rt_scheduler = __get_rt_scheduler();
-    let len_cfs = cfs_scheduler.get_cfs_queue_len(cpu_id);
+    let len_cfs =
+        ((cfs_scheduler.cpu_load(cpu_id as usize) + NICE_0_LOAD / 2) / NICE_0_LOAD) as usize;
     let len_rt = rt_scheduler.rt_queue_len(cpu_id);
+    let len_dl = __get_dl_scheduler().nr_running(cpu_id as usize);
     // let load_rt = rt_scheduler.get_load_list_len(cpu_id);
     // kdebug!("this cpu_id {} is load rt {}", cpu_id, load_rt);
 
-    return (len_rt + len_cfs) as u32;
+    return (len_dl + len_rt + len_cfs) as u32;
 }
+/// 获取进程可以运行的在线cpu的集合
+///
+/// 进程实际允许运行的cpu集合见`ProcessControlBlock::effective_affinity`。
+/// 如果该集合不包含任何cpu id小于`cpu_num`的在线cpu（例如它被绑定的cpu已经下线），
+/// 那么将其亲和性重置为在线的cpu的集合。进程组（cpuset）允许的cpu也都不在线时，
+/// 同时将进程组允许的cpu集合重置为在线的cpu的集合，否则进程会一直被视为不允许在任何cpu上运行
+///
+/// ## 参数
+///
+/// - `pcb` : 要运行的进程
+/// - `online` : 在线的cpu的集合
+/// - `cpu_num` : cpu的数目
+pub fn task_allowed_cpus(
+    pcb: &Arc<ProcessControlBlock>,
+    online: CpuMask,
+    cpu_num: usize,
+) -> CpuMask {
+    let usable = |mask: CpuMask| (mask & online).iter().any(|cpu_id| cpu_id < cpu_num);
+    let allowed = pcb.effective_affinity() & online;
+    if usable(allowed) {
+        return allowed;
+    }
+    kwarn!(
+        "pid {:?} is not allowed to run on any online cpu, reset its cpu affinity",
+        pcb.pid()
+    );
+    pcb.sched_info()
+        .set_cpu_affinity(online)
+        .expect("No cpu is online");
+    if let Some(tg) = pcb.basic().tg().filter(|tg| !usable(tg.cpus_allowed())) {
+        kwarn!(
+            "pid {:?}: no cpu of its cpuset is online, reset the cpuset",
+            pcb.pid()
+        );
+        tg.set_cpus_allowed(online).expect("No cpu is online");
+    }
+    return pcb.effective_affinity() & online;
+}
+
 // 负载均衡
 pub fn loads_balance(pcb: Arc<ProcessControlBlock>) {
+    // 正在被迁移的进程的on_cpu与last_cpu可能已经过时，由完成迁移的一方决定它的去向
+    if pcb.sched_info().is_migrating() {
+        return;
+    }
     // 对pcb的迁移情况进行调整
     // 获取总的CPU数量
     let cpu_num = unsafe { smp_get_total_cpu() };
-    // 获取当前负载最小的CPU的id
-    let mut min_loads_cpu_id = smp_get_processor_id();
-    let mut min_loads = get_cpu_loads(smp_get_processor_id());
-    for cpu_id in 0..cpu_num {
+    // 只在允许进程运行的在线cpu中选择
+    let allowed = task_allowed_cpus(&pcb, cpu_online_mask(), cpu_num as usize);
+    let is_allowed = |cpu_id: &u32| *cpu_id < cpu_num && allowed.contains(*cpu_id as usize);
+    // 获取当前负载最小的CPU的id。
+    // 负载相同时，优先选择进程上一次运行的cpu，因为该cpu上的缓存可能仍然是热的
+    let mut min_loads_cpu_id = pcb
+        .sched_info()
+        .last_cpu()
+        .filter(is_allowed)
+        .or_else(|| Some(smp_get_processor_id()).filter(is_allowed))
+        .or_else(|| (0..cpu_num).find(is_allowed))
+        .unwrap();
+    let mut min_loads = get_cpu_loads(min_loads_cpu_id);
+    for cpu_id in (0..cpu_num).filter(is_allowed) {
         let tmp_cpu_loads = get_cpu_loads(cpu_id);
-        if min_loads - tmp_cpu_loads > 0 {
+        if tmp_cpu_loads < min_loads {
             min_loads_cpu_id = cpu_id;
             min_loads = tmp_cpu_loads;
         }
@@ -92,6 +356,67 @@ pub fn loads_balance(pcb: Arc<ProcessControlBlock>) {
         // kdebug!("set migrating, pcb:{:?}", pcb);
     }
 }
+
+/// 将cpu下线，并将它的运行队列中的进程迁移到其他在线的cpu上
+///
+/// 每个进程被迁移到允许它运行的、当前负载最小的在线cpu，负载随着迁移而更新，
+/// 因此进程会被均匀地分散到其余的cpu上。该cpu上正在运行的进程不在队列中，
+/// 它被标记为需要调度，在被抢占、重新入队时迁移。
+///
+/// ## 返回值
+///
+/// - 成功：Ok(())
+/// - 失败：cpu不存在或者已经下线时，返回`EINVAL`；该cpu是最后一个在线的cpu时，返回`EBUSY`
+pub fn offline_cpu(cpu_id: u32) -> Result<(), SystemError> {
+    let cpu_num = unsafe { smp_get_total_cpu() };
+    if cpu_id >= cpu_num || !cpu_online(cpu_id) {
+        return Err(SystemError::EINVAL);
+    }
+    if !(0..cpu_num).any(|cpu| cpu != cpu_id && cpu_online(cpu)) {
+        return Err(SystemError::EBUSY);
+    }
+
+    let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+    // 先标记为下线，此后负载均衡不会再选中该cpu
+    set_cpu_online(cpu_id, false);
+
+    let cpu = cpu_id as usize;
+    let migrated = match __get_cfs_scheduler().drain_cpu(cpu, cpu_online_mask()) {
+        Ok(migrated) => migrated,
+        Err(e) => {
+            // 其他cpu在此期间下线了，没能迁移的进程仍在该cpu的队列中，恢复为在线
+            set_cpu_online(cpu_id, true);
+            return Err(e);
+        }
+    };
+    // 实时进程与deadline进程重新入队，由负载均衡选择目标cpu
+    let rt_scheduler = __get_rt_scheduler();
+    let dl_scheduler = __get_dl_scheduler();
+    let mut others = Vec::new();
+    while let Some(pcb) = rt_scheduler.pick_next_task_rt(cpu_id) {
+        others.push(pcb);
+    }
+    while let Some(pcb) = dl_scheduler.pick_next_task_dl(cpu) {
+        others.push(pcb);
+    }
+    let migrated = migrated + others.len();
+    for pcb in others {
+        sched_enqueue(pcb, false);
+    }
+
+    let running = CPU_EXECUTING.get(cpu_id);
+    if !running.is_idle() {
+        if let Some(pcb) = ProcessManager::find(running) {
+            pcb.set_need_schedule();
+            if cpu_id != smp_get_processor_id() {
+                kick_cpu(cpu_id).expect("offline_cpu: Failed to kick cpu");
+            }
+        }
+    }
+    kinfo!("cpu {} is offline, {} tasks migrated", cpu_id, migrated);
+    return Ok(());
+}
+
 /// @brief 具体的调度器应当实现的trait
 pub trait Scheduler {
     /// @brief 使用该调度器发起调度的时候，要调用的函数
@@ -109,10 +434,39 @@ pub fn do_sched() -> Option<Arc<ProcessControlBlock>> {
     compiler_fence(core::sync::atomic::Ordering::SeqCst);
     let cfs_scheduler: &mut SchedulerCFS = __get_cfs_scheduler();
     let rt_scheduler: &mut SchedulerRT = __get_rt_scheduler();
+    let dl_scheduler: &mut SchedulerDeadline = __get_dl_scheduler();
     compiler_fence(core::sync::atomic::Ordering::SeqCst);
 
+    let cpu_id = smp_get_processor_id();
+    let current = ProcessManager::current_pcb();
+    // 下线的cpu只运行idle进程，正在运行的进程重新入队，从而被迁移到在线的cpu上
+    if !cpu_online(cpu_id) {
+        if current.pid().is_idle() {
+            return None;
+        }
+        current.flags().remove(ProcessFlags::NEED_SCHEDULE);
+        if current.sched_info().state() == ProcessState::Runnable {
+            sched_enqueue(current, false);
+        }
+        return Some(ProcessManager::idle_pcb()[cpu_id as usize].clone());
+    }
+    // deadline进程优先于实时进程与cfs进程运行
+    if dl_scheduler.should_run(cpu_id as usize, &current) {
+        return dl_scheduler.sched();
+    }
+    // 运行时间耗尽的deadline进程不能继续运行，也不会被重新入队，由补充运行时间的定时器重新加入队列
+    if SchedulerDeadline::is_throttled(&current) {
+        current.flags().remove(ProcessFlags::NEED_SCHEDULE);
+        return Some(
+            rt_scheduler
+                .pick_next_task_rt(cpu_id)
+                .unwrap_or_else(|| cfs_scheduler.this_cpu_queue().dequeue()),
+        );
+    }
+    drop(current);
+
     let next: Arc<ProcessControlBlock>;
-    match rt_scheduler.pick_next_task_rt(smp_get_processor_id()) {
+    match rt_scheduler.pick_next_task_rt(cpu_id) {
         Some(p) => {
             next = p;
             // 将pick的进程放回原处
@@ -129,8 +483,8 @@ pub fn do_sched() -> Option<Arc<ProcessControlBlock>> {
 /// @brief 将进程加入调度队列
 ///
 /// @param pcb 要被加入队列的pcb
-/// @param reset_time 是否重置虚拟运行时间
-pub fn sched_enqueue(pcb: Arc<ProcessControlBlock>, mut reset_time: bool) {
+/// @param reset_time 进程是否刚刚被唤醒，若是，则按照`CFSQueue::place_entity`调整cfs进程的虚拟运行时间
+pub fn sched_enqueue(pcb: Arc<ProcessControlBlock>, reset_time: bool) {
     compiler_fence(core::sync::atomic::Ordering::SeqCst);
     if pcb.sched_info().state() != ProcessState::Runnable {
         return;
@@ -145,21 +499,87 @@ pub fn sched_enqueue(pcb: Arc<ProcessControlBlock>, mut reset_time: bool) {
     if pcb.flags().contains(ProcessFlags::NEED_MIGRATE) {
         // kdebug!("migrating pcb:{:?}", pcb);
         pcb.flags().remove(ProcessFlags::NEED_MIGRATE);
-        pcb.sched_info().set_on_cpu(pcb.sched_info().migrate_to());
-        reset_time = true;
+        // 其他cpu正在迁移这个进程时，由它决定进程的去向
+        if pcb.sched_info().start_migration() {
+            let prev_cpu = pcb.sched_info().on_cpu();
+            let target_cpu = pcb.sched_info().migrate_to();
+            // 新创建的进程的虚拟运行时间已经在fork时确定，不需要调整
+            if let (Some(src), Some(dst)) = (prev_cpu, target_cpu) {
+                if pcb.sched_info().policy().is_fair() {
+                    cfs_scheduler.migrate_entity(&pcb, src as usize, dst as usize);
+                } else if src != dst {
+                    trace_sched_migrate(&pcb, src as usize, dst as usize);
+                }
+            }
+            pcb.sched_info().finish_migration(target_cpu);
+        }
     }
 
     assert!(pcb.sched_info().on_cpu().is_some());
+    trace_sched_enqueue(&pcb);
 
     match pcb.sched_info().policy() {
-        SchedPolicy::CFS => {
+        SchedPolicy::CFS | SchedPolicy::BATCH | SchedPolicy::IDLE => {
             if reset_time {
                 cfs_scheduler.enqueue_reset_vruntime(pcb.clone());
             } else {
-                cfs_scheduler.enqueue(pcb.clone());
+                cfs_scheduler.enqueue_pcb(pcb.clone());
+            }
+        }
+        SchedPolicy::FIFO | SchedPolicy::RR => {
+            rt_scheduler.enqueue(pcb.clone());
+            ProcessManager::wakeup_preempt(&pcb);
+        }
+        SchedPolicy::DEADLINE => {
+            let dl_scheduler = __get_dl_scheduler();
+            if reset_time {
+                dl_scheduler.task_woken(&pcb);
+            }
+            let cpu_id = pcb.sched_info().on_cpu().unwrap();
+            if dl_scheduler.enqueue_on(pcb.clone(), cpu_id as usize) {
+                dl_scheduler.check_preempt(&pcb, cpu_id);
             }
         }
-        SchedPolicy::FIFO | SchedPolicy::RR => rt_scheduler.enqueue(pcb.clone()),
+    }
+}
+
+/// 将进程从它所在的运行队列中移出
+///
+/// ## 返回值
+///
+/// 进程是否在运行队列中
+pub fn sched_dequeue(pcb: &Arc<ProcessControlBlock>) -> bool {
+    let cpu_id = match pcb.sched_info().on_cpu() {
+        Some(cpu_id) => cpu_id as usize,
+        None => return false,
+    };
+    let dequeued = match pcb.sched_info().policy() {
+        SchedPolicy::CFS | SchedPolicy::BATCH | SchedPolicy::IDLE => {
+            __get_cfs_scheduler().dequeue_pcb(pcb, cpu_id)
+        }
+        SchedPolicy::FIFO | SchedPolicy::RR => __get_rt_scheduler().dequeue_pcb(pcb, cpu_id),
+        SchedPolicy::DEADLINE => __get_dl_scheduler().dequeue_pcb(pcb, cpu_id),
+    };
+    if dequeued {
+        trace_sched_dequeue(pcb);
+    }
+    return dequeued;
+}
+
+/// 在fork时，为子进程确定调度相关的初始状态
+///
+/// 必须在父进程所在的cpu上、子进程被唤醒之前调用
+///
+/// ## 参数
+///
+/// - `parent` : 父进程的pcb
+/// - `child` : 子进程的pcb
+pub fn sched_fork(parent: &Arc<ProcessControlBlock>, child: &Arc<ProcessControlBlock>) {
+    match child.sched_info().policy() {
+        SchedPolicy::CFS | SchedPolicy::BATCH | SchedPolicy::IDLE => {
+            __get_cfs_scheduler().task_fork(parent, child)
+        }
+        SchedPolicy::FIFO | SchedPolicy::RR | SchedPolicy::DEADLINE => {}
     }
 }
 
@@ -167,8 +587,39 @@ pub fn sched_enqueue(pcb: Arc<ProcessControlBlock>, mut reset_time: bool) {
 ///!  CFS_SCHEDULER_PTR: Option<Box<SchedulerCFS>>是不是需要修改，现在已经每个进程组一个SchedulerCFS
 pub fn cfs_sched_enqueue(pcb: Arc<ProcessControlBlock>, mut reset_time: bool) {
     let mytg = pcb.basic().tg();
-    let cpu_queue: Vec<&mut CFSQueue> = mytg.cfs().get_cpu_queue();
-    cpu_queue[pcb.sched_info().on_cpu().unwrap() as usize].enqueue(pcb.se());
+    let mut cpu_queue = mytg
+        .cfs()
+        .cpu_queue(pcb.sched_info().on_cpu().unwrap() as usize);
+    cpu_queue.enqueue_se(pcb.se());
+}
+
+/// 判断进程是否应当在中断返回时被抢占
+///
+/// 进程的时间片耗尽（或者被更高优先级的进程抢占）时会被设置`NEED_SCHEDULE`标志，
+/// 但是如果进程正持有自旋锁（`preempt_count`不为0），就不能在此时被抢占。
+pub fn need_resched(pcb: &Arc<ProcessControlBlock>) -> bool {
+    return pcb.flags().contains(ProcessFlags::NEED_SCHEDULE) && pcb.preempt_count() == 0;
+}
+
+/// 在中断处理程序返回之前调用，如果当前进程需要被抢占，那么发起调度
+///
+/// 这保证了一直在执行计算、不会主动睡眠的进程，在时间片耗尽后的那一次时钟中断返回时就会被切换，
+/// 而不是一直运行到它下一次主动让出cpu。
+///
+/// ## 返回值
+///
+/// 是否发起了调度
+#[no_mangle]
+pub extern "C" fn sched_irq_return() -> bool {
+    let current = ProcessManager::current_pcb();
+    if !need_resched(&current) {
+        return false;
+    }
+    drop(current);
+
+    compiler_fence(Ordering::SeqCst);
+    sched();
+    return true;
 }
 
 /// @brief 初始化进程调度器模块
@@ -179,22 +630,130 @@ pub extern "C" fn sched_init() {
     unsafe {
         sched_cfs_init();
         sched_rt_init();
+        sched_dl_init();
     }
     kinfo!("Schedulers initialized");
 }
 
 /// @brief 当时钟中断到达时，更新时间片
 /// 请注意，该函数只能被时钟中断处理程序调用
+///
+/// @param user_mode 时钟中断发生时，cpu是否处于用户态
 #[allow(dead_code)]
 #[no_mangle]
-pub extern "C" fn sched_update_jiffies() {
+pub extern "C" fn sched_update_jiffies(user_mode: i32) {
+    let current = ProcessManager::current_pcb();
+    let idle = current.pid().is_idle();
+    account_cpu_tick(smp_get_processor_id(), idle, user_mode != 0);
+    if !idle {
+        // 将这个时钟周期计入当前进程的用户态或内核态运行时间
+        current
+            .sched_info()
+            .account_time(user_mode != 0, SCHED_TICK_INTERVAL_US);
+        ProcessManager::itimer_tick(&current, user_mode != 0, SCHED_TICK_INTERVAL_US);
+        ProcessManager::check_cpu_rlimit(&current);
+    }
+    sched_stall_tick(smp_get_processor_id(), &current, sched_clock());
+    drop(current);
+    calc_global_load_tick();
+
     let policy = ProcessManager::current_pcb().sched_info().policy();
     match policy {
-        SchedPolicy::CFS => {
+        SchedPolicy::CFS | SchedPolicy::BATCH | SchedPolicy::IDLE => {
             __get_cfs_scheduler().timer_update_jiffies();
         }
         SchedPolicy::FIFO | SchedPolicy::RR => {
             __get_rt_scheduler().timer_update_jiffies();
         }
+        SchedPolicy::DEADLINE => {
+            __get_dl_scheduler()
+                .timer_update(&ProcessManager::current_pcb(), SCHED_TICK_INTERVAL_NS);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, string::ToString};
+
+    use super::{
+        account_cpu_tick, account_switch_latency, cpu_idle_time_us, cpu_tick_stat, switch_latency,
+        SCHED_TICK_INTERVAL_US,
+    };
+    use crate::{
+        arch::CurrentIrqArch,
+        exception::InterruptArch,
+        process::{
+            kthread::{KernelThreadClosure, KernelThreadMechanism},
+            ProcessFlags,
+        },
+        sched::clock::sched_clock,
+        smp::core::smp_get_processor_id,
+    };
+
+    use super::CPU_NR_IOWAIT;
+    use core::sync::atomic::Ordering;
+
+    /// 进程被标记为需要调度后被切换出去，延迟计入当前cpu的统计；没有被切换出去时丢弃记录
+    #[test]
+    fn test_switch_latency() {
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let stat = switch_latency(smp_get_processor_id());
+        let count = stat.count();
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "switch-latency".to_string(),
+        )
+        .unwrap();
+
+        for latency in [1000, 3000, 2000] {
+            pcb.sched_info().mark_resched(sched_clock() - latency);
+            account_switch_latency(&pcb, true);
+        }
+        assert_eq!(stat.count(), count + 3);
+        assert!(stat.max_ns() >= 3000);
+        assert!(stat.avg_ns() > 0);
+
+        // 标记仍然有效时不丢弃，标记被清除后丢弃
+        pcb.set_need_schedule();
+        account_switch_latency(&pcb, false);
+        assert!(pcb.sched_info().take_resched_at().is_some());
+        pcb.set_need_schedule();
+        pcb.flags().remove(ProcessFlags::NEED_SCHEDULE);
+        account_switch_latency(&pcb, false);
+        assert!(pcb.sched_info().take_resched_at().is_none());
+        assert_eq!(stat.count(), count + 3);
+    }
+
+    /// cpu空闲的时钟周期计入idle时间，有进程等待io时计入iowait时间
+    #[test]
+    fn test_cpu_idle_ticks() {
+        // 关中断，避免真实的时钟中断同时修改计数
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let cpu = smp_get_processor_id();
+        let stat = cpu_tick_stat(cpu);
+        let idle = stat.idle_us();
+        let iowait = stat.iowait_us();
+        let system = stat.system_us();
+
+        for _ in 0..3 {
+            account_cpu_tick(cpu, true, false);
+        }
+        assert_eq!(stat.idle_us(), idle + 3 * SCHED_TICK_INTERVAL_US);
+        assert_eq!(stat.iowait_us(), iowait);
+
+        CPU_NR_IOWAIT[cpu as usize].fetch_add(1, Ordering::SeqCst);
+        account_cpu_tick(cpu, true, false);
+        CPU_NR_IOWAIT[cpu as usize].fetch_sub(1, Ordering::SeqCst);
+        assert_eq!(stat.iowait_us(), iowait + SCHED_TICK_INTERVAL_US);
+        assert_eq!(
+            cpu_idle_time_us(cpu),
+            idle + iowait + 4 * SCHED_TICK_INTERVAL_US
+        );
+
+        // 运行进程的时钟周期不计入空闲时间
+        account_cpu_tick(cpu, false, false);
+        assert_eq!(stat.system_us(), system + SCHED_TICK_INTERVAL_US);
+        assert_eq!(stat.idle_us(), idle + 3 * SCHED_TICK_INTERVAL_US);
     }
 }
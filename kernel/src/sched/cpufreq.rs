@@ -0,0 +1,62 @@
+use core::fmt::Debug;
+
+/// cpu利用率的满值，表示cpu在所有时间里都在运行进程
+pub const SCHED_CAPACITY_SCALE: u64 = 1024;
+
+/// 利用率平均值的衰减位数：每个时钟中断，旧的平均值保留 (2^UTIL_AVG_SHIFT - 1) / 2^UTIL_AVG_SHIFT
+const UTIL_AVG_SHIFT: u32 = 3;
+
+/// cpu频率调节器
+///
+/// 调度器在每次时钟中断时，以cpu当前的利用率调用`update`，
+/// 平台相关的电源管理代码可以据此调整cpu的频率（类似Linux的schedutil）
+pub trait CpuFreqGovernor: Debug + Send + Sync {
+    /// 报告cpu的利用率
+    ///
+    /// ## 参数
+    ///
+    /// - `cpu` : cpu的id
+    /// - `util` : cpu的利用率，范围为`[0, SCHED_CAPACITY_SCALE]`
+    ///
+    /// 在时钟中断中、关中断的情况下调用，不能睡眠
+    fn update(&self, cpu: usize, util: u64);
+}
+
+/// 不做任何事情的频率调节器，保持cpu的频率不变
+#[derive(Debug, Default)]
+pub struct NoopGovernor;
+
+impl CpuFreqGovernor for NoopGovernor {
+    fn update(&self, _cpu: usize, _util: u64) {}
+}
+
+/// 根据一次时钟中断的采样，更新cpu利用率的滑动平均值
+///
+/// ## 参数
+///
+/// - `util_avg` : 原来的平均值
+/// - `busy` : 这次时钟中断时，cpu是否在运行非IDLE进程
+///
+/// ## 返回值
+///
+/// 新的平均值
+pub fn update_util_avg(util_avg: u64, busy: bool) -> u64 {
+    let sample = if busy { SCHED_CAPACITY_SCALE } else { 0 };
+    let kept = util_avg - (util_avg >> UTIL_AVG_SHIFT);
+    return kept + (sample >> UTIL_AVG_SHIFT);
+}
+
+/// 计算报告给频率调节器的cpu利用率
+///
+/// 有进程在队列中等待时，cpu已经饱和，利用率为满值；否则为运行时间的滑动平均值
+///
+/// ## 参数
+///
+/// - `util_avg` : 运行时间的滑动平均值
+/// - `nr_running` : 队列中等待运行的进程数
+pub fn cpu_util(util_avg: u64, nr_running: usize) -> u64 {
+    if nr_running > 0 {
+        return SCHED_CAPACITY_SCALE;
+    }
+    return core::cmp::min(util_avg, SCHED_CAPACITY_SCALE);
+}
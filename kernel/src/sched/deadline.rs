@@ -0,0 +1,553 @@
+use core::sync::atomic::compiler_fence;
+
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+
+use crate::{
+    arch::cpu::current_cpu_id,
+    kBUG,
+    libs::spinlock::SpinLock,
+    mm::percpu::{PerCpu, PerCpuVar},
+    process::{Pid, ProcessControlBlock, ProcessFlags, ProcessManager, ProcessState},
+    smp::{
+        core::{smp_get_processor_id, smp_possible_cpu_num},
+        kick_cpu,
+    },
+    syscall::SystemError,
+    time::timer::{clock, Timer, TimerFunction},
+};
+
+use super::{
+    core::{sched_enqueue, Scheduler, CPU_EXECUTING},
+    SchedPolicy,
+};
+
+/// 声明全局的deadline调度器实例
+pub static mut DL_SCHEDULER_PTR: Option<Box<SchedulerDeadline>> = None;
+
+/// 获取deadline调度器实例的可变引用
+#[inline]
+pub fn __get_dl_scheduler() -> &'static mut SchedulerDeadline {
+    return unsafe { DL_SCHEDULER_PTR.as_mut().unwrap() };
+}
+
+/// 初始化deadline调度器
+pub unsafe fn sched_dl_init() {
+    if DL_SCHEDULER_PTR.is_none() {
+        DL_SCHEDULER_PTR = Some(Box::new(SchedulerDeadline::new()));
+    } else {
+        kBUG!("Try to init Deadline Scheduler twice.");
+        panic!("Try to init Deadline Scheduler twice.");
+    }
+}
+
+/// 带宽的定点数精度，`BW_UNIT`表示一个cpu的全部时间
+const BW_SHIFT: u32 = 20;
+pub const BW_UNIT: u64 = 1 << BW_SHIFT;
+/// deadline进程最多能够占用的cpu时间的百分比，与Linux的sched_rt_runtime_us的默认值一致，
+/// 为其他进程留出5%的时间
+const DL_BW_PERCENT: u64 = 95;
+
+/// deadline调度器使用的时钟（单位：纳秒）
+///
+/// 与内核定时器使用同一个时间基准，使得补充运行时间的定时器与截止时间可以直接比较
+fn dl_clock() -> u64 {
+    return clock() * 1000;
+}
+
+/// deadline进程的调度参数（单位：纳秒）
+///
+/// 进程在每个`period`内，需要在`deadline`之前获得`runtime`的运行时间
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineParams {
+    pub runtime_ns: u64,
+    pub deadline_ns: u64,
+    pub period_ns: u64,
+}
+
+impl DeadlineParams {
+    pub const fn new(runtime_ns: u64, deadline_ns: u64, period_ns: u64) -> Self {
+        Self {
+            runtime_ns,
+            deadline_ns,
+            period_ns,
+        }
+    }
+
+    /// 校验调度参数
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：规范化之后的参数，`period_ns`为0时取`deadline_ns`
+    /// - 失败：不满足`0 < runtime <= deadline <= period`时，返回`EINVAL`
+    pub fn validate(&self) -> Result<Self, SystemError> {
+        let period_ns = if self.period_ns == 0 {
+            self.deadline_ns
+        } else {
+            self.period_ns
+        };
+        if self.runtime_ns == 0
+            || self.runtime_ns > self.deadline_ns
+            || self.deadline_ns > period_ns
+        {
+            return Err(SystemError::EINVAL);
+        }
+        return Ok(Self::new(self.runtime_ns, self.deadline_ns, period_ns));
+    }
+
+    /// 进程占用的带宽，`BW_UNIT`表示一个cpu的全部时间
+    pub fn bandwidth(&self) -> u64 {
+        if self.period_ns == 0 {
+            return 0;
+        }
+        return ((self.runtime_ns as u128) << BW_SHIFT) as u64 / self.period_ns;
+    }
+}
+
+/// 进程的deadline调度状态
+#[derive(Debug, Default)]
+pub struct DeadlineEntity {
+    /// 调度参数
+    params: DeadlineParams,
+    /// 当前周期内剩余的运行时间（单位：纳秒）
+    runtime_left: i64,
+    /// 当前周期的绝对截止时间（单位：纳秒）
+    abs_deadline: u64,
+    /// 下一个周期开始的时刻（单位：纳秒）
+    next_period: u64,
+    /// 当前周期的运行时间已经耗尽，在下一个周期开始之前不能运行
+    throttled: bool,
+    /// 在下一个周期开始时补充运行时间的定时器
+    replenish_timer: Option<Arc<Timer>>,
+}
+
+impl DeadlineEntity {
+    pub fn params(&self) -> DeadlineParams {
+        return self.params;
+    }
+
+    /// 设置调度参数，并从此刻开始一个新的周期
+    pub fn set_params(&mut self, params: DeadlineParams, now: u64) {
+        self.params = params;
+        self.cancel_timer();
+        self.replenish(now);
+    }
+
+    pub fn abs_deadline(&self) -> u64 {
+        return self.abs_deadline;
+    }
+
+    pub fn runtime_left(&self) -> i64 {
+        return self.runtime_left;
+    }
+
+    pub fn is_throttled(&self) -> bool {
+        return self.throttled;
+    }
+
+    /// 从`now`开始一个新的周期，补充运行时间
+    pub fn replenish(&mut self, now: u64) {
+        self.runtime_left = self.params.runtime_ns as i64;
+        self.abs_deadline = now + self.params.deadline_ns;
+        self.next_period = now + self.params.period_ns;
+        self.throttled = false;
+    }
+
+    /// 进程被唤醒时，如果当前周期的截止时间已经过去，那么开始一个新的周期
+    pub fn on_wakeup(&mut self, now: u64) {
+        if !self.throttled && now >= self.abs_deadline {
+            self.replenish(now);
+        }
+    }
+
+    /// 扣除进程运行的时间
+    ///
+    /// ## 返回值
+    ///
+    /// 当前周期的运行时间是否在这次扣除后耗尽
+    pub fn charge(&mut self, delta_ns: u64) -> bool {
+        if self.throttled {
+            return false;
+        }
+        self.runtime_left -= delta_ns as i64;
+        if self.runtime_left > 0 {
+            return false;
+        }
+        self.throttled = true;
+        return true;
+    }
+
+    fn cancel_timer(&mut self) {
+        if let Some(timer) = self.replenish_timer.take() {
+            timer.cancel();
+        }
+    }
+}
+
+/// deadline进程的带宽准入控制
+#[derive(Debug)]
+pub struct DeadlineBandwidth {
+    /// 已经被deadline进程占用的带宽
+    total: u64,
+    /// 所有cpu能够分配给deadline进程的带宽
+    capacity: u64,
+}
+
+impl DeadlineBandwidth {
+    pub fn new(cpu_num: usize) -> Self {
+        return Self {
+            total: 0,
+            capacity: cpu_num as u64 * BW_UNIT * DL_BW_PERCENT / 100,
+        };
+    }
+
+    pub fn total(&self) -> u64 {
+        return self.total;
+    }
+
+    /// 将一个进程占用的带宽从`old`改为`new`
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(())
+    /// - 失败：增加带宽后超过容量时，返回`EBUSY`，已占用的带宽不变
+    pub fn change(&mut self, old: u64, new: u64) -> Result<(), SystemError> {
+        let total = self.total - old + new;
+        if new > old && total > self.capacity {
+            return Err(SystemError::EBUSY);
+        }
+        self.total = total;
+        return Ok(());
+    }
+}
+
+/// 在deadline进程的下一个周期开始时，补充运行时间的定时器函数
+#[derive(Debug)]
+struct DeadlineReplenishHelper {
+    pcb: Weak<ProcessControlBlock>,
+}
+
+impl TimerFunction for DeadlineReplenishHelper {
+    fn run(&mut self) -> Result<(), SystemError> {
+        if let Some(pcb) = self.pcb.upgrade() {
+            __get_dl_scheduler().replenish(&pcb);
+        }
+        return Ok(());
+    }
+}
+
+/// deadline调度器类（最早截止时间优先）
+pub struct SchedulerDeadline {
+    /// 每个cpu的队列，以（绝对截止时间，pid）为键
+    cpu_queue: PerCpuVar<SpinLock<BTreeMap<(u64, Pid), Arc<ProcessControlBlock>>>>,
+    /// 带宽准入控制
+    bandwidth: SpinLock<DeadlineBandwidth>,
+}
+
+impl SchedulerDeadline {
+    pub fn new() -> SchedulerDeadline {
+        return Self::with_cpu_num(smp_possible_cpu_num());
+    }
+
+    /// 创建一个deadline调度器，准入控制按照`cpu_num`个cpu的容量计算
+    pub fn with_cpu_num(cpu_num: usize) -> SchedulerDeadline {
+        let cpu_queue = (0..PerCpu::MAX_CPU_NUM)
+            .map(|_| SpinLock::new(BTreeMap::new()))
+            .collect::<Vec<_>>();
+        return SchedulerDeadline {
+            cpu_queue: PerCpuVar::new(cpu_queue).expect("Failed to create deadline per cpu queues"),
+            bandwidth: SpinLock::new(DeadlineBandwidth::new(cpu_num)),
+        };
+    }
+
+    /// 为进程申请deadline带宽
+    ///
+    /// ## 参数
+    ///
+    /// - `old` : 进程原来占用的带宽，进程原来不是deadline进程时为0
+    /// - `new` : 进程新的带宽，进程不再是deadline进程时为0
+    ///
+    /// ## 返回值
+    ///
+    /// 带宽不足时，返回`EBUSY`
+    pub fn admit(&self, old: u64, new: u64) -> Result<(), SystemError> {
+        return self.bandwidth.lock_irqsave().change(old, new);
+    }
+
+    /// 将进程加入指定cpu的队列
+    ///
+    /// ## 返回值
+    ///
+    /// 进程是否被加入了队列。IDLE进程以及运行时间已经耗尽的进程不会被加入队列
+    pub fn enqueue_on(&self, pcb: Arc<ProcessControlBlock>, cpu_id: usize) -> bool {
+        if pcb.pid().is_idle() {
+            return false;
+        }
+        let abs_deadline = {
+            let sched_info = pcb.sched_info();
+            let dl = sched_info.dl();
+            if dl.is_throttled() {
+                return false;
+            }
+            dl.abs_deadline()
+        };
+        let pid = pcb.pid();
+        self.cpu_queue
+            .force_get(cpu_id)
+            .lock_irqsave()
+            .insert((abs_deadline, pid), pcb);
+        return true;
+    }
+
+    /// 将进程从指定cpu的队列中移出
+    ///
+    /// ## 返回值
+    ///
+    /// 进程是否在队列中
+    pub fn dequeue_pcb(&self, pcb: &Arc<ProcessControlBlock>, cpu_id: usize) -> bool {
+        let mut queue = self.cpu_queue.force_get(cpu_id).lock_irqsave();
+        let key = queue
+            .iter()
+            .find(|(_, p)| Arc::ptr_eq(p, pcb))
+            .map(|(key, _)| *key);
+        return key.and_then(|key| queue.remove(&key)).is_some();
+    }
+
+    /// 取出指定cpu上截止时间最早的进程
+    pub fn pick_next_task_dl(&self, cpu_id: usize) -> Option<Arc<ProcessControlBlock>> {
+        return self
+            .cpu_queue
+            .force_get(cpu_id)
+            .lock_irqsave()
+            .pop_first()
+            .map(|(_, pcb)| pcb);
+    }
+
+    /// 指定cpu的队列中最早的截止时间
+    pub fn earliest_deadline(&self, cpu_id: usize) -> Option<u64> {
+        return self
+            .cpu_queue
+            .force_get(cpu_id)
+            .lock_irqsave()
+            .first_key_value()
+            .map(|((deadline, _), _)| *deadline);
+    }
+
+    /// 指定cpu的队列中的进程数
+    pub fn nr_running(&self, cpu_id: usize) -> usize {
+        return self.cpu_queue.force_get(cpu_id).lock_irqsave().len();
+    }
+
+    /// 判断进程是否为运行时间已经耗尽的deadline进程
+    pub fn is_throttled(pcb: &Arc<ProcessControlBlock>) -> bool {
+        let sched_info = pcb.sched_info();
+        return sched_info.policy() == SchedPolicy::DEADLINE && sched_info.dl().is_throttled();
+    }
+
+    /// 当前cpu是否应当由deadline调度器进行调度
+    ///
+    /// 队列中有deadline进程，或者当前进程是仍有运行时间的deadline进程时，
+    /// deadline进程优先于实时进程与cfs进程运行
+    pub fn should_run(&self, cpu_id: usize, current: &Arc<ProcessControlBlock>) -> bool {
+        if self.nr_running(cpu_id) > 0 {
+            return true;
+        }
+        let sched_info = current.sched_info();
+        return sched_info.policy() == SchedPolicy::DEADLINE
+            && sched_info.state() == ProcessState::Runnable
+            && !sched_info.dl().is_throttled();
+    }
+
+    /// 进程被唤醒时，如果当前周期的截止时间已经过去，那么开始一个新的周期
+    pub fn task_woken(&self, pcb: &Arc<ProcessControlBlock>) {
+        pcb.sched_info().dl().on_wakeup(dl_clock());
+    }
+
+    /// 时钟中断时，扣除当前deadline进程的运行时间。
+    /// 运行时间耗尽的进程被限流，直到下一个周期开始时才能再次运行
+    ///
+    /// ## 参数
+    ///
+    /// - `current` : 当前进程
+    /// - `delta_ns` : 时钟周期的长度（单位：纳秒）
+    pub fn timer_update(&self, current: &Arc<ProcessControlBlock>, delta_ns: u64) {
+        let sched_info = current.sched_info();
+        let mut dl = sched_info.dl();
+        if !dl.charge(delta_ns) {
+            return;
+        }
+        let timer = Timer::new(
+            Box::new(DeadlineReplenishHelper {
+                pcb: Arc::downgrade(current),
+            }),
+            dl.next_period / 1000,
+        );
+        dl.cancel_timer();
+        dl.replenish_timer = Some(timer.clone());
+        drop(dl);
+        drop(sched_info);
+        timer.activate();
+        current.set_need_schedule();
+    }
+
+    /// 下一个周期开始，为被限流的进程补充运行时间，并将其重新加入队列
+    fn replenish(&self, pcb: &Arc<ProcessControlBlock>) {
+        let on_cpu = {
+            let sched_info = pcb.sched_info();
+            if sched_info.policy() != SchedPolicy::DEADLINE {
+                return;
+            }
+            let mut dl = sched_info.dl();
+            dl.replenish_timer = None;
+            dl.replenish(dl_clock());
+            sched_info.on_cpu()
+        };
+        // 进程仍在cpu上运行（还没有来得及被切换出去）时，它可以直接继续运行
+        if let Some(cpu_id) = on_cpu {
+            if CPU_EXECUTING.get(cpu_id) == pcb.pid() {
+                return;
+            }
+        }
+        sched_enqueue(pcb.clone(), false);
+    }
+
+    /// 进程被加入某个cpu的队列后，如果它的截止时间早于该cpu上正在运行的进程，
+    /// 或者该cpu上正在运行的不是deadline进程，那么抢占该cpu
+    ///
+    /// ## 返回值
+    ///
+    /// 是否需要抢占
+    pub fn check_preempt(&self, pcb: &Arc<ProcessControlBlock>, cpu_id: u32) -> bool {
+        let running = match ProcessManager::find(CPU_EXECUTING.get(cpu_id)) {
+            Some(running) => running,
+            None => return false,
+        };
+        if Arc::ptr_eq(&running, pcb) {
+            return false;
+        }
+        let should_preempt = {
+            let running_info = running.sched_info();
+            running_info.policy() != SchedPolicy::DEADLINE
+                || running_info.dl().abs_deadline() > pcb.sched_info().dl().abs_deadline()
+        };
+        if should_preempt {
+            running.set_need_schedule();
+            if cpu_id != smp_get_processor_id() {
+                kick_cpu(cpu_id).expect("deadline check_preempt: Failed to kick cpu");
+            }
+        }
+        return should_preempt;
+    }
+}
+
+impl Scheduler for SchedulerDeadline {
+    /// 在当前cpu上进行调度，选择截止时间最早的进程。
+    /// 请注意，进入该函数之前，需要关中断
+    fn sched(&mut self) -> Option<Arc<ProcessControlBlock>> {
+        let current = ProcessManager::current_pcb();
+        current.flags().remove(ProcessFlags::NEED_SCHEDULE);
+        let cpu_id = current_cpu_id() as usize;
+
+        // 队列为空时，当前进程是唯一可运行的deadline进程，继续运行
+        let next = self.pick_next_task_dl(cpu_id)?;
+
+        let current_runnable = current.sched_info().state() == ProcessState::Runnable;
+        let current_earlier = {
+            let sched_info = current.sched_info();
+            let dl = sched_info.dl();
+            sched_info.policy() == SchedPolicy::DEADLINE
+                && !dl.is_throttled()
+                && dl.abs_deadline() <= next.sched_info().dl().abs_deadline()
+        };
+        if current_runnable && current_earlier {
+            self.enqueue_on(next, cpu_id);
+            return None;
+        }
+
+        compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        if current_runnable {
+            sched_enqueue(current, false);
+        }
+        return Some(next);
+    }
+
+    fn enqueue_pcb(&mut self, pcb: Arc<ProcessControlBlock>) {
+        let cpu_id = pcb.sched_info().on_cpu().unwrap() as usize;
+        self.enqueue_on(pcb, cpu_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, string::ToString, sync::Arc, vec::Vec};
+
+    use crate::{
+        process::kthread::{KernelThreadClosure, KernelThreadMechanism},
+        syscall::SystemError,
+    };
+
+    use super::{DeadlineBandwidth, DeadlineParams, SchedulerDeadline, BW_UNIT};
+
+    /// 队列按照绝对截止时间排序，截止时间最早的进程最先被选中
+    #[test]
+    fn test_edf_order() {
+        let scheduler = SchedulerDeadline::with_cpu_num(1);
+        let deadlines = [30_000_000, 10_000_000, 20_000_000];
+        let pcbs = deadlines
+            .iter()
+            .map(|deadline| {
+                let pcb = KernelThreadMechanism::create(
+                    KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+                    "dl-edf".to_string(),
+                )
+                .unwrap();
+                pcb.sched_info()
+                    .dl()
+                    .set_params(DeadlineParams::new(1_000_000, *deadline, *deadline), 0);
+                assert!(scheduler.enqueue_on(pcb.clone(), 0));
+                pcb
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(scheduler.earliest_deadline(0), Some(10_000_000));
+        assert!(Arc::ptr_eq(
+            &scheduler.pick_next_task_dl(0).unwrap(),
+            &pcbs[1]
+        ));
+        assert!(Arc::ptr_eq(
+            &scheduler.pick_next_task_dl(0).unwrap(),
+            &pcbs[2]
+        ));
+
+        // 运行时间耗尽的进程被限流，不能加入队列
+        assert!(pcbs[1].sched_info().dl().charge(1_000_000));
+        assert!(!scheduler.enqueue_on(pcbs[1].clone(), 0));
+        assert!(scheduler.dequeue_pcb(&pcbs[0], 0));
+        assert!(scheduler.pick_next_task_dl(0).is_none());
+    }
+
+    /// 总带宽超过容量时，新的deadline进程被拒绝
+    #[test]
+    fn test_dl_admission() {
+        assert_eq!(
+            DeadlineParams::new(2, 1, 1).validate(),
+            Err(SystemError::EINVAL)
+        );
+        let params = DeadlineParams::new(5, 10, 0).validate().unwrap();
+        assert_eq!(params.period_ns, 10);
+        assert_eq!(params.bandwidth(), BW_UNIT / 2);
+
+        let mut bw = DeadlineBandwidth::new(1);
+        bw.change(0, BW_UNIT / 2).unwrap();
+        bw.change(0, BW_UNIT * 4 / 10).unwrap();
+        assert_eq!(bw.change(0, BW_UNIT / 10), Err(SystemError::EBUSY));
+        assert_eq!(bw.total(), BW_UNIT * 9 / 10);
+        // 释放带宽之后可以再次申请
+        bw.change(BW_UNIT * 4 / 10, 0).unwrap();
+        bw.change(0, BW_UNIT / 10).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+use core::sync::atomic::{AtomicIsize, AtomicU64, Ordering};
+
+use crate::{
+    mm::percpu::PerCpu,
+    process::Pid,
+    smp::core::{smp_get_processor_id, smp_possible_cpu_num},
+};
+
+use super::{
+    cfs::__get_cfs_scheduler,
+    core::{CPU_EXECUTING, SCHED_TICK_INTERVAL_US},
+    deadline::__get_dl_scheduler,
+    rt::__get_rt_scheduler,
+};
+
+/// 负载均值使用的定点数的小数部分位数
+pub const FSHIFT: u64 = 11;
+/// 定点数表示的1.0
+pub const FIXED_1: u64 = 1 << FSHIFT;
+/// 1分钟平均负载的衰减系数：1/exp(5sec/1min)
+pub const EXP_1: u64 = 1884;
+/// 5分钟平均负载的衰减系数：1/exp(5sec/5min)
+pub const EXP_5: u64 = 2014;
+/// 15分钟平均负载的衰减系数：1/exp(5sec/15min)
+pub const EXP_15: u64 = 2037;
+
+/// 两次计算平均负载之间间隔的调度器时钟中断数（5秒）
+const LOAD_FREQ_TICKS: u64 = 5 * 1000000 / SCHED_TICK_INTERVAL_US + 1;
+
+/// 负责采样活跃进程数、计算平均负载的cpu，其他cpu不进行采样，避免重复计算
+const LOAD_SAMPLE_CPU: u32 = 0;
+
+/// 系统在最近1、5、15分钟内的平均负载（定点数）
+static AVENRUN: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+/// 距离上一次计算平均负载，经过的调度器时钟中断数
+static LOAD_UPDATE_TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// 每个cpu上进入不可中断睡眠的进程数减去在这个cpu上离开不可中断睡眠的进程数
+///
+/// 进程可能在一个cpu上睡眠、在另一个cpu上被唤醒，因此单个cpu的值可能为负数，只有所有cpu的总和有意义
+static CPU_NR_UNINTERRUPTIBLE: [AtomicIsize; PerCpu::MAX_CPU_NUM] = {
+    const ZERO: AtomicIsize = AtomicIsize::new(0);
+    [ZERO; PerCpu::MAX_CPU_NUM]
+};
+
+/// 进程在`cpu`上进入不可中断睡眠
+pub fn inc_nr_uninterruptible(cpu: u32) {
+    CPU_NR_UNINTERRUPTIBLE[cpu as usize].fetch_add(1, Ordering::SeqCst);
+}
+
+/// 进程在`cpu`上离开不可中断睡眠
+pub fn dec_nr_uninterruptible(cpu: u32) {
+    CPU_NR_UNINTERRUPTIBLE[cpu as usize].fetch_sub(1, Ordering::SeqCst);
+}
+
+/// 获取系统中处于不可中断睡眠的进程数
+pub fn nr_uninterruptible() -> usize {
+    let sum: isize = (0..smp_possible_cpu_num())
+        .map(|cpu| CPU_NR_UNINTERRUPTIBLE[cpu].load(Ordering::SeqCst))
+        .sum();
+    return sum.max(0) as usize;
+}
+
+/// 获取某个cpu上可运行的进程数，包括正在这个cpu上运行的进程，不包括IDLE进程
+fn cpu_nr_running(cpu: u32) -> usize {
+    let queued = __get_cfs_scheduler().cpu_queue(cpu as usize).nr_running()
+        + __get_rt_scheduler().rt_queue_len(cpu)
+        + __get_dl_scheduler().nr_running(cpu as usize);
+    let running = (CPU_EXECUTING.get(cpu) != Pid::IDLE) as usize;
+    return queued + running;
+}
+
+/// 获取系统中活跃的进程数，即所有cpu上可运行的进程数与处于不可中断睡眠的进程数之和
+pub fn nr_active() -> usize {
+    let nr_running: usize = (0..smp_possible_cpu_num() as u32).map(cpu_nr_running).sum();
+    return nr_running + nr_uninterruptible();
+}
+
+/// 获取系统最近1、5、15分钟的平均负载
+///
+/// ## 返回值
+///
+/// 以`FSHIFT`位小数的定点数表示的三个负载值
+pub fn loadavg() -> (u64, u64, u64) {
+    return (
+        AVENRUN[0].load(Ordering::SeqCst),
+        AVENRUN[1].load(Ordering::SeqCst),
+        AVENRUN[2].load(Ordering::SeqCst),
+    );
+}
+
+/// 获取定点数负载值的整数部分
+#[inline(always)]
+pub const fn load_int(x: u64) -> u64 {
+    x >> FSHIFT
+}
+
+/// 获取定点数负载值的小数部分（保留两位小数）
+#[inline(always)]
+pub const fn load_frac(x: u64) -> u64 {
+    load_int((x & (FIXED_1 - 1)) * 100)
+}
+
+/// 使用指数加权移动平均，计算新的负载值
+///
+/// ## 参数
+///
+/// - `load`: 上一次的负载值（定点数）
+/// - `exp`: 衰减系数
+/// - `active`: 当前活跃的进程数（定点数）
+pub const fn calc_load(load: u64, exp: u64, active: u64) -> u64 {
+    let mut newload = load * exp + active * (FIXED_1 - exp);
+    // 负载上升时向上取整，避免负载永远无法到达active
+    if active >= load {
+        newload += FIXED_1 - 1;
+    }
+    return newload / FIXED_1;
+}
+
+/// 调度器时钟中断到来时调用，每5秒对活跃进程数进行一次采样，更新平均负载
+///
+/// 只在`LOAD_SAMPLE_CPU`上进行计算
+pub fn calc_global_load_tick() {
+    if !should_sample(smp_get_processor_id(), &LOAD_UPDATE_TICKS) {
+        return;
+    }
+    update_avenrun(&AVENRUN, nr_active());
+}
+
+/// 在`cpu`上发生一次调度器时钟中断，判断这次中断是否应当进行采样
+///
+/// ## 参数
+///
+/// - `cpu` : 发生时钟中断的cpu
+/// - `ticks` : 距离上一次采样，采样cpu经过的时钟中断数
+///
+/// ## 返回值
+///
+/// 只有采样cpu每经过`LOAD_FREQ_TICKS`次时钟中断返回一次true
+fn should_sample(cpu: u32, ticks: &AtomicU64) -> bool {
+    if cpu != LOAD_SAMPLE_CPU {
+        return false;
+    }
+    if ticks.fetch_add(1, Ordering::SeqCst) + 1 < LOAD_FREQ_TICKS {
+        return false;
+    }
+    ticks.store(0, Ordering::SeqCst);
+    return true;
+}
+
+/// 使用一次采样得到的活跃进程数，更新1、5、15分钟的平均负载
+fn update_avenrun(avenrun: &[AtomicU64; 3], nr_active: usize) {
+    let active = nr_active as u64 * FIXED_1;
+    for (avg, exp) in avenrun.iter().zip([EXP_1, EXP_5, EXP_15]) {
+        avg.store(
+            calc_load(avg.load(Ordering::SeqCst), exp, active),
+            Ordering::SeqCst,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    use crate::smp::core::smp_get_processor_id;
+
+    use super::{
+        calc_load, dec_nr_uninterruptible, inc_nr_uninterruptible, load_int, nr_active,
+        nr_uninterruptible, should_sample, update_avenrun, EXP_1, EXP_15, EXP_5, FIXED_1,
+        LOAD_FREQ_TICKS, LOAD_SAMPLE_CPU,
+    };
+
+    /// 以恒定负载驱动EWMA，负载值应当收敛到该恒定值
+    #[test]
+    fn test_calc_load_converges() {
+        let active = 3 * FIXED_1;
+        let mut avg = [0u64; 3];
+        // 3小时的采样次数
+        for _ in 0..2160 {
+            for (load, exp) in avg.iter_mut().zip([EXP_1, EXP_5, EXP_15]) {
+                *load = calc_load(*load, exp, active);
+            }
+        }
+
+        for load in avg {
+            assert_eq!(load, active);
+            assert_eq!(load_int(load), 3);
+        }
+    }
+
+    /// 没有活跃进程时，负载值应当衰减到0
+    #[test]
+    fn test_calc_load_decays() {
+        let mut load = 2 * FIXED_1;
+        for _ in 0..720 {
+            load = calc_load(load, EXP_1, 0);
+        }
+        assert_eq!(load, 0);
+    }
+
+    /// 只有采样cpu进行采样，并且每`LOAD_FREQ_TICKS`次时钟中断恰好采样一次
+    #[test]
+    fn test_sample_cadence() {
+        let ticks = AtomicU64::new(0);
+        let other = LOAD_SAMPLE_CPU + 1;
+        let mut samples = 0;
+        for i in 1..=3 * LOAD_FREQ_TICKS {
+            // 其他cpu的时钟中断既不采样，也不推进采样的计数
+            assert!(!should_sample(other, &ticks));
+            if should_sample(LOAD_SAMPLE_CPU, &ticks) {
+                assert_eq!(i % LOAD_FREQ_TICKS, 0);
+                samples += 1;
+            }
+        }
+        assert_eq!(samples, 3);
+        assert_eq!(ticks.load(Ordering::SeqCst), 0);
+    }
+
+    /// 处于不可中断睡眠的进程被计入活跃进程数，从而提高平均负载
+    #[test]
+    fn test_uninterruptible_raises_load() {
+        let cpu = smp_get_processor_id();
+        let uninterruptible = nr_uninterruptible();
+        for _ in 0..2 {
+            inc_nr_uninterruptible(cpu);
+        }
+        assert_eq!(nr_uninterruptible(), uninterruptible + 2);
+        let injected = nr_active();
+        assert!(injected >= uninterruptible + 2);
+
+        let avenrun = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+        // 1小时的采样次数
+        for _ in 0..720 {
+            update_avenrun(&avenrun, injected);
+        }
+        assert_eq!(load_int(avenrun[0].load(Ordering::SeqCst)), injected as u64);
+
+        for _ in 0..2 {
+            dec_nr_uninterruptible(cpu);
+        }
+        assert_eq!(nr_uninterruptible(), uninterruptible);
+    }
+}
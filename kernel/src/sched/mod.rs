@@ -1,8 +1,16 @@
 pub mod cfs;
+pub mod clock;
 pub mod completion;
 pub mod core;
+pub mod deadline;
+pub mod cpufreq;
+pub mod loadavg;
 pub mod rt;
 pub mod syscall;
+pub mod trace;
+pub mod watchdog;
+
+use crate::syscall::SystemError;
 
 /// 调度策略
 #[allow(dead_code)]
@@ -10,10 +18,107 @@ pub mod syscall;
 pub enum SchedPolicy {
     /// 完全公平调度
     CFS,
+    /// 使用cfs调度的批处理进程，不获得交互式补偿，时间片更长
+    BATCH,
+    /// 使用cfs调度的极低优先级进程，只在没有其他进程可运行时才能获得可观的运行时间
+    IDLE,
     /// 先进先出调度
     FIFO,
     /// 轮转调度
     RR,
+    /// 最早截止时间优先调度
+    DEADLINE,
+}
+
+impl SchedPolicy {
+    /// 用户态sched_param中，实时调度策略的最小优先级
+    pub const RT_PARAM_MIN: i32 = 1;
+    /// 用户态sched_param中，实时调度策略的最大优先级
+    pub const RT_PARAM_MAX: i32 = 99;
+
+    /// 是否为实时调度策略
+    pub fn is_rt(&self) -> bool {
+        return matches!(self, SchedPolicy::FIFO | SchedPolicy::RR);
+    }
+
+    /// 是否由cfs调度器调度
+    pub fn is_fair(&self) -> bool {
+        return matches!(
+            self,
+            SchedPolicy::CFS | SchedPolicy::BATCH | SchedPolicy::IDLE
+        );
+    }
+
+    /// 是否为需要`CAP_SYS_NICE`能力才能设置的调度策略
+    pub fn is_privileged(&self) -> bool {
+        return self.is_rt() || *self == SchedPolicy::DEADLINE;
+    }
+
+    /// 将用户态sched_param中的优先级转换为调度优先级
+    ///
+    /// 实时调度策略的优先级范围为`[RT_PARAM_MIN, RT_PARAM_MAX]`，数值越大优先级越高，
+    /// 对应实时队列的下标`RT_PARAM_MAX - sched_priority`；由cfs调度的调度策略的优先级必须为0
+    ///
+    /// ## 返回值
+    ///
+    /// 优先级不在调度策略允许的范围内时，返回`EINVAL`。
+    /// deadline调度策略需要通过sched_setattr设置调度参数，总是返回`EINVAL`
+    pub fn priority_from_param(&self, sched_priority: i32) -> Result<SchedPriority, SystemError> {
+        if *self == SchedPolicy::DEADLINE {
+            return Err(SystemError::EINVAL);
+        }
+        if self.is_rt() {
+            if sched_priority < Self::RT_PARAM_MIN || sched_priority > Self::RT_PARAM_MAX {
+                return Err(SystemError::EINVAL);
+            }
+            return Ok(SchedPriority(Self::RT_PARAM_MAX - sched_priority));
+        }
+        if sched_priority != 0 {
+            return Err(SystemError::EINVAL);
+        }
+        return Ok(SchedPriority::DEFAULT);
+    }
+
+    /// 将调度优先级转换为用户态sched_param中的优先级，是`priority_from_param`的逆运算
+    ///
+    /// 不是实时调度策略时，总是返回0
+    pub fn param_from_priority(&self, priority: SchedPriority) -> i32 {
+        if self.is_rt() {
+            return Self::RT_PARAM_MAX - priority.data();
+        }
+        return 0;
+    }
+}
+
+impl TryFrom<usize> for SchedPolicy {
+    type Error = SystemError;
+
+    /// 将Linux的调度策略编号（SCHED_NORMAL、SCHED_FIFO、SCHED_RR、SCHED_BATCH、SCHED_IDLE、SCHED_DEADLINE）
+    /// 转换为调度策略
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        return match value {
+            0 => Ok(SchedPolicy::CFS),
+            1 => Ok(SchedPolicy::FIFO),
+            2 => Ok(SchedPolicy::RR),
+            3 => Ok(SchedPolicy::BATCH),
+            5 => Ok(SchedPolicy::IDLE),
+            6 => Ok(SchedPolicy::DEADLINE),
+            _ => Err(SystemError::EINVAL),
+        };
+    }
+}
+
+impl From<SchedPolicy> for usize {
+    fn from(value: SchedPolicy) -> Self {
+        return match value {
+            SchedPolicy::CFS => 0,
+            SchedPolicy::FIFO => 1,
+            SchedPolicy::RR => 2,
+            SchedPolicy::BATCH => 3,
+            SchedPolicy::IDLE => 5,
+            SchedPolicy::DEADLINE => 6,
+        };
+    }
 }
 
 /// 调度优先级
@@ -23,6 +128,8 @@ pub struct SchedPriority(i32);
 impl SchedPriority {
     const MIN: i32 = 0;
     const MAX: i32 = 139;
+    /// 普通进程的默认优先级
+    pub const DEFAULT: SchedPriority = SchedPriority(100);
 
     /// 创建一个新的调度优先级
     pub const fn new(priority: i32) -> Option<Self> {
@@ -42,3 +149,62 @@ impl SchedPriority {
         self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::syscall::SystemError;
+
+    use super::{SchedPolicy, SchedPriority};
+
+    /// cfs调度策略的优先级只能为0，实时调度策略的优先级在`[RT_PARAM_MIN, RT_PARAM_MAX]`之内，
+    /// 并且能够被还原为用户态的优先级
+    #[test]
+    fn test_priority_from_param() {
+        for policy in [SchedPolicy::CFS, SchedPolicy::BATCH, SchedPolicy::IDLE] {
+            assert_eq!(policy.priority_from_param(0), Ok(SchedPriority::DEFAULT));
+            assert_eq!(policy.priority_from_param(1), Err(SystemError::EINVAL));
+            assert_eq!(policy.param_from_priority(SchedPriority::DEFAULT), 0);
+        }
+
+        for policy in [SchedPolicy::FIFO, SchedPolicy::RR] {
+            for invalid in [0, SchedPolicy::RT_PARAM_MAX + 1, -1] {
+                assert_eq!(
+                    policy.priority_from_param(invalid),
+                    Err(SystemError::EINVAL)
+                );
+            }
+            for valid in [SchedPolicy::RT_PARAM_MIN, 42, SchedPolicy::RT_PARAM_MAX] {
+                let priority = policy.priority_from_param(valid).unwrap();
+                assert_eq!(policy.param_from_priority(priority), valid);
+            }
+        }
+        assert_eq!(
+            SchedPolicy::FIFO.priority_from_param(10).unwrap().data(),
+            89
+        );
+
+        // deadline调度策略的参数只能通过sched_setattr设置
+        assert_eq!(
+            SchedPolicy::DEADLINE.priority_from_param(0),
+            Err(SystemError::EINVAL)
+        );
+        assert!(SchedPolicy::DEADLINE.is_privileged());
+        assert!(!SchedPolicy::CFS.is_privileged());
+    }
+
+    /// 调度策略与Linux的编号相互转换
+    #[test]
+    fn test_policy_numbers() {
+        for policy in [
+            SchedPolicy::CFS,
+            SchedPolicy::FIFO,
+            SchedPolicy::RR,
+            SchedPolicy::BATCH,
+            SchedPolicy::IDLE,
+            SchedPolicy::DEADLINE,
+        ] {
+            assert_eq!(SchedPolicy::try_from(usize::from(policy)), Ok(policy));
+        }
+        assert_eq!(SchedPolicy::try_from(4), Err(SystemError::EINVAL));
+    }
+}
@@ -4,9 +4,9 @@ use alloc::{boxed::Box, collections::LinkedList, sync::Arc, vec::Vec};
 
 use crate::{
     arch::cpu::current_cpu_id,
-    include::bindings::bindings::MAX_CPU_NUM,
     kBUG, kdebug,
     libs::spinlock::SpinLock,
+    mm::percpu::{PerCpu, PerCpuVar},
     process::{ProcessControlBlock, ProcessFlags, ProcessManager},
 };
 
@@ -80,45 +80,53 @@ impl RTQueue {
         }
         queue.push_front(pcb);
     }
+    /// 将pcb从队列中移出
+    ///
+    /// ## 返回值
+    ///
+    /// pcb是否在队列中
+    pub fn remove(&mut self, pcb: &Arc<ProcessControlBlock>) -> bool {
+        let mut queue = self.locked_queue.lock_irqsave();
+        return queue.drain_filter(|p| Arc::ptr_eq(p, pcb)).count() > 0;
+    }
+
     pub fn get_rt_queue_size(&mut self) -> usize {
-        let queue = self.locked_queue.lock();
+        let queue = self.locked_queue.lock_irqsave();
         return queue.len();
     }
 }
 
 /// @brief RT调度器类
 pub struct SchedulerRT {
-    cpu_queue: Vec<Vec<&'static mut RTQueue>>,
-    load_list: Vec<&'static mut LinkedList<u64>>,
+    /// 每个cpu上，每个优先级的rt队列
+    cpu_queue: PerCpuVar<Vec<RTQueue>>,
+    /// 每个cpu的负载统计队列
+    load_list: PerCpuVar<LinkedList<u64>>,
 }
 
 impl SchedulerRT {
-    const RR_TIMESLICE: isize = 100;
+    /// RR进程的时间片（单位：时钟中断次数）
+    pub const RR_TIMESLICE: isize = 100;
     const MAX_RT_PRIO: isize = 100;
 
     pub fn new() -> SchedulerRT {
-        // 暂时手动指定核心数目
-        // todo: 从cpu模块来获取核心的数目
-        let mut result = SchedulerRT {
-            cpu_queue: Default::default(),
-            load_list: Default::default(),
-        };
-
-        // 为每个cpu核心创建队列
-        for cpu_id in 0..MAX_CPU_NUM {
-            result.cpu_queue.push(Vec::new());
-            // 每个CPU有MAX_RT_PRIO个优先级队列
-            for _ in 0..SchedulerRT::MAX_RT_PRIO {
-                result.cpu_queue[cpu_id as usize].push(Box::leak(Box::new(RTQueue::new())));
-            }
-        }
+        // 为每个cpu核心创建队列，每个CPU有MAX_RT_PRIO个优先级队列
+        let cpu_queue = (0..PerCpu::MAX_CPU_NUM)
+            .map(|_| {
+                (0..SchedulerRT::MAX_RT_PRIO)
+                    .map(|_| RTQueue::new())
+                    .collect()
+            })
+            .collect();
         // 为每个cpu核心创建负载统计队列
-        for _ in 0..MAX_CPU_NUM {
-            result
-                .load_list
-                .push(Box::leak(Box::new(LinkedList::new())));
-        }
-        return result;
+        let load_list = (0..PerCpu::MAX_CPU_NUM)
+            .map(|_| LinkedList::new())
+            .collect();
+
+        return SchedulerRT {
+            cpu_queue: PerCpuVar::new(cpu_queue).expect("Failed to create rt per cpu queues"),
+            load_list: PerCpuVar::new(load_list).expect("Failed to create rt per cpu load list"),
+        };
     }
 
     /// @brief 挑选下一个可执行的rt进程
@@ -126,7 +134,8 @@ impl SchedulerRT {
         // 循环查找，直到找到
         // 这里应该是优先级数量，而不是CPU数量，需要修改
         for i in 0..SchedulerRT::MAX_RT_PRIO {
-            let cpu_queue_i: &mut RTQueue = self.cpu_queue[cpu_id as usize][i as usize];
+            let cpu_queue_i: &mut RTQueue =
+                &mut self.cpu_queue.force_get_mut(cpu_id as usize)[i as usize];
             let proc: Option<Arc<ProcessControlBlock>> = cpu_queue_i.dequeue();
             if proc.is_some() {
                 return proc;
@@ -139,7 +148,7 @@ impl SchedulerRT {
     pub fn rt_queue_len(&mut self, cpu_id: u32) -> usize {
         let mut sum = 0;
         for prio in 0..SchedulerRT::MAX_RT_PRIO {
-            sum += self.cpu_queue[cpu_id as usize][prio as usize].get_rt_queue_size();
+            sum += self.cpu_queue.force_get_mut(cpu_id as usize)[prio as usize].get_rt_queue_size();
         }
         return sum as usize;
     }
@@ -147,14 +156,24 @@ impl SchedulerRT {
     #[allow(dead_code)]
     #[inline]
     pub fn load_list_len(&mut self, cpu_id: u32) -> usize {
-        return self.load_list[cpu_id as usize].len();
+        return self.load_list.force_get(cpu_id as usize).len();
+    }
+
+    /// 将进程从指定cpu的rt队列中移出
+    ///
+    /// ## 返回值
+    ///
+    /// 进程是否在队列中
+    pub fn dequeue_pcb(&mut self, pcb: &Arc<ProcessControlBlock>, cpu_id: usize) -> bool {
+        let priority = pcb.sched_info().priority().data() as usize;
+        return self.cpu_queue.force_get_mut(cpu_id)[priority].remove(pcb);
     }
 
     pub fn enqueue_front(&mut self, pcb: Arc<ProcessControlBlock>) {
         let cpu_id = current_cpu_id() as usize;
         let priority = pcb.sched_info().priority().data() as usize;
 
-        self.cpu_queue[cpu_id][priority].enqueue_front(pcb);
+        self.cpu_queue.force_get_mut(cpu_id)[priority].enqueue_front(pcb);
     }
 
     pub fn timer_update_jiffies(&self) {
@@ -203,7 +222,7 @@ impl Scheduler for SchedulerRT {
                     if proc.sched_info().rt_time_slice() <= 0 {
                         proc.sched_info()
                             .set_rt_time_slice(SchedulerRT::RR_TIMESLICE as isize);
-                        proc.flags().insert(ProcessFlags::NEED_SCHEDULE);
+                        proc.set_need_schedule();
                         sched_enqueue(proc, false);
                     }
                     // 目标进程时间片未耗尽，切换到目标进程
@@ -216,7 +235,8 @@ impl Scheduler for SchedulerRT {
                 }
                 // curr优先级更大，说明一定是实时进程，将所选进程入队列，此时需要入队首
                 else {
-                    self.cpu_queue[cpu_id as usize][priority.data() as usize].enqueue_front(proc);
+                    self.cpu_queue.force_get_mut(cpu_id as usize)[priority.data() as usize]
+                        .enqueue_front(proc);
                 }
             }
             _ => panic!("unsupported schedule policy"),
@@ -226,7 +246,7 @@ impl Scheduler for SchedulerRT {
 
     fn enqueue_pcb(&mut self, pcb: Arc<ProcessControlBlock>) {
         let cpu_id = pcb.sched_info().on_cpu().unwrap();
-        let cpu_queue = &mut self.cpu_queue[cpu_id as usize];
+        let cpu_queue = self.cpu_queue.force_get_mut(cpu_id as usize);
         let priority = pcb.sched_info().priority().data() as usize;
         cpu_queue[priority].enqueue(pcb);
     }
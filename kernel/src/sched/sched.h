@@ -59,6 +59,7 @@
 // int sched_setscheduler(struct process_control_block *p, int policy, const struct sched_param *param);
 
 // ================= Rust 实现 =============
-extern void sched_update_jiffies();
+extern void sched_update_jiffies(int user_mode);
 extern void sched_init();
 extern void sched();
+extern bool sched_irq_return();
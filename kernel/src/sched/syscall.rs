@@ -1,12 +1,45 @@
 use crate::{
     arch::CurrentIrqArch,
     exception::InterruptArch,
-    process::ProcessManager,
+    process::{Pid, ProcessManager},
     smp::core::smp_get_processor_id,
-    syscall::{Syscall, SystemError},
+    syscall::{
+        user_access::{UserBufferReader, UserBufferWriter},
+        Syscall, SystemError,
+    },
 };
 
-use super::core::{do_sched, CPU_EXECUTING};
+use super::{
+    core::{account_switch_latency, do_sched, CPU_EXECUTING},
+    deadline::DeadlineParams,
+    trace::trace_sched_switch,
+    SchedPolicy,
+};
+
+/// setpriority的目标为单个进程
+pub const PRIO_PROCESS: usize = 0;
+
+/// 调度参数，与Linux的`struct sched_param`一致
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SchedParam {
+    pub sched_priority: i32,
+}
+
+/// 调度属性，与Linux的`struct sched_attr`一致
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SchedAttr {
+    pub size: u32,
+    pub sched_policy: u32,
+    pub sched_flags: u64,
+    pub sched_nice: i32,
+    pub sched_priority: u32,
+    /// deadline调度策略的参数（单位：纳秒）
+    pub sched_runtime: u64,
+    pub sched_deadline: u64,
+    pub sched_period: u64,
+}
 
 impl Syscall {
     /// @brief 让系统立即运行调度器的系统调用
@@ -21,17 +54,230 @@ impl Syscall {
         }
         // 根据调度结果统一进行切换
         let pcb = do_sched();
+        let current_pcb = ProcessManager::current_pcb();
+        let next_pcb = pcb.filter(|next_pcb| next_pcb.pid() != current_pcb.pid());
+        account_switch_latency(&current_pcb, next_pcb.is_some());
 
-        if pcb.is_some() {
-            let next_pcb = pcb.unwrap();
-            let current_pcb = ProcessManager::current_pcb();
-
-            if current_pcb.pid() != next_pcb.pid() {
-                CPU_EXECUTING.set(smp_get_processor_id(), next_pcb.pid());
-                unsafe { ProcessManager::switch_process(current_pcb, next_pcb) };
-            }
+        if let Some(next_pcb) = next_pcb {
+            // 只有可运行的进程会被加入运行队列，因此不可能选中已经死亡的进程
+            debug_assert!(
+                !next_pcb.sched_info().state().is_dead(),
+                "sched: picked dead process {:?}",
+                next_pcb.pid()
+            );
+            let cpu_id = smp_get_processor_id();
+            current_pcb.sched_info().set_last_cpu(cpu_id);
+            CPU_EXECUTING.set(cpu_id, next_pcb.pid());
+            trace_sched_switch(&current_pcb, &next_pcb);
+            unsafe { ProcessManager::switch_process(current_pcb, next_pcb) };
         }
         drop(irq_guard);
         return Ok(0);
     }
+
+    /// 获取当前进程正在运行的cpu以及NUMA节点
+    ///
+    /// ## 参数
+    ///
+    /// - `cpu` : 用于存放cpu id的指针，为空时不写入
+    /// - `node` : 用于存放NUMA节点号的指针，为空时不写入。目前不支持NUMA，总是写入0
+    /// - `from_user` : 指针是否来自用户空间
+    ///
+    /// ## 返回值
+    ///
+    /// - 成功：Ok(0)
+    /// - 失败：指针不合法时，返回`EFAULT`
+    pub fn getcpu(cpu: *mut u32, node: *mut u32, from_user: bool) -> Result<usize, SystemError> {
+        // 先校验所有的指针，避免只写入了其中一个
+        let cpu_buf = if cpu.is_null() {
+            None
+        } else {
+            Some(UserBufferWriter::new(
+                cpu,
+                core::mem::size_of::<u32>(),
+                from_user,
+            )?)
+        };
+        let node_buf = if node.is_null() {
+            None
+        } else {
+            Some(UserBufferWriter::new(
+                node,
+                core::mem::size_of::<u32>(),
+                from_user,
+            )?)
+        };
+
+        if let Some(mut cpu_buf) = cpu_buf {
+            cpu_buf.copy_one_to_user(&ProcessManager::current_cpu(), 0)?;
+        }
+        if let Some(mut node_buf) = node_buf {
+            node_buf.copy_one_to_user(&0u32, 0)?;
+        }
+        return Ok(0);
+    }
+
+    /// 设置进程的调度策略与优先级
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 进程的pid，为0时表示当前进程
+    /// - `policy` : 调度策略的编号（SCHED_NORMAL、SCHED_FIFO、SCHED_RR）
+    /// - `param` : 调度参数
+    pub fn sched_setscheduler(
+        pid: Pid,
+        policy: usize,
+        param: *const SchedParam,
+    ) -> Result<usize, SystemError> {
+        let policy = SchedPolicy::try_from(policy)?;
+        if param.is_null() {
+            return Err(SystemError::EINVAL);
+        }
+        let reader = UserBufferReader::new(param, core::mem::size_of::<SchedParam>(), true)?;
+        let param = *reader.read_one_from_user::<SchedParam>(0)?;
+
+        let pid = if pid == Pid(0) {
+            ProcessManager::current_pcb().pid()
+        } else {
+            pid
+        };
+        ProcessManager::sched_setscheduler(pid, policy, param.sched_priority)?;
+        return Ok(0);
+    }
+
+    /// 设置进程的调度属性，目前只支持调度策略、优先级以及deadline调度参数
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 进程的pid，为0时表示当前进程
+    /// - `attr` : 调度属性
+    /// - `flags` : 保留，必须为0
+    pub fn sched_setattr(
+        pid: Pid,
+        attr: *const SchedAttr,
+        flags: usize,
+    ) -> Result<usize, SystemError> {
+        if attr.is_null() || flags != 0 {
+            return Err(SystemError::EINVAL);
+        }
+        let reader = UserBufferReader::new(attr, core::mem::size_of::<SchedAttr>(), true)?;
+        let attr = *reader.read_one_from_user::<SchedAttr>(0)?;
+        let policy = SchedPolicy::try_from(attr.sched_policy as usize)?;
+
+        let pid = if pid == Pid(0) {
+            ProcessManager::current_pcb().pid()
+        } else {
+            pid
+        };
+        if policy == SchedPolicy::DEADLINE {
+            let params =
+                DeadlineParams::new(attr.sched_runtime, attr.sched_deadline, attr.sched_period);
+            ProcessManager::sched_setdeadline(pid, params)?;
+        } else {
+            ProcessManager::sched_setscheduler(pid, policy, attr.sched_priority as i32)?;
+        }
+        return Ok(0);
+    }
+
+    /// 获取进程的调度策略的编号
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 进程的pid，为0时表示当前进程
+    pub fn sched_getscheduler(pid: Pid) -> Result<usize, SystemError> {
+        let pid = if pid == Pid(0) {
+            ProcessManager::current_pcb().pid()
+        } else {
+            pid
+        };
+        return ProcessManager::sched_getscheduler(pid).map(usize::from);
+    }
+
+    /// 在不改变调度策略的情况下，设置进程的优先级
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 进程的pid，为0时表示当前进程
+    /// - `param` : 调度参数
+    pub fn sched_setparam(pid: Pid, param: *const SchedParam) -> Result<usize, SystemError> {
+        if param.is_null() {
+            return Err(SystemError::EINVAL);
+        }
+        let reader = UserBufferReader::new(param, core::mem::size_of::<SchedParam>(), true)?;
+        let param = *reader.read_one_from_user::<SchedParam>(0)?;
+
+        let pid = if pid == Pid(0) {
+            ProcessManager::current_pcb().pid()
+        } else {
+            pid
+        };
+        ProcessManager::sched_setparam(pid, param)?;
+        return Ok(0);
+    }
+
+    /// 获取进程的调度参数
+    ///
+    /// ## 参数
+    ///
+    /// - `pid` : 进程的pid，为0时表示当前进程
+    /// - `param` : 用于存放调度参数的指针
+    pub fn sched_getparam(pid: Pid, param: *mut SchedParam) -> Result<usize, SystemError> {
+        if param.is_null() {
+            return Err(SystemError::EINVAL);
+        }
+        let mut writer = UserBufferWriter::new(param, core::mem::size_of::<SchedParam>(), true)?;
+
+        let pid = if pid == Pid(0) {
+            ProcessManager::current_pcb().pid()
+        } else {
+            pid
+        };
+        let result = ProcessManager::sched_getparam(pid)?;
+        writer.copy_one_to_user(&result, 0)?;
+        return Ok(0);
+    }
+
+    /// 设置进程的nice值，目前只支持`PRIO_PROCESS`
+    ///
+    /// ## 参数
+    ///
+    /// - `which` : 目标的种类，必须为`PRIO_PROCESS`
+    /// - `who` : 进程的pid，为0时表示当前进程
+    /// - `nice` : 新的nice值，超出范围时会被截断
+    pub fn setpriority(which: usize, who: Pid, nice: i32) -> Result<usize, SystemError> {
+        if which != PRIO_PROCESS {
+            return Err(SystemError::EINVAL);
+        }
+        let pid = if who == Pid(0) {
+            ProcessManager::current_pcb().pid()
+        } else {
+            who
+        };
+        ProcessManager::setpriority(pid, nice)?;
+        return Ok(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::null_mut;
+
+    use crate::{
+        arch::CurrentIrqArch, exception::InterruptArch, smp::core::smp_get_processor_id,
+        syscall::Syscall,
+    };
+
+    /// 关中断保证线程不会被迁移，getcpu返回的cpu应当就是正在执行的cpu
+    #[test]
+    fn test_getcpu() {
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let mut cpu = u32::MAX;
+        let mut node = u32::MAX;
+        assert_eq!(Syscall::getcpu(&mut cpu, &mut node, false), Ok(0));
+        assert_eq!(cpu, smp_get_processor_id());
+        assert_eq!(node, 0);
+
+        // 两个指针都可以为空
+        assert_eq!(Syscall::getcpu(null_mut(), null_mut(), false), Ok(0));
+    }
 }
@@ -0,0 +1,184 @@
+use core::{
+    fmt::Debug,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{libs::spinlock::SpinLock, process::ProcessControlBlock};
+
+/// 调度器的跟踪点
+///
+/// 跟踪器、性能分析器等外部子系统实现该trait，并通过`register_sched_tracer`注册，
+/// 调度器在发生对应的事件时调用它的方法。未实现的方法默认什么都不做。
+///
+/// 所有方法都可能在关中断、持有调度器的锁的情况下调用，不能睡眠，也不能注册或注销跟踪点
+pub trait SchedTracer: Debug + Send + Sync {
+    /// 进程切换，在切换上下文之前调用
+    fn on_switch(&self, _prev: &Arc<ProcessControlBlock>, _next: &Arc<ProcessControlBlock>) {}
+
+    /// 进程被加入运行队列
+    fn on_enqueue(&self, _pcb: &Arc<ProcessControlBlock>) {}
+
+    /// 进程被移出运行队列
+    fn on_dequeue(&self, _pcb: &Arc<ProcessControlBlock>) {}
+
+    /// 进程从源cpu迁移到目标cpu
+    fn on_migrate(&self, _pcb: &Arc<ProcessControlBlock>, _src: usize, _dst: usize) {}
+}
+
+/// 已经注册的跟踪点
+static SCHED_TRACERS: SpinLock<Vec<Arc<dyn SchedTracer>>> = SpinLock::new(Vec::new());
+
+/// 已经注册的跟踪点的数目，为0时调度器不需要获取锁
+static NR_SCHED_TRACERS: AtomicUsize = AtomicUsize::new(0);
+
+/// 注册一个跟踪点
+pub fn register_sched_tracer(tracer: Arc<dyn SchedTracer>) {
+    let mut tracers = SCHED_TRACERS.lock_irqsave();
+    tracers.push(tracer);
+    NR_SCHED_TRACERS.store(tracers.len(), Ordering::SeqCst);
+}
+
+/// 注销一个跟踪点
+///
+/// ## 返回值
+///
+/// 跟踪点是否已经被注册
+pub fn unregister_sched_tracer(tracer: &Arc<dyn SchedTracer>) -> bool {
+    let mut tracers = SCHED_TRACERS.lock_irqsave();
+    let len = tracers.len();
+    tracers.retain(|t| Arc::as_ptr(t) as *const () != Arc::as_ptr(tracer) as *const ());
+    NR_SCHED_TRACERS.store(tracers.len(), Ordering::SeqCst);
+    return tracers.len() != len;
+}
+
+/// 以每个已经注册的跟踪点调用`f`，没有注册跟踪点时直接返回
+#[inline(always)]
+fn for_each_tracer(f: impl Fn(&dyn SchedTracer)) {
+    if NR_SCHED_TRACERS.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    for tracer in SCHED_TRACERS.lock_irqsave().iter() {
+        f(tracer.as_ref());
+    }
+}
+
+/// 触发进程切换的跟踪点
+#[inline]
+pub fn trace_sched_switch(prev: &Arc<ProcessControlBlock>, next: &Arc<ProcessControlBlock>) {
+    for_each_tracer(|tracer| tracer.on_switch(prev, next));
+}
+
+/// 触发进程入队的跟踪点
+#[inline]
+pub fn trace_sched_enqueue(pcb: &Arc<ProcessControlBlock>) {
+    for_each_tracer(|tracer| tracer.on_enqueue(pcb));
+}
+
+/// 触发进程出队的跟踪点
+#[inline]
+pub fn trace_sched_dequeue(pcb: &Arc<ProcessControlBlock>) {
+    for_each_tracer(|tracer| tracer.on_dequeue(pcb));
+}
+
+/// 触发进程迁移的跟踪点
+#[inline]
+pub fn trace_sched_migrate(pcb: &Arc<ProcessControlBlock>, src: usize, dst: usize) {
+    for_each_tracer(|tracer| tracer.on_migrate(pcb, src, dst));
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, string::ToString, sync::Arc, vec::Vec};
+    use core::sync::atomic::Ordering;
+
+    use crate::{
+        libs::spinlock::SpinLock,
+        process::{
+            kthread::{KernelThreadClosure, KernelThreadMechanism},
+            Pid, ProcessControlBlock, ProcessManager,
+        },
+        sched::core::sched_dequeue,
+    };
+
+    use super::{
+        register_sched_tracer, trace_sched_switch, unregister_sched_tracer, SchedTracer,
+        NR_SCHED_TRACERS,
+    };
+
+    #[derive(Debug)]
+    struct NopTracer;
+
+    impl SchedTracer for NopTracer {}
+
+    /// 记录观察到的事件以及相关进程的pid
+    #[derive(Debug)]
+    struct RecordingTracer {
+        events: SpinLock<Vec<(&'static str, Pid)>>,
+    }
+
+    impl RecordingTracer {
+        fn saw(&self, event: &'static str, pid: Pid) -> bool {
+            return self.events.lock_irqsave().contains(&(event, pid));
+        }
+    }
+
+    impl SchedTracer for RecordingTracer {
+        fn on_switch(&self, _prev: &Arc<ProcessControlBlock>, next: &Arc<ProcessControlBlock>) {
+            self.events.lock_irqsave().push(("switch", next.pid()));
+        }
+
+        fn on_enqueue(&self, pcb: &Arc<ProcessControlBlock>) {
+            self.events.lock_irqsave().push(("enqueue", pcb.pid()));
+        }
+
+        fn on_dequeue(&self, pcb: &Arc<ProcessControlBlock>) {
+            self.events.lock_irqsave().push(("dequeue", pcb.pid()));
+        }
+    }
+
+    /// 注册的跟踪点在进程入队、出队以及切换时被调用，注销后不再被调用
+    #[test]
+    fn test_sched_tracer() {
+        let recorder = Arc::new(RecordingTracer {
+            events: SpinLock::new(Vec::new()),
+        });
+        let tracer: Arc<dyn SchedTracer> = recorder.clone();
+        register_sched_tracer(tracer.clone());
+
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "sched-tracer".to_string(),
+        )
+        .unwrap();
+        ProcessManager::add_pcb(pcb.clone()).ok();
+        ProcessManager::wakeup(&pcb).unwrap();
+        assert!(recorder.saw("enqueue", pcb.pid()));
+        assert!(sched_dequeue(&pcb));
+        assert!(recorder.saw("dequeue", pcb.pid()));
+        trace_sched_switch(&ProcessManager::current_pcb(), &pcb);
+        assert!(recorder.saw("switch", pcb.pid()));
+
+        assert!(unregister_sched_tracer(&tracer));
+        assert!(!unregister_sched_tracer(&tracer));
+        let seen = recorder.events.lock_irqsave().len();
+        trace_sched_switch(&ProcessManager::current_pcb(), &pcb);
+        assert_eq!(recorder.events.lock_irqsave().len(), seen);
+    }
+
+    /// 注册后调度器能够看到跟踪点，注销只对已经注册的跟踪点生效
+    #[test]
+    fn test_register_sched_tracer() {
+        let tracer: Arc<dyn SchedTracer> = Arc::new(NopTracer);
+        let other: Arc<dyn SchedTracer> = Arc::new(NopTracer);
+        let nr = NR_SCHED_TRACERS.load(Ordering::SeqCst);
+
+        register_sched_tracer(tracer.clone());
+        assert_eq!(NR_SCHED_TRACERS.load(Ordering::SeqCst), nr + 1);
+        assert!(!unregister_sched_tracer(&other));
+        assert!(unregister_sched_tracer(&tracer));
+        assert!(!unregister_sched_tracer(&tracer));
+        assert_eq!(NR_SCHED_TRACERS.load(Ordering::SeqCst), nr);
+    }
+}
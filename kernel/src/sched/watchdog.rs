@@ -0,0 +1,163 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::sync::Arc;
+
+use crate::{
+    kwarn,
+    mm::percpu::PerCpu,
+    process::{ProcessControlBlock, ProcessFlags},
+};
+
+use super::{
+    cfs::__get_cfs_scheduler,
+    core::SCHED_TICK_INTERVAL_NS,
+    deadline::__get_dl_scheduler,
+    rt::__get_rt_scheduler,
+};
+
+/// 进程被标记为需要调度之后，如果在有其他进程等待的情况下仍然持续运行超过该时长（单位：纳秒），
+/// 就认为调度器发生了停滞
+pub const SCHED_STALL_THRESHOLD_NS: u64 = 100 * SCHED_TICK_INTERVAL_NS;
+
+/// 每个cpu上最近一次报告的停滞中，进程被标记为需要调度的时刻
+///
+/// 该时刻在进程被切换出去时就会被清除，因此可以唯一地标识一次停滞，避免同一次停滞在每个时钟周期都被报告
+static CPU_STALL_REPORTED: [AtomicU64; PerCpu::MAX_CPU_NUM] = {
+    const ZERO: AtomicU64 = AtomicU64::new(0);
+    [ZERO; PerCpu::MAX_CPU_NUM]
+};
+
+/// 已经报告的调度停滞的次数
+static NR_SCHED_STALLS: AtomicU64 = AtomicU64::new(0);
+
+/// 获取已经报告的调度停滞的次数
+pub fn sched_stall_count() -> u64 {
+    return NR_SCHED_STALLS.load(Ordering::SeqCst);
+}
+
+/// 获取在某个cpu的运行队列中等待的进程数
+fn nr_waiting(cpu_id: u32) -> usize {
+    let cfs = __get_cfs_scheduler().cpu_queue(cpu_id as usize).nr_running();
+    let rt = __get_rt_scheduler().rt_queue_len(cpu_id);
+    let dl = __get_dl_scheduler().nr_running(cpu_id as usize);
+    return cfs + rt + dl;
+}
+
+/// 在时钟中断中检查当前cpu是否发生了调度停滞，见`check_sched_stall`
+pub fn sched_stall_tick(cpu_id: u32, current: &Arc<ProcessControlBlock>, now_ns: u64) -> bool {
+    if current.pid().is_idle() || !current.flags().contains(ProcessFlags::NEED_SCHEDULE) {
+        return false;
+    }
+    return check_sched_stall(cpu_id, current, now_ns, nr_waiting(cpu_id));
+}
+
+/// 检查当前cpu是否发生了调度停滞
+///
+/// 当前进程被标记为需要调度、并且有其他进程在等待时，如果距离它被标记已经超过`SCHED_STALL_THRESHOLD_NS`，
+/// 说明本应发生的进程切换没有发生（例如`preempt_count`没有被正确地减少），打印警告。每次停滞只报告一次
+///
+/// ## 参数
+///
+/// - `cpu_id` : 当前cpu的id
+/// - `current` : 当前cpu上正在运行的进程
+/// - `now_ns` : 当前调度器时钟的读数（单位：纳秒）
+/// - `nr_waiting` : 在当前cpu的运行队列中等待的进程数
+///
+/// ## 返回值
+///
+/// 是否报告了停滞
+fn check_sched_stall(
+    cpu_id: u32,
+    current: &Arc<ProcessControlBlock>,
+    now_ns: u64,
+    nr_waiting: usize,
+) -> bool {
+    if nr_waiting == 0 || !current.flags().contains(ProcessFlags::NEED_SCHEDULE) {
+        return false;
+    }
+    let resched_at = match current.sched_info().resched_at() {
+        Some(resched_at) => resched_at,
+        None => return false,
+    };
+    let stalled_ns = now_ns.saturating_sub(resched_at);
+    if stalled_ns < SCHED_STALL_THRESHOLD_NS {
+        return false;
+    }
+    if CPU_STALL_REPORTED[cpu_id as usize].swap(resched_at, Ordering::SeqCst) == resched_at {
+        return false;
+    }
+
+    NR_SCHED_STALLS.fetch_add(1, Ordering::SeqCst);
+    kwarn!(
+        "sched: cpu {} stalled: pid {:?} still running {} ms after being marked for rescheduling, preempt_count={}, {} tasks waiting",
+        cpu_id,
+        current.pid(),
+        stalled_ns / 1_000_000,
+        current.preempt_count(),
+        nr_waiting
+    );
+    return true;
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, string::ToString};
+
+    use crate::{
+        arch::CurrentIrqArch,
+        exception::InterruptArch,
+        process::kthread::{KernelThreadClosure, KernelThreadMechanism},
+        sched::clock::sched_clock,
+        smp::core::smp_get_processor_id,
+    };
+
+    use super::{check_sched_stall, sched_stall_count, SCHED_STALL_THRESHOLD_NS};
+
+    /// 持有自旋锁而无法被切换出去的进程超过阈值后被报告一次，没有进程等待时不报告
+    #[test]
+    fn test_sched_stall_detected() {
+        // 关中断，避免真实的时钟中断同时进行检查
+        let _guard = unsafe { CurrentIrqArch::save_and_disable_irq() };
+        let cpu = smp_get_processor_id();
+        let pcb = KernelThreadMechanism::create(
+            KernelThreadClosure::EmptyClosure((Box::new(|| 0), ())),
+            "sched-stall".to_string(),
+        )
+        .unwrap();
+        let count = sched_stall_count();
+
+        // 模拟一个preempt_count没有被正确减少的进程
+        pcb.preempt_disable();
+        let now = sched_clock() + SCHED_STALL_THRESHOLD_NS;
+        pcb.sched_info().mark_resched(now - SCHED_STALL_THRESHOLD_NS / 2);
+        pcb.set_need_schedule();
+        assert!(!check_sched_stall(cpu, &pcb, now, 1));
+
+        assert!(check_sched_stall(
+            cpu,
+            &pcb,
+            now + SCHED_STALL_THRESHOLD_NS,
+            1
+        ));
+        assert_eq!(sched_stall_count(), count + 1);
+        // 同一次停滞只报告一次
+        assert!(!check_sched_stall(
+            cpu,
+            &pcb,
+            now + 2 * SCHED_STALL_THRESHOLD_NS,
+            1
+        ));
+        assert_eq!(sched_stall_count(), count + 1);
+
+        // 没有进程等待时，继续运行是正常的
+        pcb.sched_info().take_resched_at();
+        pcb.sched_info().mark_resched(now);
+        assert!(!check_sched_stall(
+            cpu,
+            &pcb,
+            now + 2 * SCHED_STALL_THRESHOLD_NS,
+            0
+        ));
+        pcb.preempt_enable();
+    }
+}
@@ -1,5 +1,44 @@
+use crate::{
+    include::bindings::bindings::{smp_get_possible_cpu_num, MAX_CPU_NUM},
+    libs::spinlock::SpinLock,
+};
+
+use super::cpumask::CpuMask;
+
+/// 在线的cpu的集合。下线的cpu不再运行除idle进程以外的进程，也不会被负载均衡选中
+static CPU_ONLINE_MASK: SpinLock<CpuMask> = SpinLock::new(CpuMask::full());
+
 /// @brief 获取当前的cpu id
 #[inline]
 pub fn smp_get_processor_id() -> u32 {
     return crate::arch::cpu::current_cpu_id();
 }
+
+/// 获取系统中可能存在的cpu数目
+///
+/// 由于cpu id即为APIC ID，返回值为最大的cpu id + 1，可以作为以cpu id为下标的数组的长度。
+/// 该函数在ACPI初始化完成后即可调用，不需要等待其他核心启动。
+pub fn smp_possible_cpu_num() -> usize {
+    let num = unsafe { smp_get_possible_cpu_num() } as usize;
+    return num.clamp(1, MAX_CPU_NUM as usize);
+}
+
+/// 获取在线的cpu的集合
+pub fn cpu_online_mask() -> CpuMask {
+    return *CPU_ONLINE_MASK.lock_irqsave();
+}
+
+/// 判断cpu是否在线
+pub fn cpu_online(cpu_id: u32) -> bool {
+    return CPU_ONLINE_MASK.lock_irqsave().contains(cpu_id as usize);
+}
+
+/// 将cpu标记为在线或者下线
+pub fn set_cpu_online(cpu_id: u32, online: bool) {
+    let mut mask = CPU_ONLINE_MASK.lock_irqsave();
+    if online {
+        mask.set(cpu_id as usize);
+    } else {
+        mask.clear(cpu_id as usize);
+    }
+}
@@ -0,0 +1,107 @@
+use core::ops::BitAnd;
+
+use crate::mm::percpu::PerCpu;
+
+/// cpu位图，第i位为1表示cpu i在集合中
+///
+/// 用于表示进程允许运行的cpu（cpu亲和性）等cpu集合
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuMask {
+    bits: [u64; CpuMask::WORDS],
+}
+
+impl CpuMask {
+    const BITS: usize = u64::BITS as usize;
+    const WORDS: usize = (PerCpu::MAX_CPU_NUM + Self::BITS - 1) / Self::BITS;
+
+    /// 创建一个空的cpu集合
+    pub const fn new() -> Self {
+        Self {
+            bits: [0; Self::WORDS],
+        }
+    }
+
+    /// 创建一个包含所有cpu的集合
+    pub const fn full() -> Self {
+        Self {
+            bits: [u64::MAX; Self::WORDS],
+        }
+    }
+
+    /// 将cpu加入集合
+    ///
+    /// ## Panics
+    ///
+    /// `cpu_id`不小于`PerCpu::MAX_CPU_NUM`时panic
+    pub fn set(&mut self, cpu_id: usize) {
+        self.bits[cpu_id / Self::BITS] |= 1 << (cpu_id % Self::BITS);
+    }
+
+    /// 将cpu从集合中移除
+    ///
+    /// ## Panics
+    ///
+    /// `cpu_id`不小于`PerCpu::MAX_CPU_NUM`时panic
+    pub fn clear(&mut self, cpu_id: usize) {
+        self.bits[cpu_id / Self::BITS] &= !(1 << (cpu_id % Self::BITS));
+    }
+
+    /// 判断cpu是否在集合中，超出范围的cpu总是不在集合中
+    pub fn contains(&self, cpu_id: usize) -> bool {
+        if cpu_id >= PerCpu::MAX_CPU_NUM {
+            return false;
+        }
+        return self.bits[cpu_id / Self::BITS] & (1 << (cpu_id % Self::BITS)) != 0;
+    }
+
+    /// 集合是否为空
+    pub fn is_empty(&self) -> bool {
+        return self.bits.iter().all(|word| *word == 0);
+    }
+
+    /// 按照从小到大的顺序遍历集合中的cpu
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        return (0..PerCpu::MAX_CPU_NUM).filter(move |cpu_id| self.contains(*cpu_id));
+    }
+}
+
+impl Default for CpuMask {
+    /// 默认允许在所有cpu上运行
+    fn default() -> Self {
+        return Self::full();
+    }
+}
+
+impl BitAnd for CpuMask {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        let mut result = self;
+        for (word, rhs) in result.bits.iter_mut().zip(rhs.bits.iter()) {
+            *word &= *rhs;
+        }
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::CpuMask;
+
+    /// 集合的加入、移除、求交集
+    #[test]
+    fn test_cpumask_ops() {
+        let mut a = CpuMask::new();
+        assert!(a.is_empty());
+        a.set(1);
+        a.set(70);
+        assert!(a.contains(1) && a.contains(70) && !a.contains(2));
+        assert!(!a.contains(usize::MAX));
+
+        let mut b = CpuMask::full();
+        b.clear(1);
+        assert_eq!((a & b).iter().collect::<Vec<_>>(), [70]);
+    }
+}
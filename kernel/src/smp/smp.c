@@ -186,6 +186,34 @@ static void __smp__flush_tlb_ipi_handler(uint64_t irq_num, uint64_t param, struc
     flush_tlb();
 }
 
+/**
+ * @brief 获取系统中可能存在的cpu数目（最大的可用处理器的APIC ID + 1）
+ *
+ * 与smp_get_total_cpu()不同，本函数直接读取ACPI的MADT表，在smp_init()之前（acpi_init()之后）即可调用。
+ * 由于cpu id就是APIC ID，因此以cpu id作为下标的数组，需要使用本函数的返回值作为长度。
+ *
+ * @return uint32_t
+ */
+uint32_t smp_get_possible_cpu_num()
+{
+    ul tmp_vaddr[MAX_SUPPORTED_PROCESSOR_NUM] = {0};
+    uint32_t proc_num = 0;
+    apic_get_ics(ACPI_ICS_TYPE_PROCESSOR_LOCAL_APIC, tmp_vaddr, &proc_num);
+
+    uint32_t possible = 1; // BSP一定存在
+    for (int i = 0; i < proc_num; ++i)
+    {
+        struct acpi_Processor_Local_APIC_Structure_t *lapic =
+            (struct acpi_Processor_Local_APIC_Structure_t *)(tmp_vaddr[i]);
+        // 跳过无法被启用的处理器
+        if (!((lapic->flags & 0x1) || (lapic->flags & 0x2)))
+            continue;
+        if (lapic->local_apic_id + 1 > possible)
+            possible = lapic->local_apic_id + 1;
+    }
+    return possible;
+}
+
 /**
  * @brief 获取当前全部的cpu数目
  *
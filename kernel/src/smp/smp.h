@@ -19,5 +19,7 @@ extern int64_t rs_kick_cpu(uint32_t cpu_id);
 
 uint32_t smp_get_total_cpu();
 
+uint32_t smp_get_possible_cpu_num();
+
 extern void set_current_core_tss(uint64_t stack_start, uint64_t ist0);
 extern void rs_load_current_core_tss();
\ No newline at end of file
@@ -20,8 +20,9 @@ use crate::{
     mm::{verify_area, MemoryManagementArch, VirtAddr},
     net::syscall::SockAddr,
     process::Pid,
+    sched::syscall::{SchedAttr, SchedParam},
     time::{
-        syscall::{PosixTimeZone, PosixTimeval},
+        syscall::{PosixITimerval, PosixTimeZone, PosixTimeval},
         TimeSpec,
     },
 };
@@ -297,6 +298,8 @@ pub enum SystemError {
     EOWNERDEAD = 129,
     /// 状态不可恢复 State not recoverable.
     ENOTRECOVERABLE = 130,
+    /// 系统调用被信号打断，需要重新执行或者返回EINTR（仅在内核内部使用，不会返回给用户程序）
+    ERESTARTSYS = 512,
 }
 
 impl SystemError {
@@ -377,6 +380,20 @@ pub const SYS_FCNTL: usize = 51;
 pub const SYS_FTRUNCATE: usize = 52;
 
 pub const SYS_SETPGID: usize = 53;
+pub const SYS_GETCPU: usize = 54;
+pub const SYS_SETITIMER: usize = 55;
+pub const SYS_GETITIMER: usize = 56;
+pub const SYS_CLOCK_NANOSLEEP: usize = 57;
+pub const SYS_SCHED_SETSCHEDULER: usize = 58;
+pub const SYS_SCHED_GETSCHEDULER: usize = 59;
+pub const SYS_SCHED_SETATTR: usize = 60;
+pub const SYS_FUTEX: usize = 61;
+pub const SYS_SET_TID_ADDRESS: usize = 62;
+pub const SYS_CLONE: usize = 63;
+pub const SYS_SETPRIORITY: usize = 64;
+pub const SYS_SCHED_SETPARAM: usize = 65;
+pub const SYS_SCHED_GETPARAM: usize = 66;
+pub const SYS_GETTID: usize = 67;
 #[derive(Debug)]
 pub struct Syscall;
 
@@ -497,6 +514,7 @@ impl Syscall {
 
             SYS_FORK => Self::fork(frame),
             SYS_VFORK => Self::vfork(frame),
+            SYS_CLONE => Self::clone(frame, args[0] as u32, args[1]),
 
             SYS_BRK => {
                 let new_brk = VirtAddr::new(args[0]);
@@ -708,6 +726,7 @@ impl Syscall {
             }
 
             SYS_GETPID => Self::getpid().map(|pid| pid.into()),
+            SYS_GETTID => Self::gettid().map(|tid| tid.into()),
 
             SYS_SCHED => Self::sched(frame.from_user()),
             SYS_DUP => {
@@ -936,6 +955,64 @@ impl Syscall {
 
             SYS_SETPGID => Self::setpgid(args[0], args[1]),
 
+            SYS_GETCPU => {
+                let cpu = args[0] as *mut u32;
+                let node = args[1] as *mut u32;
+                Self::getcpu(cpu, node, frame.from_user())
+            }
+
+            SYS_SETITIMER => {
+                let new_value = args[1] as *const PosixITimerval;
+                let old_value = args[2] as *mut PosixITimerval;
+                Self::setitimer(args[0], new_value, old_value)
+            }
+
+            SYS_GETITIMER => Self::getitimer(args[0], args[1] as *mut PosixITimerval),
+
+            SYS_CLOCK_NANOSLEEP => {
+                let request = args[2] as *const TimeSpec;
+                let remain = args[3] as *mut TimeSpec;
+                Self::clock_nanosleep(args[0], args[1], request, remain)
+            }
+
+            SYS_SCHED_SETSCHEDULER => {
+                let param = args[2] as *const SchedParam;
+                Self::sched_setscheduler(Pid::new(args[0]), args[1], param)
+            }
+
+            SYS_SCHED_GETSCHEDULER => Self::sched_getscheduler(Pid::new(args[0])),
+
+            SYS_SCHED_SETATTR => {
+                let attr = args[1] as *const SchedAttr;
+                Self::sched_setattr(Pid::new(args[0]), attr, args[2])
+            }
+            SYS_SETPRIORITY => Self::setpriority(args[0], Pid::new(args[1]), args[2] as i32),
+
+            SYS_SCHED_SETPARAM => {
+                let param = args[1] as *const SchedParam;
+                Self::sched_setparam(Pid::new(args[0]), param)
+            }
+            SYS_SCHED_GETPARAM => {
+                let param = args[1] as *mut SchedParam;
+                Self::sched_getparam(Pid::new(args[0]), param)
+            }
+
+            SYS_FUTEX => {
+                let uaddr = args[0] as *const u32;
+                let timeout = args[3] as *const u8;
+                Self::futex(
+                    uaddr,
+                    args[1] as u32,
+                    args[2] as u32,
+                    timeout,
+                    frame.from_user(),
+                )
+            }
+            SYS_SET_TID_ADDRESS => {
+                let tidptr = args[0] as *mut i32;
+                Self::set_tid_address(tidptr, frame.from_user()).map(|pid| pid.into())
+            }
+
             SYS_GETPPID => Self::getppid().map(|pid| pid.into()),
             SYS_FSTAT => {
                 let fd = args[0] as i32;
@@ -975,6 +1052,11 @@ impl Syscall {
             _ => panic!("Unsupported syscall ID: {}", syscall_num),
         };
 
+        // 目前还不支持在信号处理完成后重新执行系统调用，被信号打断的系统调用统一返回EINTR
+        let r = r.map_err(|e| match e {
+            SystemError::ERESTARTSYS => SystemError::EINTR,
+            e => e,
+        });
         let r = r.unwrap_or_else(|e| e.to_posix_errno() as usize);
         return r;
     }
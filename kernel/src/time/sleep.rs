@@ -1,66 +1,125 @@
 use core::{arch::x86_64::_rdtsc, hint::spin_loop};
 
-use alloc::{boxed::Box, sync::Arc};
-
 use crate::{
-    arch::{sched::sched, CurrentIrqArch},
-    exception::InterruptArch,
     include::bindings::bindings::{useconds_t, Cpu_tsc_freq},
-    process::ProcessManager,
     syscall::SystemError,
 };
 
 use super::{
-    timer::{next_n_us_timer_jiffies, Timer, WakeUpHelper},
+    timekeeping::getnstimeofday,
+    timer::{clock, schedule_timeout},
     TimeSpec,
 };
 
-/// @brief 休眠指定时间（单位：纳秒）
-///
-/// @param sleep_time 指定休眠的时间
+/// 系统实时时钟（墙上时间）
+pub const CLOCK_REALTIME: usize = 0;
+/// 单调时钟，从系统启动开始计时
+pub const CLOCK_MONOTONIC: usize = 1;
+/// clock_nanosleep的标志位：请求的时间是一个绝对时刻
+pub const TIMER_ABSTIME: usize = 1;
+
+/// 对于小于该时长（单位：微秒）的休眠，使用spin/rdtsc来进行定时
+const SPIN_SLEEP_THRESHOLD_US: u64 = 500;
+
+/// 将TimeSpec转换为纳秒数
 ///
-/// @return Ok(TimeSpec) 剩余休眠时间
+/// ## 返回值
 ///
-/// @return Err(SystemError) 错误码
-pub fn nanosleep(sleep_time: TimeSpec) -> Result<TimeSpec, SystemError> {
-    if sleep_time.tv_nsec < 0 || sleep_time.tv_nsec >= 1000000000 {
+/// 秒数为负数，或者纳秒数不在`[0, 1000000000)`范围内时，返回`EINVAL`
+fn timespec_to_ns(ts: &TimeSpec) -> Result<u64, SystemError> {
+    if ts.tv_sec < 0 || ts.tv_nsec < 0 || ts.tv_nsec >= 1000000000 {
         return Err(SystemError::EINVAL);
     }
-    // 对于小于500us的时间，使用spin/rdtsc来进行定时
+    return Ok((ts.tv_sec as u64).saturating_mul(1000000000) + ts.tv_nsec as u64);
+}
+
+fn us_to_timespec(us: u64) -> TimeSpec {
+    return TimeSpec::new((us / 1000000) as i64, ((us % 1000000) * 1000) as i64);
+}
 
-    if sleep_time.tv_nsec < 500000 {
-        let expired_tsc: u64 =
-            unsafe { _rdtsc() + (sleep_time.tv_nsec as u64 * Cpu_tsc_freq) / 1000000000 };
+/// 休眠指定的时长，被信号打断时返回剩余的时长
+///
+/// ## 参数
+///
+/// - `ns` : 休眠的时长（单位：纳秒）
+///
+/// ## 返回值
+///
+/// 剩余休眠时间（单位：微秒），完整地睡眠了指定的时长时为0
+fn do_nanosleep(ns: u64) -> Result<u64, SystemError> {
+    if ns < SPIN_SLEEP_THRESHOLD_US * 1000 {
+        let expired_tsc: u64 = unsafe { _rdtsc() + (ns * Cpu_tsc_freq) / 1000000000 };
         while unsafe { _rdtsc() } < expired_tsc {
             spin_loop()
         }
-        return Ok(TimeSpec {
-            tv_sec: 0,
-            tv_nsec: 0,
-        });
+        return Ok(0);
     }
-    // 创建定时器
-    let handler: Box<WakeUpHelper> = WakeUpHelper::new(ProcessManager::current_pcb());
-    let timer: Arc<Timer> = Timer::new(
-        handler,
-        next_n_us_timer_jiffies((sleep_time.tv_nsec / 1000) as u64),
-    );
-
-    let irq_guard: crate::exception::IrqFlagsGuard =
-        unsafe { CurrentIrqArch::save_and_disable_irq() };
-    ProcessManager::mark_sleep(true).ok();
-    timer.activate();
 
-    drop(irq_guard);
+    // 向上取整，保证至少睡眠请求的时长
+    let deadline = clock() + (ns + 999) / 1000;
+    loop {
+        let now = clock();
+        if now >= deadline {
+            return Ok(0);
+        }
+        match schedule_timeout((deadline - now) as i64) {
+            // 被其他进程提前唤醒时，继续睡眠剩余的时间
+            Ok(_) => continue,
+            Err(SystemError::ERESTARTSYS) => {
+                return Ok(deadline.saturating_sub(clock()).max(1));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
 
-    sched();
+/// @brief 休眠指定时间（单位：纳秒）
+///
+/// @param sleep_time 指定休眠的时间
+///
+/// @return Ok(TimeSpec) 剩余休眠时间，被信号打断时不为0
+///
+/// @return Err(SystemError) 错误码
+pub fn nanosleep(sleep_time: TimeSpec) -> Result<TimeSpec, SystemError> {
+    let ns = timespec_to_ns(&sleep_time)?;
+    return Ok(us_to_timespec(do_nanosleep(ns)?));
+}
 
-    // TODO: 增加信号唤醒的功能后，返回正确的剩余时间
+/// 按照指定的时钟休眠
+///
+/// ## 参数
+///
+/// - `clockid` : 时钟，支持`CLOCK_REALTIME`与`CLOCK_MONOTONIC`
+/// - `flags` : 为`TIMER_ABSTIME`时，`request`是该时钟上的绝对时刻，否则是相对的时长
+/// - `request` : 请求休眠的时间
+///
+/// ## 返回值
+///
+/// - 成功：剩余休眠时间，被信号打断时不为0。绝对时刻模式下，剩余时间没有意义，总是为0
+/// - 失败：时钟不支持或者时间不合法时，返回`EINVAL`；绝对时刻模式下被信号打断时，返回`ERESTARTSYS`
+pub fn clock_nanosleep(
+    clockid: usize,
+    flags: usize,
+    request: TimeSpec,
+) -> Result<TimeSpec, SystemError> {
+    let req_ns = timespec_to_ns(&request)?;
+    if flags & TIMER_ABSTIME == 0 {
+        if clockid != CLOCK_REALTIME && clockid != CLOCK_MONOTONIC {
+            return Err(SystemError::EINVAL);
+        }
+        return nanosleep(request);
+    }
 
-    return Ok(TimeSpec {
-        tv_sec: 0,
-        tv_nsec: 0,
-    });
+    let now_ns = match clockid {
+        CLOCK_MONOTONIC => clock() * 1000,
+        CLOCK_REALTIME => timespec_to_ns(&getnstimeofday()).unwrap_or(0),
+        _ => return Err(SystemError::EINVAL),
+    };
+    // 绝对时刻不需要剩余时间，被信号打断时只能通过错误码告知调用者
+    if do_nanosleep(req_ns.saturating_sub(now_ns))? != 0 {
+        return Err(SystemError::ERESTARTSYS);
+    }
+    return Ok(TimeSpec::new(0, 0));
 }
 
 /// @brief 休眠指定时间（单位：微秒）
@@ -101,3 +160,71 @@ pub extern "C" fn rs_usleep(usec: useconds_t) -> i32 {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        process::{
+            itimer::{ITimerVal, ITimerWhich},
+            ProcessManager,
+        },
+        syscall::SystemError,
+        time::{timer::clock, TimeSpec},
+    };
+
+    use super::{clock_nanosleep, nanosleep, CLOCK_MONOTONIC, TIMER_ABSTIME};
+
+    /// 睡眠被信号提前打断时，返回不为0的剩余时间；绝对时刻模式下返回错误码
+    #[test]
+    fn test_nanosleep_interrupted() {
+        assert_eq!(nanosleep(TimeSpec::new(0, -1)), Err(SystemError::EINVAL));
+        assert_eq!(
+            nanosleep(TimeSpec::new(0, 1000000000)),
+            Err(SystemError::EINVAL)
+        );
+        assert_eq!(
+            clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, TimeSpec::new(-1, 0)),
+            Err(SystemError::EINVAL)
+        );
+
+        let current = ProcessManager::current_pcb();
+        ProcessManager::setitimer(&current, ITimerWhich::Real, ITimerVal::new(0, 2000)).unwrap();
+        let start = clock();
+        let rem = nanosleep(TimeSpec::new(1, 0)).unwrap();
+        assert!(clock() - start < 1000000);
+        assert!(rem.tv_sec > 0 || rem.tv_nsec > 0);
+
+        current.sig_state().dequeue();
+        ProcessManager::clear_pending_signal(&current);
+
+        // 绝对时刻模式下被信号打断时，返回错误码而不是0
+        ProcessManager::setitimer(&current, ITimerWhich::Real, ITimerVal::new(0, 2000)).unwrap();
+        let deadline = clock() + 1000000;
+        assert_eq!(
+            clock_nanosleep(
+                CLOCK_MONOTONIC,
+                TIMER_ABSTIME,
+                TimeSpec::new(
+                    (deadline / 1000000) as i64,
+                    ((deadline % 1000000) * 1000) as i64
+                )
+            ),
+            Err(SystemError::ERESTARTSYS)
+        );
+        assert!(clock() < deadline);
+
+        current.sig_state().dequeue();
+        ProcessManager::clear_pending_signal(&current);
+
+        // 已经过去的绝对时刻，立即返回
+        let now = clock();
+        assert_eq!(
+            clock_nanosleep(
+                CLOCK_MONOTONIC,
+                TIMER_ABSTIME,
+                TimeSpec::new((now / 1000000) as i64, 0)
+            ),
+            Ok(TimeSpec::new(0, 0))
+        );
+    }
+}
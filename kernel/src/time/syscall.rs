@@ -4,8 +4,18 @@ use core::{
 };
 
 use crate::{
-    syscall::{user_access::UserBufferWriter, Syscall, SystemError},
-    time::{sleep::nanosleep, TimeSpec},
+    process::{
+        itimer::{ITimerVal, ITimerWhich},
+        ProcessManager,
+    },
+    syscall::{
+        user_access::{UserBufferReader, UserBufferWriter},
+        Syscall, SystemError,
+    },
+    time::{
+        sleep::{clock_nanosleep, nanosleep},
+        TimeSpec,
+    },
 };
 
 use super::timekeeping::do_gettimeofday;
@@ -30,6 +40,42 @@ pub struct PosixTimeZone {
     pub tz_dsttime: c_int,
 }
 
+impl PosixTimeval {
+    /// 转换为微秒数，时间为负数或者微秒数不在`[0, 1000000)`范围内时，返回`EINVAL`
+    pub fn to_us(&self) -> Result<u64, SystemError> {
+        if self.tv_sec < 0 || self.tv_usec < 0 || self.tv_usec >= 1000000 {
+            return Err(SystemError::EINVAL);
+        }
+        return Ok(self.tv_sec as u64 * 1000000 + self.tv_usec as u64);
+    }
+
+    pub fn from_us(us: u64) -> Self {
+        return Self {
+            tv_sec: (us / 1000000) as PosixTimeT,
+            tv_usec: (us % 1000000) as PosixSusecondsT,
+        };
+    }
+}
+
+/// 间隔定时器的值，与Linux的`struct itimerval`一致
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone)]
+pub struct PosixITimerval {
+    /// 定时器到期后重新装载的值
+    pub it_interval: PosixTimeval,
+    /// 距离定时器到期的时间
+    pub it_value: PosixTimeval,
+}
+
+impl From<ITimerVal> for PosixITimerval {
+    fn from(value: ITimerVal) -> Self {
+        return Self {
+            it_interval: PosixTimeval::from_us(value.interval_us),
+            it_value: PosixTimeval::from_us(value.value_us),
+        };
+    }
+}
+
 /// 系统时区 暂时写定为东八区
 pub const SYS_TIMEZONE: PosixTimeZone = PosixTimeZone {
     tz_minuteswest: -480,
@@ -45,7 +91,7 @@ impl Syscall {
     ///
     /// @return Ok(i32) 0
     ///
-    /// @return Err(SystemError) 错误码
+    /// @return Err(SystemError) 错误码，被信号打断时返回EINTR
     pub fn nanosleep(
         sleep_time: *const TimeSpec,
         rm_time: *mut TimeSpec,
@@ -58,15 +104,49 @@ impl Syscall {
             tv_nsec: unsafe { *sleep_time }.tv_nsec,
         };
 
-        let r: Result<usize, SystemError> = nanosleep(slt_spec).map(|slt_spec| {
-            if rm_time != null_mut() {
-                unsafe { *rm_time }.tv_sec = slt_spec.tv_sec;
-                unsafe { *rm_time }.tv_nsec = slt_spec.tv_nsec;
-            }
-            0
-        });
+        let rem = nanosleep(slt_spec)?;
+        if rem == TimeSpec::default() {
+            return Ok(0);
+        }
+        if rm_time != null_mut() {
+            unsafe { *rm_time = rem };
+        }
+        return Err(SystemError::EINTR);
+    }
+
+    /// 按照指定的时钟休眠
+    ///
+    /// ## 参数
+    ///
+    /// - `clockid` : 时钟，支持`CLOCK_REALTIME`与`CLOCK_MONOTONIC`
+    /// - `flags` : 为`TIMER_ABSTIME`时，`request`是绝对时刻
+    /// - `request` : 请求休眠的时间
+    /// - `remain` : 被信号打断时，写入剩余的休眠时间（仅相对时间模式），可以为空
+    ///
+    /// ## 返回值
+    ///
+    /// 被信号打断时返回`EINTR`，绝对时刻模式下同样如此
+    pub fn clock_nanosleep(
+        clockid: usize,
+        flags: usize,
+        request: *const TimeSpec,
+        remain: *mut TimeSpec,
+    ) -> Result<usize, SystemError> {
+        if request.is_null() {
+            return Err(SystemError::EFAULT);
+        }
+        let reader = UserBufferReader::new(request, core::mem::size_of::<TimeSpec>(), true)?;
+        let request = *reader.read_one_from_user::<TimeSpec>(0)?;
 
-        return r;
+        let rem = clock_nanosleep(clockid, flags, request)?;
+        if rem == TimeSpec::default() {
+            return Ok(0);
+        }
+        if !remain.is_null() {
+            let mut writer = UserBufferWriter::new(remain, core::mem::size_of::<TimeSpec>(), true)?;
+            writer.copy_one_to_user(&rem, 0)?;
+        }
+        return Err(SystemError::EINTR);
     }
 
     /// 获取cpu时间
@@ -107,4 +187,47 @@ impl Syscall {
 
         return Ok(0);
     }
+
+    /// 设置当前进程的间隔定时器
+    ///
+    /// ## 参数
+    ///
+    /// - `which` : 定时器的种类（ITIMER_REAL、ITIMER_VIRTUAL、ITIMER_PROF）
+    /// - `new_value` : 新的值
+    /// - `old_value` : 用于返回定时器原来的值，可以为空
+    pub fn setitimer(
+        which: usize,
+        new_value: *const PosixITimerval,
+        old_value: *mut PosixITimerval,
+    ) -> Result<usize, SystemError> {
+        let which = ITimerWhich::try_from(which)?;
+        if new_value.is_null() {
+            return Err(SystemError::EFAULT);
+        }
+        let reader =
+            UserBufferReader::new(new_value, core::mem::size_of::<PosixITimerval>(), true)?;
+        let new_value = *reader.read_one_from_user::<PosixITimerval>(0)?;
+        let new_value = ITimerVal::new(new_value.it_interval.to_us()?, new_value.it_value.to_us()?);
+
+        let old = ProcessManager::setitimer(&ProcessManager::current_pcb(), which, new_value)?;
+        if !old_value.is_null() {
+            let mut writer =
+                UserBufferWriter::new(old_value, core::mem::size_of::<PosixITimerval>(), true)?;
+            writer.copy_one_to_user(&PosixITimerval::from(old), 0)?;
+        }
+        return Ok(0);
+    }
+
+    /// 获取当前进程的间隔定时器的当前值
+    pub fn getitimer(which: usize, value: *mut PosixITimerval) -> Result<usize, SystemError> {
+        let which = ITimerWhich::try_from(which)?;
+        if value.is_null() {
+            return Err(SystemError::EFAULT);
+        }
+        let mut writer =
+            UserBufferWriter::new(value, core::mem::size_of::<PosixITimerval>(), true)?;
+        let current = ProcessManager::getitimer(&ProcessManager::current_pcb(), which);
+        writer.copy_one_to_user(&PosixITimerval::from(current), 0)?;
+        return Ok(0);
+    }
 }
@@ -110,6 +110,25 @@ impl Timer {
         drop(timer_list);
     }
 
+    /// 将尚未触发的定时器从定时器链表中移除
+    ///
+    /// ## 返回值
+    ///
+    /// 如果定时器仍在链表中，并被成功移除，返回true；如果定时器已经触发或者从未被激活，返回false
+    pub fn cancel(&self) -> bool {
+        let mut timer_list = TIMER_LIST.lock_irqsave();
+        let pos = timer_list
+            .iter()
+            .position(|elt| core::ptr::eq(elt.as_ref(), self));
+        if let Some(pos) = pos {
+            let mut temp_list: LinkedList<Arc<Timer>> = timer_list.split_off(pos);
+            temp_list.pop_front();
+            timer_list.append(&mut temp_list);
+            return true;
+        }
+        return false;
+    }
+
     #[inline]
     fn run(&self) {
         let r = self.0.lock().timer_func.run();
@@ -231,11 +250,12 @@ pub fn next_n_us_timer_jiffies(expire_us: u64) -> u64 {
 ///
 /// @return Ok(i64) 剩余需要休眠的时间(单位：jiffies)
 ///
-/// @return Err(SystemError) 错误码
+/// @return Err(SystemError) 错误码，睡眠被信号打断时返回ERESTARTSYS
 pub fn schedule_timeout(mut timeout: i64) -> Result<i64, SystemError> {
     // kdebug!("schedule_timeout");
     if timeout == MAX_TIMEOUT {
         sched();
+        ProcessManager::sleep_outcome().into_result()?;
         return Ok(MAX_TIMEOUT);
     } else if timeout < 0 {
         kerror!("timeout can't less than 0");
@@ -249,12 +269,18 @@ pub fn schedule_timeout(mut timeout: i64) -> Result<i64, SystemError> {
             WakeUpHelper::new(ProcessManager::current_pcb()),
             timeout as u64,
         );
-        ProcessManager::mark_sleep(true).ok();
+        // 已有待处理的信号，不进入睡眠
+        ProcessManager::mark_sleep(true)?.into_result()?;
         timer.activate();
 
         drop(irq_guard);
 
         sched();
+        // 如果是被信号或者其他进程提前唤醒的，定时器还未触发，需要将其移除，
+        // 避免它在之后错误地唤醒当前进程
+        timer.cancel();
+        ProcessManager::sleep_outcome().into_result()?;
+
         let time_remaining: i64 = timeout - TIMER_JIFFIES.load(Ordering::SeqCst) as i64;
         if time_remaining >= 0 {
             // 被提前唤醒，返回剩余时间
@@ -346,3 +372,57 @@ pub extern "C" fn rs_timer_get_first_expire() -> i64 {
 pub extern "C" fn rs_update_timer_jiffies(add_jiffies: u64) -> u64 {
     return update_timer_jiffies(add_jiffies);
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use crate::{process::ProcessManager, syscall::SystemError};
+
+    use super::{clock, schedule_timeout, Timer, WakeUpHelper, TIMER_LIST};
+
+    /// 睡眠10个jiffies后，剩余时间应当接近0
+    #[test]
+    fn test_schedule_timeout_expires() {
+        let start = clock();
+        let remaining = schedule_timeout(10).unwrap();
+        assert!(clock() - start >= 10);
+        assert!(remaining <= 1);
+    }
+
+    /// 已有待处理的信号时，可中断的睡眠立即以ERESTARTSYS返回
+    #[test]
+    fn test_schedule_timeout_interrupted() {
+        let current = ProcessManager::current_pcb();
+        assert_eq!(ProcessManager::signal_wakeup(&current), Ok(false));
+
+        let start = clock();
+        assert_eq!(schedule_timeout(1000), Err(SystemError::ERESTARTSYS));
+        assert!(clock() - start < 1000);
+        assert!(current.sched_info().state().is_runnable());
+
+        ProcessManager::clear_pending_signal(&current);
+        assert!(schedule_timeout(1).is_ok());
+    }
+
+    /// 被取消的定时器不再留在定时器链表中
+    #[test]
+    fn test_timer_cancel() {
+        let timer = Timer::new(
+            WakeUpHelper::new(ProcessManager::current_pcb()),
+            clock() + 1000000,
+        );
+        assert!(!timer.cancel());
+
+        timer.activate();
+        assert!(TIMER_LIST
+            .lock_irqsave()
+            .iter()
+            .any(|elt| Arc::ptr_eq(elt, &timer)));
+        assert!(timer.cancel());
+        assert!(!TIMER_LIST
+            .lock_irqsave()
+            .iter()
+            .any(|elt| Arc::ptr_eq(elt, &timer)));
+    }
+}
@@ -0,0 +1,197 @@
+/**
+ * @file main.c
+ * @brief 测量进程管理中热点路径的开销
+ *
+ * 用法: bench_process <测试项> [参数]
+ *
+ * - find [进程数] : 多个进程同时通过getpgid查找进程，测量进程表查找的吞吐量
+ * - fork [进程数] : 连续创建进程且暂不回收，测量每次fork的延迟分布，观察进程表扩容带来的长尾
+ * - current : 反复调用getpid，测量一次只获取当前进程的pcb的系统调用的开销
+ *
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/time.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define FIND_ITERS 200000
+#define FIND_DEFAULT_NPROC 4
+#define FIND_MAX_NPROC 64
+
+#define FORK_DEFAULT_NPROC 2000
+#define FORK_MAX_NPROC 30000
+
+#define CURRENT_ITERS 1000000
+
+static long now_us(void)
+{
+    struct timeval tv;
+    gettimeofday(&tv, NULL);
+    return tv.tv_sec * 1000000L + tv.tv_usec;
+}
+
+/**
+ * @brief 在子进程中反复查找父进程与自身，输出每秒的查找次数
+ */
+static void find_worker(int id, pid_t target)
+{
+    pid_t self = getpid();
+    long start = now_us();
+    for (int i = 0; i < FIND_ITERS; i++)
+    {
+        // 交替查找不同的pid，使查找分布到不同的分片上
+        getpgid((i & 1) ? target : self);
+    }
+    long elapsed = now_us() - start;
+    if (elapsed <= 0)
+        elapsed = 1;
+    printf("find worker %d: %d lookups in %ld us, %ld lookups/s\n", id, FIND_ITERS, elapsed,
+           FIND_ITERS * 1000000L / elapsed);
+}
+
+static int bench_find(int nproc)
+{
+    if (nproc <= 0 || nproc > FIND_MAX_NPROC)
+        nproc = FIND_DEFAULT_NPROC;
+
+    pid_t parent = getpid();
+    long start = now_us();
+    for (int i = 0; i < nproc; i++)
+    {
+        pid_t pid = fork();
+        if (pid < 0)
+        {
+            perror("fork");
+            return -1;
+        }
+        if (pid == 0)
+        {
+            find_worker(i, parent);
+            exit(0);
+        }
+    }
+    for (int i = 0; i < nproc; i++)
+        wait(NULL);
+    long elapsed = now_us() - start;
+    if (elapsed <= 0)
+        elapsed = 1;
+
+    long total = (long)nproc * FIND_ITERS;
+    printf("find: %d workers, %ld lookups in %ld us, %ld lookups/s in total\n", nproc, total, elapsed,
+           total * 1000000L / elapsed);
+    return 0;
+}
+
+static int cmp_long(const void *a, const void *b)
+{
+    long x = *(const long *)a;
+    long y = *(const long *)b;
+    return (x > y) - (x < y);
+}
+
+static int bench_fork(int nproc)
+{
+    if (nproc <= 0 || nproc > FORK_MAX_NPROC)
+        nproc = FORK_DEFAULT_NPROC;
+
+    long *latency = malloc(sizeof(long) * nproc);
+    if (latency == NULL)
+    {
+        perror("malloc");
+        return -1;
+    }
+
+    // 子进程先不退出，使进程表中的进程数持续增长
+    int pipefd[2];
+    if (pipe(pipefd) < 0)
+    {
+        perror("pipe");
+        free(latency);
+        return -1;
+    }
+
+    int created = 0;
+    for (; created < nproc; created++)
+    {
+        long start = now_us();
+        pid_t pid = fork();
+        if (pid < 0)
+        {
+            perror("fork");
+            break;
+        }
+        if (pid == 0)
+        {
+            char c;
+            close(pipefd[1]);
+            read(pipefd[0], &c, 1);
+            exit(0);
+        }
+        latency[created] = now_us() - start;
+    }
+
+    // 关闭写端，所有子进程的read返回后退出
+    close(pipefd[0]);
+    close(pipefd[1]);
+    for (int i = 0; i < created; i++)
+        wait(NULL);
+
+    if (created == 0)
+    {
+        free(latency);
+        return -1;
+    }
+    qsort(latency, created, sizeof(long), cmp_long);
+    long sum = 0;
+    for (int i = 0; i < created; i++)
+        sum += latency[i];
+    printf("fork: %d processes, avg %ld us, p50 %ld us, p99 %ld us, p99.9 %ld us, max %ld us\n", created,
+           sum / created, latency[created / 2], latency[created * 99 / 100], latency[created * 999 / 1000],
+           latency[created - 1]);
+    free(latency);
+    return created == nproc ? 0 : -1;
+}
+
+/**
+ * @brief getpid在内核中只获取当前进程的pcb，用它的开销近似current_pcb()的开销（包含系统调用本身的开销）
+ */
+static int bench_current(void)
+{
+    long start = now_us();
+    for (int i = 0; i < CURRENT_ITERS; i++)
+        getpid();
+    long elapsed = now_us() - start;
+    if (elapsed <= 0)
+        elapsed = 1;
+    printf("current: %d getpid calls in %ld us, %ld ns per call\n", CURRENT_ITERS, elapsed,
+           elapsed * 1000L / CURRENT_ITERS);
+    return 0;
+}
+
+static void usage(void)
+{
+    printf("usage: bench_process find|fork [nproc]\n");
+    printf("       bench_process current\n");
+}
+
+int main(int argc, char *argv[])
+{
+    if (argc < 2)
+    {
+        usage();
+        return 1;
+    }
+    int arg = argc > 2 ? atoi(argv[2]) : 0;
+    if (strcmp(argv[1], "find") == 0)
+        return bench_find(arg) == 0 ? 0 : 1;
+    if (strcmp(argv[1], "fork") == 0)
+        return bench_fork(arg) == 0 ? 0 : 1;
+    if (strcmp(argv[1], "current") == 0)
+        return bench_current() == 0 ? 0 : 1;
+
+    usage();
+    return 1;
+}
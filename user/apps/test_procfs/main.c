@@ -0,0 +1,138 @@
+#include <dirent.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+
+/**
+ * @brief 打开一个文件，检查/proc/<pid>/fd下能否找到对应的目录项
+ */
+int test_fd_dir()
+{
+    const char *target = "/bin/about.elf";
+    int fd = open(target, O_RDONLY);
+    if (fd == -1)
+    {
+        printf("[test_fd_dir] failed to open %s\n", target);
+        return -1;
+    }
+
+    char path[64];
+    sprintf(path, "/proc/%d/fd", getpid());
+    DIR *dir = opendir(path);
+    if (dir == NULL)
+    {
+        printf("[test_fd_dir] failed to open %s\n", path);
+        close(fd);
+        return -1;
+    }
+
+    char fd_name[16];
+    sprintf(fd_name, "%d", fd);
+    int found = 0;
+    struct dirent *ent;
+    while ((ent = readdir(dir)) != NULL)
+    {
+        printf("%s/%s\n", path, ent->d_name);
+        if (strcmp(ent->d_name, fd_name) == 0)
+            found = 1;
+    }
+    closedir(dir);
+    close(fd);
+
+    if (!found)
+    {
+        printf("[test_fd_dir] fd %d not listed in %s\n", fd, path);
+        return -1;
+    }
+    return 0;
+}
+
+/**
+ * @brief 读取一个/proc下的文件的内容
+ */
+int read_proc_file(const char *path, char *buf, int size)
+{
+    int fd = open(path, O_RDONLY);
+    if (fd == -1)
+    {
+        printf("failed to open %s\n", path);
+        return -1;
+    }
+    int len = read(fd, buf, size - 1);
+    close(fd);
+    if (len < 0)
+    {
+        printf("failed to read %s\n", path);
+        return -1;
+    }
+    buf[len] = '\0';
+    return len;
+}
+
+/**
+ * @brief 检查/proc/loadavg的格式是否为`0.00 0.00 0.00 1/42 1234`
+ */
+int test_loadavg()
+{
+    char buf[128];
+    if (read_proc_file("/proc/loadavg", buf, sizeof(buf)) < 0)
+        return -1;
+    printf("/proc/loadavg: %s", buf);
+
+    unsigned int avg_int[3], avg_frac[3];
+    int runnable, total, last_pid;
+    int n = sscanf(buf, "%u.%2u %u.%2u %u.%2u %d/%d %d", &avg_int[0], &avg_frac[0], &avg_int[1],
+                   &avg_frac[1], &avg_int[2], &avg_frac[2], &runnable, &total, &last_pid);
+    if (n != 9)
+    {
+        printf("[test_loadavg] unexpected field layout, matched %d fields\n", n);
+        return -1;
+    }
+    if (runnable < 1 || runnable > total || last_pid < getpid())
+    {
+        printf("[test_loadavg] unexpected values: %d/%d %d\n", runnable, total, last_pid);
+        return -1;
+    }
+    return 0;
+}
+
+/**
+ * @brief 检查/proc/uptime的两个字段均为浮点数，并且系统运行时间不为0
+ */
+int test_uptime()
+{
+    char buf[128];
+    if (read_proc_file("/proc/uptime", buf, sizeof(buf)) < 0)
+        return -1;
+    printf("/proc/uptime: %s", buf);
+
+    double uptime, idle;
+    int n = sscanf(buf, "%lf %lf", &uptime, &idle);
+    if (n != 2)
+    {
+        printf("[test_uptime] unexpected field layout, matched %d fields\n", n);
+        return -1;
+    }
+    if (uptime <= 0)
+    {
+        printf("[test_uptime] uptime should be nonzero after boot\n");
+        return -1;
+    }
+    return 0;
+}
+
+int main()
+{
+    int ret = 0;
+    if (test_fd_dir() != 0)
+        ret = -1;
+    if (test_loadavg() != 0)
+        ret = -1;
+    if (test_uptime() != 0)
+        ret = -1;
+
+    printf("test_procfs %s\n", ret == 0 ? "passed" : "failed");
+    return ret;
+}